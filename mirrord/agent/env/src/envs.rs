@@ -44,6 +44,10 @@ pub const STEALER_FLUSH_CONNECTIONS_CONNTRACK: CheckedEnv<bool> =
 /// iptables.
 pub const NFTABLES: CheckedEnv<bool> = CheckedEnv::new("MIRRORD_AGENT_NFTABLES");
 
+/// Instructs the agent to program nftables directly over netlink, in its own table, instead of
+/// running the iptables binaries.
+pub const NFTABLES_NETLINK: CheckedEnv<bool> = CheckedEnv::new("MIRRORD_AGENT_NFTABLES_NETLINK");
+
 /// Instructs the agent to produce logs in JSON format.
 pub const JSON_LOG: CheckedEnv<bool> = CheckedEnv::new("MIRRORD_AGENT_JSON_LOG");
 
@@ -53,6 +57,23 @@ pub const DNS_TIMEOUT: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_AGENT_DNS_TIME
 /// Sets a hard limit on DNS query attempts.
 pub const DNS_ATTEMPTS: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_AGENT_DNS_ATTEMPTS");
 
+/// Limits how many remote files and directories a single client can have open at the same time.
+pub const QUOTA_MAX_OPEN_FILES: CheckedEnv<u32> =
+    CheckedEnv::new("MIRRORD_AGENT_QUOTA_MAX_OPEN_FILES");
+
+/// Limits how many outgoing connections a single client can have open at the same time.
+pub const QUOTA_MAX_OUTGOING_CONNECTIONS: CheckedEnv<u32> =
+    CheckedEnv::new("MIRRORD_AGENT_QUOTA_MAX_OUTGOING_CONNECTIONS");
+
+/// Limits how many DNS requests a single client can make per second.
+pub const QUOTA_DNS_REQUESTS_PER_SECOND: CheckedEnv<u32> =
+    CheckedEnv::new("MIRRORD_AGENT_QUOTA_DNS_REQUESTS_PER_SECOND");
+
+/// Limits the rate (bytes per second) at which a single client can send data through its
+/// outgoing connections.
+pub const QUOTA_OUTGOING_BANDWIDTH: CheckedEnv<u64> =
+    CheckedEnv::new("MIRRORD_AGENT_QUOTA_OUTGOING_BANDWIDTH");
+
 /// Used in incoming traffic redirection to produce correct iptables rules.
 pub const POD_IPS: CheckedEnv<Vec<IpAddr>> = CheckedEnv::new("MIRRORD_AGENT_POD_IPS");
 
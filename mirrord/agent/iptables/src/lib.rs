@@ -19,6 +19,7 @@ use crate::{
         exclusion::{MeshExclusion, WithMeshExclusion},
         istio::AmbientRedirect,
     },
+    nftables::NftablesRedirect,
     prerouting::PreroutingRedirect,
     redirect::Redirect,
     standard::StandardRedirect,
@@ -28,11 +29,14 @@ mod chain;
 pub mod error;
 mod flush_connections;
 mod mesh;
+mod nftables;
 mod output;
 mod prerouting;
 mod redirect;
 mod standard;
 
+pub use nftables::NftablesRedirect;
+
 /// Holds the iptables chain names for this agent instance.
 ///
 /// When `MIRRORD_AGENT_IPTABLES_IDENTIFIER` is set, allows multiple agents to coexist in the same
@@ -45,6 +49,8 @@ pub struct ChainNames {
     mesh: String,
     standard: String,
     exclude_from_mesh: String,
+    /// Name of the table used by [`NftablesRedirect`].
+    nftables: String,
 }
 
 impl ChainNames {
@@ -55,6 +61,7 @@ impl ChainNames {
             mesh: format!("MRDOUT_{id}"),
             standard: format!("MRDSTD_{id}"),
             exclude_from_mesh: format!("MRDMSH_{id}"),
+            nftables: format!("mirrord_{id}"),
         }
     }
 
@@ -68,6 +75,7 @@ impl ChainNames {
             mesh: "MIRRORD_OUTPUT".to_owned(),
             standard: "MIRRORD_STANDARD".to_owned(),
             exclude_from_mesh: "MIRRORD_EXCLUDE_FROM_MESH".to_owned(),
+            nftables: "mirrord".to_owned(),
         }
     }
 }
@@ -180,6 +188,14 @@ impl IPTables for IPTablesWrapper {
     }
 }
 
+/// Detects the service mesh that manages the traffic with rules in the given iptables backend.
+///
+/// [`NftablesRedirect`] can't work together with such rules, so the iptables redirects must be
+/// used when a mesh is detected.
+pub fn detect_mesh<IPT: IPTables>(ipt: &IPT) -> IPTablesResult<Option<MeshVendor>> {
+    MeshVendor::detect(ipt)
+}
+
 #[enum_dispatch(Redirect)]
 enum Redirects<IPT: IPTables + Send + Sync> {
     Ambient(AmbientRedirect<IPT>),
@@ -188,6 +204,7 @@ enum Redirects<IPT: IPTables + Send + Sync> {
     FlushConnections(FlushConnections<Redirects<IPT>>),
     PrerouteFallback(PreroutingRedirect<IPT>),
     WithMeshExclusion(WithMeshExclusion<IPT, Redirects<IPT>>),
+    Nftables(NftablesRedirect),
 }
 
 /// Wrapper struct for IPTables so it flushes on drop.
@@ -260,6 +277,29 @@ where
         Ok(Self { redirect })
     }
 
+    /// Like [`SafeIpTables::create`], but uses [`NftablesRedirect`], which programs nftables
+    /// directly over netlink instead of running the iptables binaries.
+    ///
+    /// Service meshes are not supported, as their rules are managed with iptables. Check for them
+    /// with [`detect_mesh`] first.
+    pub async fn create_nftables(
+        chain_names: &ChainNames,
+        flush_connections: bool,
+        pod_ips: Option<&str>,
+        ipv6: bool,
+    ) -> IPTablesResult<Self> {
+        let mut redirect =
+            Redirects::Nftables(NftablesRedirect::create(chain_names, pod_ips, ipv6));
+
+        if flush_connections {
+            redirect = Redirects::FlushConnections(FlushConnections::create(Box::new(redirect))?)
+        }
+
+        redirect.mount_entrypoint().await?;
+
+        Ok(Self { redirect })
+    }
+
     /// List rules from previous mirrord agent that exist on the IP table
     #[tracing::instrument(level = Level::TRACE, skip(ipt, chain_names) ret, err)]
     pub async fn list_mirrord_rules<'a>(
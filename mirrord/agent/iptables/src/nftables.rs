@@ -0,0 +1,371 @@
+use std::{collections::BTreeSet, io, net::IpAddr, ops::Not};
+
+use async_trait::async_trait;
+use mirrord_agent_env::envs;
+use nix::unistd::getgid;
+use tokio::sync::{Mutex, MutexGuard};
+
+use self::netlink::{CmpOp, Expr, Family, Hook, Message, MetaKey, PayloadBase};
+use crate::{ChainNames, Redirect, error::IPTablesResult};
+
+mod netlink;
+
+/// Index of the loopback interface, which is always the first one in a network namespace.
+const LOOPBACK_IFINDEX: u32 = 1;
+
+/// `IPPROTO_TCP`.
+const TCP: u8 = 6;
+
+/// Same as the `dstnat` priority, used by the iptables `nat` table.
+const NAT_PRIORITY: i32 = -100;
+
+/// Redirects traffic with nftables rules, programmed directly over netlink.
+///
+/// Unlike the iptables based redirects, this one does not run any binaries. All rules live in a
+/// dedicated table (named after the agent's [`ChainNames`]) with two base chains, so [`Redirect`]
+/// operations map to single netlink batches, which the kernel applies atomically:
+///
+/// - mounting creates the table (replacing a leftover one) with its chains;
+/// - adding or removing a redirect rewrites both chains;
+/// - unmounting deletes the whole table.
+///
+/// The netlink socket I/O is synchronous, so it runs on the blocking threads of the current
+/// runtime, which share the network namespace of the thread that spawned them.
+///
+/// Service meshes are not supported, as their rules are managed with iptables, see
+/// [`detect_mesh`](crate::detect_mesh).
+pub struct NftablesRedirect {
+    family: Family,
+    table: String,
+    /// Rules that keep the agent's own traffic out of [`Self::OUTPUT`].
+    output_exclusions: Vec<Vec<Expr>>,
+    /// `(redirected_port, target_port)` pairs, the chains are rebuilt from these on every change.
+    redirects: Mutex<BTreeSet<(u16, u16)>>,
+}
+
+impl NftablesRedirect {
+    const PREROUTING: &'static str = "prerouting";
+    const OUTPUT: &'static str = "output";
+
+    /// Prepares the redirect, nothing is sent to the kernel until
+    /// [`Redirect::mount_entrypoint`].
+    ///
+    /// `pod_ips` is the same comma separated list that is used by the iptables redirects.
+    pub fn create(chain_names: &ChainNames, pod_ips: Option<&str>, ipv6: bool) -> Self {
+        let family = if ipv6 { Family::Ipv6 } else { Family::Ipv4 };
+        let gid = getgid().as_raw();
+
+        let owned_by_agent = [
+            Expr::Meta(MetaKey::SkGid),
+            Expr::Cmp(CmpOp::Eq, gid.to_ne_bytes().to_vec()),
+            Expr::Meta(MetaKey::L4Proto),
+            Expr::Cmp(CmpOp::Eq, vec![TCP]),
+        ];
+
+        // Equivalent of `-m owner --gid-owner {gid} -p tcp ! -s {pod_ips} -j RETURN`.
+        let not_from_pod = pod_ips
+            .into_iter()
+            .flat_map(|pod_ips| pod_ips.split(','))
+            .filter_map(|pod_ip| pod_ip.trim().parse::<IpAddr>().ok())
+            .filter(|pod_ip| pod_ip.is_ipv6() == ipv6)
+            .flat_map(|pod_ip| {
+                let (offset, octets) = match pod_ip {
+                    IpAddr::V4(ip) => (12, ip.octets().to_vec()),
+                    IpAddr::V6(ip) => (8, ip.octets().to_vec()),
+                };
+                [
+                    Expr::Payload {
+                        base: PayloadBase::Network,
+                        offset,
+                        len: octets.len() as u32,
+                    },
+                    Expr::Cmp(CmpOp::Neq, octets),
+                ]
+            });
+        let mut output_exclusions = vec![
+            owned_by_agent
+                .iter()
+                .cloned()
+                .chain(not_from_pod)
+                .chain([Expr::Return])
+                .collect::<Vec<_>>(),
+        ];
+
+        // Same as in `OutputRedirect`, see `envs::EXTERNAL_IP_FIX`.
+        if envs::EXTERNAL_IP_FIX.from_env_or_default() {
+            let mark = envs::PASSTHROUGH_FWMARK.to_ne_bytes().to_vec();
+            output_exclusions.push(
+                owned_by_agent
+                    .into_iter()
+                    .chain([
+                        Expr::Meta(MetaKey::Mark),
+                        Expr::And(mark.clone()),
+                        Expr::Cmp(CmpOp::Eq, mark),
+                        Expr::Return,
+                    ])
+                    .collect(),
+            );
+        }
+
+        Self {
+            family,
+            table: chain_names.nftables.clone(),
+            output_exclusions,
+            redirects: Default::default(),
+        }
+    }
+
+    /// Checks whether the table of an agent with these [`ChainNames`] exists, e.g. because the
+    /// agent did not clean up after itself.
+    pub async fn exists(chain_names: &ChainNames, ipv6: bool) -> IPTablesResult<bool> {
+        let family = if ipv6 { Family::Ipv6 } else { Family::Ipv4 };
+        let table = chain_names.nftables.clone();
+
+        Ok(blocking(move || netlink::table_exists(family, &table)).await?)
+    }
+
+    /// Deletes the table of an agent with these [`ChainNames`], if it exists.
+    pub async fn remove(chain_names: &ChainNames, ipv6: bool) -> IPTablesResult<()> {
+        let family = if ipv6 { Family::Ipv6 } else { Family::Ipv4 };
+
+        // Creating the table first makes the deletion succeed even if there is nothing to delete.
+        batch(vec![
+            Message::new_table(family, &chain_names.nftables),
+            Message::delete_table(family, &chain_names.nftables),
+        ])
+        .await?;
+
+        Ok(())
+    }
+
+    /// `tcp dport {redirected_port} redirect to :{target_port}`
+    fn redirect_rule(redirected_port: u16, target_port: u16) -> [Expr; 6] {
+        [
+            Expr::Meta(MetaKey::L4Proto),
+            Expr::Cmp(CmpOp::Eq, vec![TCP]),
+            Expr::Payload {
+                base: PayloadBase::Transport,
+                offset: 2,
+                len: 2,
+            },
+            Expr::Cmp(CmpOp::Eq, redirected_port.to_be_bytes().to_vec()),
+            Expr::Immediate(target_port.to_be_bytes().to_vec()),
+            Expr::Redirect,
+        ]
+    }
+
+    /// Messages that fill both (empty) chains.
+    fn rules(&self, redirects: &BTreeSet<(u16, u16)>) -> impl Iterator<Item = Message> {
+        let exclusions = self
+            .output_exclusions
+            .iter()
+            .map(|rule| Message::new_rule(self.family, &self.table, Self::OUTPUT, rule));
+
+        let redirects = redirects
+            .iter()
+            .flat_map(|&(redirected_port, target_port)| {
+                let rule = Self::redirect_rule(redirected_port, target_port);
+
+                // Only local traffic that goes through the loopback reaches the `output` chain,
+                // like `-o lo` in `OutputRedirect`.
+                let output_rule = [
+                    Expr::Meta(MetaKey::Oif),
+                    Expr::Cmp(CmpOp::Eq, LOOPBACK_IFINDEX.to_ne_bytes().to_vec()),
+                ]
+                .into_iter()
+                .chain(rule.iter().cloned())
+                .collect::<Vec<_>>();
+
+                [
+                    Message::new_rule(self.family, &self.table, Self::PREROUTING, &rule),
+                    Message::new_rule(self.family, &self.table, Self::OUTPUT, &output_rule),
+                ]
+            });
+
+        exclusions.chain(redirects)
+    }
+
+    /// Rewrites both chains with the given `redirects`, in a single batch.
+    async fn sync(&self, redirects: &BTreeSet<(u16, u16)>) -> io::Result<()> {
+        let messages = [
+            Message::flush_chain(self.family, &self.table, Self::PREROUTING),
+            Message::flush_chain(self.family, &self.table, Self::OUTPUT),
+        ]
+        .into_iter()
+        .chain(self.rules(redirects))
+        .collect();
+
+        batch(messages).await
+    }
+
+    async fn redirects(&self) -> MutexGuard<'_, BTreeSet<(u16, u16)>> {
+        self.redirects.lock().await
+    }
+}
+
+/// Runs the synchronous netlink I/O on a blocking thread.
+async fn blocking<T, F>(io: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(io)
+        .await
+        .map_err(io::Error::other)?
+}
+
+/// [`netlink::batch`] on a blocking thread.
+async fn batch(messages: Vec<Message>) -> io::Result<()> {
+    blocking(move || netlink::batch(messages)).await
+}
+
+#[async_trait]
+impl Redirect for NftablesRedirect {
+    async fn mount_entrypoint(&self) -> IPTablesResult<()> {
+        let redirects = self.redirects().await;
+
+        // Creating and deleting the table first removes any leftovers with the same name.
+        let messages = [
+            Message::new_table(self.family, &self.table),
+            Message::delete_table(self.family, &self.table),
+            Message::new_table(self.family, &self.table),
+            Message::new_nat_chain(
+                self.family,
+                &self.table,
+                Self::PREROUTING,
+                Hook::Prerouting,
+                NAT_PRIORITY,
+            ),
+            Message::new_nat_chain(
+                self.family,
+                &self.table,
+                Self::OUTPUT,
+                Hook::Output,
+                NAT_PRIORITY,
+            ),
+        ]
+        .into_iter()
+        .chain(self.rules(&redirects))
+        .collect();
+
+        batch(messages).await?;
+
+        Ok(())
+    }
+
+    async fn unmount_entrypoint(&self) -> IPTablesResult<()> {
+        batch(vec![Message::delete_table(self.family, &self.table)]).await?;
+
+        Ok(())
+    }
+
+    async fn add_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
+        let mut redirects = self.redirects().await;
+        if redirects.insert((redirected_port, target_port)) {
+            self.sync(&redirects).await.inspect_err(|_| {
+                redirects.remove(&(redirected_port, target_port));
+            })?;
+        }
+
+        Ok(())
+    }
+
+    async fn remove_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
+        let mut redirects = self.redirects().await;
+        if redirects.remove(&(redirected_port, target_port)).not() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("redirect from {redirected_port} to {target_port} does not exist"),
+            )
+            .into());
+        }
+
+        self.sync(&redirects).await.inspect_err(|_| {
+            redirects.insert((redirected_port, target_port));
+        })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        os::unix::process::CommandExt,
+        process::{Command, Stdio},
+    };
+
+    use nix::sched::{CloneFlags, unshare};
+
+    use super::*;
+
+    /// Set in the child process that runs the test in its own namespaces.
+    const IN_NAMESPACE: &str = "MIRRORD_TEST_NFTABLES_IN_NAMESPACE";
+
+    /// Runs `test` (the name of the calling test) again in a child process, in a new user and
+    /// network namespace, where we are allowed to manage nftables without any privileges.
+    ///
+    /// Returns `true` in the child process, where the test should do its job.
+    fn in_user_and_net_namespace(test: &str) -> bool {
+        if env::var_os(IN_NAMESPACE).is_some() {
+            return true;
+        }
+
+        let mut command = Command::new(env::current_exe().unwrap());
+        command
+            .args(["--exact", test, "--nocapture", "--test-threads", "1"])
+            .env(IN_NAMESPACE, "1")
+            .stdin(Stdio::null());
+        // Safety: `unshare` is async-signal-safe.
+        unsafe {
+            command.pre_exec(|| {
+                unshare(CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNET)?;
+                Ok(())
+            });
+        }
+
+        match command.status() {
+            Ok(status) => assert!(status.success(), "test in namespace failed with {status}"),
+            Err(error) => eprintln!("skipping {test}, could not create namespaces: {error}"),
+        }
+
+        false
+    }
+
+    #[tokio::test]
+    async fn redirects_in_namespace() {
+        if !in_user_and_net_namespace("nftables::tests::redirects_in_namespace") {
+            return;
+        }
+
+        let chain_names = ChainNames::new("test");
+        let redirect = NftablesRedirect::create(&chain_names, Some("10.0.0.1,fd00::1"), false);
+        let count = |chain| netlink::count_rules(Family::Ipv4, &chain_names.nftables, chain);
+
+        assert!(!NftablesRedirect::exists(&chain_names, false).await.unwrap());
+        redirect.mount_entrypoint().await.unwrap();
+        assert!(NftablesRedirect::exists(&chain_names, false).await.unwrap());
+        assert_eq!(count(NftablesRedirect::OUTPUT).unwrap(), 1);
+
+        redirect.add_redirect(80, 40000).await.unwrap();
+        redirect.add_redirect(8080, 40000).await.unwrap();
+        assert_eq!(count(NftablesRedirect::PREROUTING).unwrap(), 2);
+        assert_eq!(count(NftablesRedirect::OUTPUT).unwrap(), 3);
+
+        redirect.remove_redirect(80, 40000).await.unwrap();
+        assert!(redirect.remove_redirect(80, 40000).await.is_err());
+        assert_eq!(count(NftablesRedirect::PREROUTING).unwrap(), 1);
+        assert_eq!(count(NftablesRedirect::OUTPUT).unwrap(), 2);
+
+        // Mounting again replaces the table, keeping the current redirects.
+        redirect.mount_entrypoint().await.unwrap();
+        assert_eq!(count(NftablesRedirect::PREROUTING).unwrap(), 1);
+
+        redirect.unmount_entrypoint().await.unwrap();
+        assert!(!NftablesRedirect::exists(&chain_names, false).await.unwrap());
+
+        // Removing a table that does not exist is fine.
+        NftablesRedirect::remove(&chain_names, false).await.unwrap();
+    }
+}
@@ -0,0 +1,602 @@
+//! Minimal `nf_tables` netlink client, just enough to manage the mirrord table.
+//!
+//! Messages are encoded by hand, the constants come from `linux/netlink.h`,
+//! `linux/netfilter/nfnetlink.h` and `linux/netfilter/nf_tables.h`.
+//!
+//! Every operation opens its own socket, so replies of a failed operation never leak into the next
+//! one.
+
+use std::{
+    io,
+    os::fd::{AsRawFd, OwnedFd},
+};
+
+use nix::sys::socket::{
+    self, AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol, SockType,
+};
+
+const NLMSG_HDRLEN: usize = 16;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_ACK: u16 = 0x4;
+#[cfg(test)]
+const NLM_F_DUMP: u16 = 0x300;
+const NLM_F_CREATE: u16 = 0x400;
+const NLM_F_APPEND: u16 = 0x800;
+
+const NLA_HDRLEN: usize = 4;
+const NLA_F_NESTED: u16 = 0x8000;
+
+const NFNL_MSG_BATCH_BEGIN: u16 = 0x10;
+const NFNL_MSG_BATCH_END: u16 = 0x11;
+const NFNL_SUBSYS_NFTABLES: u16 = 10;
+
+const NFT_MSG_NEWTABLE: u16 = 0;
+const NFT_MSG_GETTABLE: u16 = 1;
+const NFT_MSG_DELTABLE: u16 = 2;
+const NFT_MSG_NEWCHAIN: u16 = 3;
+const NFT_MSG_NEWRULE: u16 = 6;
+#[cfg(test)]
+const NFT_MSG_GETRULE: u16 = 7;
+const NFT_MSG_DELRULE: u16 = 8;
+
+const NFTA_TABLE_NAME: u16 = 1;
+
+const NFTA_CHAIN_TABLE: u16 = 1;
+const NFTA_CHAIN_NAME: u16 = 3;
+const NFTA_CHAIN_HOOK: u16 = 4;
+const NFTA_CHAIN_TYPE: u16 = 7;
+
+const NFTA_HOOK_HOOKNUM: u16 = 1;
+const NFTA_HOOK_PRIORITY: u16 = 2;
+
+const NFTA_RULE_TABLE: u16 = 1;
+const NFTA_RULE_CHAIN: u16 = 2;
+const NFTA_RULE_EXPRESSIONS: u16 = 4;
+
+const NFTA_LIST_ELEM: u16 = 1;
+const NFTA_EXPR_NAME: u16 = 1;
+const NFTA_EXPR_DATA: u16 = 2;
+
+const NFTA_DATA_VALUE: u16 = 1;
+const NFTA_DATA_VERDICT: u16 = 2;
+const NFTA_VERDICT_CODE: u16 = 1;
+
+const NFT_REG_VERDICT: u32 = 0;
+const NFT_REG_1: u32 = 1;
+const NFT_RETURN: i32 = -5;
+
+/// Big enough for any reply we get, dumps are split into multiple datagrams by the kernel.
+const RECEIVE_BUFFER_LEN: usize = 64 * 1024;
+
+/// Address family of an nftables table (`NFPROTO_*`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    Ipv4,
+    Ipv6,
+}
+
+impl Family {
+    fn nfproto(self) -> u8 {
+        match self {
+            Self::Ipv4 => 2,
+            Self::Ipv6 => 10,
+        }
+    }
+}
+
+/// Netfilter hook of a base chain (`NF_INET_*`).
+#[derive(Debug, Clone, Copy)]
+pub enum Hook {
+    Prerouting,
+    Output,
+}
+
+impl Hook {
+    fn number(self) -> u32 {
+        match self {
+            Self::Prerouting => 0,
+            Self::Output => 3,
+        }
+    }
+}
+
+/// Keys of the `meta` expression (`NFT_META_*`).
+#[derive(Debug, Clone, Copy)]
+pub enum MetaKey {
+    /// Packet mark, `u32` in host byte order.
+    Mark,
+    /// Output interface index, `u32` in host byte order.
+    Oif,
+    /// Group id of the socket that sent the packet, `u32` in host byte order.
+    SkGid,
+    /// Transport protocol number, `u8`.
+    L4Proto,
+}
+
+impl MetaKey {
+    fn key(self) -> u32 {
+        match self {
+            Self::Mark => 3,
+            Self::Oif => 4,
+            Self::SkGid => 11,
+            Self::L4Proto => 16,
+        }
+    }
+}
+
+/// Headers that the `payload` expression can load from (`NFT_PAYLOAD_*`).
+#[derive(Debug, Clone, Copy)]
+pub enum PayloadBase {
+    Network,
+    Transport,
+}
+
+impl PayloadBase {
+    fn base(self) -> u32 {
+        match self {
+            Self::Network => 1,
+            Self::Transport => 2,
+        }
+    }
+}
+
+/// Comparison operators of the `cmp` expression (`NFT_CMP_*`).
+#[derive(Debug, Clone, Copy)]
+pub enum CmpOp {
+    Eq,
+    Neq,
+}
+
+/// A single rule expression.
+///
+/// All of them work on the same data register, as our rules are simple sequences of
+/// load-and-compare steps.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    /// Loads a `meta` key into the register.
+    Meta(MetaKey),
+    /// Loads `len` bytes at `offset` of a packet header into the register.
+    Payload {
+        base: PayloadBase,
+        offset: u32,
+        len: u32,
+    },
+    /// Compares the register with the data, the rule stops matching when this does not hold.
+    Cmp(CmpOp, Vec<u8>),
+    /// Masks the register with the data.
+    And(Vec<u8>),
+    /// Loads the data into the register.
+    Immediate(Vec<u8>),
+    /// Stops evaluating the chain.
+    Return,
+    /// Redirects the packet to the local port (big endian `u16`) stored in the register.
+    Redirect,
+}
+
+/// An nftables netlink message, encoded as it is being built.
+#[derive(Debug, Clone)]
+pub struct Message {
+    buffer: Vec<u8>,
+}
+
+impl Message {
+    fn new(kind: u16, flags: u16, family: u8, resource_id: u16) -> Self {
+        let mut buffer = Vec::with_capacity(256);
+        // `nlmsghdr`, length and sequence number are set when sending.
+        buffer.extend_from_slice(&0u32.to_ne_bytes());
+        buffer.extend_from_slice(&kind.to_ne_bytes());
+        buffer.extend_from_slice(&flags.to_ne_bytes());
+        buffer.extend_from_slice(&0u32.to_ne_bytes());
+        buffer.extend_from_slice(&0u32.to_ne_bytes());
+        // `nfgenmsg`, `NFNETLINK_V0`.
+        buffer.push(family);
+        buffer.push(0);
+        buffer.extend_from_slice(&resource_id.to_be_bytes());
+
+        Self { buffer }
+    }
+
+    fn nftables(kind: u16, flags: u16, family: Family) -> Self {
+        Self::new(
+            (NFNL_SUBSYS_NFTABLES << 8) | kind,
+            NLM_F_REQUEST | flags,
+            family.nfproto(),
+            0,
+        )
+    }
+
+    fn batch(kind: u16) -> Self {
+        Self::new(kind, NLM_F_REQUEST, 0, NFNL_SUBSYS_NFTABLES)
+    }
+
+    fn attr(&mut self, kind: u16, data: &[u8]) -> &mut Self {
+        let len = (NLA_HDRLEN + data.len()) as u16;
+        self.buffer.extend_from_slice(&len.to_ne_bytes());
+        self.buffer.extend_from_slice(&kind.to_ne_bytes());
+        self.buffer.extend_from_slice(data);
+        self.pad();
+        self
+    }
+
+    fn attr_str(&mut self, kind: u16, value: &str) -> &mut Self {
+        let mut data = Vec::with_capacity(value.len() + 1);
+        data.extend_from_slice(value.as_bytes());
+        data.push(0);
+        self.attr(kind, &data)
+    }
+
+    /// Netfilter integer attributes are always big endian.
+    fn attr_u32(&mut self, kind: u16, value: u32) -> &mut Self {
+        self.attr(kind, &value.to_be_bytes())
+    }
+
+    fn nested(&mut self, kind: u16, fill: impl FnOnce(&mut Self)) -> &mut Self {
+        let start = self.buffer.len();
+        self.buffer.extend_from_slice(&0u16.to_ne_bytes());
+        self.buffer
+            .extend_from_slice(&(kind | NLA_F_NESTED).to_ne_bytes());
+        fill(self);
+
+        let len = (self.buffer.len() - start) as u16;
+        if let Some(header) = self.buffer.get_mut(start..start + 2) {
+            header.copy_from_slice(&len.to_ne_bytes());
+        }
+        self
+    }
+
+    fn pad(&mut self) {
+        self.buffer.resize(align(self.buffer.len()), 0);
+    }
+
+    fn expression(&mut self, name: &str, fill: impl FnOnce(&mut Self)) -> &mut Self {
+        self.nested(NFTA_LIST_ELEM, |message| {
+            message.attr_str(NFTA_EXPR_NAME, name);
+            message.nested(NFTA_EXPR_DATA, fill);
+        })
+    }
+
+    fn data_value(&mut self, kind: u16, data: &[u8]) -> &mut Self {
+        self.nested(kind, |message| {
+            message.attr(NFTA_DATA_VALUE, data);
+        })
+    }
+
+    fn expressions(&mut self, expressions: &[Expr]) -> &mut Self {
+        self.nested(NFTA_RULE_EXPRESSIONS, |message| {
+            for expression in expressions {
+                message.encode_expression(expression);
+            }
+        })
+    }
+
+    fn encode_expression(&mut self, expression: &Expr) {
+        match expression {
+            Expr::Meta(key) => self.expression("meta", |message| {
+                message.attr_u32(1, NFT_REG_1).attr_u32(2, key.key());
+            }),
+            Expr::Payload { base, offset, len } => self.expression("payload", |message| {
+                message
+                    .attr_u32(1, NFT_REG_1)
+                    .attr_u32(2, base.base())
+                    .attr_u32(3, *offset)
+                    .attr_u32(4, *len);
+            }),
+            Expr::Cmp(op, data) => self.expression("cmp", |message| {
+                let op = match op {
+                    CmpOp::Eq => 0,
+                    CmpOp::Neq => 1,
+                };
+                message
+                    .attr_u32(1, NFT_REG_1)
+                    .attr_u32(2, op)
+                    .data_value(3, data);
+            }),
+            Expr::And(mask) => self.expression("bitwise", |message| {
+                message
+                    .attr_u32(1, NFT_REG_1)
+                    .attr_u32(2, NFT_REG_1)
+                    .attr_u32(3, mask.len() as u32)
+                    .data_value(4, mask)
+                    .data_value(5, &vec![0; mask.len()]);
+            }),
+            Expr::Immediate(data) => self.expression("immediate", |message| {
+                message.attr_u32(1, NFT_REG_1).data_value(2, data);
+            }),
+            Expr::Return => self.expression("immediate", |message| {
+                message.attr_u32(1, NFT_REG_VERDICT).nested(2, |message| {
+                    message.nested(NFTA_DATA_VERDICT, |message| {
+                        message.attr_u32(NFTA_VERDICT_CODE, NFT_RETURN as u32);
+                    });
+                });
+            }),
+            Expr::Redirect => self.expression("redir", |message| {
+                message.attr_u32(1, NFT_REG_1);
+            }),
+        };
+    }
+
+    /// Sets the length and sequence number in the `nlmsghdr`.
+    fn finish(&mut self, sequence: u32) {
+        let len = self.buffer.len() as u32;
+        if let Some(header) = self.buffer.get_mut(..4) {
+            header.copy_from_slice(&len.to_ne_bytes());
+        }
+        if let Some(header) = self.buffer.get_mut(8..12) {
+            header.copy_from_slice(&sequence.to_ne_bytes());
+        }
+    }
+
+    /// Creates the table, if it does not exist.
+    pub fn new_table(family: Family, table: &str) -> Self {
+        let mut message = Self::nftables(NFT_MSG_NEWTABLE, NLM_F_ACK | NLM_F_CREATE, family);
+        message.attr_str(NFTA_TABLE_NAME, table);
+        message
+    }
+
+    /// Deletes the table, together with all of its chains and rules.
+    pub fn delete_table(family: Family, table: &str) -> Self {
+        let mut message = Self::nftables(NFT_MSG_DELTABLE, NLM_F_ACK, family);
+        message.attr_str(NFTA_TABLE_NAME, table);
+        message
+    }
+
+    fn get_table(family: Family, table: &str) -> Self {
+        let mut message = Self::nftables(NFT_MSG_GETTABLE, NLM_F_ACK, family);
+        message.attr_str(NFTA_TABLE_NAME, table);
+        message
+    }
+
+    /// Creates a `nat` base chain, attached to `hook`.
+    pub fn new_nat_chain(
+        family: Family,
+        table: &str,
+        chain: &str,
+        hook: Hook,
+        priority: i32,
+    ) -> Self {
+        let mut message = Self::nftables(NFT_MSG_NEWCHAIN, NLM_F_ACK | NLM_F_CREATE, family);
+        message
+            .attr_str(NFTA_CHAIN_TABLE, table)
+            .attr_str(NFTA_CHAIN_NAME, chain)
+            .nested(NFTA_CHAIN_HOOK, |message| {
+                message
+                    .attr_u32(NFTA_HOOK_HOOKNUM, hook.number())
+                    .attr_u32(NFTA_HOOK_PRIORITY, priority as u32);
+            })
+            .attr_str(NFTA_CHAIN_TYPE, "nat");
+        message
+    }
+
+    /// Deletes all rules of the chain.
+    pub fn flush_chain(family: Family, table: &str, chain: &str) -> Self {
+        let mut message = Self::nftables(NFT_MSG_DELRULE, NLM_F_ACK, family);
+        message
+            .attr_str(NFTA_RULE_TABLE, table)
+            .attr_str(NFTA_RULE_CHAIN, chain);
+        message
+    }
+
+    /// Appends a rule to the chain.
+    pub fn new_rule(family: Family, table: &str, chain: &str, expressions: &[Expr]) -> Self {
+        let mut message = Self::nftables(
+            NFT_MSG_NEWRULE,
+            NLM_F_ACK | NLM_F_CREATE | NLM_F_APPEND,
+            family,
+        );
+        message
+            .attr_str(NFTA_RULE_TABLE, table)
+            .attr_str(NFTA_RULE_CHAIN, chain)
+            .expressions(expressions);
+        message
+    }
+
+    #[cfg(test)]
+    fn dump_rules(family: Family, table: &str, chain: &str) -> Self {
+        let mut message = Self::nftables(NFT_MSG_GETRULE, NLM_F_DUMP, family);
+        message
+            .attr_str(NFTA_RULE_TABLE, table)
+            .attr_str(NFTA_RULE_CHAIN, chain);
+        message
+    }
+}
+
+/// Reply of the kernel to one of our messages.
+#[derive(Debug, PartialEq, Eq)]
+enum Reply {
+    Ack,
+    Done,
+    Object,
+}
+
+/// A netlink socket connected to the netfilter subsystem.
+struct NetlinkSocket {
+    fd: OwnedFd,
+    sequence: u32,
+}
+
+impl NetlinkSocket {
+    fn connect() -> io::Result<Self> {
+        let fd = socket::socket(
+            AddressFamily::Netlink,
+            SockType::Raw,
+            SockFlag::SOCK_CLOEXEC,
+            SockProtocol::NetlinkNetFilter,
+        )?;
+        socket::bind(fd.as_raw_fd(), &NetlinkAddr::new(0, 0))?;
+
+        Ok(Self { fd, sequence: 0 })
+    }
+
+    fn send(&mut self, messages: impl IntoIterator<Item = Message>) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        for mut message in messages {
+            self.sequence += 1;
+            message.finish(self.sequence);
+            buffer.append(&mut message.buffer);
+        }
+
+        let sent = socket::send(self.fd.as_raw_fd(), &buffer, MsgFlags::empty())?;
+        if sent != buffer.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "netlink message was not sent whole",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Reads replies until `done` returns `true` for one of them.
+    ///
+    /// Error replies are turned into [`io::Error`]s.
+    fn receive(&self, mut done: impl FnMut(Reply) -> bool) -> io::Result<()> {
+        let mut buffer = vec![0; RECEIVE_BUFFER_LEN];
+
+        loop {
+            let received = socket::recv(self.fd.as_raw_fd(), &mut buffer, MsgFlags::empty())?;
+            let mut replies = buffer.get(..received).unwrap_or_default();
+
+            while let Some((header, _)) = replies.split_first_chunk::<NLMSG_HDRLEN>() {
+                let [l0, l1, l2, l3, t0, t1, ..] = *header;
+                let len = u32::from_ne_bytes([l0, l1, l2, l3]) as usize;
+                let payload = replies
+                    .get(NLMSG_HDRLEN..len)
+                    .ok_or_else(|| invalid_reply(len))?;
+
+                let reply = match u16::from_ne_bytes([t0, t1]) {
+                    NLMSG_ERROR => {
+                        let (code, _) = payload
+                            .split_first_chunk::<4>()
+                            .ok_or_else(|| invalid_reply(len))?;
+                        match i32::from_ne_bytes(*code) {
+                            0 => Reply::Ack,
+                            code => return Err(io::Error::from_raw_os_error(-code)),
+                        }
+                    }
+                    NLMSG_DONE => Reply::Done,
+                    _ => Reply::Object,
+                };
+
+                if done(reply) {
+                    return Ok(());
+                }
+
+                replies = replies.get(align(len)..).unwrap_or_default();
+            }
+        }
+    }
+}
+
+fn invalid_reply(len: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid netlink reply of length {len}"),
+    )
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Sends `messages` in a single batch, which the kernel applies as one transaction: either all of
+/// them succeed, or none of them is applied.
+pub fn batch(messages: Vec<Message>) -> io::Result<()> {
+    let mut socket = NetlinkSocket::connect()?;
+
+    let mut pending_acks = messages.len();
+    socket.send(
+        std::iter::once(Message::batch(NFNL_MSG_BATCH_BEGIN))
+            .chain(messages)
+            .chain(std::iter::once(Message::batch(NFNL_MSG_BATCH_END))),
+    )?;
+
+    if pending_acks == 0 {
+        return Ok(());
+    }
+
+    socket.receive(|reply| {
+        if reply == Reply::Ack {
+            pending_acks -= 1;
+        }
+        pending_acks == 0
+    })
+}
+
+/// Checks whether the table exists.
+pub fn table_exists(family: Family, table: &str) -> io::Result<bool> {
+    let mut socket = NetlinkSocket::connect()?;
+    socket.send([Message::get_table(family, table)])?;
+
+    match socket.receive(|reply| reply == Reply::Ack) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Counts the rules in the chain.
+#[cfg(test)]
+pub fn count_rules(family: Family, table: &str, chain: &str) -> io::Result<usize> {
+    let mut socket = NetlinkSocket::connect()?;
+    socket.send([Message::dump_rules(family, table, chain)])?;
+
+    let mut rules = 0;
+    socket.receive(|reply| match reply {
+        Reply::Object => {
+            rules += 1;
+            false
+        }
+        Reply::Ack => false,
+        Reply::Done => true,
+    })?;
+
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_table() {
+        let mut message = Message::new_table(Family::Ipv4, "mirrord");
+        message.finish(7);
+
+        let expected: &[&[u8]] = &[
+            // nlmsghdr
+            &32u32.to_ne_bytes(),
+            &((NFNL_SUBSYS_NFTABLES << 8) | NFT_MSG_NEWTABLE).to_ne_bytes(),
+            &(NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE).to_ne_bytes(),
+            &7u32.to_ne_bytes(),
+            &0u32.to_ne_bytes(),
+            // nfgenmsg
+            &[2, 0, 0, 0],
+            // NFTA_TABLE_NAME
+            &12u16.to_ne_bytes(),
+            &NFTA_TABLE_NAME.to_ne_bytes(),
+            b"mirrord\0",
+        ];
+
+        assert_eq!(message.buffer, expected.concat());
+    }
+
+    #[test]
+    fn encode_nested_length() {
+        let mut message = Message::new_rule(Family::Ipv4, "t", "c", &[Expr::Redirect]);
+        message.finish(1);
+
+        // nlmsghdr + nfgenmsg + table + chain
+        let expressions = message.buffer.get(NLMSG_HDRLEN + 4 + 8 + 8..).unwrap();
+        let (header, _) = expressions.split_first_chunk::<4>().unwrap();
+        let [l0, l1, t0, t1] = *header;
+
+        assert_eq!(usize::from(u16::from_ne_bytes([l0, l1])), expressions.len());
+        assert_eq!(
+            u16::from_ne_bytes([t0, t1]),
+            NFTA_RULE_EXPRESSIONS | NLA_F_NESTED
+        );
+    }
+}
@@ -6,7 +6,7 @@ use std::{
 
 use actix_codec::Framed;
 use futures::{SinkExt, TryStreamExt};
-use mirrord_protocol::{ClientMessage, DaemonCodec, DaemonMessage};
+use mirrord_protocol::{ClientMessage, DaemonCodec, DaemonMessage, compression::Compression};
 use mirrord_tls_util::{GetSanError, HasSubjectAlternateNames};
 use thiserror::Error;
 use tokio::net::TcpStream;
@@ -123,6 +123,14 @@ impl ClientConnection {
         Ok(())
     }
 
+    /// Sets compression of the [`DaemonMessage`]s sent to the client.
+    pub fn set_compression(&mut self, compression: Option<Compression>) {
+        match &mut self.framed {
+            ConnectionFramed::Tcp(framed) => framed.codec_mut().set_compression(compression),
+            ConnectionFramed::Tls(framed) => framed.codec_mut().set_compression(compression),
+        }
+    }
+
     /// Receives a [`ClientMessage`] from the client.
     #[tracing::instrument(level = "trace", err)]
     pub async fn receive(&mut self) -> io::Result<Option<ClientMessage>> {
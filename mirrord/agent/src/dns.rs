@@ -1,7 +1,8 @@
 use std::{
     collections::HashMap,
     future, io,
-    path::PathBuf,
+    ops::Not,
+    path::{Path, PathBuf},
     sync::{Arc, atomic::Ordering},
     time::Duration,
 };
@@ -12,15 +13,19 @@ use hickory_resolver::{
     config::{LookupIpStrategy, ServerOrderingStrategy},
     lookup_ip::LookupIp,
     net::{DnsError, NetError, runtime::TokioRuntimeProvider},
-    proto::rr::{IntoName, Name},
+    proto::{
+        ProtoError,
+        rr::{DNSClass, IntoName, Name, Record, RecordType},
+        serialize::binary::{BinEncodable, BinEncoder},
+    },
     system_conf::parse_resolv_conf,
 };
 use mirrord_agent_env::envs;
 use mirrord_protocol::{
     DnsLookupError, ResolveErrorKindInternal, ResponseError,
     dns::{
-        AddressFamily, DnsLookup, GetAddrInfoRequest, GetAddrInfoRequestV2, GetAddrInfoResponse,
-        LookupRecord,
+        AddressFamily, DnsLookup, DnsQueryRequest, DnsQueryResponse, DnsRecord, GetAddrInfoRequest,
+        GetAddrInfoRequestV2, GetAddrInfoResponse, LookupRecord,
     },
 };
 use thiserror::Error;
@@ -30,12 +35,14 @@ use tokio::{
         mpsc::{Receiver, Sender},
         oneshot,
     },
-    task::{Id, JoinSet},
+    task::{Id, JoinError, JoinSet},
 };
 use tokio_util::sync::CancellationToken;
 use tracing::{Level, warn};
 
-use crate::{error::AgentResult, metrics::DNS_REQUEST_COUNT, task::status::BgTaskStatus};
+use crate::{
+    error::AgentResult, metrics::DNS_REQUEST_COUNT, quota::DnsRateLimit, task::status::BgTaskStatus,
+};
 
 #[derive(Debug)]
 pub(crate) enum ClientGetAddrInfoRequest {
@@ -54,11 +61,22 @@ impl ClientGetAddrInfoRequest {
 
 /// Sent from per-client [`DnsApi`] to the global [`DnsWorker`].
 #[derive(Debug)]
-pub(crate) struct DnsCommand {
-    request: ClientGetAddrInfoRequest,
-    response_tx: oneshot::Sender<Result<DnsLookup, ResolveErrorKindInternal>>,
+pub(crate) enum DnsCommand {
+    /// Resolve a hostname to IP addresses.
+    GetAddrInfo {
+        request: ClientGetAddrInfoRequest,
+        response_tx: oneshot::Sender<Result<DnsLookup, ResolveErrorKindInternal>>,
+    },
+    /// Query records of an arbitrary type (e.g. `SRV`).
+    Query {
+        request: DnsQueryRequest,
+        response_tx: oneshot::Sender<Result<Vec<DnsRecord>, ResolveErrorKindInternal>>,
+    },
 }
 
+/// Sender for the result of a [`DnsCommand`].
+type ResponseTx<T> = oneshot::Sender<Result<T, ResolveErrorKindInternal>>;
+
 /// Background task for resolving hostnames to IP addresses.
 /// Should be run in the same network namespace as the agent's target.
 pub(crate) struct DnsWorker {
@@ -77,11 +95,16 @@ pub(crate) struct DnsWorker {
     ///
     /// Configured via [`envs::DNS_TIMEOUT`].
     timeout: Option<Duration>,
-    /// Background tasks that handle the DNS requests.
+    /// Background tasks that handle the [`DnsCommand::GetAddrInfo`] requests.
     ///
     /// Each of these builds a new [`Resolver`] and performs one lookup.
     tasks: JoinSet<Result<DnsLookup, InternalLookupError>>,
-    response_txs: HashMap<Id, oneshot::Sender<Result<DnsLookup, ResolveErrorKindInternal>>>,
+    response_txs: HashMap<Id, ResponseTx<DnsLookup>>,
+    /// Background tasks that handle the [`DnsCommand::Query`] requests.
+    ///
+    /// Each of these builds a new [`Resolver`] and performs one query.
+    query_tasks: JoinSet<Result<Vec<DnsRecord>, InternalLookupError>>,
+    query_response_txs: HashMap<Id, ResponseTx<Vec<DnsRecord>>>,
 }
 
 impl DnsWorker {
@@ -120,24 +143,18 @@ impl DnsWorker {
             attempts,
             tasks: Default::default(),
             response_txs: Default::default(),
+            query_tasks: Default::default(),
+            query_response_txs: Default::default(),
         }
     }
 
-    /// Reads `/etc/resolv.conf` and `/etc/hosts` files, then uses [`Resolver`] to
-    /// resolve address of the given `host`.
-    ///
-    /// # TODO
-    ///
-    /// We could probably cache results here.
-    /// We cannot cache the [`Resolver`] itself, becaues the configuration in `etc` may
-    /// change.
-    #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
-    async fn do_lookup(
-        etc_path: PathBuf,
-        request: GetAddrInfoRequestV2,
+    /// Reads `/etc/resolv.conf` and `/etc/hosts` files, and builds a [`Resolver`] from them.
+    async fn build_resolver(
+        etc_path: &Path,
         attempts: Option<usize>,
         timeout: Option<Duration>,
-    ) -> Result<DnsLookup, InternalLookupError> {
+        ip_strategy: LookupIpStrategy,
+    ) -> Result<Resolver<TokioRuntimeProvider>, InternalLookupError> {
         // Prepares the `Resolver` after reading some `/etc` DNS files.
         //
         // We care about logging these errors, at an `error!` level.
@@ -164,7 +181,7 @@ impl DnsWorker {
                 options.attempts = attempts;
             }
 
-            options.ip_strategy = request.family.convert();
+            options.ip_strategy = ip_strategy;
 
             tracing::debug!(?config, ?options, "Updated resolv configuration");
 
@@ -185,8 +202,27 @@ impl DnsWorker {
             Ok(resolver)
         }
         .await;
-        let resolver = resolver
-            .inspect_err(|error| tracing::error!(%error, "Failed to build a DNS resolver"))?;
+
+        resolver.inspect_err(|error| tracing::error!(%error, "Failed to build a DNS resolver"))
+    }
+
+    /// Reads `/etc/resolv.conf` and `/etc/hosts` files, then uses [`Resolver`] to
+    /// resolve address of the given `host`.
+    ///
+    /// # TODO
+    ///
+    /// We could probably cache results here.
+    /// We cannot cache the [`Resolver`] itself, becaues the configuration in `etc` may
+    /// change.
+    #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
+    async fn do_lookup(
+        etc_path: PathBuf,
+        request: GetAddrInfoRequestV2,
+        attempts: Option<usize>,
+        timeout: Option<Duration>,
+    ) -> Result<DnsLookup, InternalLookupError> {
+        let resolver =
+            Self::build_resolver(&etc_path, attempts, timeout, request.family.convert()).await?;
 
         let result = if request.node.to_ip().is_some() {
             // If `request.node` is an IP address,
@@ -214,6 +250,48 @@ impl DnsWorker {
         Ok(lookup)
     }
 
+    /// Reads `/etc/resolv.conf` and `/etc/hosts` files, then uses [`Resolver`] to
+    /// query records of the requested type.
+    ///
+    /// The [`Resolver`] supports only the `IN` class.
+    #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
+    async fn do_query(
+        etc_path: PathBuf,
+        request: DnsQueryRequest,
+        attempts: Option<usize>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<DnsRecord>, InternalLookupError> {
+        if request.class != u16::from(DNSClass::IN) {
+            return Err(InternalLookupError::UnsupportedClass(request.class));
+        }
+
+        let resolver =
+            Self::build_resolver(&etc_path, attempts, timeout, LookupIpStrategy::default()).await?;
+
+        // Relaxed parsing mode, same as in `Self::do_lookup`.
+        // Service names used in SRV queries always contain `_` characters.
+        let mut name = Name::from_str_relaxed(&request.name)
+            .map_err(|error| format!("name rejected by hickory: {error:?}"))
+            .map_err(NetError::Msg)?;
+        // Without `search`, the name is absolute, like in `res_query`.
+        if request.search.not() {
+            name.set_fqdn(true);
+        }
+
+        let lookup = resolver
+            .lookup(name, RecordType::from(request.record_type))
+            .await
+            .inspect(|lookup| tracing::trace!(?lookup, "DNS query finished"))
+            .inspect_err(|error| tracing::debug!(%error, "DNS query failed"))?;
+
+        lookup
+            .answers()
+            .iter()
+            .map(ProtocolConversion::<Result<DnsRecord, ProtoError>>::convert)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| InternalLookupError::ResolveError(NetError::Proto(error)))
+    }
+
     /// Handles the given [`DnsCommand`] in a separate [`tokio::task`].
     #[tracing::instrument(level = Level::TRACE, skip(self))]
     fn handle_message(&mut self, message: DnsCommand) {
@@ -221,46 +299,73 @@ impl DnsWorker {
         let timeout = self.timeout;
         let attempts = self.attempts;
 
-        let handle = self.tasks.spawn(Self::do_lookup(
-            etc_path,
-            message.request.into_v2(),
-            attempts,
-            timeout,
-        ));
-        self.response_txs.insert(handle.id(), message.response_tx);
+        match message {
+            DnsCommand::GetAddrInfo {
+                request,
+                response_tx,
+            } => {
+                let handle = self.tasks.spawn(Self::do_lookup(
+                    etc_path,
+                    request.into_v2(),
+                    attempts,
+                    timeout,
+                ));
+                self.response_txs.insert(handle.id(), response_tx);
+            }
+            DnsCommand::Query {
+                request,
+                response_tx,
+            } => {
+                let handle = self
+                    .query_tasks
+                    .spawn(Self::do_query(etc_path, request, attempts, timeout));
+                self.query_response_txs.insert(handle.id(), response_tx);
+            }
+        }
 
         DNS_REQUEST_COUNT.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Sends the result of a finished DNS task to the matching response channel.
+    fn send_result<T>(
+        result: Result<(Id, Result<T, InternalLookupError>), JoinError>,
+        response_txs: &mut HashMap<Id, ResponseTx<T>>,
+    ) {
+        DNS_REQUEST_COUNT.fetch_sub(1, Ordering::Relaxed);
+        let (id, result) = match result {
+            Ok((id, result)) => (id, result.map_err(Into::into)),
+            Err(error) => (
+                error.id(),
+                Err(ResolveErrorKindInternal::Message(
+                    "DNS task panicked".into(),
+                )),
+            ),
+        };
+
+        match response_txs.remove(&id) {
+            Some(response_tx) => {
+                let _ = response_tx.send(result);
+            }
+            None => {
+                warn!(
+                    ?id,
+                    "Received a DNS result with no matching response channel"
+                );
+            }
+        }
+    }
+
     pub(crate) async fn run(mut self, cancellation_token: CancellationToken) {
         loop {
             tokio::select! {
                 _ = cancellation_token.cancelled() => break,
 
                 Some(result) = self.tasks.join_next_with_id() => {
-                    DNS_REQUEST_COUNT.fetch_sub(1, Ordering::Relaxed);
-                    let (id, result) = match result {
-                        Ok((id, result)) => (
-                            id,
-                            result.map_err(Into::into),
-                        ),
-                        Err(error) => {
-                            (
-                                error.id(),
-                                Err(ResolveErrorKindInternal::Message("DNS task panicked".into()))
-                            )
-                        }
-                    };
-
-                    let response_tx = self.response_txs.remove(&id);
-                    match response_tx {
-                        Some(response_tx) => {
-                            let _ = response_tx.send(result);
-                        }
-                        None => {
-                            warn!(?id, "Received a DNS result with no matching response channel");
-                        }
-                    }
+                    Self::send_result(result, &mut self.response_txs);
+                }
+
+                Some(result) = self.query_tasks.join_next_with_id() => {
+                    Self::send_result(result, &mut self.query_response_txs);
                 }
 
                 message = self.request_rx.recv() => match message {
@@ -274,7 +379,7 @@ impl DnsWorker {
 
 impl Drop for DnsWorker {
     fn drop(&mut self) {
-        let dropped_tasks = self.tasks.len();
+        let dropped_tasks = self.tasks.len() + self.query_tasks.len();
         DNS_REQUEST_COUNT.fetch_sub(dropped_tasks, Ordering::Relaxed);
     }
 }
@@ -285,15 +390,57 @@ pub(crate) struct DnsApi {
     /// [`DnsWorker`] processes all requests concurrently, so we use a combination of [`oneshot`]
     /// channels and [`FuturesOrdered`] to preserve order of responses.
     responses: FuturesOrdered<oneshot::Receiver<Result<DnsLookup, ResolveErrorKindInternal>>>,
+    /// Same as [`Self::responses`], but for [`DnsQueryRequest`]s.
+    ///
+    /// Kept separate, because the clients match the responses to requests separately for each
+    /// request type.
+    query_responses:
+        FuturesOrdered<oneshot::Receiver<Result<Vec<DnsRecord>, ResolveErrorKindInternal>>>,
+    /// [`None`] when the client can make any number of DNS requests.
+    rate_limit: Option<DnsRateLimit>,
 }
 
 impl DnsApi {
-    pub(crate) fn new(task_status: BgTaskStatus, task_sender: Sender<DnsCommand>) -> Self {
+    pub(crate) fn new(
+        task_status: BgTaskStatus,
+        task_sender: Sender<DnsCommand>,
+        rate_limit: Option<DnsRateLimit>,
+    ) -> Self {
         Self {
             task_status,
             request_tx: task_sender,
             responses: Default::default(),
+            query_responses: Default::default(),
+            rate_limit,
+        }
+    }
+
+    /// Sends the command to the [`DnsWorker`], unless the client is over its [`DnsRateLimit`].
+    ///
+    /// In the latter case, the command is failed immediately.
+    async fn send_command(&self, command: DnsCommand) -> AgentResult<()> {
+        let exceeded = self
+            .rate_limit
+            .as_ref()
+            .and_then(|rate_limit| rate_limit.try_acquire().err());
+
+        match (exceeded, command) {
+            (None, command) => {
+                if self.request_tx.send(command).await.is_err() {
+                    return Err(self.task_status.wait_assert_running().await);
+                }
+            }
+            (Some(exceeded), DnsCommand::GetAddrInfo { response_tx, .. }) => {
+                let _ =
+                    response_tx.send(Err(ResolveErrorKindInternal::Message(exceeded.to_string())));
+            }
+            (Some(exceeded), DnsCommand::Query { response_tx, .. }) => {
+                let _ =
+                    response_tx.send(Err(ResolveErrorKindInternal::Message(exceeded.to_string())));
+            }
         }
+
+        Ok(())
     }
 
     /// Schedules a new DNS request.
@@ -305,13 +452,11 @@ impl DnsApi {
     ) -> AgentResult<()> {
         let (response_tx, response_rx) = oneshot::channel();
 
-        let command = DnsCommand {
+        let command = DnsCommand::GetAddrInfo {
             request,
             response_tx,
         };
-        if self.request_tx.send(command).await.is_err() {
-            return Err(self.task_status.wait_assert_running().await);
-        }
+        self.send_command(command).await?;
 
         self.responses.push_back(response_rx);
 
@@ -343,6 +488,46 @@ impl DnsApi {
             Err(..) => Err(self.task_status.wait_assert_running().await),
         }
     }
+
+    /// Schedules a new DNS query.
+    ///
+    /// Results of scheduled queries are available via [`Self::recv_query`] (order is preserved).
+    pub(crate) async fn make_query(&mut self, request: DnsQueryRequest) -> AgentResult<()> {
+        let (response_tx, response_rx) = oneshot::channel();
+
+        let command = DnsCommand::Query {
+            request,
+            response_tx,
+        };
+        self.send_command(command).await?;
+
+        self.query_responses.push_back(response_rx);
+
+        Ok(())
+    }
+
+    /// Returns the result of the oldest outstanding DNS query issued with this struct (see
+    /// [`Self::make_query`]).
+    ///
+    /// If there is no outstanding DNS query, never returns.
+    ///
+    /// # Tracing
+    ///
+    /// Do not instrument this method, see [`Self::recv`].
+    pub(crate) async fn recv_query(&mut self) -> AgentResult<DnsQueryResponse> {
+        let Some(response) = self.query_responses.next().await else {
+            return future::pending().await;
+        };
+
+        match response {
+            Ok(response) => {
+                Ok(DnsQueryResponse(response.map_err(|kind| {
+                    ResponseError::DnsLookup(DnsLookupError { kind })
+                })))
+            }
+            Err(..) => Err(self.task_status.wait_assert_running().await),
+        }
+    }
 }
 
 /// Errors that can occur in [`DnsWorker::do_lookup`].
@@ -352,6 +537,8 @@ enum InternalLookupError {
     ReadConfigurationError(#[from] io::Error),
     #[error("resolve error: {0}")]
     ResolveError(#[from] NetError),
+    #[error("unsupported DNS class {0}, only IN is supported")]
+    UnsupportedClass(u16),
 }
 
 impl From<InternalLookupError> for ResolveErrorKindInternal {
@@ -359,6 +546,9 @@ impl From<InternalLookupError> for ResolveErrorKindInternal {
         match value {
             InternalLookupError::ReadConfigurationError(error) => error.kind().convert(),
             InternalLookupError::ResolveError(error) => (&error).convert(),
+            error @ InternalLookupError::UnsupportedClass(..) => {
+                ResolveErrorKindInternal::Message(error.to_string())
+            }
         }
     }
 }
@@ -428,6 +618,25 @@ impl ProtocolConversion<DnsLookup> for LookupIp {
     }
 }
 
+impl ProtocolConversion<Result<DnsRecord, ProtoError>> for &Record {
+    fn convert(self) -> Result<DnsRecord, ProtoError> {
+        let mut data = Vec::new();
+        let mut encoder = BinEncoder::new(&mut data);
+        // Disables name compression, names in the data cannot point to other parts of the
+        // message.
+        encoder.set_canonical_names(true);
+        self.data.emit(&mut encoder)?;
+
+        Ok(DnsRecord {
+            name: self.name.to_string(),
+            record_type: self.data.record_type().into(),
+            class: self.dns_class.into(),
+            ttl: self.ttl,
+            data,
+        })
+    }
+}
+
 impl ProtocolConversion<LookupIpStrategy> for AddressFamily {
     fn convert(self) -> LookupIpStrategy {
         match self {
@@ -449,9 +658,12 @@ impl ProtocolConversion<LookupIpStrategy> for AddressFamily {
 
 #[cfg(test)]
 mod test {
-    use hickory_resolver::proto::rr::Name;
+    use hickory_resolver::proto::rr::{Name, RData, Record, rdata::SRV};
+    use mirrord_protocol::dns::{DNS_CLASS_IN, DnsRecord};
     use rstest::rstest;
 
+    use super::ProtocolConversion;
+
     /// Verifies that [`Name::from_str_relaxed`] behaves as expected.
     ///
     /// This includes accepting underscores in names.
@@ -463,4 +675,32 @@ mod test {
     fn parse_dns_name_with_hickory(#[case] node_name: &str) {
         Name::from_str_relaxed(node_name).unwrap();
     }
+
+    /// Verifies that [`Record`]s are converted into [`DnsRecord`]s with uncompressed data.
+    #[test]
+    fn convert_srv_record() {
+        let name = Name::from_str_relaxed("_kafka._tcp.example.com.").unwrap();
+        let target = Name::from_str_relaxed("broker.example.com.").unwrap();
+        let record = Record::from_rdata(name, 30, RData::SRV(SRV::new(10, 5, 9092, target)));
+
+        let converted: DnsRecord = (&record).convert().unwrap();
+
+        let mut expected_data = vec![0, 10, 0, 5, 0x23, 0x84];
+        for label in ["broker", "example", "com"] {
+            expected_data.push(label.len() as u8);
+            expected_data.extend_from_slice(label.as_bytes());
+        }
+        expected_data.push(0);
+
+        assert_eq!(
+            converted,
+            DnsRecord {
+                name: "_kafka._tcp.example.com.".into(),
+                record_type: 33,
+                class: DNS_CLASS_IN,
+                ttl: 30,
+                data: expected_data,
+            }
+        );
+    }
 }
@@ -17,19 +17,23 @@ use futures::{TryFutureExt, future::OptionFuture};
 use metrics::{CLIENT_COUNT, start_metrics};
 use mirrord_agent_env::envs;
 use mirrord_agent_iptables::{
-    ChainNames, IPTablesWrapper, SafeIpTables,
+    ChainNames, IPTablesWrapper, NftablesRedirect, SafeIpTables,
     error::{IPTablesError, IPTablesResult},
 };
-use mirrord_protocol::{ClientMessage, DaemonMessage, GetEnvVarsRequest};
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, GetEnvVarsRequest,
+    compression::Compression,
+    resume::{ClientSessionResume, DaemonSessionResume, ResumeSessionRequest},
+};
 use socket2::SockRef;
 use tokio::{
     net::{TcpListener, TcpSocket, TcpStream},
     process::Command,
     select,
     signal::unix::SignalKind,
-    sync::mpsc::Sender,
+    sync::mpsc::{Receiver, Sender},
     task::JoinSet,
-    time::{Duration, timeout},
+    time::{Duration, sleep_until, timeout},
 };
 use tokio_util::sync::CancellationToken;
 use tracing::{Level, debug, error, trace, warn};
@@ -48,6 +52,8 @@ use crate::{
     mirror::TcpMirrorApi,
     namespace::NamespaceType,
     outgoing::{TcpOutgoingApi, UdpOutgoingApi, seqpacket::SeqpacketApi},
+    quota::{ClientQuotas, DnsRateLimit, QuotaExceeded, QuotaLimits, QuotaReporter},
+    resume::{Disconnected, ResumableSession, ResumableSessions, ResumeAttempt, SessionLog},
     reverse_dns::ReverseDnsApi,
     runtime::{self, get_container},
     steal::{StealerCommand, TcpStealerApi},
@@ -153,6 +159,10 @@ struct State {
     tls_connector: Option<AgentTlsConnector>,
     /// [`tokio::runtime`] that should be used for network operations ([`BackgroundTasks`]).
     network_runtime: Arc<BgTaskRuntime>,
+    /// Sessions of the clients that enabled session resumption.
+    resumable_sessions: ResumableSessions,
+    /// Quotas applied to every client, see [`ClientQuotas`].
+    quota_limits: QuotaLimits,
 }
 
 impl State {
@@ -165,6 +175,8 @@ impl State {
             .map(AgentTlsConnector::new)
             .transpose()?;
 
+        let quota_limits = QuotaLimits::from_env()?;
+
         let mut env: HashMap<String, String> = HashMap::new();
 
         let (ephemeral, container) = match &args.mode {
@@ -248,6 +260,8 @@ impl State {
             ephemeral,
             tls_connector,
             network_runtime: Arc::new(network_runtime),
+            resumable_sessions: Default::default(),
+            quota_limits,
         })
     }
 
@@ -264,11 +278,25 @@ impl State {
     ) -> u32 {
         let client_id = self.next_client_id.fetch_add(1, Ordering::Relaxed);
 
-        let result = ClientConnection::new(stream, client_id, self.tls_connector.clone())
-            .map_err(AgentError::from)
-            .and_then(|connection| ClientConnectionHandler::new(client_id, connection, tasks, self))
-            .and_then(|client| client.start(cancellation_token))
-            .await;
+        let result = async {
+            let mut connection =
+                ClientConnection::new(stream, client_id, self.tls_connector.clone()).await?;
+            let Some(first_message) = connection.receive().await? else {
+                return Ok(());
+            };
+
+            if let ClientMessage::SessionResume(ClientSessionResume::Resume(request)) =
+                first_message
+            {
+                return self.resume_session(request, connection).await;
+            }
+
+            ClientConnectionHandler::new(client_id, connection, tasks, self)
+                .await?
+                .start(first_message, cancellation_token)
+                .await
+        }
+        .await;
 
         match result {
             Ok(()) => {
@@ -287,6 +315,27 @@ impl State {
         client_id
     }
 
+    /// Hands the new `connection` over to the session that the client wants to resume.
+    ///
+    /// If the session cannot be resumed, notifies the client with
+    /// [`DaemonSessionResume::Expired`].
+    async fn resume_session(
+        &self,
+        request: ResumeSessionRequest,
+        connection: ClientConnection,
+    ) -> AgentResult<()> {
+        let Err(mut connection) = self.resumable_sessions.resume(request, connection).await else {
+            debug!("Client session was resumed on a new connection");
+            return Ok(());
+        };
+
+        connection
+            .send(DaemonMessage::SessionResume(DaemonSessionResume::Expired))
+            .await?;
+
+        Ok(())
+    }
+
     fn is_with_mesh_exclusion(&self) -> bool {
         self.ephemeral
             && envs::EXCLUDE_FROM_MESH.from_env_or_default()
@@ -357,6 +406,16 @@ struct ClientConnectionHandler {
     ready_for_logs: bool,
     /// Client's version of [`mirrord_protocol`].
     protocol_version: ClientProtocolVersion,
+    /// Quota violations reported by this client's components, see [`ClientQuotas`].
+    quota_warnings: Receiver<QuotaExceeded>,
+    /// Counts messages exchanged with the client, see [`mirrord_protocol::resume`].
+    log: SessionLog,
+    /// Set when the client has sent us [`ClientSessionResume::Enable`].
+    resume: Option<ResumableSession>,
+    /// Compression of [`DaemonMessage`]s requested by the client.
+    ///
+    /// Applied again when the session is resumed on a new connection.
+    compression: Option<Compression>,
 }
 
 impl Drop for ClientConnectionHandler {
@@ -380,7 +439,10 @@ impl ClientConnectionHandler {
 
         let file_pid = pid.or_else(|| state.ephemeral.then_some(1));
 
-        let file_manager = FileManager::new(file_pid);
+        let (quota_reporter, quota_warnings) = QuotaReporter::new();
+        let quotas = ClientQuotas::new(state.quota_limits, &quota_reporter);
+
+        let file_manager = FileManager::new(file_pid, quotas.open_files);
 
         let tcp_mirror_api = bg_tasks
             .mirror_handle
@@ -392,11 +454,12 @@ impl ClientConnectionHandler {
             &mut connection,
         )
         .await?;
-        let dns_api = Self::create_dns_api(bg_tasks.dns);
-        let reverse_dns_api = ReverseDnsApi::new(&state.network_runtime);
-        let tcp_outgoing_api = TcpOutgoingApi::new(&state.network_runtime, file_pid);
-        let udp_outgoing_api = UdpOutgoingApi::new(&state.network_runtime);
-        let seqpacket_api = SeqpacketApi::new(&state.network_runtime, file_pid);
+        let dns_api = Self::create_dns_api(bg_tasks.dns, quotas.dns.clone());
+        let reverse_dns_api = ReverseDnsApi::new(&state.network_runtime, quotas.dns);
+        let tcp_outgoing_api =
+            TcpOutgoingApi::new(&state.network_runtime, file_pid, quotas.outgoing.clone());
+        let udp_outgoing_api = UdpOutgoingApi::new(&state.network_runtime, quotas.outgoing.clone());
+        let seqpacket_api = SeqpacketApi::new(&state.network_runtime, file_pid, quotas.outgoing);
 
         let client_handler = Self {
             id,
@@ -412,6 +475,10 @@ impl ClientConnectionHandler {
             state,
             ready_for_logs: false,
             protocol_version,
+            quota_warnings,
+            log: Default::default(),
+            resume: None,
+            compression: None,
         };
 
         CLIENT_COUNT.fetch_add(1, Ordering::Relaxed);
@@ -445,10 +512,13 @@ impl ClientConnectionHandler {
         }
     }
 
-    fn create_dns_api(task: BackgroundTask<DnsCommand>) -> DnsApi {
+    fn create_dns_api(
+        task: BackgroundTask<DnsCommand>,
+        rate_limit: Option<DnsRateLimit>,
+    ) -> DnsApi {
         match task {
             BackgroundTask::Running(task_status, task_sender) => {
-                DnsApi::new(task_status, task_sender)
+                DnsApi::new(task_status, task_sender, rate_limit)
             }
             BackgroundTask::Disabled => unreachable!("dns task is never disabled"),
         }
@@ -456,87 +526,136 @@ impl ClientConnectionHandler {
 
     /// Starts a loop that handles client connection and state.
     ///
-    /// Breaks upon receiver/sender drop.
+    /// Breaks upon receiver/sender drop. If the client enabled session resumption, the loop
+    /// continues after the connection is lost, until the session is resumed or expires.
     #[tracing::instrument(level = Level::TRACE, skip(self))]
-    async fn start(mut self, cancellation_token: CancellationToken) -> AgentResult<()> {
-        let error = loop {
-            select! {
-                message = self.connection.receive() => {
-                    let Some(message) = message? else {
-                        debug!("Client {} disconnected", self.id);
+    async fn start(
+        mut self,
+        first_message: ClientMessage,
+        cancellation_token: CancellationToken,
+    ) -> AgentResult<()> {
+        let error = 'session: {
+            match self.handle_received_message(first_message).await {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(e) => {
+                    error!("Error handling client message: {e:?}");
+                    break 'session e;
+                }
+            }
+
+            loop {
+                let resume_deadline = self
+                    .resume
+                    .as_ref()
+                    .and_then(|resume| resume.disconnected.as_ref())
+                    .map(|disconnected| disconnected.deadline);
+
+                select! {
+                    message = self.connection.receive(), if resume_deadline.is_none() => {
+                        let message = match message {
+                            Ok(Some(message)) => message,
+                            Ok(None) | Err(..) if self.resume.is_some() => {
+                                debug!(?message, "Client {} connection lost, waiting for the session to be resumed", self.id);
+                                self.disconnect();
+                                continue;
+                            }
+                            Ok(None) => {
+                                debug!("Client {} disconnected", self.id);
+                                return Ok(());
+                            }
+                            Err(error) => return Err(error.into()),
+                        };
+
+                        match self.handle_received_message(message).await {
+                            Ok(true) => {},
+                            Ok(false) => return Ok(()),
+                            Err(e) => {
+                                error!("Error handling client message: {e:?}");
+                                break e;
+                            }
+                        }
+                    },
+                    Some(Some(attempt)) = OptionFuture::from(
+                        self.resume.as_mut().map(|resume| resume.attempts.recv())
+                    ) => {
+                        if self.handle_resume_attempt(attempt).await.not() {
+                            return Ok(());
+                        }
+                    },
+                    Some(()) = OptionFuture::from(resume_deadline.map(sleep_until)) => {
+                        warn!("Client {} did not resume the session in time", self.id);
                         return Ok(());
-                    };
-
-                    match self.handle_client_message(message).await {
-                        Ok(true) => {},
-                        Ok(false) => return Ok(()),
-                        Err(e) => {
-                            error!("Error handling client message: {e:?}");
-                            break e;
+                    },
+                    // poll the sniffer API only when it's available
+                    // exit when it stops (means something bad happened if
+                    // it ran and then stopped)
+                    message = async {
+                        match self.tcp_mirror_api { Some(ref mut mirror_api) => {
+                            mirror_api.recv().await
+                        } _ => {
+                            unreachable!()
+                        }}
+                    }, if self.tcp_mirror_api.is_some() => match message {
+                        Ok(message) => {
+                            self.respond(message).await?;
                         }
-                    }
-                },
-                // poll the sniffer API only when it's available
-                // exit when it stops (means something bad happened if
-                // it ran and then stopped)
-                message = async {
-                    match self.tcp_mirror_api { Some(ref mut mirror_api) => {
-                        mirror_api.recv().await
-                    } _ => {
-                        unreachable!()
-                    }}
-                }, if self.tcp_mirror_api.is_some() => match message {
-                    Ok(message) => {
-                        self.respond(message).await?;
-                    }
-                    Err(e) => break e,
-                },
-                message = async {
-                    match self.tcp_stealer_api { Some(ref mut stealer_api) => {
-                        stealer_api.recv().await
-                    } _ => {
-                        unreachable!()
-                    }}
-                }, if self.tcp_stealer_api.is_some() => match message {
-                    Ok(message) => self.respond(message).await?,
-                    Err(e) => break e,
-                },
-                message = self.tcp_outgoing_api.recv_from_task() => match message {
-                    Ok(message) => {
-                        // Being explicit here.
-                        // Throttle permits should be dropped only when the message has been sent and flushed.
-                        let _throttle = message.throttle;
-                        self.respond(message.message).await?
+                        Err(e) => break e,
+                    },
+                    message = async {
+                        match self.tcp_stealer_api { Some(ref mut stealer_api) => {
+                            stealer_api.recv().await
+                        } _ => {
+                            unreachable!()
+                        }}
+                    }, if self.tcp_stealer_api.is_some() => match message {
+                        Ok(message) => self.respond(message).await?,
+                        Err(e) => break e,
+                    },
+                    message = self.tcp_outgoing_api.recv_from_task() => match message {
+                        Ok(message) => {
+                            // Being explicit here.
+                            // Throttle permits should be dropped only when the message has been sent and flushed.
+                            let _throttle = message.throttle;
+                            self.respond(message.message).await?
+                        },
+                        Err(e) => break e,
+                    },
+                    message = self.udp_outgoing_api.recv_from_task() => match message {
+                        Ok(message) => {
+                            // Being explicit here.
+                            // Throttle permits should be dropped only when the message has been sent and flushed.
+                            let _throttle = message.throttle;
+                            self.respond(DaemonMessage::UdpOutgoing(message.message)).await?
+                        },
+                        Err(e) => break e,
+                    },
+                    message = self.seqpacket_api.recv_from_task() => match message {
+                        Ok(message) => {
+                            // Being explicit here.
+                            // Throttle permits should be dropped only when the message has been sent and flushed.
+                            let _throttle = message.throttle;
+                            self.respond(message.message).await?
+                        },
+                        Err(e) => break e,
+                    },
+                    message = self.dns_api.recv() => match message {
+                        Ok(message) => self.respond(DaemonMessage::GetAddrInfoResponse(message)).await?,
+                        Err(e) => break e,
+                    },
+                    message = self.dns_api.recv_query() => match message {
+                        Ok(message) => self.respond(DaemonMessage::DnsQuery(message)).await?,
+                        Err(e) => break e,
                     },
-                    Err(e) => break e,
-                },
-                message = self.udp_outgoing_api.recv_from_task() => match message {
-                    Ok(message) => {
-                        // Being explicit here.
-                        // Throttle permits should be dropped only when the message has been sent and flushed.
-                        let _throttle = message.throttle;
-                        self.respond(DaemonMessage::UdpOutgoing(message.message)).await?
+                    message = self.reverse_dns_api.recv() => match message {
+                        Ok(message) => self.respond(DaemonMessage::ReverseDnsLookup(Ok(message))).await?,
+                        Err(e) => break e,
                     },
-                    Err(e) => break e,
-                },
-                message = self.seqpacket_api.recv_from_task() => match message {
-                    Ok(message) => {
-                        // Being explicit here.
-                        // Throttle permits should be dropped only when the message has been sent and flushed.
-                        let _throttle = message.throttle;
-                        self.respond(message.message).await?
+                    Some(exceeded) = self.quota_warnings.recv() => {
+                        self.respond(DaemonMessage::LogMessage(exceeded.log_message())).await?
                     },
-                    Err(e) => break e,
-                },
-                message = self.dns_api.recv() => match message {
-                    Ok(message) => self.respond(DaemonMessage::GetAddrInfoResponse(message)).await?,
-                    Err(e) => break e,
-                },
-                message = self.reverse_dns_api.recv() => match message {
-                    Ok(message) => self.respond(DaemonMessage::ReverseDnsLookup(Ok(message))).await?,
-                    Err(e) => break e,
-                },
-                _ = cancellation_token.cancelled() => return Ok(()),
+                    _ = cancellation_token.cancelled() => return Ok(()),
+                }
             }
         };
 
@@ -548,13 +667,109 @@ impl ClientConnectionHandler {
     }
 
     /// Sends a [`DaemonMessage`] response to the connected client (`mirrord-layer`).
+    ///
+    /// If the client enabled session resumption, failing to send the message does not end the
+    /// session. The message is replayed when the session is resumed.
     #[tracing::instrument(level = "trace", skip(self))]
     async fn respond(&mut self, response: DaemonMessage) -> AgentResult<()> {
         if matches!(&response, DaemonMessage::LogMessage(..)) && self.ready_for_logs.not() {
             return Ok(());
         }
 
-        self.connection.send(response).await.map_err(Into::into)
+        self.log.record(&response);
+
+        match &self.resume {
+            None => self.connection.send(response).await.map_err(Into::into),
+            Some(ResumableSession {
+                disconnected: Some(disconnected),
+                ..
+            }) => {
+                if self.log.replayable_from() > disconnected.sent {
+                    return Err(AgentError::ResumeReplayOverflow);
+                }
+
+                Ok(())
+            }
+            Some(..) => {
+                if let Err(error) = self.connection.send(response).await {
+                    debug!(
+                        %error,
+                        "Client {} connection lost, waiting for the session to be resumed",
+                        self.id,
+                    );
+                    self.disconnect();
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Marks the connection with the client as lost, if the client enabled session resumption.
+    fn disconnect(&mut self) {
+        if let Some(resume) = self.resume.as_mut()
+            && resume.disconnected.is_none()
+        {
+            resume.disconnected = Some(Disconnected::new(&self.log));
+        }
+    }
+
+    /// Continues the session on the new connection from the [`ResumeAttempt`].
+    ///
+    /// Returns `false` if the session cannot be resumed and should end.
+    #[tracing::instrument(level = Level::TRACE, skip_all, fields(request = ?attempt.request))]
+    async fn handle_resume_attempt(&mut self, attempt: ResumeAttempt) -> bool {
+        let ResumeAttempt {
+            request,
+            mut connection,
+            reject,
+        } = attempt;
+
+        let missed = self
+            .log
+            .replay_from(request.received)
+            .filter(|_| request.replayable_from <= self.log.received);
+        let Some(missed) = missed else {
+            warn!(
+                "Client {} session cannot be resumed, messages that need to be replayed are no longer available",
+                self.id,
+            );
+            let _ = reject.send(connection);
+            return false;
+        };
+        // Dropping the sender notifies that the connection was taken.
+        drop(reject);
+
+        connection.set_compression(self.compression);
+        self.connection = connection;
+        if let Some(resume) = self.resume.as_mut() {
+            resume.disconnected = None;
+        }
+        debug!(
+            replayed = missed.len(),
+            "Client {} session resumed on a new connection", self.id,
+        );
+
+        // Sent directly, these are not counted again.
+        let resumed = DaemonMessage::SessionResume(DaemonSessionResume::Resumed {
+            received: self.log.received,
+        });
+        for message in std::iter::once(resumed).chain(missed) {
+            if let Err(error) = self.connection.send(message).await {
+                debug!(%error, "Client {} connection lost during replay", self.id);
+                self.disconnect();
+                break;
+            }
+        }
+
+        true
+    }
+
+    /// Counts the message in the [`SessionLog`], and handles it with
+    /// [`Self::handle_client_message`].
+    async fn handle_received_message(&mut self, message: ClientMessage) -> AgentResult<bool> {
+        self.log.received += 1;
+        self.handle_client_message(message).await
     }
 
     /// Handles incoming messages from the connected client (`mirrord-layer`).
@@ -613,6 +828,9 @@ impl ClientConnectionHandler {
                 self.reverse_dns_api
                     .request_reverse_lookup(request.ip_address);
             }
+            ClientMessage::DnsQuery(request) => {
+                self.dns_api.make_query(request).await?;
+            }
             ClientMessage::Ping => self.respond(DaemonMessage::Pong).await?,
             // Message handled exclusively by the operator, see its docs for details.
             ClientMessage::OperatorPong(_) => (),
@@ -665,6 +883,31 @@ impl ClientConnectionHandler {
             ClientMessage::ReadyForLogs => {
                 self.ready_for_logs = true;
             }
+            ClientMessage::EnableCompression(compression) => {
+                self.compression = Some(compression);
+                self.connection.set_compression(Some(compression));
+            }
+            ClientMessage::SessionResume(ClientSessionResume::Enable) => {
+                let token = self
+                    .resume
+                    .get_or_insert_with(|| self.state.resumable_sessions.register())
+                    .token;
+                self.log.enable_replay();
+
+                self.respond(DaemonMessage::SessionResume(DaemonSessionResume::Token(
+                    token,
+                )))
+                .await?;
+            }
+            ClientMessage::SessionResume(ClientSessionResume::Resume(..)) => {
+                self.respond(DaemonMessage::Close(
+                    "session can be resumed only with the first message on a new connection"
+                        .to_owned(),
+                ))
+                .await?;
+
+                return Ok(false);
+            }
             ClientMessage::Vpn(_message) => {
                 self.respond(DaemonMessage::Close("VPN is not supported".into()))
                     .await?;
@@ -792,6 +1035,51 @@ async fn get_rules(
     Ok(rules)
 }
 
+/// Result of the service mesh detection in [`uses_nftables_netlink`], which runs only once, so that
+/// the redirection rules are always created and removed with the same backend.
+static NO_MESH_DETECTED: tokio::sync::OnceCell<bool> = tokio::sync::OnceCell::const_new();
+
+/// Whether the redirection rules are managed with [`NftablesRedirect`] instead of the iptables
+/// binaries, see [`envs::NFTABLES_NETLINK`].
+///
+/// Falls back to the iptables binaries when a service mesh is detected, as the mesh rules are
+/// managed with iptables. Must run in the target's network namespace.
+pub(crate) async fn uses_nftables_netlink(with_mesh_exclusion: bool) -> bool {
+    if envs::NFTABLES_NETLINK.from_env_or_default().not() || with_mesh_exclusion {
+        return false;
+    }
+
+    *NO_MESH_DETECTED.get_or_init(detect_no_mesh).await
+}
+
+/// Detects a service mesh in the target's network namespace, returns `true` if there's none.
+///
+/// Failures are treated as no mesh.
+async fn detect_no_mesh() -> bool {
+    let nftables = envs::NFTABLES.try_from_env().unwrap_or_default();
+    let ipv6 = IP_VERSION_AVAILABILITY.v4.not();
+    let detected = tokio::task::spawn_blocking(move || {
+        mirrord_agent_iptables::detect_mesh(&mirrord_agent_iptables::get_iptables(nftables, ipv6))
+    })
+    .await;
+
+    match detected {
+        Ok(Ok(None)) => true,
+        Ok(Ok(Some(mesh))) => {
+            debug!(%mesh, "Service mesh detected, not using nftables over netlink");
+            false
+        }
+        Ok(Err(error)) => {
+            warn!(%error, "Failed to detect service mesh, using nftables over netlink");
+            true
+        }
+        Err(error) => {
+            warn!(%error, "Service mesh detection panicked, using nftables over netlink");
+            true
+        }
+    }
+}
+
 /// Get existing iptable rules created by this agent instance.
 ///
 /// If `clean_existing_rules` is set, the iptables will be cleaned after fetching the existing
@@ -806,6 +1094,21 @@ async fn check_existing_rules(
 
     let IpVersionAvailability { v4, v6 } = &*IP_VERSION_AVAILABILITY;
 
+    if uses_nftables_netlink(with_mesh_exclusion).await {
+        let mut tables = Vec::new();
+        for (enabled, ipv6, family) in [(*v4, false, "ip"), (*v6, true, "ip6")] {
+            if enabled && NftablesRedirect::exists(&chain_names, ipv6).await? {
+                tables.push(format!("{family} nftables table"));
+            }
+        }
+
+        if clean_existing_rules && tables.is_empty().not() {
+            clear_iptable_chain(*v4, *v6, with_mesh_exclusion, &chain_names).await?;
+        }
+
+        return Ok(tables);
+    }
+
     let iptables = v4.then(|| mirrord_agent_iptables::get_iptables(nftables, false));
     let ip6tables = v6.then(|| mirrord_agent_iptables::get_iptables(nftables, true));
 
@@ -1105,6 +1408,21 @@ async fn clear_iptable_chain(
     with_mesh_exclusion: bool,
     chain_names: &ChainNames,
 ) -> Result<(), IPTablesError> {
+    if uses_nftables_netlink(with_mesh_exclusion).await {
+        let v4_result = if clear_ipv4 {
+            NftablesRedirect::remove(chain_names, false).await
+        } else {
+            Ok(())
+        };
+        let v6_result = if clear_ipv6 {
+            NftablesRedirect::remove(chain_names, true).await
+        } else {
+            Ok(())
+        };
+
+        return v4_result.and(v6_result);
+    }
+
     let nftables = envs::NFTABLES.try_from_env().unwrap_or_default();
 
     let clear = async |v6| -> Result<(), IPTablesError> {
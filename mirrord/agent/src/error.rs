@@ -4,7 +4,7 @@ use thiserror::Error;
 
 use crate::{
     client_connection::TlsSetupError, http::filter::FilterCreationError,
-    incoming::RedirectorTaskError, namespace::NamespaceError, runtime,
+    incoming::RedirectorTaskError, namespace::NamespaceError, quota::InvalidQuotaEnv, runtime,
     util::error::AgentRuntimeError,
 };
 
@@ -51,6 +51,9 @@ pub(crate) enum AgentError {
         Box<FilterCreationError>,
     ),
 
+    #[error(transparent)]
+    InvalidQuota(#[from] InvalidQuotaEnv),
+
     #[error("Timeout on accepting first client connection")]
     FirstConnectionTimeout,
 
@@ -68,6 +71,11 @@ pub(crate) enum AgentError {
 
     #[error(transparent)]
     Timeout(#[from] tokio::time::error::Elapsed),
+
+    #[error(
+        "Client session cannot be resumed anymore, too many messages were sent while disconnected"
+    )]
+    ResumeReplayOverflow,
 }
 
 pub(crate) type AgentResult<T, E = AgentError> = std::result::Result<T, E>;
@@ -21,7 +21,8 @@ use nix::unistd::UnlinkatFlags;
 use tracing::{Level, error, trace};
 
 use crate::{
-    error::AgentResult, metrics::OPEN_FD_COUNT, util::path_resolver::InTargetPathResolver,
+    error::AgentResult, metrics::OPEN_FD_COUNT, quota::OpenFilesQuota,
+    util::path_resolver::InTargetPathResolver,
 };
 
 trait PathExt {
@@ -102,13 +103,13 @@ pub(crate) struct FileManager {
     dir_streams: HashMap<u64, Enumerate<ReadDir>>,
     getdents_streams: HashMap<u64, Peekable<GetDEnts64Stream>>,
     fds_iter: RangeInclusive<u64>,
+    /// [`None`] when the client can open any number of files.
+    open_files_quota: Option<OpenFilesQuota>,
 }
 
 impl Drop for FileManager {
     fn drop(&mut self) {
-        let descriptors =
-            self.open_files.len() + self.dir_streams.len() + self.getdents_streams.len();
-        OPEN_FD_COUNT.fetch_sub(descriptors, std::sync::atomic::Ordering::Relaxed);
+        OPEN_FD_COUNT.fetch_sub(self.descriptors(), std::sync::atomic::Ordering::Relaxed);
     }
 }
 
@@ -268,7 +269,7 @@ impl FileManager {
     }
 
     #[tracing::instrument(level = Level::TRACE, ret)]
-    pub fn new(pid: Option<u64>) -> Self {
+    pub fn new(pid: Option<u64>, open_files_quota: Option<OpenFilesQuota>) -> Self {
         let path_resolver = pid.map(InTargetPathResolver::new);
 
         Self {
@@ -277,6 +278,21 @@ impl FileManager {
             dir_streams: Default::default(),
             getdents_streams: Default::default(),
             fds_iter: (0..=u64::MAX),
+            open_files_quota,
+        }
+    }
+
+    /// Number of descriptors held open on behalf of the client (also counted in
+    /// [`OPEN_FD_COUNT`]).
+    fn descriptors(&self) -> usize {
+        self.open_files.len() + self.dir_streams.len() + self.getdents_streams.len()
+    }
+
+    /// Returns [`Err`] if the client has already reached its [`OpenFilesQuota`].
+    fn check_open_files_quota(&self) -> RemoteResult<()> {
+        match &self.open_files_quota {
+            Some(quota) => quota.check(self.descriptors()).map_err(ResponseError::from),
+            None => Ok(()),
         }
     }
 
@@ -294,6 +310,8 @@ impl FileManager {
         path: PathBuf,
         open_options: OpenOptionsInternal,
     ) -> RemoteResult<OpenFileResponse> {
+        self.check_open_files_quota()?;
+
         let path = self.resolve_path(&path)?;
         let file = OpenOptions::from(open_options).open(&path)?;
 
@@ -332,6 +350,8 @@ impl FileManager {
         if let RemoteFile::Directory(relative_dir) = relative_dir {
             let path = relative_dir.join(&path);
 
+            self.check_open_files_quota()?;
+
             let file = OpenOptions::from(open_options).open(&path)?;
 
             let fd = self.fds_iter.next().ok_or_else(|| {
@@ -852,6 +872,8 @@ impl FileManager {
 
     #[tracing::instrument(level = Level::TRACE, skip(self), err(level = Level::DEBUG))]
     pub(crate) fn fdopen_dir(&mut self, fd: u64) -> RemoteResult<OpenDirResponse> {
+        self.check_open_files_quota()?;
+
         let path = match self
             .open_files
             .get(&fd)
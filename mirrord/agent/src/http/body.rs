@@ -1,11 +1,12 @@
 use std::{
     pin::Pin,
-    task::{Context, Poll},
+    task::{Context, Poll, ready},
     vec,
 };
 
-use bytes::Bytes;
-use hyper::body::{Body, Frame, Incoming};
+use bytes::{Bytes, BytesMut};
+use http_body_util::combinators::BoxBody;
+use hyper::body::{Body, Frame, Incoming, SizeHint};
 
 /// [`Body`] that consist of some first [`Frame`]s that were already received,
 /// and the optional rest of the body.
@@ -38,3 +39,88 @@ where
         Pin::new(tail).poll_frame(cx)
     }
 }
+
+/// [`Body`] that keeps a copy of the wrapped body's data, as long as it fits within the limit.
+///
+/// When the wrapped body finishes, the copy is passed to the callback.
+/// If the body fails or is dropped before it finishes, the callback is never called.
+pub struct CapturingBody {
+    inner: BoxBody<Bytes, hyper::Error>,
+    /// [`None`] if the data exceeded [`Self::max_size`].
+    buffer: Option<BytesMut>,
+    max_size: usize,
+    on_finish: Option<Box<dyn FnOnce(Option<Bytes>) + Send + Sync>>,
+}
+
+impl CapturingBody {
+    pub fn new<F>(inner: BoxBody<Bytes, hyper::Error>, max_size: usize, on_finish: F) -> Self
+    where
+        F: 'static + FnOnce(Option<Bytes>) + Send + Sync,
+    {
+        Self {
+            inner,
+            buffer: Some(Default::default()),
+            max_size,
+            on_finish: Some(Box::new(on_finish)),
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(on_finish) = self.on_finish.take() {
+            on_finish(self.buffer.take().map(BytesMut::freeze));
+        }
+    }
+}
+
+impl Body for CapturingBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref()
+                    && let Some(buffer) = this.buffer.as_mut()
+                {
+                    if buffer.len() + data.len() > this.max_size {
+                        this.buffer = None;
+                    } else {
+                        buffer.extend_from_slice(data);
+                    }
+                }
+
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Some(Err(error)) => {
+                this.on_finish = None;
+                Poll::Ready(Some(Err(error)))
+            }
+            None => {
+                this.finish();
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for CapturingBody {
+    fn drop(&mut self) {
+        // hyper does not poll bodies that report the end of stream upfront.
+        if self.inner.is_end_stream() {
+            self.finish();
+        }
+    }
+}
@@ -9,7 +9,10 @@ use jaq_core::{
 };
 use jaq_json::Val;
 use mirrord_agent_env::envs::JAQ_TIME_LIMIT;
-use mirrord_protocol::tcp::{HttpMethodFilter, JqQuery};
+use mirrord_protocol::{
+    tcp::{HttpMethodFilter, JqQuery},
+    websocket,
+};
 use serde_json::Value;
 use serde_json_path::JsonPath;
 use tokio_retry::strategy::ExponentialBackoff;
@@ -38,6 +41,14 @@ pub enum HttpFilter {
 
     /// Header based on header using jq
     HeaderJq(JqQuery),
+
+    /// Filter for WebSocket upgrade requests.
+    WebSocket {
+        /// Matched against each subprotocol offered by the client.
+        subprotocol: Option<Regex>,
+        /// Matched against the [`WebSocketInitialMessage`].
+        initial_message: Option<Regex>,
+    },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -81,6 +92,18 @@ impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
                     .map(HttpFilter::HeaderJq)
                     .map_err(FilterCreationError::Jq)
             }
+            mirrord_protocol::tcp::HttpFilter::WebSocket(filter) => Ok(Self::WebSocket {
+                subprotocol: filter
+                    .subprotocol
+                    .as_ref()
+                    .map(|subprotocol| Regex::new(&format!("(?i){subprotocol}")))
+                    .transpose()?,
+                initial_message: filter
+                    .initial_message
+                    .as_ref()
+                    .map(|message| Regex::new(message.as_str()))
+                    .transpose()?,
+            }),
         }
     }
 }
@@ -208,6 +231,49 @@ impl HttpFilter {
 
                 false
             }
+            Self::WebSocket {
+                subprotocol,
+                initial_message,
+            } => {
+                if websocket::is_websocket_upgrade(&parts.headers).not() {
+                    return false;
+                }
+
+                let subprotocol_matches = subprotocol.as_ref().is_none_or(|filter| {
+                    websocket::offered_subprotocols(&parts.headers).any(|protocol| {
+                        filter
+                            .is_match(protocol)
+                            .inspect_err(|error| {
+                                tracing::error!(
+                                    protocol,
+                                    ?error,
+                                    "Error while matching WebSocket subprotocol"
+                                );
+                            })
+                            .unwrap_or(false)
+                    })
+                });
+
+                // The initial message is only available when the agent accepted the WebSocket
+                // before filtering, see `RedirectedHttp::accept_websocket`.
+                subprotocol_matches
+                    && initial_message.as_ref().is_none_or(|filter| {
+                        parts
+                            .extensions
+                            .get::<WebSocketInitialMessage>()
+                            .is_some_and(|message| {
+                                filter
+                                    .is_match(&message.0)
+                                    .inspect_err(|error| {
+                                        tracing::error!(
+                                            ?error,
+                                            "Error while matching initial WebSocket message"
+                                        );
+                                    })
+                                    .unwrap_or(false)
+                            })
+                    })
+            }
         }
     }
 
@@ -218,8 +284,28 @@ impl HttpFilter {
             _ => false,
         }
     }
+
+    /// Whether this filter needs the [`WebSocketInitialMessage`] to match WebSocket upgrades.
+    pub fn needs_websocket_message(&self) -> bool {
+        match self {
+            HttpFilter::Composite { filters, .. } => {
+                filters.iter().any(HttpFilter::needs_websocket_message)
+            }
+            HttpFilter::WebSocket {
+                initial_message, ..
+            } => initial_message.is_some(),
+            _ => false,
+        }
+    }
 }
 
+/// First data message sent by the client after a WebSocket upgrade, lossily converted to UTF-8.
+///
+/// Stored in the request [`Parts::extensions`] by the agent after it accepted the WebSocket on its
+/// own, and used by [`HttpFilter::WebSocket`].
+#[derive(Clone, Debug)]
+pub struct WebSocketInitialMessage(pub String);
+
 async fn eval_jaq(query: JqQuery, payload: String) -> Result<bool, String> {
     static TIME_LIMIT: LazyLock<Duration> = LazyLock::new(|| {
         Duration::from_secs(JAQ_TIME_LIMIT.try_from_env().ok().flatten().unwrap_or(500))
@@ -328,9 +414,9 @@ mod test {
     use std::{ops::Not, str::FromStr};
 
     use hyper::Request;
-    use mirrord_protocol::tcp::{self, Filter, HttpMethodFilter};
+    use mirrord_protocol::tcp::{self, Filter, HttpMethodFilter, WebSocketFilter};
 
-    use super::HttpFilter;
+    use super::{HttpFilter, WebSocketInitialMessage};
 
     #[tokio::test]
     async fn matching_all_filter() {
@@ -404,4 +490,60 @@ mod test {
         let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
         assert!(!filter.matches::<&[u8]>(&mut input, None).await);
     }
+
+    #[tokio::test]
+    async fn matching_websocket_filter() {
+        let tcp_filter = tcp::HttpFilter::WebSocket(WebSocketFilter {
+            subprotocol: Some(Filter::new("^graphql-".to_owned()).unwrap()),
+            initial_message: Some(
+                Filter::new(r#""type":\s*"connection_init""#.to_owned()).unwrap(),
+            ),
+        });
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+        assert!(filter.needs_websocket_message());
+
+        let request = || {
+            Request::builder()
+                .method("GET")
+                .uri("/subscriptions")
+                .header("upgrade", "websocket")
+                .header("sec-websocket-protocol", "chat, GraphQL-WS")
+                .body(())
+                .unwrap()
+                .into_parts()
+                .0
+        };
+
+        // initial message not received yet
+        let mut input = request();
+        assert!(filter.matches::<&[u8]>(&mut input, None).await.not());
+
+        // should match
+        let mut input = request();
+        input.extensions.insert(WebSocketInitialMessage(
+            r#"{"type": "connection_init"}"#.to_owned(),
+        ));
+        assert!(filter.matches::<&[u8]>(&mut input, None).await);
+
+        // should fail, wrong message
+        let mut input = request();
+        input
+            .extensions
+            .insert(WebSocketInitialMessage(r#"{"type": "ping"}"#.to_owned()));
+        assert!(filter.matches::<&[u8]>(&mut input, None).await.not());
+
+        // should fail, not an upgrade
+        let mut input = Request::builder()
+            .method("GET")
+            .uri("/subscriptions")
+            .header("sec-websocket-protocol", "graphql-ws")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        input.extensions.insert(WebSocketInitialMessage(
+            r#"{"type": "connection_init"}"#.to_owned(),
+        ));
+        assert!(filter.matches::<&[u8]>(&mut input, None).await.not());
+    }
 }
@@ -19,7 +19,10 @@ use std::{
 use composed::ComposedRedirector;
 pub use connection::{
     IncomingStream, IncomingStreamItem,
-    http::{MirroredHttp, RedirectedHttp, ResponseBodyProvider, ResponseProvider, StolenHttp},
+    http::{
+        FallbackRequest, MirroredHttp, RedirectedHttp, ResponseBodyProvider, ResponseProvider,
+        StolenHttp,
+    },
     tcp::{RedirectedTcp, StolenTcp},
 };
 pub use error::{ConnError, RedirectorTaskError};
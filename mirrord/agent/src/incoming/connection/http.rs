@@ -1,43 +1,73 @@
 use std::{
     fmt::{self, Debug},
+    io,
+    ops::Not,
     str::FromStr,
     sync::{Arc, LazyLock},
     time::Duration,
 };
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures::StreamExt;
-use http::{header::CONTENT_LENGTH, request::Parts};
-use http_body_util::{BodyExt, StreamBody, combinators::BoxBody};
+use http::{
+    HeaderValue, Method, Request,
+    header::{
+        CONNECTION, CONTENT_LENGTH, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_EXTENSIONS,
+        SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL, UPGRADE,
+    },
+    request::Parts,
+};
+use http_body_util::{BodyExt, Empty, StreamBody, combinators::BoxBody};
 use hyper::{
     Response,
-    body::Frame,
-    http::{StatusCode, request, response},
+    body::{Frame, Incoming},
+    http::{StatusCode, Version, request, response},
+    upgrade::Upgraded,
 };
+use hyper_util::rt::TokioIo;
 use mirrord_agent_env::envs;
-use mirrord_protocol::tcp::InternalHttpBodyFrame;
+use mirrord_nightly_polyfill::error::Report;
+use mirrord_protocol::{
+    Payload,
+    tcp::{InternalHttpBodyFrame, InternalHttpResponse},
+    websocket::{
+        self, WebSocketFrameCodec, WebSocketFrameError, WebSocketMessage, WebSocketMessageCodec,
+        WebSocketMessageStream,
+    },
+};
 use tokio::{
+    io::AsyncReadExt,
     runtime::Handle,
     sync::{
         broadcast,
         mpsc::{self, error::SendError},
-        oneshot,
+        oneshot, watch,
     },
-    time::error::Elapsed,
+    time::{Instant, error::Elapsed},
 };
 use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
 use tracing::instrument;
 
 use super::{ConnectionInfo, IncomingStream, body_utils::FramesReader};
 use crate::{
-    http::{BoxResponse, body::RolledBackBody, extract_requests::ExtractedRequest},
+    http::{
+        BoxResponse,
+        body::{CapturingBody, RolledBackBody},
+        error::MirrordErrorResponse,
+        extract_requests::ExtractedRequest,
+        filter::WebSocketInitialMessage,
+    },
     incoming::{
         ConnError, IncomingStreamItem, RedirectorTaskConfig,
         connection::{
-            http_task::{HttpTask, StealingClient, UpgradeDataRx},
+            http_task::{
+                AcceptedWebSocket, HttpTask, PassthroughConnection, StealingClient, UpgradeDataRx,
+            },
             optional_broadcast::OptionalBroadcast,
         },
     },
+    metrics::{BYPASSED_REQUESTS, GaugeVecMetricGuard},
 };
 
 /// A redirected HTTP request.
@@ -57,8 +87,27 @@ pub struct RedirectedHttp {
 
     /// Configuration of the RedirectorTask that created this
     redirector_config: RedirectorTaskConfig,
+
+    /// Set when the agent already completed the WebSocket handshake with the HTTP client, see
+    /// [`Self::accept_websocket`].
+    accepted_websocket: Option<AcceptedWebSocket>,
+
+    /// Set when the response sent to the HTTP client is captured for the mirror handles, see
+    /// [`Self::capture_response`].
+    response_capture_tx: Option<watch::Sender<Option<Arc<CapturedResponse>>>>,
 }
 
+/// Copy of the response sent to the HTTP client.
+///
+/// The body is [`None`] if it exceeded [`MAX_BODY_BUFFER_SIZE`].
+pub type CapturedResponse = InternalHttpResponse<Option<Payload>>;
+
+/// Receives the [`CapturedResponse`] for a mirrored HTTP request.
+///
+/// The value stays [`None`] until the response body is finished. If the response fails, the sender
+/// is dropped without setting the value.
+pub type CapturedResponseRx = watch::Receiver<Option<Arc<CapturedResponse>>>;
+
 #[derive(thiserror::Error, Debug)]
 pub enum BufferBodyError {
     #[error(transparent)]
@@ -69,6 +118,22 @@ pub enum BufferBodyError {
     Timeout(#[from] Elapsed),
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum AcceptWebSocketError {
+    #[error("request is not an HTTP/1.1 WebSocket upgrade")]
+    NotWebSocket,
+    #[error(transparent)]
+    Conn(#[from] ConnError),
+    #[error("failed to decode WebSocket frames: {0}")]
+    Frame(#[from] WebSocketFrameError),
+    #[error("client closed the WebSocket before sending the initial message")]
+    ClosedEarly,
+    #[error("initial WebSocket message exceeded max configured size of {} bytes", *MAX_BODY_BUFFER_SIZE)]
+    MessageTooBig,
+    #[error("receiving initial WebSocket message took longer than the max configured timeout of {}ms", MAX_BODY_BUFFER_TIMEOUT.as_millis())]
+    Timeout(#[from] Elapsed),
+}
+
 impl RedirectedHttp {
     /// Should be called in the target's Linux network namespace,
     /// as [`Handle::current()`] is stored in this struct.
@@ -84,6 +149,8 @@ impl RedirectedHttp {
             mirror_tx: None,
             runtime_handle: Handle::current(),
             redirector_config,
+            accepted_websocket: None,
+            response_capture_tx: None,
         }
     }
 
@@ -99,7 +166,9 @@ impl RedirectedHttp {
     ///
     /// For the data to flow, you must start the request task with either [`Self::steal`] or
     /// [`Self::pass_through`].
-    pub fn mirror(&mut self) -> MirroredHttp {
+    ///
+    /// The response sent to the HTTP client is captured only if `capture_response` is set.
+    pub fn mirror(&mut self, capture_response: bool) -> MirroredHttp {
         let rx = match &self.mirror_tx {
             Some(tx) => tx.subscribe(),
             None => {
@@ -135,9 +204,54 @@ impl RedirectedHttp {
                 body_finished: self.request.body_tail.is_none(),
             },
             stream: IncomingStream::Mirror(BroadcastStream::new(rx)),
+            response_rx: capture_response.then(|| self.capture_response()),
         }
     }
 
+    /// Makes this request keep a copy of the response sent to the HTTP client, and returns a
+    /// receiver for the copy.
+    ///
+    /// The copy is made only if some of the receivers are still alive when the response arrives.
+    fn capture_response(&mut self) -> CapturedResponseRx {
+        if let Some(tx) = &self.response_capture_tx {
+            return tx.subscribe();
+        }
+
+        let (capture_tx, capture_rx) = watch::channel(None);
+        self.response_capture_tx = Some(capture_tx.clone());
+
+        let (response_tx, response_rx) = oneshot::channel::<BoxResponse>();
+        let client_tx = std::mem::replace(&mut self.request.response_tx, response_tx);
+
+        self.runtime_handle.spawn(async move {
+            let Ok(response) = response_rx.await else {
+                return;
+            };
+
+            if capture_tx.is_closed() {
+                let _ = client_tx.send(response);
+                return;
+            }
+
+            let (parts, body) = response.into_parts();
+            let status = parts.status;
+            let version = parts.version;
+            let headers = parts.headers.clone();
+            let body = CapturingBody::new(body, *MAX_BODY_BUFFER_SIZE, move |body| {
+                capture_tx.send_replace(Some(Arc::new(CapturedResponse {
+                    status,
+                    version,
+                    headers,
+                    body: body.map(Payload),
+                })));
+            });
+
+            let _ = client_tx.send(Response::from_parts(parts, BoxBody::new(body)));
+        });
+
+        capture_rx
+    }
+
     /// Acquires a steal handle to this request,
     /// and starts the request task in the background.
     ///
@@ -160,6 +274,7 @@ impl RedirectedHttp {
         let task = HttpTask {
             body_tail: self.request.body_tail,
             on_upgrade: self.request.upgrade,
+            accepted_websocket: self.accepted_websocket,
             destination: StealingClient {
                 data_tx: tx,
                 mirror_data_tx: self.mirror_tx.into(),
@@ -177,6 +292,7 @@ impl RedirectedHttp {
                 upgrade_tx,
             },
             redirector_config: self.redirector_config,
+            runtime_handle: self.runtime_handle,
         }
     }
 
@@ -188,6 +304,7 @@ impl RedirectedHttp {
             self.info,
             self.mirror_tx.into(),
             self.request,
+            self.accepted_websocket,
             self.redirector_config,
         );
         self.runtime_handle.spawn(task.run());
@@ -255,6 +372,161 @@ impl RedirectedHttp {
         // Set body_tail to none since we've extracted everything from it
         result.inspect(|_| self.request.body_tail = None)
     }
+
+    /// Whether this request is an HTTP/1.1 WebSocket upgrade that can be accepted with
+    /// [`Self::accept_websocket`].
+    pub fn is_websocket_upgrade(&self) -> bool {
+        self.request.parts.version == Version::HTTP_11
+            && self.request.body_tail.is_none()
+            && self.request.parts.headers.contains_key(SEC_WEBSOCKET_KEY)
+            && websocket::is_websocket_upgrade(&self.request.parts.headers)
+    }
+
+    /// Completes the WebSocket handshake with the HTTP client on the agent side, and waits for the
+    /// first data message sent by the client.
+    ///
+    /// Used when an [`HttpFilter`](crate::http::filter::HttpFilter) needs to see the initial
+    /// message before we can decide where the request goes. The message is stored in the request
+    /// [`Parts::extensions`] as a [`WebSocketInitialMessage`]. All data read from the client is
+    /// later sent to the request destination, before any other data.
+    ///
+    /// The `101 Switching Protocols` response sent here selects the first subprotocol offered by
+    /// the client (if any), and no extensions. The request is narrowed down to the same
+    /// subprotocol and no extensions, so that the request destination cannot pick different ones.
+    /// The response later produced by the request destination is discarded.
+    ///
+    /// Reuses the limits configured for HTTP body buffering.
+    #[instrument(level = "trace", ret)]
+    pub async fn accept_websocket(&mut self) -> Result<(), AcceptWebSocketError> {
+        if self.is_websocket_upgrade().not() {
+            return Err(AcceptWebSocketError::NotWebSocket);
+        }
+
+        let headers = &self.request.parts.headers;
+        let accept_key = headers
+            .get(SEC_WEBSOCKET_KEY)
+            .map(|key| derive_accept_key(key.as_bytes()))
+            .ok_or(AcceptWebSocketError::NotWebSocket)?;
+        let mut response = Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .version(Version::HTTP_11)
+            .header(CONNECTION, "upgrade")
+            .header(UPGRADE, "websocket")
+            .header(SEC_WEBSOCKET_ACCEPT, accept_key);
+        let subprotocol = websocket::offered_subprotocols(headers)
+            .next()
+            .and_then(|subprotocol| HeaderValue::from_str(subprotocol).ok());
+        if let Some(subprotocol) = &subprotocol {
+            response = response.header(SEC_WEBSOCKET_PROTOCOL, subprotocol);
+        }
+        let response = response
+            .body(Empty::new().map_err(|_| unreachable!()).boxed())
+            .map_err(|error| {
+                ConnError::AgentBug(format!(
+                    "failed to build a WebSocket handshake response: {error} [{}:{}]",
+                    file!(),
+                    line!()
+                ))
+            })?;
+
+        // The request destination will not be able to respond to the client anymore.
+        let (response_tx, _) = oneshot::channel();
+        let _ = std::mem::replace(&mut self.request.response_tx, response_tx).send(response);
+
+        // The client will speak the protocol negotiated above, and the request destination must
+        // see the same one.
+        let headers = &mut self.request.parts.headers;
+        headers.remove(SEC_WEBSOCKET_EXTENSIONS);
+        headers.remove(SEC_WEBSOCKET_PROTOCOL);
+        if let Some(subprotocol) = subprotocol {
+            headers.insert(SEC_WEBSOCKET_PROTOCOL, subprotocol);
+        }
+
+        // From now on, the request destination cannot complete the upgrade with the client, so
+        // every outcome has to be recorded in `accepted_websocket`, and `upgrade` is never polled
+        // again.
+        let deadline = Instant::now() + *MAX_BODY_BUFFER_TIMEOUT;
+        let upgraded = match tokio::time::timeout_at(deadline, &mut self.request.upgrade).await {
+            Ok(Ok(upgraded)) => upgraded,
+            Ok(Err(error)) => {
+                let error = ConnError::IncomingHttpError(Arc::new(error));
+                self.accepted_websocket = Some(AcceptedWebSocket::Failed(error.clone()));
+                return Err(error.into());
+            }
+            Err(elapsed) => {
+                self.accepted_websocket = Some(AcceptedWebSocket::Failed(
+                    ConnError::IncomingIoError(Arc::new(io::ErrorKind::TimedOut.into())),
+                ));
+                return Err(elapsed.into());
+            }
+        };
+
+        let mut upgraded = TokioIo::new(upgraded);
+        let mut prefix = BytesMut::new();
+        let result = tokio::time::timeout_at(
+            deadline,
+            Self::read_initial_message(&mut upgraded, &mut prefix),
+        )
+        .await;
+
+        // Even if we failed to get the message, the data read so far goes to the request
+        // destination.
+        self.accepted_websocket = Some(AcceptedWebSocket::Upgraded {
+            upgraded: upgraded.into_inner(),
+            prefix: prefix.freeze(),
+        });
+
+        let message = result??;
+        self.request
+            .parts
+            .extensions
+            .insert(WebSocketInitialMessage(
+                String::from_utf8_lossy(message.payload()).into_owned(),
+            ));
+
+        Ok(())
+    }
+
+    /// Reads from the upgraded connection until the client sends the first WebSocket data message.
+    ///
+    /// All data read is appended to `prefix`.
+    async fn read_initial_message(
+        upgraded: &mut TokioIo<Upgraded>,
+        prefix: &mut BytesMut,
+    ) -> Result<WebSocketMessage, AcceptWebSocketError> {
+        let mut messages =
+            WebSocketMessageStream::new(WebSocketMessageCodec::new(WebSocketFrameCodec {
+                max_payload_len: *MAX_BODY_BUFFER_SIZE,
+            }));
+        let mut chunk = BytesMut::with_capacity(8 * 1024);
+
+        loop {
+            chunk.clear();
+            let read = upgraded
+                .read_buf(&mut chunk)
+                .await
+                .map_err(From::from)
+                .map_err(ConnError::IncomingIoError)?;
+            if read == 0 {
+                return Err(AcceptWebSocketError::ClosedEarly);
+            }
+
+            prefix.extend_from_slice(&chunk);
+            if prefix.len() > *MAX_BODY_BUFFER_SIZE {
+                return Err(AcceptWebSocketError::MessageTooBig);
+            }
+
+            for message in messages.push(&chunk)? {
+                match message {
+                    WebSocketMessage::Close(..) => {
+                        return Err(AcceptWebSocketError::ClosedEarly);
+                    }
+                    message if message.is_data() => return Ok(message),
+                    _ => {}
+                }
+            }
+        }
+    }
 }
 
 impl Debug for RedirectedHttp {
@@ -317,6 +589,10 @@ pub struct StolenHttp {
     pub stream: IncomingStream,
     pub response_provider: ResponseProvider,
     pub redirector_config: RedirectorTaskConfig,
+    /// Handle to the [`tokio::runtime`] in which the request was redirected.
+    ///
+    /// Used to spawn the [`FallbackRequest`] task.
+    pub runtime_handle: Handle,
 }
 
 impl Debug for StolenHttp {
@@ -336,6 +612,145 @@ pub struct RequestHead {
     pub body_finished: bool,
 }
 
+/// Copy of a [`StolenHttp`] request, kept so that the request can still be passed through to its
+/// original destination, when the stealing client fails to handle it.
+///
+/// See [`HttpRequestFallback`](mirrord_protocol::tcp::HttpRequestFallback).
+pub struct FallbackRequest {
+    info: Arc<ConnectionInfo>,
+    parts: Parts,
+    /// [`None`] if the request cannot be passed through, because it's an HTTP upgrade or its body
+    /// exceeded [`MAX_BODY_BUFFER_SIZE`].
+    body: Option<Vec<Frame<Bytes>>>,
+    body_size: usize,
+    body_finished: bool,
+    redirector_config: RedirectorTaskConfig,
+    runtime_handle: Handle,
+}
+
+impl FallbackRequest {
+    pub fn new(request: &StolenHttp) -> Self {
+        let parts = request.request_head.parts.clone();
+        let is_upgrade = parts.headers.contains_key(UPGRADE) || parts.method == Method::CONNECT;
+
+        let mut fallback = Self {
+            info: request.info.clone(),
+            parts,
+            body: is_upgrade.not().then(Vec::new),
+            body_size: 0,
+            body_finished: request.request_head.body_finished,
+            redirector_config: request.redirector_config.clone(),
+            runtime_handle: request.runtime_handle.clone(),
+        };
+        for frame in &request.request_head.body_head {
+            fallback.push_frame(frame);
+        }
+
+        fallback
+    }
+
+    /// Copies the next frame of the request body.
+    pub fn push_frame(&mut self, frame: &InternalHttpBodyFrame) {
+        let Some(body) = self.body.as_mut() else {
+            return;
+        };
+
+        if let InternalHttpBodyFrame::Data(data) = frame {
+            self.body_size += data.len();
+        }
+        if self.body_size > *MAX_BODY_BUFFER_SIZE {
+            self.body = None;
+        } else {
+            body.push(frame.clone().into());
+        }
+    }
+
+    /// Marks the request body as finished.
+    pub fn finish_body(&mut self) {
+        self.body_finished = true;
+    }
+
+    /// Whether the whole request is available, and [`Self::pass_through`] can be called.
+    pub fn is_ready(&self) -> bool {
+        self.body_finished || self.body.is_none()
+    }
+
+    /// Passes the request through to its original destination, and sends the response with the
+    /// given `response_provider`.
+    ///
+    /// If the request copy is not available, responds with a [`MirrordErrorResponse`].
+    pub fn pass_through(self, response_provider: ResponseProvider) {
+        let Self {
+            info,
+            parts,
+            body,
+            redirector_config,
+            runtime_handle,
+            ..
+        } = self;
+        let ResponseProvider {
+            response_tx,
+            upgrade_tx,
+        } = response_provider;
+        // We never keep copies of HTTP upgrades.
+        let _ = upgrade_tx.send(None);
+
+        let Some(body) = body else {
+            let error_response = MirrordErrorResponse::new(
+                parts.version,
+                "the request could not be passed through to its original destination, \
+                because it was an HTTP upgrade or its body was too big",
+            );
+            let _ = response_tx.send(error_response.into());
+            return;
+        };
+
+        runtime_handle.spawn(async move {
+            let _metric = GaugeVecMetricGuard::new(
+                &BYPASSED_REQUESTS,
+                vec![info.original_destination.port().to_string()],
+            );
+
+            let version = parts.version;
+            let body = RolledBackBody::<Incoming> {
+                head: body.into_iter(),
+                tail: None,
+            };
+            let request = Request::from_parts(parts, body);
+
+            let response =
+                match HttpTask::<PassthroughConnection>::send_request(&info, request).await {
+                    Ok(mut response) => {
+                        HttpTask::<PassthroughConnection>::modify_response(
+                            &mut response,
+                            &redirector_config,
+                        );
+                        response.map(BoxBody::new)
+                    }
+                    Err(error) => {
+                        let message = format!(
+                            "failed to pass the request to its original destination: {}",
+                            Report::new(&error).pretty(true)
+                        );
+                        MirrordErrorResponse::new(version, message).into()
+                    }
+                };
+            let _ = response_tx.send(response);
+        });
+    }
+}
+
+impl Debug for FallbackRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FallbackRequest")
+            .field("info", &self.info)
+            .field("body_size", &self.body_size)
+            .field("body_finished", &self.body_finished)
+            .field("available", &self.body.is_some())
+            .finish()
+    }
+}
+
 /// Can be used by a stealing client to send an HTTP response for a stolen HTTP request.
 pub struct ResponseProvider {
     response_tx: oneshot::Sender<BoxResponse>,
@@ -420,6 +835,11 @@ pub struct MirroredHttp {
     pub request_head: RequestHead,
     /// Will not return frames that are already in [`Self::request_head`].
     pub stream: IncomingStream,
+    /// Receives the copy of the response sent to the HTTP client, if it was requested with
+    /// [`MirrorHandle::capture_responses`](crate::incoming::MirrorHandle::capture_responses).
+    ///
+    /// Drop it as soon as possible if you don't need the copy.
+    pub response_rx: Option<CapturedResponseRx>,
 }
 
 impl MirroredHttp {
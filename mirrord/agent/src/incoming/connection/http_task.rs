@@ -1,4 +1,4 @@
-use std::{future::Future, sync::Arc};
+use std::{future::Future, ops::Not, sync::Arc};
 
 use bytes::{Bytes, BytesMut};
 use futures::StreamExt;
@@ -40,12 +40,32 @@ use crate::{
 
 pub type UpgradeDataRx = mpsc::Receiver<Bytes>;
 
+/// WebSocket upgrade handled by the agent before the request reached its destination.
+///
+/// See [`RedirectedHttp::accept_websocket`](super::http::RedirectedHttp::accept_websocket).
+pub enum AcceptedWebSocket {
+    /// The HTTP client accepted the handshake.
+    Upgraded {
+        /// Upgraded connection with the HTTP client.
+        upgraded: Upgraded,
+        /// Data already read from the HTTP client, has to be sent to the destination first.
+        prefix: Bytes,
+    },
+    /// The handshake response was sent, but the upgrade failed, so the connection cannot be
+    /// used anymore.
+    Failed(ConnError),
+}
+
 /// Background task responsible for handling IO on a redirected HTTP request.
 pub struct HttpTask<D> {
     /// Frames that we need to send to the request destination.
     pub body_tail: Option<Incoming>,
     /// Extracted from the original request.
     pub on_upgrade: OnUpgrade,
+    /// Set when the agent already handled the WebSocket handshake with the HTTP client.
+    ///
+    /// In this case, [`Self::on_upgrade`] was already consumed and must not be polled again.
+    pub accepted_websocket: Option<AcceptedWebSocket>,
     /// Destination of the request.
     pub destination: D,
 }
@@ -97,12 +117,25 @@ where
             return Ok(());
         };
 
-        let upgraded_peer = (&mut self.on_upgrade)
-            .await
-            .map_err(From::from)
-            .map_err(ConnError::IncomingHttpError)?;
+        let (upgraded_peer, prefix) = match self.accepted_websocket.take() {
+            Some(AcceptedWebSocket::Upgraded { upgraded, prefix }) => (upgraded, prefix),
+            Some(AcceptedWebSocket::Failed(error)) => return Err(error),
+            None => {
+                let upgraded = (&mut self.on_upgrade)
+                    .await
+                    .map_err(From::from)
+                    .map_err(ConnError::IncomingHttpError)?;
+                (upgraded, Bytes::new())
+            }
+        };
         let mut upgraded_peer = TokioIo::new(upgraded_peer);
 
+        if prefix.is_empty().not() {
+            upgraded_destination
+                .send_data(CowBytes::Owned(prefix))
+                .await?;
+        }
+
         copy_bidirectional::copy_bidirectional(&mut upgraded_peer, &mut upgraded_destination).await
     }
 }
@@ -112,6 +145,7 @@ impl HttpTask<PassthroughConnection> {
         info: Arc<ConnectionInfo>,
         mirror_data_tx: OptionalBroadcast,
         request: ExtractedRequest,
+        accepted_websocket: Option<AcceptedWebSocket>,
         redirector_config: RedirectorTaskConfig,
     ) -> Self {
         let metric = GaugeVecMetricGuard::new(
@@ -180,11 +214,12 @@ impl HttpTask<PassthroughConnection> {
         Self {
             body_tail: request.body_tail,
             on_upgrade: request.upgrade,
+            accepted_websocket,
             destination,
         }
     }
 
-    async fn send_request<B>(
+    pub(super) async fn send_request<B>(
         info: &ConnectionInfo,
         request: Request<B>,
     ) -> Result<Response<Incoming>, ConnError>
@@ -229,7 +264,7 @@ impl HttpTask<PassthroughConnection> {
     ///
     /// Currently just inserts the mirrord agent
     /// header.
-    fn modify_response(
+    pub(super) fn modify_response(
         response: &mut Response<Incoming>,
         redirector_config: &RedirectorTaskConfig,
     ) {
@@ -89,16 +89,27 @@ impl IpTablesRedirector {
                 .get()
                 .expect("Should be set during state initialization!"),
         );
-        let iptables = mirrord_agent_iptables::get_iptables(ntfables, self.ipv6);
-        let iptables = SafeIpTables::create(
-            iptables,
-            &chain_names,
-            self.flush_connections,
-            self.pod_ips.as_deref(),
-            self.ipv6,
-            self.with_mesh_exclusion.is_some(),
-        )
-        .await?;
+        let iptables =
+            if crate::entrypoint::uses_nftables_netlink(self.with_mesh_exclusion.is_some()).await {
+                SafeIpTables::create_nftables(
+                    &chain_names,
+                    self.flush_connections,
+                    self.pod_ips.as_deref(),
+                    self.ipv6,
+                )
+                .await?
+            } else {
+                let iptables = mirrord_agent_iptables::get_iptables(ntfables, self.ipv6);
+                SafeIpTables::create(
+                    iptables,
+                    &chain_names,
+                    self.flush_connections,
+                    self.pod_ips.as_deref(),
+                    self.ipv6,
+                    self.with_mesh_exclusion.is_some(),
+                )
+                .await?
+            };
 
         if let Some((exclusion, port)) = iptables.exclusion().zip(self.with_mesh_exclusion)
             && let Err(error) = exclusion.add_exclusion(port)
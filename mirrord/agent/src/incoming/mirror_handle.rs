@@ -1,4 +1,10 @@
-use std::fmt;
+use std::{
+    fmt,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
 use futures::StreamExt;
 use tokio::sync::{mpsc, oneshot};
@@ -27,6 +33,9 @@ pub struct MirrorHandle {
     task_error: TaskError,
     /// For receiving mirrored connections.
     mirrored_ports: StreamMap<u16, StreamNotifyClose<ReceiverStream<MirroredTraffic>>>,
+    /// Shared with the [`RedirectorTask`](super::RedirectorTask), see
+    /// [`Self::capture_responses`].
+    capture_responses: Arc<AtomicBool>,
 }
 
 impl MirrorHandle {
@@ -35,9 +44,18 @@ impl MirrorHandle {
             message_tx,
             task_error,
             mirrored_ports: Default::default(),
+            capture_responses: Default::default(),
         }
     }
 
+    /// Makes the [`RedirectorTask`](super::RedirectorTask) capture the responses to the HTTP
+    /// requests mirrored through this handle, see [`MirroredHttp::response_rx`].
+    ///
+    /// Applies to all ports, including the ones that are already mirrored.
+    pub fn capture_responses(&self) {
+        self.capture_responses.store(true, Ordering::Relaxed);
+    }
+
     /// Issues a request to start mirroring from the given port.
     ///
     /// If this port is already mirrored, does nothing.
@@ -53,7 +71,11 @@ impl MirrorHandle {
         let (receiver_tx, receiver_rx) = oneshot::channel();
         if self
             .message_tx
-            .send(RedirectRequest::Mirror { port, receiver_tx })
+            .send(RedirectRequest::Mirror {
+                port,
+                capture_responses: self.capture_responses.clone(),
+                receiver_tx,
+            })
             .await
             .is_err()
         {
@@ -96,6 +118,7 @@ impl Clone for MirrorHandle {
             message_tx: self.message_tx.clone(),
             task_error: self.task_error.clone(),
             mirrored_ports: Default::default(),
+            capture_responses: Default::default(),
         }
     }
 }
@@ -4,7 +4,10 @@ use std::{
     fmt,
     ops::Not,
     pin::Pin,
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
     time::Duration,
 };
 
@@ -263,8 +266,9 @@ where
             let mut redirected = RedirectedTcp::new(conn.stream, conn.info);
 
             for mirror_tx in &port_state.mirror_txs {
-                if let Err(TrySendError::Full(..)) =
-                    mirror_tx.try_send(MirroredTraffic::Tcp(redirected.mirror()))
+                if let Err(TrySendError::Full(..)) = mirror_tx
+                    .tx
+                    .try_send(MirroredTraffic::Tcp(redirected.mirror()))
                 {
                     tracing::warn!(
                         connection = ?redirected,
@@ -363,8 +367,10 @@ where
         let mut redirected = RedirectedHttp::new(info, request, self.config.clone());
 
         for mirror_tx in &port_state.mirror_txs {
-            if let Err(TrySendError::Full(..)) =
-                mirror_tx.try_send(MirroredTraffic::Http(redirected.mirror()))
+            let capture_response = mirror_tx.capture_responses.load(Ordering::Relaxed);
+            if let Err(TrySendError::Full(..)) = mirror_tx
+                .tx
+                .try_send(MirroredTraffic::Http(redirected.mirror(capture_response)))
             {
                 tracing::warn!(
                     request = ?redirected,
@@ -389,8 +395,16 @@ where
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
     async fn handle_client_request(&mut self, message: RedirectRequest) -> Result<(), R::Error> {
         match message {
-            RedirectRequest::Mirror { port, receiver_tx } => {
+            RedirectRequest::Mirror {
+                port,
+                capture_responses,
+                receiver_tx,
+            } => {
                 let (conn_tx, conn_rx) = mpsc::channel(32);
+                let mirror_tx = MirrorTx {
+                    tx: conn_tx.clone(),
+                    capture_responses,
+                };
 
                 match self.ports.entry(port) {
                     Entry::Vacant(e) => {
@@ -401,7 +415,7 @@ where
                         self.redirector.add_redirection(port).await?;
                         e.insert_entry(PortState {
                             steal_tx: None,
-                            mirror_txs: vec![conn_tx.clone()],
+                            mirror_txs: vec![mirror_tx],
                             shutdown: Default::default(),
                             connections: Default::default(),
                             cleanup_sleep: None,
@@ -409,7 +423,7 @@ where
                     }
                     Entry::Occupied(mut e) => {
                         e.get_mut().cleanup_sleep = None;
-                        e.get_mut().mirror_txs.push(conn_tx.clone());
+                        e.get_mut().mirror_txs.push(mirror_tx);
                     }
                 };
 
@@ -480,7 +494,7 @@ where
         } = state;
 
         *steal_tx = steal_tx.take().filter(|tx| tx.is_closed().not());
-        mirror_txs.retain(|tx| tx.is_closed().not());
+        mirror_txs.retain(|mirror_tx| mirror_tx.tx.is_closed().not());
 
         // Drain finished connections
         while let Some(joined) = connections.try_join_next() {
@@ -654,6 +668,8 @@ pub enum RedirectRequest {
     },
     Mirror {
         port: u16,
+        /// See [`MirrorHandle::capture_responses`].
+        capture_responses: Arc<AtomicBool>,
         receiver_tx: oneshot::Sender<MirroredConnectionsRx>,
     },
 }
@@ -723,7 +739,7 @@ struct PortState {
     /// Stealer's traffic channel.
     steal_tx: Option<mpsc::Sender<StolenTraffic>>,
     /// Mirrorers' traffic channel.
-    mirror_txs: Vec<mpsc::Sender<MirroredTraffic>>,
+    mirror_txs: Vec<MirrorTx>,
     /// Used to initiate a graceful shutdown of redirected
     /// connections, once the all clients cancel their subscriptions.
     shutdown: CancellationToken,
@@ -733,6 +749,13 @@ struct PortState {
     cleanup_sleep: Option<Pin<Box<Sleep>>>,
 }
 
+/// Mirrorer's traffic channel in the [`PortState`].
+struct MirrorTx {
+    tx: mpsc::Sender<MirroredTraffic>,
+    /// Shared with the [`MirrorHandle`], see [`MirrorHandle::capture_responses`].
+    capture_responses: Arc<AtomicBool>,
+}
+
 impl fmt::Debug for PortState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("PortState")
@@ -767,16 +790,22 @@ mod test {
     use std::{ops::Not, time::Duration};
 
     use bytes::Bytes;
-    use http_body_util::Empty;
+    use http_body_util::{BodyExt, Empty, Full};
+    use hyper::{
+        Response, StatusCode,
+        header::{SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_PROTOCOL},
+        server::conn::http1,
+        service::service_fn,
+    };
     use hyper_util::rt::TokioIo;
     use rstest::rstest;
     use tokio::{
-        io::{AsyncReadExt, AsyncWriteExt},
+        io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
         net::TcpListener,
     };
 
     use crate::incoming::{
-        RedirectorTask, RedirectorTaskConfig, StolenTraffic, test::DummyRedirector,
+        MirroredTraffic, RedirectorTask, RedirectorTaskConfig, StolenTraffic, test::DummyRedirector,
     };
 
     #[rstest]
@@ -965,4 +994,167 @@ mod test {
         std::mem::drop(handle);
         redirector_task.await.unwrap().unwrap();
     }
+
+    /// Verifies that a mirror handle receives a copy of the response to a mirrored HTTP request,
+    /// and that the HTTP client receives the original response intact.
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test]
+    async fn mirrored_request_response_capture() {
+        let (redirector, _state, mut conn_tx) = DummyRedirector::new();
+        let (task, _, mut mirror_handle) = RedirectorTask::new(
+            redirector,
+            Default::default(),
+            RedirectorTaskConfig::from_env(),
+        );
+        tokio::spawn(task.run());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let destination = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = http1::Builder::new()
+                .serve_connection(
+                    TokioIo::new(stream),
+                    service_fn(|_| async {
+                        let mut response = Response::new(Full::new(Bytes::from_static(b"hello")));
+                        *response.status_mut() = StatusCode::CREATED;
+                        Ok::<_, hyper::Error>(response)
+                    }),
+                )
+                .await;
+        });
+
+        mirror_handle.capture_responses();
+        mirror_handle.mirror(destination.port()).await.unwrap();
+        let client_conn = conn_tx.make_connection(destination).await;
+
+        let http_client_task = tokio::spawn(async {
+            let (mut sender, client_conn) =
+                hyper::client::conn::http1::handshake::<_, Empty<Bytes>>(TokioIo::new(client_conn))
+                    .await
+                    .unwrap();
+            tokio::spawn(client_conn);
+            let response = sender
+                .send_request(hyper::Request::new(Default::default()))
+                .await
+                .unwrap();
+            let status = response.status();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            (status, body)
+        });
+
+        let MirroredTraffic::Http(mut http) = mirror_handle.next().await.unwrap().unwrap() else {
+            panic!("falsely detected TCP traffic");
+        };
+
+        let captured = http
+            .response_rx
+            .as_mut()
+            .unwrap()
+            .wait_for(Option::is_some)
+            .await
+            .unwrap()
+            .clone()
+            .unwrap();
+        assert_eq!(captured.status, StatusCode::CREATED);
+        assert_eq!(
+            captured.body.as_deref(),
+            Some(&Bytes::from_static(b"hello"))
+        );
+
+        let (status, body) = http_client_task.await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body.as_ref(), b"hello");
+    }
+
+    /// Reads an HTTP/1 message head, and returns it lowercased.
+    async fn read_head<S: AsyncRead + Unpin>(stream: &mut S) -> String {
+        let mut head = Vec::new();
+        while head.ends_with(b"\r\n\r\n").not() {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        String::from_utf8(head).unwrap().to_lowercase()
+    }
+
+    /// Verifies that when the agent completes a WebSocket handshake on its own, the request passed
+    /// to the destination offers only the subprotocol selected by the agent, and no extensions.
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test]
+    async fn accepted_websocket_narrows_handshake() {
+        let (redirector, _state, mut conn_tx) = DummyRedirector::new();
+        let (task, mut handle, _) = RedirectorTask::new(
+            redirector,
+            Default::default(),
+            RedirectorTaskConfig::from_env(),
+        );
+        tokio::spawn(task.run());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let destination = listener.local_addr().unwrap();
+        handle.steal(destination.port()).await.unwrap();
+
+        let mut client_conn = conn_tx.make_connection(destination).await;
+        client_conn
+            .write_all(
+                b"GET /ws HTTP/1.1\r\n\
+                Host: localhost\r\n\
+                Connection: Upgrade\r\n\
+                Upgrade: websocket\r\n\
+                Sec-WebSocket-Version: 13\r\n\
+                Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                Sec-WebSocket-Protocol: first, second\r\n\
+                Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits\r\n\
+                \r\n",
+            )
+            .await
+            .unwrap();
+
+        let StolenTraffic::Http(mut http) = handle.next().await.unwrap().unwrap() else {
+            panic!("falsely detected TCP traffic");
+        };
+
+        let client_task = tokio::spawn(async move {
+            let response = read_head(&mut client_conn).await;
+            assert!(response.starts_with("http/1.1 101"), "{response}");
+            assert!(
+                response.contains("sec-websocket-protocol: first\r\n"),
+                "{response}"
+            );
+            assert!(
+                response.contains("sec-websocket-extensions").not(),
+                "{response}"
+            );
+
+            // Masked text frame with `hello`.
+            let mask = [1, 2, 3, 4];
+            let mut frame = vec![0x81, 0x80 | 5];
+            frame.extend_from_slice(&mask);
+            frame.extend(b"hello".iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+            client_conn.write_all(&frame).await.unwrap();
+
+            client_conn
+        });
+
+        http.accept_websocket().await.unwrap();
+        let _client_conn = client_task.await.unwrap();
+
+        let headers = &http.parts().headers;
+        assert_eq!(headers.get(SEC_WEBSOCKET_PROTOCOL).unwrap(), "first");
+        assert!(headers.contains_key(SEC_WEBSOCKET_EXTENSIONS).not());
+
+        http.pass_through();
+        let (mut destination_conn, _) = listener.accept().await.unwrap();
+        let request = read_head(&mut destination_conn).await;
+        assert!(
+            request.contains("sec-websocket-protocol: first\r\n"),
+            "{request}"
+        );
+        assert!(request.contains("second").not(), "{request}");
+        assert!(
+            request.contains("sec-websocket-extensions").not(),
+            "{request}"
+        );
+    }
 }
@@ -36,6 +36,10 @@ mod namespace;
 #[cfg(target_os = "linux")]
 mod outgoing;
 #[cfg(target_os = "linux")]
+mod quota;
+#[cfg(target_os = "linux")]
+mod resume;
+#[cfg(target_os = "linux")]
 mod reverse_dns;
 #[cfg(target_os = "linux")]
 mod runtime;
@@ -9,7 +9,7 @@ use mirrord_protocol::{
     ConnectionId, DaemonMessage, LogMessage, Port, RequestId,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, DaemonTcp,
-        HttpRequestMetadata, IncomingTrafficTransportType, InternalHttpBodyNew,
+        HttpRequestMetadata, HttpResponseShadow, IncomingTrafficTransportType, InternalHttpBodyNew,
         InternalHttpRequest, LayerTcp, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV1,
         NewTcpConnectionV2, TcpClose, TcpData,
     },
@@ -40,6 +40,8 @@ pub struct TcpMirrorApi {
     queued_messages: VecDeque<DaemonTcp>,
     port_filters: HashMap<Port, HttpFilter>,
     ongoing_requests: JoinSet<MirroredHttp>,
+    /// Tasks waiting for the responses to the mirrored HTTP requests.
+    pending_shadows: JoinSet<HttpResponseShadow>,
 }
 
 impl TcpMirrorApi {
@@ -60,6 +62,7 @@ impl TcpMirrorApi {
             queued_messages: Default::default(),
             port_filters: Default::default(),
             ongoing_requests: Default::default(),
+            pending_shadows: Default::default(),
         }
     }
 
@@ -88,6 +91,9 @@ impl TcpMirrorApi {
                 self.port_filters.remove(&port);
                 self.mirror_handle.stop_mirror(port);
             }
+            LayerTcp::ShadowHttpResponses => {
+                self.mirror_handle.capture_responses();
+            }
         }
 
         Ok(())
@@ -219,6 +225,15 @@ impl TcpMirrorApi {
                 }
             },
 
+            Some(result) = self.pending_shadows.join_next() => match result {
+                Ok(shadow) => DaemonTcp::HttpResponseShadow(shadow),
+                Err(error) => {
+                    return Ok(DaemonMessage::LogMessage(LogMessage::error(format!(
+                        "Failed to capture the response to a mirrored HTTP request: {error}"
+                    ))));
+                }
+            },
+
             traffic = Self::next(&mut self.mirror_handle, &mut self.ongoing_requests, &self.protocol_version, &self.port_filters) => match traffic? {
                 MirroredTraffic::Tcp(tcp) if self.protocol_version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
                     let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
@@ -276,6 +291,23 @@ impl TcpMirrorApi {
 
                     self.incoming_streams.insert(id, http.stream);
 
+                    // Set only if the client requested [`HttpResponseShadow`]s with
+                    // [`LayerTcp::ShadowHttpResponses`].
+                    if let Some(mut response_rx) = http.response_rx {
+                        self.pending_shadows.spawn(async move {
+                            let response = response_rx
+                                .wait_for(Option::is_some)
+                                .await
+                                .ok()
+                                .and_then(|response| response.as_deref().cloned());
+                            HttpResponseShadow {
+                                connection_id: id,
+                                request_id: Self::REQUEST_ID,
+                                response,
+                            }
+                        });
+                    }
+
                     let message = ChunkedRequestStartV2 {
                         connection_id: id,
                         request_id: Self::REQUEST_ID,
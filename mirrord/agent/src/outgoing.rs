@@ -1,6 +1,7 @@
 use std::{
     collections::{HashMap, VecDeque},
     fmt,
+    ops::Not,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
@@ -8,7 +9,11 @@ use std::{
 };
 
 use bytes::Bytes;
-use futures::{FutureExt, Stream, future::BoxFuture, stream::FuturesUnordered};
+use futures::{
+    FutureExt, Stream,
+    future::{self, BoxFuture},
+    stream::FuturesUnordered,
+};
 use mirrord_protocol::{
     ConnectionId, DaemonMessage, LogMessage, RemoteError, RemoteResult, ResponseError,
     outgoing::{tcp::*, *},
@@ -32,6 +37,7 @@ use crate::{
     error::AgentResult,
     metrics::TCP_OUTGOING_CONNECTION,
     outgoing::throttle::ThrottledStream,
+    quota::OutgoingQuotas,
     task::{
         BgTaskRuntime,
         status::{BgTaskStatus, IntoStatus},
@@ -43,6 +49,7 @@ mod socket_stream;
 mod throttle;
 mod udp;
 
+pub(crate) use throttle::BandwidthLimiter;
 pub(crate) use udp::UdpOutgoingApi;
 
 /// Possibly throttled message.
@@ -86,7 +93,9 @@ impl TcpOutgoingApi {
     ///   will be passed to an
     ///   [`InTargetPathResolver`](crate::util::path_resolver::InTargetPathResolver) to resolve unix
     ///   socket paths.
-    pub(crate) fn new(runtime: &BgTaskRuntime, pid: Option<u64>) -> Self {
+    ///
+    /// * `quotas` - limits on the client's outgoing connections.
+    pub(crate) fn new(runtime: &BgTaskRuntime, pid: Option<u64>, quotas: OutgoingQuotas) -> Self {
         // IMPORTANT: this makes tokio tasks spawn on `runtime`.
         // Do not remove this.
         let _rt = runtime.handle().enter();
@@ -94,8 +103,9 @@ impl TcpOutgoingApi {
         let (layer_tx, layer_rx) = mpsc::channel(1000);
         let (daemon_tx, daemon_rx) = mpsc::channel(1000);
 
-        let task_status = tokio::spawn(TcpOutgoingTask::new(pid, layer_rx, daemon_tx).run())
-            .into_status("TcpOutgoingTask");
+        let task_status =
+            tokio::spawn(TcpOutgoingTask::new(pid, layer_rx, daemon_tx, quotas).run())
+                .into_status("TcpOutgoingTask");
 
         Self {
             task_status,
@@ -138,6 +148,11 @@ struct TcpOutgoingTask {
     connects_v1: FuturesQueue<BoxFuture<'static, RemoteResult<Connected>>>,
     connects_v2: FuturesUnordered<BoxFuture<'static, (RemoteResult<Connected>, Uid)>>,
     throttler: Arc<Semaphore>,
+    quotas: OutgoingQuotas,
+    /// Quota permits of the open connections, see [`OutgoingQuotas::acquire_connection`].
+    connection_permits: HashMap<ConnectionId, OwnedSemaphorePermit>,
+    /// Writes and closes held back by the bandwidth limit.
+    delayed: DelayedMessages<LayerTcpOutgoing>,
 }
 
 impl Drop for TcpOutgoingTask {
@@ -177,6 +192,7 @@ impl TcpOutgoingTask {
         pid: Option<u64>,
         layer_rx: Receiver<LayerTcpOutgoing>,
         daemon_tx: Sender<Throttled<DaemonMessage>>,
+        quotas: OutgoingQuotas,
     ) -> Self {
         Self {
             next_connection_id: 0,
@@ -188,9 +204,34 @@ impl TcpOutgoingTask {
             connects_v1: Default::default(),
             connects_v2: Default::default(),
             throttler: Arc::new(Semaphore::new(Self::THROTTLE_PERMITS)),
+            quotas,
+            connection_permits: Default::default(),
+            delayed: Default::default(),
         }
     }
 
+    /// Returns the message if it can be handled right away, see [`DelayedMessages::delay`].
+    ///
+    /// Closes go through the limit as well, so that they don't overtake the delayed writes.
+    fn delay_for_bandwidth(&mut self, message: LayerTcpOutgoing) -> Option<LayerTcpOutgoing> {
+        let bytes = match &message {
+            LayerTcpOutgoing::Write(write) => write.bytes.len(),
+            LayerTcpOutgoing::Close(..) => 0,
+            LayerTcpOutgoing::Connect(..) | LayerTcpOutgoing::ConnectV2(..) => {
+                return Some(message);
+            }
+        };
+
+        self.delayed.delay(&self.quotas, message, bytes)
+    }
+
+    /// Releases the connection's quota permit and updates the metrics, once both halves of the
+    /// connection are gone.
+    fn connection_closed(&mut self, connection_id: ConnectionId) {
+        self.connection_permits.remove(&connection_id);
+        TCP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
     /// Runs this task as long as the channels connecting it with the [`TcpOutgoingApi`] are open.
     #[tracing::instrument(level = Level::TRACE, skip(self))]
     async fn run(mut self) {
@@ -198,15 +239,22 @@ impl TcpOutgoingTask {
             let channel_closed = select! {
                 biased;
 
-                message = self.layer_rx.recv() => match message {
+                // Stops while too much data is held back by the bandwidth limit.
+                message = self.layer_rx.recv(), if self.delayed.is_full().not() => match message {
                     // We have a message from the layer to be handled.
-                    Some(message) => {
-                        self.handle_layer_msg(message).await.is_err()
+                    Some(message) => match self.delay_for_bandwidth(message) {
+                        Some(message) => self.handle_layer_msg(message).await.is_err(),
+                        None => false,
                     },
                     // Our channel with the layer is closed, this task is no longer needed.
                     None => true,
                 },
 
+                // A message held back by the bandwidth limit can be handled now.
+                Some(message) = self.delayed.next() => {
+                    self.handle_layer_msg(message).await.is_err()
+                },
+
                 // We have data coming from one of our peers.
                 Some((connection_id, remote_read)) = self.readers.next() => {
                     self.handle_connection_read(connection_id, remote_read.transpose()).await.is_err()
@@ -269,7 +317,7 @@ impl TcpOutgoingTask {
 
                 self.readers.remove(&connection_id);
                 self.writers.remove(&connection_id);
-                TCP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                self.connection_closed(connection_id);
 
                 self.daemon_tx
                     .send(
@@ -312,7 +360,7 @@ impl TcpOutgoingTask {
                         "Layer connection is shut down as well, sending close message.",
                     );
 
-                    TCP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                    self.connection_closed(connection_id);
 
                     self.daemon_tx
                         .send(
@@ -327,9 +375,21 @@ impl TcpOutgoingTask {
         Ok(())
     }
 
+    /// Makes the connection, unless the client is over its connections quota.
+    fn connect_within_quota(
+        &self,
+        remote_address: SocketAddress,
+    ) -> BoxFuture<'static, RemoteResult<Connected>> {
+        match self.quotas.acquire_connection() {
+            Ok(permit) => Self::connect(remote_address, self.pid, permit).boxed(),
+            Err(exceeded) => future::ready(Err(exceeded.into())).boxed(),
+        }
+    }
+
     async fn connect(
         remote_address: SocketAddress,
         target_pid: Option<u64>,
+        permit: Option<OwnedSemaphorePermit>,
     ) -> RemoteResult<Connected> {
         let started_at = Instant::now();
         let socket_stream = tokio::time::timeout(
@@ -350,6 +410,7 @@ impl TcpOutgoingTask {
             stream: socket_stream,
             remote_address,
             local_address,
+            permit,
         })
     }
 
@@ -371,6 +432,9 @@ impl TcpOutgoingTask {
                     self.throttler.clone(),
                 ),
             );
+            if let Some(permit) = connected.permit {
+                self.connection_permits.insert(connection_id, permit);
+            }
             TCP_OUTGOING_CONNECTION.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
             DaemonConnect {
@@ -403,7 +467,7 @@ impl TcpOutgoingTask {
             // We make connection to the requested address, split the stream into halves with
             // `io::split`, and put them into respective maps.
             LayerTcpOutgoing::Connect(LayerConnect { remote_address }) => {
-                let fut = Self::connect(remote_address, self.pid).boxed();
+                let fut = self.connect_within_quota(remote_address);
                 self.connects_v1.push(fut);
                 Ok(())
             }
@@ -412,7 +476,8 @@ impl TcpOutgoingTask {
                 uid,
                 remote_address,
             }) => {
-                let fut = Self::connect(remote_address, self.pid)
+                let fut = self
+                    .connect_within_quota(remote_address)
                     .map(move |result| (result, uid))
                     .boxed();
                 self.connects_v2.push(fut);
@@ -456,8 +521,7 @@ impl TcpOutgoingTask {
                                 connection_id,
                                 "Peer connection is shut down as well, sending close message to the client.",
                             );
-                            TCP_OUTGOING_CONNECTION
-                                .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                            self.connection_closed(connection_id);
 
                             self.daemon_tx
                                 .send(
@@ -477,7 +541,7 @@ impl TcpOutgoingTask {
                     Err(error) => {
                         self.writers.remove(&connection_id);
                         self.readers.remove(&connection_id);
-                        TCP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                        self.connection_closed(connection_id);
 
                         tracing::trace!(
                             connection_id,
@@ -509,7 +573,7 @@ impl TcpOutgoingTask {
             LayerTcpOutgoing::Close(LayerClose { connection_id }) => {
                 self.writers.remove(&connection_id);
                 self.readers.remove(&connection_id);
-                TCP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                self.connection_closed(connection_id);
 
                 Ok(())
             }
@@ -524,6 +588,8 @@ struct Connected {
     stream: SocketStream,
     remote_address: SocketAddress,
     local_address: SocketAddress,
+    /// Reserved with [`OutgoingQuotas::acquire_connection`].
+    permit: Option<OwnedSemaphorePermit>,
 }
 
 /// FIFO queue of futures, implements [`Stream`].
@@ -538,6 +604,82 @@ impl<F> FuturesQueue<F> {
     fn push(&mut self, fut: F) {
         self.inner.push_back(fut);
     }
+
+    fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+/// Layer messages held back by the client's bandwidth limit, see
+/// [`OutgoingQuotas::reserve_bandwidth`].
+///
+/// The outgoing tasks poll this along with everything else, so waiting for the limit does not
+/// stall the other connections. While this is [full](Self::is_full), the tasks stop receiving
+/// layer messages, so that the client is slowed down instead of having its data buffered in the
+/// agent.
+struct DelayedMessages<M> {
+    queue: FuturesQueue<BoxFuture<'static, (M, usize)>>,
+    /// Total size of the data sent by the queued messages.
+    bytes: usize,
+}
+
+impl<M: Send + 'static> DelayedMessages<M> {
+    /// How much data can be held back, before we stop receiving layer messages.
+    const MAX_BYTES: usize = 1024 * 1024;
+    /// How many messages can be held back, before we stop receiving layer messages.
+    const MAX_MESSAGES: usize = 1024;
+
+    /// Holds back a layer message that sends `bytes` of data, until that does not exceed the
+    /// client's bandwidth limit.
+    ///
+    /// Returns the message if it can be handled right away. Messages are also held back when
+    /// others already are, so that they don't overtake them.
+    fn delay(&mut self, quotas: &OutgoingQuotas, message: M, bytes: usize) -> Option<M> {
+        let delay = quotas.reserve_bandwidth(bytes);
+        if delay.is_zero() && self.queue.is_empty() {
+            return Some(message);
+        }
+
+        self.bytes += bytes;
+        self.queue.push(
+            tokio::time::sleep(delay)
+                .map(move |()| (message, bytes))
+                .boxed(),
+        );
+        None
+    }
+
+    fn is_full(&self) -> bool {
+        self.bytes >= Self::MAX_BYTES || self.queue.len() >= Self::MAX_MESSAGES
+    }
+}
+
+impl<M> Default for DelayedMessages<M> {
+    fn default() -> Self {
+        Self {
+            queue: Default::default(),
+            bytes: 0,
+        }
+    }
+}
+
+impl<M> Stream for DelayedMessages<M> {
+    type Item = M;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some((message, bytes)) = std::task::ready!(Pin::new(&mut this.queue).poll_next(cx))
+        else {
+            return Poll::Ready(None);
+        };
+
+        this.bytes -= bytes;
+        Poll::Ready(Some(message))
+    }
 }
 
 impl<F> Default for FuturesQueue<F> {
@@ -567,3 +709,37 @@ impl<F: Future + Unpin> Stream for FuturesQueue<F> {
         Poll::Ready(Some(result))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::ops::Not;
+
+    use tokio_stream::StreamExt;
+
+    use super::DelayedMessages;
+    use crate::{outgoing::BandwidthLimiter, quota::OutgoingQuotas};
+
+    /// Verifies that [`DelayedMessages`] fills up once enough data is held back, and makes room as
+    /// the messages are released.
+    #[tokio::test(start_paused = true)]
+    async fn delayed_messages_fill_up() {
+        const MAX_BYTES: usize = DelayedMessages::<u32>::MAX_BYTES;
+        let quotas = OutgoingQuotas {
+            connections: None,
+            bandwidth: Some(BandwidthLimiter::new(MAX_BYTES as u64)),
+        };
+        let mut delayed = DelayedMessages::default();
+
+        // Fits in the burst.
+        assert_eq!(delayed.delay(&quotas, 0, MAX_BYTES), Some(0));
+
+        assert_eq!(delayed.delay(&quotas, 1, MAX_BYTES / 2), None);
+        assert!(delayed.is_full().not());
+        assert_eq!(delayed.delay(&quotas, 2, MAX_BYTES / 2), None);
+        assert!(delayed.is_full());
+
+        assert_eq!(delayed.next().await, Some(1));
+        assert!(delayed.is_full().not());
+        assert_eq!(delayed.next().await, Some(2));
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     collections::HashMap,
     ffi::OsString,
     fmt,
+    ops::Not,
     os::unix::ffi::OsStringExt,
     path::PathBuf,
     pin::Pin,
@@ -10,7 +11,11 @@ use std::{
 };
 
 use bytes::{Bytes, BytesMut};
-use futures::{FutureExt, Stream, StreamExt, future::BoxFuture, stream::FuturesUnordered};
+use futures::{
+    FutureExt, Stream, StreamExt,
+    future::{self, BoxFuture},
+    stream::FuturesUnordered,
+};
 use mirrord_protocol::{
     ConnectionId, DaemonMessage, LogMessage, RemoteError, RemoteResult, ResponseError,
     outgoing::{seqpacket::*, *},
@@ -30,7 +35,8 @@ use tracing::Level;
 use crate::{
     error::AgentResult,
     metrics::SEQPACKET_CONNECTION,
-    outgoing::{Throttled, throttle::ThrottledStream},
+    outgoing::{DelayedMessages, Throttled, throttle::ThrottledStream},
+    quota::OutgoingQuotas,
     task::{
         BgTaskRuntime,
         status::{BgTaskStatus, IntoStatus},
@@ -53,7 +59,7 @@ pub(crate) struct SeqpacketApi {
 impl SeqpacketApi {
     /// Creates a new [`SeqpacketApi`] instance, spawning the [`SeqpacketTask`] on the given
     /// [`BgTaskRuntime`].
-    pub(crate) fn new(runtime: &BgTaskRuntime, pid: Option<u64>) -> Self {
+    pub(crate) fn new(runtime: &BgTaskRuntime, pid: Option<u64>, quotas: OutgoingQuotas) -> Self {
         // IMPORTANT: this makes tokio tasks spawn on `runtime`.
         // Do not remove this.
         let _rt = runtime.handle().enter();
@@ -61,7 +67,7 @@ impl SeqpacketApi {
         let (layer_tx, layer_rx) = mpsc::channel(1000);
         let (daemon_tx, daemon_rx) = mpsc::channel(1000);
 
-        let task_status = tokio::spawn(SeqpacketTask::new(pid, layer_rx, daemon_tx).run())
+        let task_status = tokio::spawn(SeqpacketTask::new(pid, layer_rx, daemon_tx, quotas).run())
             .into_status("SeqpacketTask");
 
         Self {
@@ -105,6 +111,11 @@ struct SeqpacketTask {
     daemon_tx: Sender<Throttled<DaemonMessage>>,
     connects: FuturesUnordered<BoxFuture<'static, (RemoteResult<ConnectedSeqpacket>, Uid)>>,
     throttler: Arc<Semaphore>,
+    quotas: OutgoingQuotas,
+    /// Quota permits of the open connections, see [`OutgoingQuotas::acquire_connection`].
+    connection_permits: HashMap<ConnectionId, OwnedSemaphorePermit>,
+    /// Writes and closes held back by the bandwidth limit.
+    delayed: DelayedMessages<LayerSeqpacket>,
 }
 
 impl Drop for SeqpacketTask {
@@ -138,6 +149,7 @@ impl SeqpacketTask {
         pid: Option<u64>,
         layer_rx: Receiver<LayerSeqpacket>,
         daemon_tx: Sender<Throttled<DaemonMessage>>,
+        quotas: OutgoingQuotas,
     ) -> Self {
         Self {
             next_connection_id: 0,
@@ -148,9 +160,25 @@ impl SeqpacketTask {
             daemon_tx,
             connects: Default::default(),
             throttler: Arc::new(Semaphore::new(Self::THROTTLE_PERMITS)),
+            quotas,
+            connection_permits: Default::default(),
+            delayed: Default::default(),
         }
     }
 
+    /// Returns the message if it can be handled right away, see [`DelayedMessages::delay`].
+    ///
+    /// Closes go through the limit as well, so that they don't overtake the delayed writes.
+    fn delay_for_bandwidth(&mut self, message: LayerSeqpacket) -> Option<LayerSeqpacket> {
+        let bytes = match &message {
+            LayerSeqpacket::Write(write) => write.bytes.len(),
+            LayerSeqpacket::Close(..) => 0,
+            LayerSeqpacket::ConnectV2(..) => return Some(message),
+        };
+
+        self.delayed.delay(&self.quotas, message, bytes)
+    }
+
     /// Runs this task as long as the channels connecting it with the [`SeqpacketApi`] are open.
     #[tracing::instrument(level = Level::TRACE, skip(self))]
     async fn run(mut self) {
@@ -158,11 +186,20 @@ impl SeqpacketTask {
             let channel_closed = select! {
                 biased;
 
-                message = self.layer_rx.recv() => match message {
-                    Some(message) => self.handle_layer_msg(message).await.is_err(),
+                // Stops while too much data is held back by the bandwidth limit.
+                message = self.layer_rx.recv(), if self.delayed.is_full().not() => match message {
+                    Some(message) => match self.delay_for_bandwidth(message) {
+                        Some(message) => self.handle_layer_msg(message).await.is_err(),
+                        None => false,
+                    },
                     None => true,
                 },
 
+                // A message held back by the bandwidth limit can be handled now.
+                Some(message) = self.delayed.next() => {
+                    self.handle_layer_msg(message).await.is_err()
+                },
+
                 Some((connection_id, remote_read)) = self.readers.next() => {
                     self.handle_connection_read(connection_id, remote_read.transpose()).await.is_err()
                 },
@@ -253,6 +290,7 @@ impl SeqpacketTask {
     async fn connect(
         remote_address: SocketAddress,
         target_pid: Option<u64>,
+        permit: Option<OwnedSemaphorePermit>,
     ) -> RemoteResult<ConnectedSeqpacket> {
         let path = Self::connect_path(remote_address.clone(), target_pid)?;
         let socket = UnixSeqpacket::connect(path).await?;
@@ -261,6 +299,7 @@ impl SeqpacketTask {
             socket,
             remote_address,
             local_address: SocketAddress::Unix(UnixAddr::Unnamed),
+            permit,
         })
     }
 
@@ -306,6 +345,9 @@ impl SeqpacketTask {
                     self.throttler.clone(),
                 ),
             );
+            if let Some(permit) = connected.permit {
+                self.connection_permits.insert(connection_id, permit);
+            }
             SEQPACKET_CONNECTION.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
             DaemonConnect {
@@ -335,9 +377,12 @@ impl SeqpacketTask {
                 uid,
                 remote_address,
             }) => {
-                let fut = Self::connect(remote_address, self.pid)
-                    .map(move |result| (result, uid))
-                    .boxed();
+                let fut = match self.quotas.acquire_connection() {
+                    Ok(permit) => Self::connect(remote_address, self.pid, permit)
+                        .map(move |result| (result, uid))
+                        .boxed(),
+                    Err(exceeded) => future::ready((Err(exceeded.into()), uid)).boxed(),
+                };
                 self.connects.push(fut);
                 Ok(())
             }
@@ -405,10 +450,12 @@ impl SeqpacketTask {
         }
     }
 
-    /// Removes the reader and writer half for this [`ConnectionId`] connection.
+    /// Removes the reader and writer half for this [`ConnectionId`] connection, and releases its
+    /// quota permit.
     fn dispose_connection(&mut self, connection_id: ConnectionId) {
         self.readers.remove(&connection_id);
         self.writers.remove(&connection_id);
+        self.connection_permits.remove(&connection_id);
         SEQPACKET_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
     }
 }
@@ -451,4 +498,6 @@ struct ConnectedSeqpacket {
     socket: UnixSeqpacket,
     remote_address: SocketAddress,
     local_address: SocketAddress,
+    /// Reserved with [`OutgoingQuotas::acquire_connection`].
+    permit: Option<OwnedSemaphorePermit>,
 }
@@ -1,13 +1,17 @@
 use std::{
     io,
     pin::Pin,
-    sync::Arc,
+    sync::{Arc, Mutex, PoisonError},
     task::{Context, Poll},
+    time::Duration,
 };
 
 use bytes::Bytes;
 use futures::{Stream, StreamExt};
-use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
 use tokio_util::sync::PollSemaphore;
 
 /// Wrapper over a [`Stream`] of [`Bytes`], that yields items only after acquiring
@@ -72,3 +76,60 @@ where
         }
     }
 }
+
+/// Limits the rate of data sent through outgoing connections.
+///
+/// Cloned instances share the limit, so one limiter can cover all outgoing connections of a
+/// client, see [`OutgoingQuotas`](crate::quota::OutgoingQuotas).
+#[derive(Clone, Debug)]
+pub(crate) struct BandwidthLimiter {
+    bytes_per_second: u64,
+    /// When the data sent so far will have been "paid off" at [`Self::bytes_per_second`].
+    paid_until: Arc<Mutex<Instant>>,
+}
+
+impl BandwidthLimiter {
+    /// How much data can be sent without any delay, expressed as the time it takes to send it at
+    /// the full rate.
+    const BURST: Duration = Duration::from_secs(1);
+
+    pub(crate) fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1),
+            paid_until: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Accounts for `bytes` of data, and returns how long the caller must wait before sending
+    /// them, so that the limit is not exceeded.
+    ///
+    /// The delays are non-decreasing, so data sent in the order of reservations never waits for
+    /// data reserved later.
+    pub(crate) fn reserve(&self, bytes: usize) -> Duration {
+        let mut paid_until = self
+            .paid_until
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64);
+        *paid_until = (*paid_until).max(now) + cost;
+        paid_until.saturating_duration_since(now + Self::BURST)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn bandwidth_limiter() {
+        let limiter = BandwidthLimiter::new(1024);
+        let started_at = Instant::now();
+
+        // Fits in the burst.
+        assert_eq!(limiter.reserve(1024), Duration::ZERO);
+
+        assert_eq!(limiter.clone().reserve(2048), Duration::from_secs(2));
+        assert_eq!(started_at.elapsed(), Duration::ZERO);
+    }
+}
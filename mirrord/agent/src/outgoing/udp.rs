@@ -2,6 +2,7 @@ use core::fmt;
 use std::{
     collections::HashMap,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    ops::Not,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
@@ -30,7 +31,8 @@ use tracing::Level;
 use crate::{
     error::AgentResult,
     metrics::UDP_OUTGOING_CONNECTION,
-    outgoing::{Throttled, throttle::ThrottledStream},
+    outgoing::{DelayedMessages, Throttled, throttle::ThrottledStream},
+    quota::OutgoingQuotas,
     task::{
         BgTaskRuntime,
         status::{BgTaskStatus, IntoStatus},
@@ -50,6 +52,11 @@ struct UdpOutgoingTask {
     layer_rx: Receiver<LayerUdpOutgoing>,
     daemon_tx: Sender<Throttled<DaemonUdpOutgoing>>,
     throttler: Arc<Semaphore>,
+    quotas: OutgoingQuotas,
+    /// Quota permits of the open connections, see [`OutgoingQuotas::acquire_connection`].
+    connection_permits: HashMap<ConnectionId, OwnedSemaphorePermit>,
+    /// Writes and closes held back by the bandwidth limit.
+    delayed: DelayedMessages<LayerUdpOutgoing>,
 }
 
 impl Drop for UdpOutgoingTask {
@@ -78,6 +85,7 @@ impl UdpOutgoingTask {
     fn new(
         layer_rx: Receiver<LayerUdpOutgoing>,
         daemon_tx: Sender<Throttled<DaemonUdpOutgoing>>,
+        quotas: OutgoingQuotas,
     ) -> Self {
         Self {
             next_connection_id: 0,
@@ -86,9 +94,33 @@ impl UdpOutgoingTask {
             layer_rx,
             daemon_tx,
             throttler: Arc::new(Semaphore::new(Self::THROTTLE_PERMITS)),
+            quotas,
+            connection_permits: Default::default(),
+            delayed: Default::default(),
         }
     }
 
+    /// Returns the message if it can be handled right away, see [`DelayedMessages::delay`].
+    ///
+    /// Closes go through the limit as well, so that they don't overtake the delayed writes.
+    fn delay_for_bandwidth(&mut self, message: LayerUdpOutgoing) -> Option<LayerUdpOutgoing> {
+        let bytes = match &message {
+            LayerUdpOutgoing::Write(write) => write.bytes.len(),
+            LayerUdpOutgoing::Close(..) => 0,
+            LayerUdpOutgoing::Connect(..) | LayerUdpOutgoing::ConnectV2(..) => {
+                return Some(message);
+            }
+        };
+
+        self.delayed.delay(&self.quotas, message, bytes)
+    }
+
+    /// Releases the connection's quota permit and updates the metrics.
+    fn connection_closed(&mut self, connection_id: ConnectionId) {
+        self.connection_permits.remove(&connection_id);
+        UDP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
     /// Runs this task as long as the channels connecting it with the [`UdpOutgoingApi`] are open.
     #[tracing::instrument(level = Level::TRACE, skip(self))]
     pub(super) async fn run(mut self) {
@@ -96,15 +128,22 @@ impl UdpOutgoingTask {
             let channel_closed = select! {
                 biased;
 
-                message = self.layer_rx.recv() => match message {
+                // Stops while too much data is held back by the bandwidth limit.
+                message = self.layer_rx.recv(), if self.delayed.is_full().not() => match message {
                     // We have a message from the layer to be handled.
-                    Some(message) => {
-                        self.handle_layer_msg(message).await.is_err()
+                    Some(message) => match self.delay_for_bandwidth(message) {
+                        Some(message) => self.handle_layer_msg(message).await.is_err(),
+                        None => false,
                     },
                     // Our channel with the layer is closed, this task is no longer needed.
                     None => true,
                 },
 
+                // A message held back by the bandwidth limit can be handled now.
+                Some(message) = self.delayed.next() => {
+                    self.handle_layer_msg(message).await.is_err()
+                },
+
                 // We have data coming from one of our peers.
                 Some((connection_id, remote_read)) = self.readers.next() => {
                     self.handle_connection_read(connection_id, remote_read.transpose()).await.is_err()
@@ -157,7 +196,7 @@ impl UdpOutgoingTask {
 
                 self.readers.remove(&connection_id);
                 self.writers.remove(&connection_id);
-                UDP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                self.connection_closed(connection_id);
 
                 let daemon_message = DaemonUdpOutgoing::Close(connection_id);
                 self.daemon_tx.send(daemon_message.into()).await?;
@@ -165,7 +204,7 @@ impl UdpOutgoingTask {
             Ok(None) => {
                 self.writers.remove(&connection_id);
                 self.readers.remove(&connection_id);
-                UDP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                self.connection_closed(connection_id);
 
                 let daemon_message = DaemonUdpOutgoing::Close(connection_id);
                 self.daemon_tx.send(daemon_message.into()).await?;
@@ -191,6 +230,8 @@ impl UdpOutgoingTask {
     ///    connection.
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::DEBUG))]
     async fn connect(&mut self, remote_address: SocketAddress) -> RemoteResult<DaemonConnect> {
+        let permit = self.quotas.acquire_connection()?;
+
         let peer_addr = remote_address.clone().try_into()?;
         let bind_addr = match peer_addr {
             std::net::SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
@@ -219,6 +260,9 @@ impl UdpOutgoingTask {
 
         self.writers.insert(connection_id, (writer, peer_address));
         self.readers.insert(connection_id, reader);
+        if let Some(permit) = permit {
+            self.connection_permits.insert(connection_id, permit);
+        }
         UDP_OUTGOING_CONNECTION.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
         Ok(DaemonConnect {
@@ -288,7 +332,7 @@ impl UdpOutgoingTask {
                     Err(error) => {
                         self.writers.remove(&connection_id);
                         self.readers.remove(&connection_id);
-                        UDP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+                        self.connection_closed(connection_id);
 
                         tracing::trace!(
                             connection_id,
@@ -305,10 +349,10 @@ impl UdpOutgoingTask {
             }
             // [layer] -> [agent]
             // `layer` closed their interceptor stream.
-            LayerUdpOutgoing::Close(LayerClose { ref connection_id }) => {
-                self.writers.remove(connection_id);
-                self.readers.remove(connection_id);
-                UDP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+            LayerUdpOutgoing::Close(LayerClose { connection_id }) => {
+                self.writers.remove(&connection_id);
+                self.readers.remove(&connection_id);
+                self.connection_closed(connection_id);
 
                 Ok(())
             }
@@ -330,7 +374,7 @@ pub(crate) struct UdpOutgoingApi {
 }
 
 impl UdpOutgoingApi {
-    pub(crate) fn new(runtime: &BgTaskRuntime) -> Self {
+    pub(crate) fn new(runtime: &BgTaskRuntime, quotas: OutgoingQuotas) -> Self {
         // IMPORTANT: this makes tokio tasks spawn on `runtime`.
         // Do not remove this.
         let _rt = runtime.handle().enter();
@@ -338,7 +382,7 @@ impl UdpOutgoingApi {
         let (layer_tx, layer_rx) = mpsc::channel(1000);
         let (daemon_tx, daemon_rx) = mpsc::channel(1000);
 
-        let task_status = tokio::spawn(UdpOutgoingTask::new(layer_rx, daemon_tx).run())
+        let task_status = tokio::spawn(UdpOutgoingTask::new(layer_rx, daemon_tx, quotas).run())
             .into_status("UdpOutgoingTask");
 
         Self {
@@ -0,0 +1,334 @@
+//! Per-client resource quotas, configured with `agent.quotas`.
+//!
+//! Every [`ClientConnectionHandler`](crate::entrypoint::ClientConnectionHandler) gets its own
+//! [`ClientQuotas`], so that one runaway client can't exhaust the target's file descriptors or
+//! flood its DNS on behalf of everyone else.
+
+use std::{
+    fmt, io,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use mirrord_agent_env::{
+    checked_env::{CheckedEnv, EnvValue},
+    envs,
+};
+use mirrord_protocol::{DnsLookupError, LogMessage, ResolveErrorKindInternal, ResponseError};
+use thiserror::Error;
+use tokio::{
+    sync::{
+        OwnedSemaphorePermit, Semaphore,
+        mpsc::{self, Receiver, Sender},
+    },
+    time::Instant,
+};
+
+use crate::outgoing::BandwidthLimiter;
+
+/// Resource limited by one of the [`ClientQuotas`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum QuotaResource {
+    OpenFiles,
+    OutgoingConnections,
+    DnsRequestsPerSecond,
+}
+
+impl fmt::Display for QuotaResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OpenFiles => f.write_str("open files"),
+            Self::OutgoingConnections => f.write_str("outgoing connections"),
+            Self::DnsRequestsPerSecond => f.write_str("DNS requests per second"),
+        }
+    }
+}
+
+/// A client tried to go over one of its [`ClientQuotas`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+#[error("quota of {limit} {resource} exceeded")]
+pub(crate) struct QuotaExceeded {
+    pub(crate) resource: QuotaResource,
+    pub(crate) limit: u64,
+}
+
+impl QuotaExceeded {
+    /// The warning we send to the client, so that the user knows why their operations fail.
+    pub(crate) fn log_message(&self) -> LogMessage {
+        LogMessage::warn(format!(
+            "mirrord-agent rejected an operation: {self}, see `agent.quotas` in the mirrord config"
+        ))
+    }
+}
+
+impl From<QuotaExceeded> for ResponseError {
+    fn from(exceeded: QuotaExceeded) -> Self {
+        match exceeded.resource {
+            QuotaResource::OpenFiles => io::Error::from_raw_os_error(libc::EMFILE).into(),
+            QuotaResource::OutgoingConnections => io::Error::from_raw_os_error(libc::EAGAIN).into(),
+            QuotaResource::DnsRequestsPerSecond => Self::DnsLookup(DnsLookupError {
+                kind: ResolveErrorKindInternal::Message(exceeded.to_string()),
+            }),
+        }
+    }
+}
+
+/// Passes [`QuotaExceeded`] errors from the client's components (which may run in separate tasks)
+/// to its [`ClientConnectionHandler`](crate::entrypoint::ClientConnectionHandler), which sends
+/// them to the client as [`LogMessage`]s.
+#[derive(Clone, Debug)]
+pub(crate) struct QuotaReporter(Sender<QuotaExceeded>);
+
+impl QuotaReporter {
+    /// How many warnings can wait for the client handler, excess warnings are dropped.
+    const CHANNEL_CAPACITY: usize = 32;
+
+    pub(crate) fn new() -> (Self, Receiver<QuotaExceeded>) {
+        let (tx, rx) = mpsc::channel(Self::CHANNEL_CAPACITY);
+        (Self(tx), rx)
+    }
+
+    /// Logs the error and queues a warning for the client.
+    pub(crate) fn report(&self, exceeded: QuotaExceeded) -> QuotaExceeded {
+        tracing::warn!(%exceeded, "Client exceeded its quota");
+        let _ = self.0.try_send(exceeded);
+        exceeded
+    }
+}
+
+/// Limits how many files and directories the client can have open in the
+/// [`FileManager`](crate::file::FileManager).
+#[derive(Debug)]
+pub(crate) struct OpenFilesQuota {
+    limit: usize,
+    reporter: QuotaReporter,
+}
+
+impl OpenFilesQuota {
+    /// Returns [`Err`] if the client can't open anything more, having `open` descriptors already.
+    pub(crate) fn check(&self, open: usize) -> Result<(), QuotaExceeded> {
+        if open < self.limit {
+            return Ok(());
+        }
+
+        Err(self.reporter.report(QuotaExceeded {
+            resource: QuotaResource::OpenFiles,
+            limit: self.limit as u64,
+        }))
+    }
+}
+
+/// Limits how many outgoing connections the client can have open, shared by all outgoing traffic
+/// tasks of the client.
+#[derive(Clone, Debug)]
+pub(crate) struct ConnectionsQuota {
+    limit: usize,
+    permits: Arc<Semaphore>,
+    reporter: QuotaReporter,
+}
+
+impl ConnectionsQuota {
+    fn new(limit: usize, reporter: QuotaReporter) -> Self {
+        let limit = limit.min(Semaphore::MAX_PERMITS);
+
+        Self {
+            limit,
+            permits: Arc::new(Semaphore::new(limit)),
+            reporter,
+        }
+    }
+
+    /// Reserves a slot for a new connection.
+    ///
+    /// The returned permit should be dropped only when the connection is closed.
+    pub(crate) fn try_acquire(&self) -> Result<OwnedSemaphorePermit, QuotaExceeded> {
+        self.permits.clone().try_acquire_owned().map_err(|_| {
+            self.reporter.report(QuotaExceeded {
+                resource: QuotaResource::OutgoingConnections,
+                limit: self.limit as u64,
+            })
+        })
+    }
+}
+
+/// Limits how many DNS requests the client can make in a second, shared by the
+/// [`DnsApi`](crate::dns::DnsApi) and [`ReverseDnsApi`](crate::reverse_dns::ReverseDnsApi).
+#[derive(Clone, Debug)]
+pub(crate) struct DnsRateLimit {
+    limit: u32,
+    /// Start of the current window, and how many requests were made in it.
+    window: Arc<Mutex<(Instant, u32)>>,
+    reporter: QuotaReporter,
+}
+
+impl DnsRateLimit {
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    fn new(limit: u32, reporter: QuotaReporter) -> Self {
+        Self {
+            limit,
+            window: Arc::new(Mutex::new((Instant::now(), 0))),
+            reporter,
+        }
+    }
+
+    /// Accounts for a new DNS request, returns [`Err`] if it should be rejected.
+    pub(crate) fn try_acquire(&self) -> Result<(), QuotaExceeded> {
+        let mut window = self.window.lock().unwrap_or_else(PoisonError::into_inner);
+        let (started_at, requests) = &mut *window;
+
+        let now = Instant::now();
+        if now.duration_since(*started_at) >= Self::WINDOW {
+            *started_at = now;
+            *requests = 0;
+        }
+
+        if *requests < self.limit {
+            *requests += 1;
+            return Ok(());
+        }
+
+        Err(self.reporter.report(QuotaExceeded {
+            resource: QuotaResource::DnsRequestsPerSecond,
+            limit: self.limit.into(),
+        }))
+    }
+}
+
+/// Quotas shared by the outgoing traffic tasks of one client.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OutgoingQuotas {
+    pub(crate) connections: Option<ConnectionsQuota>,
+    pub(crate) bandwidth: Option<BandwidthLimiter>,
+}
+
+impl OutgoingQuotas {
+    /// Reserves a slot for a new connection, see [`ConnectionsQuota::try_acquire`].
+    pub(crate) fn acquire_connection(&self) -> Result<Option<OwnedSemaphorePermit>, QuotaExceeded> {
+        self.connections
+            .as_ref()
+            .map(ConnectionsQuota::try_acquire)
+            .transpose()
+    }
+
+    /// Accounts for `bytes` more sent through the client's outgoing connections, and returns how
+    /// long to wait before sending them, see [`BandwidthLimiter::reserve`].
+    pub(crate) fn reserve_bandwidth(&self, bytes: usize) -> Duration {
+        self.bandwidth
+            .as_ref()
+            .map(|bandwidth| bandwidth.reserve(bytes))
+            .unwrap_or_default()
+    }
+}
+
+/// Malformed value of one of the `QUOTA_*` environment variables.
+#[derive(Debug, Error)]
+#[error("failed to parse quota `{name}`: {error}")]
+pub(crate) struct InvalidQuotaEnv {
+    name: &'static str,
+    error: String,
+}
+
+/// Quota limits read from the agent's environment.
+///
+/// Parsed once when the agent starts, so that a malformed value fails the agent instead of
+/// silently disabling the quota.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct QuotaLimits {
+    max_open_files: Option<u32>,
+    max_outgoing_connections: Option<u32>,
+    dns_requests_per_second: Option<u32>,
+    outgoing_bandwidth: Option<u64>,
+}
+
+impl QuotaLimits {
+    pub(crate) fn from_env() -> Result<Self, InvalidQuotaEnv> {
+        Ok(Self {
+            max_open_files: Self::limit_from_env(envs::QUOTA_MAX_OPEN_FILES)?,
+            max_outgoing_connections: Self::limit_from_env(envs::QUOTA_MAX_OUTGOING_CONNECTIONS)?,
+            dns_requests_per_second: Self::limit_from_env(envs::QUOTA_DNS_REQUESTS_PER_SECOND)?,
+            outgoing_bandwidth: Self::limit_from_env(envs::QUOTA_OUTGOING_BANDWIDTH)?,
+        })
+    }
+
+    fn limit_from_env<V>(env: CheckedEnv<V>) -> Result<Option<V>, InvalidQuotaEnv>
+    where
+        V: EnvValue,
+        V::FromReprError: fmt::Debug,
+    {
+        env.try_from_env().map_err(|error| InvalidQuotaEnv {
+            name: env.name,
+            error: format!("{error:?}"),
+        })
+    }
+}
+
+/// All quotas of one client, see [`QuotaLimits`].
+#[derive(Debug, Default)]
+pub(crate) struct ClientQuotas {
+    pub(crate) open_files: Option<OpenFilesQuota>,
+    pub(crate) dns: Option<DnsRateLimit>,
+    pub(crate) outgoing: OutgoingQuotas,
+}
+
+impl ClientQuotas {
+    pub(crate) fn new(limits: QuotaLimits, reporter: &QuotaReporter) -> Self {
+        let open_files = limits.max_open_files.map(|limit| OpenFilesQuota {
+            limit: usize::try_from(limit).unwrap_or(usize::MAX),
+            reporter: reporter.clone(),
+        });
+        let connections = limits.max_outgoing_connections.map(|limit| {
+            ConnectionsQuota::new(
+                usize::try_from(limit).unwrap_or(usize::MAX),
+                reporter.clone(),
+            )
+        });
+        let dns = limits
+            .dns_requests_per_second
+            .map(|limit| DnsRateLimit::new(limit, reporter.clone()));
+        let bandwidth = limits.outgoing_bandwidth.map(BandwidthLimiter::new);
+
+        Self {
+            open_files,
+            dns,
+            outgoing: OutgoingQuotas {
+                connections,
+                bandwidth,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn connections_quota() {
+        let (reporter, mut warnings) = QuotaReporter::new();
+        let quota = ConnectionsQuota::new(2, reporter);
+
+        let first = quota.try_acquire().unwrap();
+        let _second = quota.try_acquire().unwrap();
+        let exceeded = quota.try_acquire().unwrap_err();
+        assert_eq!(exceeded.resource, QuotaResource::OutgoingConnections);
+        assert_eq!(warnings.try_recv().unwrap(), exceeded);
+
+        drop(first);
+        quota.try_acquire().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dns_rate_limit() {
+        let (reporter, mut warnings) = QuotaReporter::new();
+        let limit = DnsRateLimit::new(2, reporter);
+
+        limit.try_acquire().unwrap();
+        limit.try_acquire().unwrap();
+        limit.try_acquire().unwrap_err();
+        assert!(warnings.try_recv().is_ok());
+
+        tokio::time::advance(DnsRateLimit::WINDOW).await;
+        limit.try_acquire().unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! Resumption of client sessions after the connection was lost, see [`mirrord_protocol::resume`].
+//!
+//! When a client enables resumption, its
+//! [`ClientConnectionHandler`](crate::entrypoint::ClientConnectionHandler) registers in
+//! [`ResumableSessions`]. After the connection is lost, the handler keeps running (and keeps its
+//! port subscriptions, open files and outgoing connections) for [`RESUME_GRACE_PERIOD`].
+//! A new connection that starts with
+//! [`ClientSessionResume::Resume`](mirrord_protocol::resume::ClientSessionResume::Resume) is
+//! handed over to the handler with a [`ResumeAttempt`].
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::Not,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use mirrord_protocol::{
+    DaemonMessage,
+    resume::{ResumeSessionRequest, ResumeToken},
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+
+use crate::client_connection::ClientConnection;
+
+/// How long the agent keeps the state of a disconnected client that enabled session resumption.
+const RESUME_GRACE_PERIOD: Duration = Duration::from_secs(5 * 60);
+
+/// Maximal number of sent [`DaemonMessage`]s that a session keeps for replay.
+///
+/// If the client is disconnected for so long that the buffer overflows, the session cannot be
+/// resumed anymore.
+const REPLAY_CAPACITY: usize = 1024;
+
+/// A new connection that wants to continue a session, sent to the session's
+/// [`ClientConnectionHandler`](crate::entrypoint::ClientConnectionHandler).
+pub(crate) struct ResumeAttempt {
+    pub request: ResumeSessionRequest,
+    pub connection: ClientConnection,
+    /// The handler returns the connection here if the session cannot be resumed.
+    pub reject: oneshot::Sender<ClientConnection>,
+}
+
+/// Sessions that can be resumed on a new connection, shared between all clients.
+#[derive(Clone, Default)]
+pub(crate) struct ResumableSessions(Arc<Mutex<HashMap<ResumeToken, mpsc::Sender<ResumeAttempt>>>>);
+
+impl ResumableSessions {
+    /// Registers a new resumable session.
+    ///
+    /// The session is removed when the returned [`ResumableSession`] is dropped.
+    pub(crate) fn register(&self) -> ResumableSession {
+        let (tx, attempts) = mpsc::channel(4);
+        let mut sessions = self.0.lock().expect("resumable sessions mutex poisoned");
+
+        let token = loop {
+            let token = ResumeToken(rand::random());
+            if sessions.contains_key(&token).not() {
+                break token;
+            }
+        };
+        sessions.insert(token, tx);
+
+        ResumableSession {
+            token,
+            attempts,
+            disconnected: None,
+            sessions: self.clone(),
+        }
+    }
+
+    /// Hands the given `connection` over to the session.
+    ///
+    /// Returns the connection back if the session does not exist, or it cannot be resumed.
+    pub(crate) async fn resume(
+        &self,
+        request: ResumeSessionRequest,
+        connection: ClientConnection,
+    ) -> Result<(), ClientConnection> {
+        let session = self
+            .0
+            .lock()
+            .expect("resumable sessions mutex poisoned")
+            .get(&request.token)
+            .cloned();
+        let Some(session) = session else {
+            return Err(connection);
+        };
+
+        let (reject, rejected) = oneshot::channel();
+        if let Err(mpsc::error::SendError(attempt)) = session
+            .send(ResumeAttempt {
+                request,
+                connection,
+                reject,
+            })
+            .await
+        {
+            return Err(attempt.connection);
+        }
+
+        // The handler drops the sender when it takes the connection.
+        match rejected.await {
+            Ok(connection) => Err(connection),
+            Err(..) => Ok(()),
+        }
+    }
+}
+
+/// A session registered in [`ResumableSessions`].
+pub(crate) struct ResumableSession {
+    pub token: ResumeToken,
+    /// Attempts to resume this session on a new connection.
+    pub attempts: mpsc::Receiver<ResumeAttempt>,
+    /// Set when the connection with the client is lost.
+    pub disconnected: Option<Disconnected>,
+    sessions: ResumableSessions,
+}
+
+impl Drop for ResumableSession {
+    fn drop(&mut self) {
+        self.sessions
+            .0
+            .lock()
+            .expect("resumable sessions mutex poisoned")
+            .remove(&self.token);
+    }
+}
+
+/// State of a [`ResumableSession`] that lost the connection with the client.
+pub(crate) struct Disconnected {
+    /// The session ends if it's not resumed until then.
+    pub deadline: Instant,
+    /// [`SessionLog::sent`] when the connection was lost.
+    ///
+    /// The client could not have received more messages than this.
+    pub sent: u64,
+}
+
+impl Disconnected {
+    pub(crate) fn new(log: &SessionLog) -> Self {
+        Self {
+            deadline: Instant::now() + RESUME_GRACE_PERIOD,
+            sent: log.sent,
+        }
+    }
+}
+
+/// Counts the messages exchanged with the client in the whole session, and keeps the last sent
+/// messages for replay.
+#[derive(Default)]
+pub(crate) struct SessionLog {
+    /// How many [`ClientMessage`](mirrord_protocol::ClientMessage)s were received.
+    pub received: u64,
+    /// How many [`DaemonMessage`]s were sent.
+    pub sent: u64,
+    /// Last sent messages, [`None`] until the client enables session resumption.
+    replay: Option<VecDeque<DaemonMessage>>,
+}
+
+impl SessionLog {
+    pub(crate) fn enable_replay(&mut self) {
+        self.replay.get_or_insert_default();
+    }
+
+    pub(crate) fn record(&mut self, message: &DaemonMessage) {
+        self.sent += 1;
+
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        replay.push_back(message.clone());
+        if replay.len() > REPLAY_CAPACITY {
+            replay.pop_front();
+        }
+    }
+
+    /// Sequence number of the oldest sent message that can still be replayed.
+    pub(crate) fn replayable_from(&self) -> u64 {
+        let kept = self.replay.as_ref().map(VecDeque::len).unwrap_or_default();
+        self.sent - kept as u64
+    }
+
+    /// Returns the sent messages, starting from the one with sequence number `from`.
+    ///
+    /// Returns [`None`] if some of these are no longer available.
+    pub(crate) fn replay_from(&self, from: u64) -> Option<Vec<DaemonMessage>> {
+        if from > self.sent {
+            return None;
+        }
+        let skip = from.checked_sub(self.replayable_from())?;
+
+        Some(
+            self.replay
+                .iter()
+                .flatten()
+                .skip(skip as usize)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn replay_window() {
+        let mut log = SessionLog::default();
+        log.record(&DaemonMessage::Pong);
+        assert_eq!(log.replayable_from(), 1);
+        assert!(log.replay_from(0).is_none());
+
+        log.enable_replay();
+        for _ in 0..REPLAY_CAPACITY + 1 {
+            log.record(&DaemonMessage::Pong);
+        }
+        assert_eq!(log.replayable_from(), 2);
+        assert!(log.replay_from(1).is_none());
+        assert_eq!(log.replay_from(2).unwrap().len(), REPLAY_CAPACITY);
+        assert_eq!(log.replay_from(log.sent).unwrap().len(), 0);
+        assert!(log.replay_from(log.sent + 1).is_none());
+    }
+}
@@ -1,11 +1,12 @@
-use std::{io, net::IpAddr, sync::Arc};
+use std::{net::IpAddr, sync::Arc};
 
 use futures::{StreamExt, stream::FuturesOrdered};
-use mirrord_protocol::{ResponseError, dns::ReverseDnsLookupResponse};
+use mirrord_protocol::{RemoteResult, ResponseError, dns::ReverseDnsLookupResponse};
 use tokio::{runtime::Handle, task::JoinHandle};
 
 use crate::{
     error::{AgentError, AgentResult},
+    quota::DnsRateLimit,
     task::BgTaskRuntime,
 };
 
@@ -20,7 +21,9 @@ use crate::{
 pub struct ReverseDnsApi {
     handle: Handle,
     /// [`FuturesOrdered`] guarantee that we produce responses in the correct order.
-    results: FuturesOrdered<JoinHandle<io::Result<String>>>,
+    results: FuturesOrdered<JoinHandle<RemoteResult<String>>>,
+    /// [`None`] when the client can make any number of DNS requests.
+    rate_limit: Option<DnsRateLimit>,
 }
 
 impl ReverseDnsApi {
@@ -28,10 +31,11 @@ impl ReverseDnsApi {
     /// [`BgTaskRuntime::handle`].
     ///
     /// If this agent has a target, this runtime should live in the target's network namespace.
-    pub fn new(network_runtime: &BgTaskRuntime) -> Self {
+    pub fn new(network_runtime: &BgTaskRuntime, rate_limit: Option<DnsRateLimit>) -> Self {
         Self {
             handle: network_runtime.handle().clone(),
             results: Default::default(),
+            rate_limit,
         }
     }
 
@@ -39,9 +43,17 @@ impl ReverseDnsApi {
     ///
     /// When available, the result will be returned from [`Self::recv`].
     pub fn request_reverse_lookup(&mut self, ip: IpAddr) {
-        let task = self
-            .handle
-            .spawn_blocking(move || dns_lookup::lookup_addr(&ip));
+        let exceeded = self
+            .rate_limit
+            .as_ref()
+            .and_then(|rate_limit| rate_limit.try_acquire().err());
+
+        let task = match exceeded {
+            Some(exceeded) => self.handle.spawn(async move { Err(exceeded.into()) }),
+            None => self
+                .handle
+                .spawn_blocking(move || dns_lookup::lookup_addr(&ip).map_err(ResponseError::from)),
+        };
         self.results.push_back(task);
     }
 
@@ -50,12 +62,10 @@ impl ReverseDnsApi {
         let Some(result) = self.results.next().await else {
             return std::future::pending().await;
         };
-        let hostname = result
-            .map_err(|error| AgentError::BackgroundTaskFailed {
-                task: "reverse_lookup",
-                error: Arc::new(error),
-            })?
-            .map_err(ResponseError::from);
+        let hostname = result.map_err(|error| AgentError::BackgroundTaskFailed {
+            task: "reverse_lookup",
+            error: Arc::new(error),
+        })?;
 
         Ok(ReverseDnsLookupResponse { hostname })
     }
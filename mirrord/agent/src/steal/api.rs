@@ -15,10 +15,10 @@ use mirrord_protocol::{
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, ChunkedResponse, DaemonTcp,
         HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_CHUNKED_REQUEST_VERSION, HTTP_FRAMED_VERSION,
-        HttpRequest, HttpRequestMetadata, HttpResponse, IncomingTrafficTransportType,
-        InternalHttpBody, InternalHttpBodyFrame, InternalHttpBodyNew, InternalHttpRequest,
-        LayerTcpSteal, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV1, NewTcpConnectionV2,
-        StealType, TcpClose, TcpData,
+        HttpRequest, HttpRequestFallback, HttpRequestMetadata, HttpResponse,
+        IncomingTrafficTransportType, InternalHttpBody, InternalHttpBodyFrame, InternalHttpBodyNew,
+        InternalHttpRequest, LayerTcpSteal, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV1,
+        NewTcpConnectionV2, StealType, TcpClose, TcpData,
     },
 };
 use tokio::sync::mpsc::{self, Receiver, Sender, error::SendError};
@@ -31,8 +31,8 @@ use crate::{
     error::AgentResult,
     http::{MIRRORD_AGENT_HTTP_HEADER_NAME, filter::HttpFilter},
     incoming::{
-        ConnError, IncomingStream, IncomingStreamItem, RedirectorTaskConfig, ResponseBodyProvider,
-        ResponseProvider, StolenHttp, StolenTcp,
+        ConnError, FallbackRequest, IncomingStream, IncomingStreamItem, RedirectorTaskConfig,
+        ResponseBodyProvider, ResponseProvider, StolenHttp, StolenTcp,
     },
     steal::api::wait_body::WaitForFullBody,
     task::status::BgTaskStatus,
@@ -77,6 +77,10 @@ pub struct TcpStealerApi {
     ///
     /// We use this queue to store them and return from [`Self::recv`] one by one.
     queued_messages: VecDeque<DaemonMessage>,
+    /// Whether the client enabled [`HttpRequestFallback`]s.
+    ///
+    /// If it did, we keep a [`FallbackRequest`] for each stolen HTTP request.
+    http_fallback: bool,
 }
 
 impl TcpStealerApi {
@@ -126,6 +130,7 @@ impl TcpStealerApi {
             requests_in_progress: Default::default(),
             connection_ids_iter: 0..=ConnectionId::MAX,
             queued_messages: Default::default(),
+            http_fallback: false,
         })
     }
 
@@ -199,12 +204,14 @@ impl TcpStealerApi {
             .connection_ids_iter
             .next()
             .ok_or(AgentError::ExhaustedConnectionId)?;
+        let fallback = self.http_fallback.then(|| FallbackRequest::new(&request));
         let StolenHttp {
             info,
             request_head,
             stream,
             response_provider,
             redirector_config,
+            ..
         } = request;
 
         if self
@@ -304,6 +311,7 @@ impl TcpStealerApi {
             ClientConnectionState::HttpRequestSent {
                 response_provider,
                 redirector_config,
+                fallback,
             },
         );
 
@@ -361,6 +369,9 @@ impl TcpStealerApi {
     fn handle_incoming_item(&mut self, connection_id: ConnectionId, item: IncomingStreamItem) {
         match item {
             IncomingStreamItem::Frame(frame) => {
+                if let Some(connection) = self.connections.get_mut(&connection_id) {
+                    connection.copy_request_frame(&frame);
+                }
                 self.queued_messages.push_back(DaemonMessage::TcpSteal(
                     DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(ChunkedRequestBodyV1 {
                         frames: vec![frame],
@@ -372,6 +383,9 @@ impl TcpStealerApi {
             }
 
             IncomingStreamItem::NoMoreFrames => {
+                if let Some(connection) = self.connections.get_mut(&connection_id) {
+                    connection.finish_request_body();
+                }
                 self.queued_messages.push_back(DaemonMessage::TcpSteal(
                     DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(ChunkedRequestBodyV1 {
                         frames: Default::default(),
@@ -436,6 +450,7 @@ impl TcpStealerApi {
                     ClientConnectionState::HttpRequestSent {
                         response_provider: request.response_provider,
                         redirector_config: request.redirector_config,
+                        fallback: None,
                     },
                 );
                 let message = if self.protocol_version.matches(&HTTP_FRAMED_VERSION) {
@@ -654,6 +669,29 @@ impl TcpStealerApi {
                     self.connections.remove(&error.connection_id);
                 }
             },
+
+            LayerTcpSteal::HttpRequestFallback(HttpRequestFallback::Enable) => {
+                self.http_fallback = true;
+            }
+
+            LayerTcpSteal::HttpRequestFallback(HttpRequestFallback::Request(request)) => {
+                if request.request_id != Self::REQUEST_ID {
+                    return Ok(());
+                }
+
+                let Some(connection) = self.connections.get_mut(&request.connection_id) else {
+                    return Ok(());
+                };
+
+                if connection.fall_back().not() {
+                    tracing::warn!(
+                        connection_id = request.connection_id,
+                        "Client requested a fallback for a request that cannot be passed through",
+                    );
+                    self.incoming_streams.remove(&request.connection_id);
+                    self.connections.remove(&request.connection_id);
+                }
+            }
         }
 
         Ok(())
@@ -676,6 +714,14 @@ enum ClientConnectionState {
     HttpRequestSent {
         response_provider: ResponseProvider,
         redirector_config: RedirectorTaskConfig,
+        /// Set when the client enabled [`HttpRequestFallback`]s.
+        fallback: Option<FallbackRequest>,
+    },
+    /// HTTP request sent, the client requested a [`HttpRequestFallback`], but the request body is
+    /// not finished yet.
+    HttpFallbackPending {
+        response_provider: ResponseProvider,
+        fallback: FallbackRequest,
     },
     /// HTTP request sent, response received, client is sending response body frames.
     HttpResponseReceived { body_provider: ResponseBodyProvider },
@@ -698,6 +744,73 @@ impl From<SendError<Frame<Bytes>>> for SendResponseError {
 }
 
 impl ClientConnectionState {
+    /// Copies a request body frame to the [`FallbackRequest`], if we have one.
+    fn copy_request_frame(&mut self, frame: &InternalHttpBodyFrame) {
+        match self {
+            Self::HttpRequestSent {
+                fallback: Some(fallback),
+                ..
+            }
+            | Self::HttpFallbackPending { fallback, .. } => fallback.push_frame(frame),
+            _ => {}
+        }
+    }
+
+    /// Marks the request body as finished in the [`FallbackRequest`], if we have one.
+    ///
+    /// Completes the pending fallback.
+    fn finish_request_body(&mut self) {
+        match self {
+            Self::HttpRequestSent {
+                fallback: Some(fallback),
+                ..
+            } => fallback.finish_body(),
+            Self::HttpFallbackPending { fallback, .. } => {
+                fallback.finish_body();
+                self.fall_back();
+            }
+            _ => {}
+        }
+    }
+
+    /// Passes the request through to its original destination, instead of waiting for the
+    /// client's response.
+    ///
+    /// If the request body is not finished yet, the fallback is completed in
+    /// [`Self::finish_request_body`].
+    ///
+    /// Returns `false` if the client did not enable the fallback, or already started the
+    /// response.
+    fn fall_back(&mut self) -> bool {
+        let state = std::mem::replace(self, Self::Closed);
+        let (response_provider, fallback) = match state {
+            Self::HttpRequestSent {
+                response_provider,
+                fallback: Some(fallback),
+                ..
+            }
+            | Self::HttpFallbackPending {
+                response_provider,
+                fallback,
+            } => (response_provider, fallback),
+            state => {
+                *self = state;
+                return false;
+            }
+        };
+
+        if fallback.is_ready() {
+            fallback.pass_through(response_provider);
+        } else {
+            *self = Self::HttpFallbackPending {
+                response_provider,
+                fallback,
+            };
+        }
+
+        true
+    }
+
     async fn send_data(&mut self, data: Bytes) {
         let sender = match self {
             Self::Tcp { data_tx } => data_tx,
@@ -755,6 +868,7 @@ impl ClientConnectionState {
             Self::HttpRequestSent {
                 response_provider,
                 redirector_config,
+                ..
             } => (response_provider, redirector_config),
             state => {
                 *self = state;
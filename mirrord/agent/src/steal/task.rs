@@ -40,7 +40,8 @@ pub struct TcpStealerTask {
     clients: HashMap<ClientId, Client>,
     /// Futures that resolve when clients disconnect (drop their [`StealerMessage`] receivers).
     disconnected_clients: FuturesUnordered<ChannelClosedFuture>,
-    /// For tracking http requests whose bodies are being buffered
+    /// For tracking http requests whose bodies (or initial WebSocket messages) are being
+    /// buffered
     ongoing_requests: JoinSet<RedirectedHttp>,
 }
 
@@ -240,11 +241,18 @@ impl TcpStealerTask {
             }
         };
 
-        if filters.values().any(HttpFilter::needs_body) {
+        let needs_body = filters.values().any(HttpFilter::needs_body);
+        let needs_websocket_message = http.is_websocket_upgrade()
+            && filters.values().any(HttpFilter::needs_websocket_message);
+
+        if needs_body || needs_websocket_message {
             ongoing.spawn(async move {
-                if let Err(error) = http.buffer_body().await {
+                if needs_body && let Err(error) = http.buffer_body().await {
                     tracing::debug!(?error, "failed to buffer request body");
                 };
+                if needs_websocket_message && let Err(error) = http.accept_websocket().await {
+                    tracing::debug!(?error, "failed to receive initial WebSocket message");
+                };
                 http
             });
         } else {
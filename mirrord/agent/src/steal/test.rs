@@ -540,6 +540,88 @@ async fn header_injection(
     );
 }
 
+/// Verifies that a stolen request is passed through to its original destination, when the
+/// client requests a fallback.
+#[rstest]
+#[tokio::test(flavor = "current_thread")]
+#[timeout(Duration::from_secs(5))]
+async fn http_fallback(
+    #[values(
+        TestHttpKind::Http1,
+        TestHttpKind::Http1Alpn,
+        TestHttpKind::Http2,
+        TestHttpKind::Http2Alpn
+    )]
+    http_kind: TestHttpKind,
+    #[values(false, true)] with_body: bool,
+) {
+    let mut setup = TestSetup::new_http(http_kind, RedirectorTaskConfig::from_env()).await;
+
+    let request = TestRequest {
+        path: "/api/v1".into(),
+        id_header: 0,
+        user_header: 0,
+        upgrade: None,
+        kind: http_kind,
+        connector: setup.tls.as_ref().map(|s| s.connector(http_kind.alpn())),
+        acceptor: setup.tls.as_ref().map(SimpleStore::acceptor),
+        body: with_body.then(|| {
+            TestBody::new(
+                || {
+                    let (tx, rx) = mpsc::channel(1);
+                    tokio::spawn(async move {
+                        // Slow body, so that the fallback is requested before the body is
+                        // finished.
+                        for chunk in [&b"hello"[..], b" ", b"there"] {
+                            tokio::time::sleep(Duration::from_millis(25)).await;
+                            tx.send(Ok(hyper::body::Frame::data(Bytes::from_static(chunk))))
+                                .await
+                                .expect("other end of channel closed");
+                        }
+                    });
+                    StreamBody::new(ReceiverStream::new(rx))
+                },
+                |_parts, body| {
+                    Box::pin(async move {
+                        let body = body.collect().await.unwrap().to_bytes();
+                        assert_eq!(&body[..], b"hello there");
+                    })
+                },
+            )
+        }),
+    };
+
+    let mut client = StealingClient::new(
+        1,
+        setup.stealer_tx.clone(),
+        "1.33.0",
+        StealType::FilteredHttpEx(
+            setup.original_server.local_addr().unwrap().port(),
+            HttpFilter::Header(Filter::new(format!("{}: 0", TestRequest::USER_ID_HEADER)).unwrap()),
+        ),
+        setup.stealer_status.clone(),
+    )
+    .await;
+    client.enable_fallback().await;
+
+    let conn = setup
+        .conn_tx
+        .make_connection(setup.original_server.local_addr().unwrap())
+        .await;
+
+    tokio::join!(
+        async {
+            let mut sender = request.make_connection(conn).await;
+            request.send(&mut sender, 0).await;
+        },
+        async {
+            client.expect_request_fall_back().await;
+            let (stream, _) = setup.original_server.accept().await.unwrap();
+            request.accept(stream, 0).await;
+        },
+    );
+}
+
 #[derive(Clone, Copy)]
 enum FailReason {
     Timeout,
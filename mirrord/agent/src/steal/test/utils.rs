@@ -22,8 +22,9 @@ use mirrord_protocol::{
     ConnectionId, DaemonMessage, LogLevel,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, ChunkedResponse, DaemonTcp,
-        HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_CHUNKED_RESPONSE_VERSION, HttpRequestMetadata,
-        HttpResponse, IncomingTrafficTransportType, InternalHttpBodyNew, InternalHttpRequest,
+        HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_CHUNKED_RESPONSE_VERSION, HttpRequestFallback,
+        HttpRequestFallbackRequest, HttpRequestMetadata, HttpResponse,
+        IncomingTrafficTransportType, InternalHttpBodyNew, InternalHttpRequest,
         InternalHttpResponse, LayerTcpSteal, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV2,
         StealType, TcpClose, TcpData,
     },
@@ -988,6 +989,60 @@ impl StealingClient {
     pub async fn recv(&mut self) -> DaemonMessage {
         self.api.recv().await.unwrap()
     }
+
+    pub async fn enable_fallback(&mut self) {
+        self.api
+            .handle_client_message(LayerTcpSteal::HttpRequestFallback(
+                HttpRequestFallback::Enable,
+            ))
+            .await
+            .unwrap();
+    }
+
+    /// Waits for a stolen request and hands it back to the agent with
+    /// [`HttpRequestFallback::Request`].
+    ///
+    /// Returns when the agent closes the stolen connection.
+    pub async fn expect_request_fall_back(&mut self) {
+        let request = match self.api.recv().await.unwrap() {
+            DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(
+                request,
+            ))) => request,
+            other => panic!(
+                "client {} received an unexpected message: {other:?}",
+                self.id
+            ),
+        };
+        println!("[{}:{}] Got request: {request:?}", file!(), line!());
+
+        self.api
+            .handle_client_message(LayerTcpSteal::HttpRequestFallback(
+                HttpRequestFallback::Request(HttpRequestFallbackRequest {
+                    connection_id: request.connection_id,
+                    request_id: request.request_id,
+                }),
+            ))
+            .await
+            .unwrap();
+
+        loop {
+            match self.api.recv().await.unwrap() {
+                DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(
+                    body,
+                ))) => {
+                    assert_eq!(body.connection_id, request.connection_id);
+                }
+                DaemonMessage::TcpSteal(DaemonTcp::Close(close)) => {
+                    assert_eq!(close.connection_id, request.connection_id);
+                    break;
+                }
+                other => panic!(
+                    "client {} received an unexpected message: {other:?}",
+                    self.id
+                ),
+            }
+        }
+    }
 }
 
 pub struct WithSizeHint<B> {
@@ -0,0 +1,123 @@
+//! Database branch migrations of the `command` flavor without an `image`.
+//!
+//! The operator runs all other migrations in the cluster. These run on the user's machine, once
+//! the session is up, with the branch connection string pointing at a port forwarded to the
+//! branch through the agent.
+
+use std::net::Ipv4Addr;
+
+use mirrord_analytics::Reporter;
+use mirrord_config::{
+    LayerConfig,
+    feature::database_branches::{DatabaseBranchConfig, SqlBranchMigrationsConfig},
+};
+use mirrord_intproxy::agent_conn::{AgentConnectInfo, AgentConnection};
+use mirrord_operator::client::database_branches::resolve_branch_id;
+use mirrord_progress::{NullProgress, Progress};
+use mirrord_protocol_io::{Client, Connection};
+use tokio::process::Command;
+
+use crate::{
+    CliError, CliResult, internal_proxy::db_portforwards::resolve_branch_mappings,
+    local_db_branches::forward_database,
+};
+
+/// Runs the local migration commands of all remote SQL branches in the config.
+///
+/// The branch connection is read from the remote environment, where the operator already points
+/// it at the branch, like [`resolve_branch_mappings`] does for the application. Literal
+/// connection values in the config point at the source database, and are never used here.
+pub(crate) async fn run_local_commands<P: Progress, R: Reporter>(
+    config: &LayerConfig,
+    connection: &mut Connection<Client>,
+    connect_info: &AgentConnectInfo,
+    analytics: &mut R,
+    progress: &P,
+) -> CliResult<()> {
+    let key = config.key.as_str();
+    let mut mappings = None;
+
+    for branch in config.feature.db_branches.iter() {
+        if branch.is_local() {
+            continue;
+        }
+
+        let (base, migrations) = match branch {
+            DatabaseBranchConfig::Pg(cfg) => (&cfg.base, &cfg.migrations),
+            DatabaseBranchConfig::Mysql(cfg) => (&cfg.base, &cfg.migrations),
+            DatabaseBranchConfig::Mariadb(cfg) => (&cfg.base, &cfg.migrations),
+            DatabaseBranchConfig::Mssql(cfg) => (&cfg.base, &cfg.migrations),
+            _ => continue,
+        };
+
+        let Some(SqlBranchMigrationsConfig::Command {
+            command,
+            image: None,
+            env,
+            ..
+        }) = migrations
+        else {
+            continue;
+        };
+
+        let mut sub = progress.subtask("running branch migrations");
+
+        if mappings.is_none() {
+            mappings = Some(
+                resolve_branch_mappings(&config.feature.db_branches, connection, key)
+                    .await
+                    .map_err(|error| CliError::BranchMigrationsError(error.to_string()))?,
+            );
+        }
+        let mappings = mappings.as_ref().expect("resolved above");
+
+        let db_id = resolve_branch_id(&base.id, key, &NullProgress);
+        let Some((remote, mapping)) = mappings
+            .iter()
+            .find(|(_, mapping)| mapping.db_id == db_id.as_ref())
+        else {
+            sub.failure(Some("branch connection could not be read from the target"));
+            return Err(CliError::BranchMigrationsError(
+                "the branch connection string is needed to run a local migration command, but \
+                 the connection is not sourced from an environment variable of the target"
+                    .to_owned(),
+            ));
+        };
+
+        let agent_conn = AgentConnection::new(config, connect_info.clone(), analytics)
+            .await
+            .map_err(|error| CliError::BranchMigrationsError(error.to_string()))?;
+        let forwarded = forward_database(agent_conn, Ipv4Addr::LOCALHOST, remote.clone()).await?;
+        let connection_string = mapping.conn_info.connection_string(forwarded.local);
+
+        let env = env
+            .as_deref()
+            .unwrap_or(SqlBranchMigrationsConfig::DEFAULT_COMMAND_ENV);
+        let (program, args) = command
+            .split_first()
+            .expect("verified that the command is not empty");
+
+        let output = Command::new(program)
+            .args(args)
+            .env(env, connection_string)
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|error| {
+                CliError::BranchMigrationsError(format!("failed to run `{program}`: {error}"))
+            })?;
+
+        if output.status.success() {
+            sub.success(Some("branch migrations applied"));
+        } else {
+            sub.failure(Some("branch migrations failed"));
+            return Err(CliError::BranchMigrationsError(format!(
+                "`{program}` exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+    }
+
+    Ok(())
+}
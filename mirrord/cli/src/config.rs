@@ -77,6 +77,10 @@ pub(super) enum Commands {
     #[cfg_attr(target_os = "windows", command(hide = true))]
     Dump(Box<DumpArgs>),
 
+    /// Print the environment of the remote target, as `mirrord exec` would provide it to the
+    /// local process.
+    Env(Box<EnvArgs>),
+
     /// Generate shell completions for the provided shell.
     /// Supported shells: bash, elvish, fish, powershell, zsh
     Completions(CompletionsArgs),
@@ -172,6 +176,9 @@ pub(super) enum Commands {
     #[command(name = "port-forward")]
     PortForward(Box<PortForwardArgs>),
 
+    /// Manage mirrord agents created without the operator.
+    Agent(Box<AgentArgs>),
+
     /// Manage database branching.
     #[command(name = "db-branches")]
     DbBranches(Box<DbBranchesArgs>),
@@ -186,6 +193,9 @@ pub(super) enum Commands {
     #[command(hide = true)]
     VerifyConfig(VerifyConfigArgs),
 
+    /// Inspect the mirrord config.
+    Config(Box<ConfigArgs>),
+
     /// Try out mirrord for Teams.
     #[cfg_attr(target_os = "windows", command(hide = true))]
     Teams,
@@ -536,6 +546,32 @@ pub(super) struct DumpArgs {
     pub ports: Vec<u16>,
 }
 
+// `mirrord env` command
+#[derive(Args, Debug)]
+pub(super) struct EnvArgs {
+    #[clap(flatten)]
+    pub params: Box<ExecParams>,
+
+    /// Format of the printed environment.
+    #[arg(long, value_enum, default_value_t = EnvFormat::Dotenv)]
+    pub format: EnvFormat,
+
+    /// Write the environment to this file instead of stdout.
+    #[arg(short = 'o', long, value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+/// Output formats of `mirrord env`.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum EnvFormat {
+    /// `KEY="value"` lines, readable by docker-compose `env_file` and dotenv libraries.
+    Dotenv,
+    /// A single JSON object.
+    Json,
+    /// `export KEY='value'` lines, to be `source`d by POSIX shells.
+    Shell,
+}
+
 /// Target-related parameters, present in more than one command.
 #[derive(Args, Debug)]
 pub(super) struct TargetParams {
@@ -551,6 +587,7 @@ pub(super) struct TargetParams {
     /// - `statefulset/{statefulset-name}[/container/{container-name}]`
     /// - `service/{service-name}[/container/{container-name}]`
     /// - `replicaset/{replicaset-name}[/container/{container-name}]`
+    /// - `daemonset/{daemonset-name}[/container/{container-name}][/node/{node-name}]`
     ///
     /// E.g `pod/my-pod/container/my-container`.
     #[arg(short = 't', long)]
@@ -950,6 +987,13 @@ pub(super) struct ListTargetArgs {
     /// Can be used multiple times to specify multiple target types.
     #[arg(short = 't', long)]
     pub target_type: Option<Vec<TargetType>>,
+
+    /// Only list targets with the given labels, e.g. `app=api,tier=web`.
+    ///
+    /// Uses the same syntax as `kubectl get -l`, and defaults to the labels of
+    /// `target.selector` from the config file.
+    #[arg(short = 'l', long)]
+    pub selector: Option<String>,
 }
 
 impl ListTargetArgs {
@@ -988,6 +1032,40 @@ pub(super) struct VerifyConfigArgs {
     pub(super) resolved: bool,
 }
 
+#[derive(Args, Debug)]
+pub(super) struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub(super) enum ConfigCommand {
+    /// Print the fully resolved config, showing where each value comes from: default, config
+    /// file, env var, CLI flag, magic, profile or auto-mount.
+    ///
+    /// Accepts the same parameters as `mirrord exec`, so that their effect on the config can be
+    /// inspected.
+    Explain(Box<ConfigExplainArgs>),
+}
+
+#[derive(Args, Debug)]
+pub(super) struct ConfigExplainArgs {
+    /// Parameters that would be passed to `mirrord exec`.
+    #[clap(flatten)]
+    pub params: Box<ExecParams>,
+
+    /// Don't access the cluster.
+    ///
+    /// Skips applying the mirrord profile and resolving the target's volume mounts for
+    /// `feature.magic.auto_mount`.
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Print the result as JSON.
+    #[arg(long)]
+    pub json: bool,
+}
+
 #[derive(Args, Debug)]
 pub(super) struct CompletionsArgs {
     pub(super) shell: Shell,
@@ -1131,6 +1209,40 @@ pub(super) struct VpnArgs {
     pub resolver_path: PathBuf,
 }
 
+#[derive(Args, Debug)]
+pub(super) struct AgentArgs {
+    #[command(subcommand)]
+    pub command: AgentCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub(super) enum AgentCommand {
+    /// Start a warm agent ahead of time, to be reused by the next session with the same target
+    /// and agent config (`agent.warm`).
+    Prewarm(Box<AgentPrewarmArgs>),
+}
+
+#[derive(Args, Debug)]
+pub(super) struct AgentPrewarmArgs {
+    /// Parameters for the target.
+    #[clap(flatten)]
+    pub target: TargetParams,
+
+    /// Parameters for the agent.
+    #[clap(flatten)]
+    pub agent: AgentParams,
+
+    /// Load config from config file.
+    ///
+    /// When using this argument without a value, defaults to "./.mirrord/mirrord.json"
+    #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+    pub config_file: Option<PathBuf>,
+
+    /// Kube context to use from the Kubeconfig.
+    #[arg(long)]
+    pub context: Option<String>,
+}
+
 #[derive(Args, Debug)]
 pub(super) struct DbBranchesArgs {
     /// Specify the namespace to operate on
@@ -1169,6 +1281,31 @@ pub(super) enum DbBranchesCommand {
         #[arg(required_unless_present = "all")]
         names: Vec<String>,
     },
+    /// Open a database client against a branch from the config, through a portforward
+    Connect {
+        /// ID of the branch, as shown by `mirrord db-branches connections`
+        name: String,
+        /// Print the connection string and keep the portforward open, instead of launching a
+        /// client
+        #[arg(long)]
+        print: bool,
+    },
+    /// Dump the contents of a branch from the config to a local file
+    Dump {
+        /// ID of the branch, as shown by `mirrord db-branches connections`
+        name: String,
+        /// File to write the dump to
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+    },
+    /// Restore the contents of a branch from the config from a local dump file
+    Restore {
+        /// ID of the branch, as shown by `mirrord db-branches connections`
+        name: String,
+        /// Dump file created with `mirrord db-branches dump`
+        #[arg(value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+    },
 }
 
 #[derive(Args, Debug)]
@@ -1578,6 +1715,14 @@ pub(super) struct UpArgs {
     #[arg(short = 'u', long)]
     pub ui: bool,
 
+    /// Show the services in an interactive terminal UI, with a tab for each service, instead of
+    /// interleaving their output.
+    ///
+    /// Services can be restarted, stopped, or switched between `split` and `steal` modes from
+    /// the UI. Ignored when stdout is not a terminal.
+    #[arg(long)]
+    pub tui: bool,
+
     /// Subcommand. When absent, `mirrord up` runs the sessions defined in
     /// the config file. With a subcommand, the flags above are ignored.
     #[command(subcommand)]
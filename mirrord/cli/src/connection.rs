@@ -5,7 +5,7 @@ use mirrord_config::{
     LayerConfig,
     agent::AgentFileConfig,
     config::MirrordConfig,
-    target::{Target, TargetDisplay},
+    target::{Target, TargetDisplay, pod::PodTarget},
 };
 use mirrord_intproxy::agent_conn::AgentConnectInfo;
 use mirrord_kube::{
@@ -28,7 +28,13 @@ use mirrord_progress::{
 use mirrord_protocol_io::{Client, Connection};
 use tracing::Level;
 
-use crate::{CliError, CliResult, MirrordCi, ci::error::CiError, up::MirrordUp};
+use crate::{
+    CliError, CliResult, MirrordCi,
+    ci::error::CiError,
+    kube::kube_client_from_layer_config,
+    up::MirrordUp,
+    warm_agent::{self, WarmAgentKey},
+};
 
 pub const AGENT_CONNECT_INFO_ENV_KEY: &str = "MIRRORD_AGENT_CONNECT_INFO";
 
@@ -245,6 +251,35 @@ where
     Ok(Some((connection, api_version)))
 }
 
+/// Replaces [`TargetConfig::selector`](mirrord_config::target::TargetConfig::selector) with a
+/// [`Target::Pod`] picked from the cluster, so that the rest of the session setup works with a
+/// named target.
+#[tracing::instrument(level = Level::TRACE, skip_all, err)]
+async fn resolve_target_selector<P: Progress>(
+    config: &mut LayerConfig,
+    progress: &P,
+) -> CliResult<()> {
+    let Some(selector) = config.target.selector.take() else {
+        return Ok(());
+    };
+
+    let mut subtask = progress.subtask("selecting target pod");
+    let client = kube_client_from_layer_config(config).await?;
+    let target =
+        ResolvedTarget::from_selector(&client, &selector, config.target.namespace.as_deref())
+            .await
+            .map_err(CliError::TargetSelectorResolution)?;
+
+    let target = Target::Pod(PodTarget {
+        pod: target.name_any(),
+        container: selector.container,
+    });
+    subtask.success(Some(&format!("selected {target}")));
+    config.target.path = Some(target);
+
+    Ok(())
+}
+
 pub(crate) struct ConnectData {
     pub(crate) info: AgentConnectInfo,
     pub(crate) connection: Connection<Client>,
@@ -269,6 +304,8 @@ pub(crate) async fn create_and_connect<P: Progress, R: Reporter>(
     mirrord_for_ci: Option<&MirrordCi>,
     mirrord_up: Option<&MirrordUp>,
 ) -> CliResult<ConnectData> {
+    resolve_target_selector(config, progress).await?;
+
     if let Some((connection, api_version)) = try_connect_using_operator(
         config,
         progress,
@@ -338,21 +375,54 @@ pub(crate) async fn create_and_connect<P: Progress, R: Reporter>(
         }
     }
 
-    let agent_container_config = ContainerConfig::default();
+    let warm = (config.agent.warm.enabled && config.agent.ephemeral.not())
+        .then(|| Duration::from_secs(config.agent.warm.idle_ttl));
+
+    let agent_container_config = ContainerConfig {
+        idle_ttl: warm.unwrap_or_default(),
+        ..Default::default()
+    };
+
+    let (agent_params, runtime_data) = k8s_api
+        .create_agent_params(&config.target, agent_container_config)
+        .await
+        .map_err(|error| CliError::friendlier_error_or_else(error, CliError::CreateAgentFailed))?;
+
+    let warm_key = warm.map(|_| WarmAgentKey::new(config, runtime_data.as_ref()));
+
+    if let Some(key) = warm_key.as_ref()
+        && let Some((agent_connect_info, connection)) = warm_agent::take(&k8s_api, key).await
+    {
+        progress.info("reusing a warm agent");
+
+        return Ok(ConnectData {
+            info: AgentConnectInfo::DirectKubernetes(agent_connect_info),
+            connection,
+            api_version,
+        });
+    }
 
     let agent_connect_info = tokio::time::timeout(
         Duration::from_secs(config.agent.startup_timeout),
-        k8s_api.create_agent(
+        k8s_api.create_agent_with_params(
             progress,
-            &config.target,
+            agent_params,
+            runtime_data,
             Some(&mut config.feature.network),
-            agent_container_config,
         ),
     )
     .await
     .unwrap_or(Err(KubeApiError::AgentReadyTimeout))
     .map_err(|error| CliError::friendlier_error_or_else(error, CliError::CreateAgentFailed))?;
 
+    // Register the agent now, the session process might not get a chance to do it when it ends.
+    if let Some(key) = warm_key {
+        warm_agent::register(key, agent_connect_info.clone())
+            .await
+            .inspect_err(|error| tracing::debug!(%error, "Failed to register warm agent"))
+            .ok();
+    }
+
     let conn = Connection::<Client>::from_stream(
         k8s_api
             .create_connection_portforward(agent_connect_info.clone())
@@ -17,7 +17,7 @@ use crate::{
 };
 
 /// Sends a ping the connection and expects a pong.
-async fn ping(connection: &mut Connection<Client>) -> CliResult<()> {
+pub(crate) async fn ping(connection: &mut Connection<Client>) -> CliResult<()> {
     connection.send(ClientMessage::Ping).await;
 
     loop {
@@ -20,6 +20,7 @@ use mirrord_protocol::{
         InternalHttpBodyFrame, InternalHttpRequest, LayerTcp, NewTcpConnectionV1,
         NewTcpConnectionV2, TcpData,
     },
+    websocket::{self, WebSocketMessage, WebSocketMessageStream},
 };
 use mirrord_protocol_io::{Client, Connection};
 use thiserror::Error;
@@ -44,6 +45,8 @@ use crate::{
 /// 1. Starts a mirrord session using the given config file and target arguments
 /// 2. Subscribes to mirror traffic from the specified ports
 /// 3. Prints all incoming traffic to stdout in a human friendly format
+///
+/// Data sent by the clients over upgraded WebSocket connections is decoded into messages.
 pub async fn dump_command(
     args: &DumpArgs,
     watch: drain::Watch,
@@ -64,7 +67,9 @@ pub async fn dump_command(
     );
 
     // Ensure a target was specified
-    let TargetConfig { path, namespace } = config.target.clone();
+    let TargetConfig {
+        path, namespace, ..
+    } = config.target.clone();
     let path: Target = match path {
         Some(Target::Targetless) | None => {
             return Err(CliError::MissingArg {
@@ -170,6 +175,10 @@ struct DumpSession {
     ///
     /// Used when handling [`DaemonTcp::Close`].
     conn_id_to_req_id: HashMap<ConnectionId, HashSet<RequestId>>,
+    /// Decoders of client messages in connections that were upgraded to WebSocket.
+    ///
+    /// Used when handling [`DaemonTcp::Data`].
+    websockets: HashMap<ConnectionId, WebSocketMessageStream>,
 }
 
 impl DumpSession {
@@ -184,6 +193,7 @@ impl DumpSession {
             ping_interval,
             queued_messages: Default::default(),
             conn_id_to_req_id: Default::default(),
+            websockets: Default::default(),
         }
     }
 
@@ -258,19 +268,28 @@ impl DumpSession {
         }
 
         match message {
-            DaemonTcp::Close(close) => match self.conn_id_to_req_id.remove(&close.connection_id) {
-                Some(request_ids) => {
-                    for request_id in request_ids {
-                        println!(
-                            "## Request ID [{}:{}] finished",
-                            close.connection_id, request_id
-                        );
+            DaemonTcp::Close(close) => {
+                self.websockets.remove(&close.connection_id);
+                match self.conn_id_to_req_id.remove(&close.connection_id) {
+                    Some(request_ids) => {
+                        for request_id in request_ids {
+                            println!(
+                                "## Request ID [{}:{}] finished",
+                                close.connection_id, request_id
+                            );
+                        }
+                    }
+                    None => {
+                        println!("## Connection ID {} closed", close.connection_id);
                     }
                 }
-                None => {
-                    println!("## Connection ID {} closed", close.connection_id);
-                }
-            },
+            }
+            DaemonTcp::Data(TcpData {
+                connection_id,
+                bytes,
+            }) if !bytes.is_empty() && self.websockets.contains_key(&connection_id) => {
+                self.print_websocket_data(connection_id, &bytes);
+            }
             DaemonTcp::Data(TcpData {
                 connection_id,
                 bytes,
@@ -294,6 +313,7 @@ impl DumpSession {
                     req.connection_id, req.request_id, req.port,
                 );
                 println!("{}", RequestHead(&req.internal_request));
+                self.track_websocket(req.connection_id, &req.internal_request);
                 println!(
                     "## Request [{}:{}] body: {} bytes",
                     req.connection_id,
@@ -317,6 +337,7 @@ impl DumpSession {
                     req.connection_id, req.request_id, req.port,
                 );
                 println!("{}", RequestHead(&req.internal_request));
+                self.track_websocket(req.connection_id, &req.internal_request);
                 for frame in req.internal_request.body.0 {
                     println!(
                         "{}",
@@ -339,6 +360,7 @@ impl DumpSession {
                         req.connection_id, req.request_id, req.port,
                     );
                     println!("{}", RequestHead(&req.internal_request));
+                    self.track_websocket(req.connection_id, &req.internal_request);
                     for frame in req.internal_request.body {
                         println!(
                             "{}",
@@ -375,6 +397,7 @@ impl DumpSession {
                         req.request_id,
                     );
                     println!("{}", RequestHead(&req.request));
+                    self.track_websocket(req.connection_id, &req.request);
                     for frame in req.request.body.frames {
                         println!(
                             "{}",
@@ -449,7 +472,7 @@ impl DumpSession {
                     }
                 );
             }
-            message @ DaemonTcp::SubscribeResult(..) => {
+            message @ (DaemonTcp::SubscribeResult(..) | DaemonTcp::HttpResponseShadow(..)) => {
                 return Err(DumpSessionError::UnexpectedAgentMessage(Box::new(
                     DaemonMessage::Tcp(message),
                 )));
@@ -459,6 +482,50 @@ impl DumpSession {
         Ok(())
     }
 
+    /// Starts decoding data in the given connection as WebSocket messages, if the request is a
+    /// WebSocket upgrade.
+    fn track_websocket<B>(
+        &mut self,
+        connection_id: ConnectionId,
+        request: &InternalHttpRequest<B>,
+    ) {
+        if websocket::is_websocket_upgrade(&request.headers) {
+            self.websockets.entry(connection_id).or_default();
+        }
+    }
+
+    /// Prints data sent by the client in an upgraded WebSocket connection.
+    ///
+    /// If the data cannot be decoded, falls back to printing raw data for the rest of the
+    /// connection.
+    fn print_websocket_data(&mut self, connection_id: ConnectionId, bytes: &[u8]) {
+        let Some(stream) = self.websockets.get_mut(&connection_id) else {
+            return;
+        };
+
+        match stream.push(bytes) {
+            Ok(messages) => {
+                for message in messages {
+                    println!(
+                        "{}",
+                        WebSocketFrameDisplay {
+                            connection_id,
+                            message
+                        }
+                    );
+                }
+            }
+            Err(error) => {
+                println!(
+                    "## Connection ID {connection_id}: failed to decode WebSocket data ({error}), \
+                    printing raw data from now on"
+                );
+                self.websockets.remove(&connection_id);
+                println!("Data (hex):\n{}", hex::encode(bytes));
+            }
+        }
+    }
+
     async fn run(mut self, progress: &mut ProgressTracker) -> Result<Infallible, DumpSessionError> {
         self.init_connection().await?;
 
@@ -502,7 +569,9 @@ impl DumpSession {
                 | DaemonMessage::SeqpacketOutgoing(..)
                 | DaemonMessage::Vpn(..)
                 | DaemonMessage::TcpSteal(..)
-                | DaemonMessage::ReverseDnsLookup(..)) => {
+                | DaemonMessage::ReverseDnsLookup(..)
+                | DaemonMessage::DnsQuery(..)
+                | DaemonMessage::SessionResume(..)) => {
                     return Err(DumpSessionError::UnexpectedAgentMessage(Box::new(message)));
                 }
             }
@@ -562,3 +631,47 @@ impl fmt::Display for RequestFrame {
         Ok(())
     }
 }
+
+/// Provides a nice display of a WebSocket message sent by the client.
+struct WebSocketFrameDisplay {
+    connection_id: ConnectionId,
+    message: WebSocketMessage,
+}
+
+impl fmt::Display for WebSocketFrameDisplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match &self.message {
+            WebSocketMessage::Text(..) => "text message",
+            WebSocketMessage::Binary(..) => "binary message",
+            WebSocketMessage::Close(..) => "close",
+            WebSocketMessage::Ping(..) => "ping",
+            WebSocketMessage::Pong(..) => "pong",
+        };
+        let payload = self.message.payload();
+        write!(
+            f,
+            "## Connection ID {}: WebSocket {kind} ({} bytes)",
+            self.connection_id,
+            payload.len()
+        )?;
+
+        match &self.message {
+            WebSocketMessage::Close(payload) => {
+                if let Some((code, reason)) = payload.split_first_chunk::<2>() {
+                    let code = u16::from_be_bytes(*code);
+                    write!(f, "\ncode {code} {}", String::from_utf8_lossy(reason))?;
+                }
+            }
+            _ if payload.is_empty() => {}
+            WebSocketMessage::Text(payload) => {
+                write!(f, "\n{}", String::from_utf8_lossy(payload))?;
+            }
+            _ => match std::str::from_utf8(payload) {
+                Ok(s) => write!(f, "\n{s}")?,
+                Err(..) => write!(f, "\n{}\n(hex)", hex::encode(payload.as_ref()))?,
+            },
+        }
+
+        Ok(())
+    }
+}
@@ -302,6 +302,12 @@ pub(crate) enum CliError {
     ))]
     EnvFileAccessError(PathBuf, dotenvy::Error),
 
+    #[error("Failed to write the environment to `{0}`: {1}")]
+    #[diagnostic(help(
+        "Please check that the path is correct and that you have permissions to write it.{GENERAL_HELP}"
+    ))]
+    EnvOutputWriteError(PathBuf, std::io::Error),
+
     #[cfg(target_os = "macos")]
     #[error("SIP Error: `{0:#?}`")]
     #[diagnostic(help(
@@ -476,6 +482,13 @@ pub(crate) enum CliError {
     ))]
     OperatorTargetResolution(KubeApiError),
 
+    #[error("Failed to select the target pod with `target.selector`: {0}")]
+    #[diagnostic(help(
+        "Please check that at least one running and ready pod has all the selector labels, \
+        e.g. with `kubectl get pods -l <labels>`.{GENERAL_HELP}"
+    ))]
+    TargetSelectorResolution(KubeApiError),
+
     #[error("A null byte was found when trying to execute process: {0}")]
     ExecNulError(#[from] NulError),
 
@@ -590,6 +603,27 @@ pub(crate) enum CliError {
     #[diagnostic(help("Install redis-server with"))]
     LocalRedisError(String),
 
+    #[error("Local database branch error: {0}")]
+    #[diagnostic(help(
+        "Local database branches run in a container, make sure that the container runtime \
+        from `feature.db_branches[].local.container_runtime` is installed and running.{GENERAL_HELP}"
+    ))]
+    LocalDbBranchError(String),
+
+    #[error("Database branch migrations failed: {0}")]
+    #[diagnostic(help(
+        "Check `feature.db_branches[].migrations` in your config, and that the migration command \
+        succeeds when run against the branch.{GENERAL_HELP}"
+    ))]
+    BranchMigrationsError(String),
+
+    #[error("Database branch client failed: {0}")]
+    #[diagnostic(help(
+        "`mirrord db-branches connect`, `dump` and `restore` run the database's own client tools \
+        (e.g. `psql`, `pg_dump`), make sure that they are installed.{GENERAL_HELP}"
+    ))]
+    DbBranchClientError(String),
+
     #[error("error while fixing kubeconfig")]
     FixKubeconfig(#[from] FixKubeconfigError),
 
@@ -43,6 +43,7 @@ use crate::{
     connection::{AGENT_CONNECT_INFO_ENV_KEY, ConnectData, create_and_connect},
     error::CliError,
     extract::extract_library,
+    local_db_branches::{self, LocalDbBranchContainer},
     up::MirrordUp,
     util::{get_user_git_branch, remove_proxy_env},
 };
@@ -87,6 +88,10 @@ pub(crate) struct MirrordExecution {
 
     /// Whether this run uses mirrord operator.
     pub uses_operator: bool,
+
+    /// Local database branches started for this run, removed when dropped.
+    #[serde(skip)]
+    local_db_branches: Vec<LocalDbBranchContainer>,
 }
 
 /// Struct that when dropped will cancel the token and wait on the join handle
@@ -233,6 +238,10 @@ impl MirrordExecution {
             remove_proxy_env();
         }
 
+        // Started before the agent connection, so that their env overrides are applied to the
+        // remote environment and the config passed to the internal proxy.
+        let local_db_branches = local_db_branches::start_all(config, progress).await?;
+
         // Spawn agent and intproxy processes, unless MIRRORD_TEST_INTPROXY_ADDR is set and used
         // instead (test-only: skips agent connection and uses an existing intproxy).
         let (mut env_vars, proxy_process, uses_operator) =
@@ -345,10 +354,13 @@ impl MirrordExecution {
                 .map(|unset| unset.to_vec())
                 .unwrap_or_default(),
             uses_operator,
+            local_db_branches,
         })
     }
 
-    async fn get_agent_version(connection: &mut Connection<Client>) -> CliResult<Version> {
+    pub(crate) async fn get_agent_version(
+        connection: &mut Connection<Client>,
+    ) -> CliResult<Version> {
         connection
             .send(ClientMessage::SwitchProtocolVersion(
                 mirrord_protocol::VERSION.clone(),
@@ -391,6 +403,14 @@ impl MirrordExecution {
             remove_proxy_env();
         }
 
+        // The user application runs in a container, where the local branches are not reachable
+        // under `localhost`.
+        if local_db_branches::any_configured(config) {
+            return Err(CliError::LocalDbBranchError(
+                "local database branches are not supported with `mirrord container`".to_owned(),
+            ));
+        }
+
         let branch_name = get_user_git_branch().await;
 
         let mirrord_up = MirrordUp::from_env();
@@ -484,6 +504,7 @@ impl MirrordExecution {
                 .map(|unset| unset.to_vec())
                 .unwrap_or_default(),
             uses_operator: matches!(connect_info, AgentConnectInfo::Operator(..)),
+            local_db_branches: Vec::new(),
         };
 
         Ok((execution, proxy_addr))
@@ -575,6 +596,18 @@ impl MirrordExecution {
                 .inspect_err(|_| analytics.set_error(AnalyticsError::EnvFetch))?
         };
 
+        local_db_branches::seed_all(config, &mut connection, &connect_info, analytics, progress)
+            .await?;
+
+        crate::branch_migrations::run_local_commands(
+            config,
+            &mut connection,
+            &connect_info,
+            analytics,
+            progress,
+        )
+        .await?;
+
         let encoded_config = config.encode()?;
 
         let mut proxy_command =
@@ -673,7 +706,7 @@ impl MirrordExecution {
 
     /// Construct filter and retrieve remote environment from the connected agent using
     /// `MirrordExecution::get_remote_env`.
-    async fn fetch_env_vars(
+    pub(crate) async fn fetch_env_vars(
         config: &LayerConfig,
         connection: &mut Connection<Client>,
     ) -> CliResult<HashMap<String, String>> {
@@ -739,7 +772,7 @@ impl MirrordExecution {
 
     /// Retrieve remote environment from the connected agent.
     #[tracing::instrument(level = Level::TRACE, skip_all)]
-    async fn get_remote_env(
+    pub(crate) async fn get_remote_env(
         connection: &mut Connection<Client>,
         env_vars_filter: HashSet<String>,
         env_vars_select: HashSet<String>,
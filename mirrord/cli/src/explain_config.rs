@@ -0,0 +1,113 @@
+use std::{borrow::Cow, ops::Not};
+
+use mirrord_config::{
+    LayerConfig,
+    config::{
+        ConfigContext,
+        explain::{ConfigExplanation, ValueOrigin},
+    },
+    target::Target,
+};
+use mirrord_kube::{error::KubeApiError, resolved::ResolvedTarget};
+use mirrord_progress::{Progress, ProgressTracker};
+use prettytable::{Table, row};
+
+use crate::{
+    CliResult, config::ConfigExplainArgs, kube::kube_client_from_layer_config,
+    profile::apply_profile_if_configured,
+};
+
+/// Resolves the config like `mirrord exec` would, and prints each value of it along with its
+/// [`ValueOrigin`].
+///
+/// ## Usage
+///
+/// ```sh
+/// mirrord config explain -f ./mirrord.json --steal
+/// ```
+///
+/// Unless `--offline` is given, this also applies the mirrord profile and
+/// `feature.magic.auto_mount`, which both require access to the cluster.
+pub(super) async fn explain_config(
+    ConfigExplainArgs {
+        params,
+        offline,
+        json,
+    }: ConfigExplainArgs,
+) -> CliResult<()> {
+    let progress = ProgressTracker::from_env("mirrord config explain");
+
+    let mut config_context = ConfigContext::default().override_envs(params.as_env_vars());
+    let (mut config, mut explanation) = LayerConfig::resolve_explained(&mut config_context)?;
+
+    if offline.not() {
+        if let Some(name) = config.profile.clone() {
+            apply_profile_if_configured(&mut config, &progress).await?;
+            explanation.record(&config, ValueOrigin::Profile { name })?;
+        }
+
+        if config.feature.magic.auto_mount {
+            apply_auto_mount(&mut config, &progress).await?;
+            explanation.record(&config, ValueOrigin::AutoMount)?;
+        }
+    }
+
+    for warning in config_context.into_warnings() {
+        progress.warning(&warning);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&explanation)?);
+    } else {
+        print_table(&explanation);
+    }
+
+    Ok(())
+}
+
+/// Resolves the target's pod spec and applies `feature.magic.auto_mount` to the config.
+///
+/// Like in `mirrord exec`, failing to resolve the pod spec only produces a warning.
+async fn apply_auto_mount<P: Progress>(config: &mut LayerConfig, progress: &P) -> CliResult<()> {
+    let target = match config.target.path.as_ref() {
+        None | Some(Target::Targetless) => return Ok(()),
+        Some(target) => target.clone(),
+    };
+
+    let client = kube_client_from_layer_config(config).await?;
+    let namespace = config.target.namespace.as_deref();
+    let pod_spec = async {
+        let resolved = ResolvedTarget::new(&client, &target, namespace).await?;
+        let pod_spec = resolved
+            .resolve_pod_spec(&client)
+            .await?
+            .map(Cow::into_owned);
+
+        Ok::<_, KubeApiError>(
+            pod_spec.map(|pod_spec| (pod_spec, resolved.container().map(ToOwned::to_owned))),
+        )
+    }
+    .await;
+
+    match pod_spec {
+        Ok(Some((pod_spec, container))) => config.apply_auto_mount(&pod_spec, container.as_deref()),
+        Ok(None) => {}
+        Err(error) => {
+            tracing::warn!(?error, "failed to resolve target pod spec");
+            progress.warning("auto_mount: failed to resolve target pod spec, skipping");
+        }
+    }
+
+    Ok(())
+}
+
+fn print_table(explanation: &ConfigExplanation) {
+    let mut table = Table::new();
+    table.add_row(row!["KEY", "VALUE", "ORIGIN"]);
+
+    for (key, explained) in explanation.values() {
+        table.add_row(row![key, explained.value, explained.origin]);
+    }
+
+    table.printstd();
+}
@@ -77,7 +77,13 @@ pub(crate) async fn extension_exec(
         .override_env_opt(LayerConfig::FILE_PATH_ENV, args.config_file.clone())
         .override_env_opt("MIRRORD_IMPERSONATED_TARGET", args.target);
 
-    let mut config = LayerConfig::resolve(&mut cfg_context)?;
+    let mut config = if let Ok(encoded) = std::env::var(mirrord_up::RESOLVED_CONFIG_ENV) {
+        // Running as a child of `mirrord up`, for a service that keeps its session across
+        // restarts.
+        LayerConfig::decode(&encoded)?
+    } else {
+        LayerConfig::resolve(&mut cfg_context)?
+    };
     crate::profile::apply_profile_if_configured(&mut config, &progress).await?;
 
     let mut analytics = AnalyticsReporter::only_error(
@@ -235,7 +235,9 @@ pub async fn proxy(
                     | message @ Some(DaemonMessage::PauseTarget(_))
                     | message @ Some(DaemonMessage::SwitchProtocolVersionResponse(_))
                     | message @ Some(DaemonMessage::Vpn(_))
-                    | message @ Some(DaemonMessage::ReverseDnsLookup(_)) => {
+                    | message @ Some(DaemonMessage::ReverseDnsLookup(_))
+                    | message @ Some(DaemonMessage::DnsQuery(_))
+                    | message @ Some(DaemonMessage::SessionResume(_)) => {
                         return Err(
                             ExternalProxyError::PingPongFailed(format!(
                                 "agent sent an unexpected message: {message:?}"
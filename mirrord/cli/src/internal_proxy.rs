@@ -10,7 +10,7 @@
 //! The proxy will either directly connect to an existing agent (currently only used for tests),
 //! or let the [`OperatorApi`](mirrord_operator::client::OperatorApi) handle the connection.
 
-mod db_portforwards;
+pub(crate) mod db_portforwards;
 
 #[cfg(not(target_os = "windows"))]
 use std::os::unix::ffi::OsStrExt;
@@ -337,6 +337,10 @@ pub(crate) async fn proxy(
             .tls_delivery
             .or(config.feature.network.incoming.https_delivery)
             .unwrap_or_default(),
+        config.feature.network.incoming.fallback,
+        config.feature.network.incoming.shadow,
+        config.feature.network.incoming.rewrite,
+        config.internal_proxy.keep_subscriptions,
         IntProxyIntervals {
             ping: ping_interval,
             process_logging: process_logging_interval,
@@ -400,7 +404,9 @@ pub(crate) async fn connect_and_ping(
             | message @ Some(DaemonMessage::PauseTarget(_))
             | message @ Some(DaemonMessage::SwitchProtocolVersionResponse(_))
             | message @ Some(DaemonMessage::Vpn(_))
-            | message @ Some(DaemonMessage::ReverseDnsLookup(_)) => {
+            | message @ Some(DaemonMessage::ReverseDnsLookup(_))
+            | message @ Some(DaemonMessage::DnsQuery(_))
+            | message @ Some(DaemonMessage::SessionResume(_)) => {
                 break Err(InternalProxyError::InitialPingPongFailed(format!(
                     "agent sent an unexpected message: {message:?}"
                 )));
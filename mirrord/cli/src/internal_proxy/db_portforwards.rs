@@ -16,6 +16,7 @@ use mirrord_protocol::{
     ClientMessage, DaemonMessage, GetEnvVarsRequest, ResponseError,
     outgoing::tcp::DaemonTcpOutgoing,
 };
+use mirrord_protocol_io::{Client, Connection};
 use thiserror::Error;
 use url::Url;
 
@@ -70,7 +71,7 @@ struct Pf {
     db_id: String,
 }
 
-enum ConnInfo {
+pub(crate) enum ConnInfo {
     /// The original URL with host:port to be replaced with local address.
     ReplaceInUrl(Url),
     /// All params available to build a URL from scratch.
@@ -90,13 +91,14 @@ enum ConnInfo {
     HostPort,
 }
 
-struct PortMapping {
-    db_id: String,
-    conn_info: ConnInfo,
+pub(crate) struct PortMapping {
+    pub(crate) db_id: String,
+    pub(crate) conn_info: ConnInfo,
 }
 
 impl ConnInfo {
-    fn connection_string(&self, local: SocketAddr) -> String {
+    /// Connection string for the branch, pointing at the `local` end of its portforward.
+    pub(crate) fn connection_string(&self, local: SocketAddr) -> String {
         match self {
             ConnInfo::ReplaceInUrl(url) => {
                 let mut url = url.clone();
@@ -181,6 +183,11 @@ fn extract_portforward_configs(config: &DatabaseBranchesConfig, key: &str) -> Ha
             continue;
         }
 
+        // Local branches are spawned by the CLI, there is nothing to forward.
+        if branch.is_local() {
+            continue;
+        }
+
         let (base, scheme) = match branch {
             DatabaseBranchConfig::Clickhouse(db) => (&db.base, Some("clickhouse")),
             DatabaseBranchConfig::Dynamodb(db) => (&db.base, Some("dynamodb")),
@@ -393,12 +400,13 @@ fn resolve_port_mappings(
         .collect()
 }
 
-pub(super) async fn setup(
+/// Reads the branch connection env vars from the agent, and resolves the remote address of each
+/// branch that can be forwarded.
+pub(crate) async fn resolve_branch_mappings(
     config: &DatabaseBranchesConfig,
-    conn: &mut AgentConnection,
-    session_id: u64,
+    connection: &mut Connection<Client>,
     key: &str,
-) -> Result<(), SetupError> {
+) -> Result<HashMap<(RemoteAddr, u16), PortMapping>, SetupError> {
     let portforwards = extract_portforward_configs(config, key);
 
     let env_vars_select = portforwards
@@ -426,14 +434,14 @@ pub(super) async fn setup(
         })
         .collect();
 
-    conn.connection
+    connection
         .send(ClientMessage::GetEnvVarsRequest(GetEnvVarsRequest {
             env_vars_filter: Default::default(),
             env_vars_select,
         }))
         .await;
 
-    let vars = match conn.connection.recv().await {
+    let vars = match connection.recv().await {
         Some(DaemonMessage::GetEnvVarsResponse(Ok(env_vars))) => env_vars,
         Some(DaemonMessage::GetEnvVarsResponse(Err(err))) => {
             return Err(SetupError::AgentError(err));
@@ -442,7 +450,16 @@ pub(super) async fn setup(
         None => return Err(SetupError::AgentConnectionDropped),
     };
 
-    let port_mappings = resolve_port_mappings(portforwards, &vars);
+    Ok(resolve_port_mappings(portforwards, &vars))
+}
+
+pub(super) async fn setup(
+    config: &DatabaseBranchesConfig,
+    conn: &mut AgentConnection,
+    session_id: u64,
+    key: &str,
+) -> Result<(), SetupError> {
+    let port_mappings = resolve_branch_mappings(config, &mut conn.connection, key).await?;
 
     let connections_state = Arc::new(port_forward::ConnectionsState::default());
     let connections_state_2 = Arc::clone(&connections_state);
@@ -573,6 +590,8 @@ mod tests {
             copy: Default::default(),
             iam_auth: None,
             migrations: None,
+            location: Default::default(),
+            local: None,
         }))
     }
 
@@ -3,7 +3,11 @@ use std::{sync::LazyLock, time::Instant};
 use futures::TryStreamExt;
 use k8s_openapi::api::core::v1::Namespace;
 use mirrord_analytics::NullReporter;
-use mirrord_config::{LayerConfig, config::ConfigContext, target::TargetType};
+use mirrord_config::{
+    LayerConfig,
+    config::ConfigContext,
+    target::{TargetType, selector::TargetSelector},
+};
 use mirrord_kube::{api::kubernetes::seeker::KubeResourceSeeker, error::KubeApiError};
 use mirrord_operator::client::OperatorApi;
 use semver::VersionReq;
@@ -67,11 +71,14 @@ impl FoundTargets {
     /// If `rich_output` is set:
     /// 1. returned [`FoundTargets`] will contain info about namespaces available in the cluster;
     /// 2. only deployment, rollout, and pod targets will be fetched.
+    ///
+    /// If `label_selector` is set, only targets with matching labels are returned.
     #[tracing::instrument(level = Level::DEBUG, skip(layer_config), name = "resolve_targets", err)]
     async fn resolve(
         layer_config: LayerConfig,
         rich_output: bool,
         target_types: Option<Vec<TargetType>>,
+        label_selector: Option<String>,
     ) -> CliResult<Self> {
         let client = kube_client_from_layer_config(&layer_config).await?;
 
@@ -104,6 +111,7 @@ impl FoundTargets {
                 .as_deref()
                 .unwrap_or(client.default_namespace()),
             copy_target: layer_config.feature.copy_target.enabled,
+            label_selector: label_selector.as_deref(),
         };
 
         let (targets, namespaces) = tokio::try_join!(
@@ -252,7 +260,19 @@ pub(super) async fn print_targets(args: ListTargetArgs, rich_output: bool) -> Cl
         }
     };
 
-    let targets = FoundTargets::resolve(layer_config, rich_output, target_types).await?;
+    // Parsing here normalizes the selector and rejects malformed ones early.
+    let label_selector = match args.selector {
+        Some(selector) => Some(TargetSelector::parse_labels(&selector)?),
+        None => layer_config
+            .target
+            .selector
+            .as_ref()
+            .map(|selector| selector.labels.clone()),
+    }
+    .map(|labels| TargetSelector::format_labels(&labels));
+
+    let targets =
+        FoundTargets::resolve(layer_config, rich_output, target_types, label_selector).await?;
 
     match args.output {
         Format::Json => {
@@ -164,6 +164,15 @@
 //! special handling that allows the omission of a target, since in the IDE, a pop-up is shown
 //! for target selection if it was missing from the [`LayerConfig`].
 //!
+//! ### `mirrord config explain [OPTIONS]`
+//!
+//! - [`explain_config()`]
+//!
+//! > Config inspection.
+//!
+//! Resolves the [`LayerConfig`] the same way `mirrord exec` would, and prints every value of it
+//! along with where the value came from.
+//!
 //! ### `mirrord operator <COMMAND>`
 //!
 //! - [`operator_command`]
@@ -316,6 +325,7 @@ use semver::Version;
 use tracing::{error, info, trace, warn};
 use which::which;
 
+mod branch_migrations;
 mod browser;
 mod ci;
 mod config;
@@ -326,6 +336,7 @@ mod diagnose;
 mod dump;
 mod error;
 mod execution;
+mod explain_config;
 mod extension;
 mod external_proxy;
 mod extract;
@@ -334,6 +345,7 @@ mod internal_proxy;
 mod is_static;
 mod kube;
 mod list;
+mod local_db_branches;
 mod local_redis;
 mod logging;
 mod newsletter;
@@ -343,6 +355,7 @@ mod preview;
 mod profile;
 mod queue_splitting;
 mod queues;
+mod remote_env;
 mod subscribe;
 mod teams;
 mod up;
@@ -350,6 +363,7 @@ mod user_data;
 mod util;
 mod verify_config;
 mod vpn;
+mod warm_agent;
 mod wsl;
 
 mod wizard;
@@ -367,6 +381,7 @@ mod attach;
 mod pitm;
 
 pub(crate) use error::{CliError, CliResult};
+use explain_config::explain_config;
 #[cfg(target_os = "windows")]
 use mirrord_layer_lib::process::windows::{console, execution::LayerManagedProcess};
 use verify_config::verify_config;
@@ -650,11 +665,17 @@ pub(crate) fn print_config<P>(
 
     let target_and_config_path_info = format!(
         "{}, {}",
-        match &config.target.path {
-            Some(path) => {
+        match (&config.target.path, &config.target.selector) {
+            (Some(path), _) => {
                 format!("mirrord will target: {}", path)
             }
-            None => "mirrord will run without a target".into(),
+            (None, Some(selector)) => {
+                format!(
+                    "mirrord will target a pod selected by labels: {}",
+                    selector.label_selector()
+                )
+            }
+            (None, None) => "mirrord will run without a target".into(),
         },
         match config_file_path {
             Some(path) => {
@@ -1104,6 +1125,7 @@ fn main() -> miette::Result<()> {
             Commands::Dump(args) => windows_unsupported!(args, "dump", {
                 dump_command(&args, watch, &user_data).await?
             }),
+            Commands::Env(args) => remote_env::env_command(*args, watch, &user_data).await?,
             Commands::Extract { path } => {
                 extract_library(
                     Some(path),
@@ -1140,6 +1162,9 @@ fn main() -> miette::Result<()> {
                 internal_proxy::proxy(config, port, watch, &user_data).await?
             }
             Commands::VerifyConfig(args) => verify_config(args).await?,
+            Commands::Config(args) => match args.command {
+                ConfigCommand::Explain(args) => explain_config(*args).await?,
+            },
             Commands::Completions(args) => {
                 let mut cmd: clap::Command = Cli::command();
                 generate(args.shell, &mut cmd, "mirrord", &mut std::io::stdout());
@@ -1198,6 +1223,7 @@ fn main() -> miette::Result<()> {
             Commands::Preview(args) => preview::preview_command(*args, watch, &user_data).await?,
             Commands::Subscribe(args) => subscribe::subscribe_command(*args).await?,
             Commands::Up(args) => up::up_command(*args, watch, &user_data).await?,
+            Commands::Agent(args) => warm_agent::agent_command(*args).await?,
             Commands::DbBranches(args) => db_branches_command(*args).await?,
             Commands::Queues(args) => queues::queues_command(*args).await?,
             Commands::Wizard(args) => wizard::wizard_command(*args, watch, &user_data).await?,
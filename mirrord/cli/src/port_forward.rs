@@ -393,7 +393,9 @@ impl PortForwarder {
             | DaemonMessage::SeqpacketOutgoing(..)
             | DaemonMessage::Vpn(..)
             | DaemonMessage::TcpSteal(..)
-            | DaemonMessage::ReverseDnsLookup(..)) => {
+            | DaemonMessage::ReverseDnsLookup(..)
+            | DaemonMessage::DnsQuery(..)
+            | DaemonMessage::SessionResume(..)) => {
                 // includes unexpected DaemonMessage::Pong
                 return Err(PortForwardError::AgentError(format!(
                     "unexpected message from agent: {message:?}"
@@ -587,6 +589,10 @@ impl ReversePortForwarder {
                     .clone()
                     .or_else(|| network_config.https_delivery.clone())
                     .unwrap_or_default(),
+                network_config.fallback.clone(),
+                network_config.shadow.clone(),
+                network_config.rewrite.clone(),
+                false,
                 MonitorTx::disabled(),
             ),
             (),
@@ -708,7 +714,9 @@ impl ReversePortForwarder {
             | message @ DaemonMessage::SwitchProtocolVersionResponse(_)
             | message @ DaemonMessage::Vpn(_)
             | message @ DaemonMessage::Pong
-            | message @ DaemonMessage::ReverseDnsLookup(_) => {
+            | message @ DaemonMessage::ReverseDnsLookup(_)
+            | message @ DaemonMessage::DnsQuery(_)
+            | message @ DaemonMessage::SessionResume(_) => {
                 return Err(PortForwardError::AgentError(format!(
                     "unexpected message from agent: {message:?}"
                 )));
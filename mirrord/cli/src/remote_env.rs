@@ -0,0 +1,183 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Not,
+};
+
+use mirrord_analytics::{AnalyticsReporter, CollectAnalytics, ExecutionKind};
+use mirrord_config::{LayerConfig, config::ConfigContext};
+use mirrord_intproxy::agent_conn::AgentConnectInfo;
+use mirrord_progress::{Progress, ProgressTracker};
+
+use crate::{
+    config::{EnvArgs, EnvFormat},
+    connection::{ConnectData, create_and_connect},
+    error::{CliError, CliResult},
+    execution::MirrordExecution,
+    user_data::UserData,
+};
+
+/// Implements the `mirrord env` command.
+///
+/// Connects to the target, and fetches its environment applying `feature.env` the same way
+/// `mirrord exec` does (`include`/`exclude`, `env_file`, `mapping`, `override` and `unset`).
+/// The result is printed or written to a file, so that it can be consumed without loading the
+/// mirrord layer (e.g. by docker-compose or IDE run configurations).
+pub(crate) async fn env_command(
+    EnvArgs {
+        params,
+        format,
+        output,
+    }: EnvArgs,
+    watch: drain::Watch,
+    user_data: &UserData,
+) -> CliResult<()> {
+    let mut cfg_context = ConfigContext::default().override_envs(params.as_env_vars());
+    let mut config = LayerConfig::resolve(&mut cfg_context)?;
+
+    let mut progress = ProgressTracker::from_env("mirrord env");
+    crate::profile::apply_profile_if_configured(&mut config, &progress).await?;
+
+    for warning in cfg_context.into_warnings() {
+        progress.warning(&warning);
+    }
+
+    let mut analytics = AnalyticsReporter::new(
+        config.telemetry,
+        ExecutionKind::Other,
+        watch,
+        user_data.machine_id(),
+        Some(config.key.as_str().to_owned()),
+    );
+    (&config).collect_analytics(analytics.get_mut());
+
+    let ConnectData {
+        info,
+        mut connection,
+        ..
+    } = create_and_connect(&mut config, &mut progress, &mut analytics, None, None, None).await?;
+
+    if matches!(
+        info,
+        AgentConnectInfo::Operator(..) | AgentConnectInfo::DirectKubernetes(..)
+    ) {
+        MirrordExecution::get_agent_version(&mut connection).await?;
+    }
+
+    let mut env_vars = MirrordExecution::fetch_env_vars(&config, &mut connection).await?;
+    if let Some(unset) = &config.feature.env.unset {
+        remove_unset(&mut env_vars, unset);
+    }
+
+    let env_vars = env_vars.into_iter().collect::<BTreeMap<_, _>>();
+    let formatted = format_env(&env_vars, format)?;
+
+    match output {
+        Some(path) => {
+            tokio::fs::write(&path, formatted)
+                .await
+                .map_err(|error| CliError::EnvOutputWriteError(path.clone(), error))?;
+            progress.success(Some(&format!(
+                "{} environment variables written to {}",
+                env_vars.len(),
+                path.display()
+            )));
+        }
+        None => {
+            progress.success(None);
+            print!("{formatted}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the variables listed in `feature.env.unset`, which are matched case-insensitively.
+fn remove_unset(env_vars: &mut HashMap<String, String>, unset: &[String]) {
+    let unset = unset
+        .iter()
+        .map(|name| name.to_lowercase())
+        .collect::<Vec<_>>();
+    env_vars.retain(|name, _| unset.contains(&name.to_lowercase()).not());
+}
+
+fn format_env(env_vars: &BTreeMap<String, String>, format: EnvFormat) -> CliResult<String> {
+    let formatted = match format {
+        EnvFormat::Json => {
+            let mut json = serde_json::to_string_pretty(env_vars)?;
+            json.push('\n');
+            json
+        }
+        EnvFormat::Dotenv => env_vars
+            .iter()
+            .map(|(name, value)| format!("{name}=\"{}\"\n", escape_dotenv(value)))
+            .collect(),
+        EnvFormat::Shell => env_vars
+            .iter()
+            .map(|(name, value)| format!("export {name}='{}'\n", value.replace('\'', r"'\''")))
+            .collect(),
+    };
+
+    Ok(formatted)
+}
+
+/// Escapes a value to be put in double quotes in a dotenv file.
+fn escape_dotenv(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '"' => escaped.push_str(r#"\""#),
+            '$' => escaped.push_str(r"\$"),
+            '\n' => escaped.push_str(r"\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn env() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("PLAIN".to_owned(), "value".to_owned()),
+            ("QUOTED".to_owned(), "it's a \"$HOME\"\nline".to_owned()),
+        ])
+    }
+
+    #[test]
+    fn dotenv_round_trip() {
+        let formatted = format_env(&env(), EnvFormat::Dotenv).unwrap();
+
+        let parsed = dotenvy::from_read_iter(formatted.as_bytes())
+            .collect::<Result<BTreeMap<_, _>, _>>()
+            .unwrap();
+        assert_eq!(parsed, env());
+    }
+
+    #[test]
+    fn shell_quoting() {
+        let formatted = format_env(&env(), EnvFormat::Shell).unwrap();
+
+        assert_eq!(
+            formatted,
+            "export PLAIN='value'\nexport QUOTED='it'\\''s a \"$HOME\"\nline'\n"
+        );
+    }
+
+    #[test]
+    fn unset_is_case_insensitive() {
+        let mut env_vars = HashMap::from([
+            ("Aws_Profile".to_owned(), "dev".to_owned()),
+            ("KEEP".to_owned(), "1".to_owned()),
+        ]);
+
+        remove_unset(&mut env_vars, &["AWS_PROFILE".to_owned()]);
+
+        assert_eq!(
+            env_vars,
+            HashMap::from([("KEEP".to_owned(), "1".to_owned())])
+        );
+    }
+}
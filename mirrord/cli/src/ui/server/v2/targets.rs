@@ -7,7 +7,7 @@ use axum::extract::{Query, State};
 use futures::StreamExt;
 use itertools::Itertools;
 use k8s_openapi::api::{
-    apps::v1::{Deployment, ReplicaSet, StatefulSet},
+    apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
     batch::v1::{CronJob, Job},
     core::v1::{Container, Pod, Service},
 };
@@ -130,6 +130,7 @@ pub(crate) async fn list_targets(
         client: &client,
         namespace,
         copy_target: true,
+        label_selector: None,
     };
 
     // Return targets according to target type param, otherwise fetch all types (sans Targetless)
@@ -201,6 +202,13 @@ pub(crate) async fn list_targets(
                     .collect::<Vec<_>>()
                     .await
             }
+            TargetType::DaemonSet => {
+                seeker
+                    .list_all_namespaced::<DaemonSet>(None, None)
+                    .filter_map(|x| into_info(x, &seeker, &client))
+                    .collect::<Vec<_>>()
+                    .await
+            }
             TargetType::Targetless => vec![], // the frontend does not yet support targetless
         });
     }
@@ -498,3 +506,27 @@ impl IntoTargetInfo for ReplicaSet {
         Ok(into_info_option(self))
     }
 }
+
+impl IntoTargetInfo for DaemonSet {
+    async fn into_info(
+        self,
+        _seeker: &KubeResourceSeeker<'_>,
+        _client: &Client,
+    ) -> Result<Option<TargetInfo>, ApiError> {
+        fn into_info_option(daemon_set: DaemonSet) -> Option<TargetInfo> {
+            let target_name = daemon_set.name()?.to_string();
+            let target_namespace = daemon_set.namespace()?.to_string();
+            let containers = daemon_set.spec?.template.spec?.containers;
+            let detected_ports = detected_ports(&containers);
+            let containers = container_names(&containers);
+            Some(TargetInfo::new(
+                TargetType::DaemonSet,
+                target_name,
+                target_namespace,
+                containers,
+                detected_ports,
+            ))
+        }
+        Ok(into_info_option(self))
+    }
+}
@@ -1,6 +1,9 @@
 //! The `mirrord up` command - runs multiple mirrord sessions from a `mirrord-up.yaml` file.
 
-use std::{io::ErrorKind, process::Stdio};
+use std::{
+    io::{ErrorKind, IsTerminal},
+    process::Stdio,
+};
 
 use miette::Diagnostic;
 use mirrord_analytics::{Analytics, AnalyticsReporter, CollectAnalytics, Reporter};
@@ -135,12 +138,16 @@ async fn run_up(args: UpArgs, analytics: &mut AnalyticsReporter) -> Result<(), U
         }
     }
 
+    let tui = args.tui && std::io::stdout().is_terminal();
+    analytics.get_mut().add("tui_enabled", tui);
+
     let result = mirrord_up::run(
         up_config,
         &args.config_file,
         key,
         correlation_id,
         ready.clone(),
+        tui,
     )
     .await;
 
@@ -170,8 +177,12 @@ impl From<&UpCliError> for ErrorCategory {
             UpCliError::ConfigNotFound
             | UpCliError::UsernameFetch(_)
             | UpCliError::Up(UpError::Parse(_))
-            | UpCliError::Up(UpError::Validation(_)) => Self::ConfigValidation,
-            UpCliError::Up(UpError::ServiceCrashed { .. }) => Self::ServiceCrash,
+            | UpCliError::Up(UpError::Validation(_))
+            | UpCliError::Up(UpError::UnknownDependency { .. })
+            | UpCliError::Up(UpError::DependencyCycle { .. })
+            | UpCliError::Up(UpError::Watch { .. }) => Self::ConfigValidation,
+            UpCliError::Up(UpError::ServiceCrashed { .. })
+            | UpCliError::Up(UpError::NotReady { .. }) => Self::ServiceCrash,
             UpCliError::Up(UpError::Io(_))
             | UpCliError::Up(UpError::Panic(_))
             | UpCliError::Up(UpError::Kube(_))
@@ -168,6 +168,44 @@ pub(crate) fn is_pid_alive(pid: u32) -> bool {
         .unwrap_or(false)
 }
 
+/// Spawn a `cleanup-guardian` process that polls our PID and cleans up the resources given in
+/// `resource_args` (e.g. `--container-name`) when we exit. Detached from both the parent process
+/// and the terminal session so it survives `execve` and Ctrl+C.
+#[cfg(unix)]
+pub(crate) fn spawn_cleanup_guardian<I, S>(resource_args: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    let watched_pid = std::process::id();
+    let exe = std::env::current_exe()?;
+
+    let mut cmd = Command::new(exe);
+    cmd.arg("cleanup-guardian")
+        .arg("--watch-pid")
+        .arg(watched_pid.to_string())
+        .args(resource_args)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+
+    unsafe {
+        cmd.pre_exec(|| {
+            reparent_to_init()?;
+            detach_io()?;
+            Ok(())
+        });
+    }
+
+    let mut child = cmd.spawn()?;
+
+    // reparent_to_init forks -- our immediate child exits right away,
+    // reparenting the real guardian to init. Wait for the immediate child.
+    tokio::spawn(async move { child.wait().await });
+
+    Ok(())
+}
+
 /// Poll `watch_pid` until it exits, then clean up the given container and/or process.
 /// Runs as the `cleanup-guardian` hidden subcommand, fully detached from the terminal.
 #[cfg(unix)]
@@ -3,7 +3,7 @@
 //! `path`. It's used by the IDE plugins to display errors/warnings quickly, without having to start
 //! mirrord-layer.
 
-use std::ops::Not;
+use std::{collections::BTreeMap, ops::Not, path::PathBuf};
 
 use error::CliResult;
 use futures::TryFutureExt;
@@ -11,8 +11,9 @@ use mirrord_config::{
     LayerConfig,
     config::ConfigContext,
     target::{
-        Target, TargetConfig, TargetType, cron_job::CronJobTarget, deployment::DeploymentTarget,
-        job::JobTarget, pod::PodTarget, replica_set::ReplicaSetTarget, rollout::RolloutTarget,
+        Target, TargetConfig, TargetType, cron_job::CronJobTarget, daemon_set::DaemonSetTarget,
+        deployment::DeploymentTarget, job::JobTarget, pod::PodTarget,
+        replica_set::ReplicaSetTarget, rollout::RolloutTarget, selector::TargetSelector,
         service::ServiceTarget, stateful_set::StatefulSetTarget,
     },
 };
@@ -56,6 +57,9 @@ enum VerifiedTarget {
 
     #[serde(untagged)]
     ReplicaSet(ReplicaSetTarget),
+
+    #[serde(untagged)]
+    DaemonSet(DaemonSetTarget),
 }
 
 impl From<Target> for VerifiedTarget {
@@ -69,6 +73,7 @@ impl From<Target> for VerifiedTarget {
             Target::StatefulSet(target) => Self::StatefulSet(target),
             Target::Service(target) => Self::Service(target),
             Target::ReplicaSet(target) => Self::ReplicaSet(target),
+            Target::DaemonSet(target) => Self::DaemonSet(target),
             Target::Targetless => Self::Targetless,
         }
     }
@@ -86,6 +91,7 @@ impl From<VerifiedTarget> for TargetType {
             VerifiedTarget::StatefulSet(_) => TargetType::StatefulSet,
             VerifiedTarget::Service(_) => TargetType::Service,
             VerifiedTarget::ReplicaSet(_) => TargetType::ReplicaSet,
+            VerifiedTarget::DaemonSet(_) => TargetType::DaemonSet,
         }
     }
 }
@@ -94,6 +100,9 @@ impl From<VerifiedTarget> for TargetType {
 struct VerifiedTargetConfig {
     path: Option<VerifiedTarget>,
     namespace: Option<String>,
+    /// When set, the target pod is picked by mirrord, and the IDE should not ask for a target.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selector: Option<TargetSelector>,
 }
 
 impl From<TargetConfig> for VerifiedTargetConfig {
@@ -101,6 +110,7 @@ impl From<TargetConfig> for VerifiedTargetConfig {
         Self {
             path: value.path.map(Into::into),
             namespace: value.namespace,
+            selector: value.selector,
         }
     }
 }
@@ -126,6 +136,12 @@ enum VerifiedConfig {
         /// used.
         #[serde(skip_serializing_if = "Option::is_none")]
         resolved_config: Option<Value>,
+        /// Config file that provided each value set in the config files, keyed by dotted value
+        /// paths (e.g. `agent.namespace`).
+        ///
+        /// Relevant when the config uses `extends`.
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        value_sources: BTreeMap<String, PathBuf>,
     },
     /// Invalid config was detected, mirrord cannot run.
     ///
@@ -195,6 +211,7 @@ pub(super) async fn verify_config(
     let verified = match layer_config {
         Ok(config) => VerifiedConfig::Success {
             config: config.target.clone().into(),
+            value_sources: config_context.value_sources().clone(),
             warnings: config_context.into_warnings(),
             compatible_target_types: TargetType::all()
                 .filter(|tt| tt.compatible_with(&config.feature))
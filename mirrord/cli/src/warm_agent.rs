@@ -0,0 +1,406 @@
+//! Warm agents, which stay idle in the cluster after a session ends, and are reused by the next
+//! session with the same target and agent configuration (`agent.warm`).
+//!
+//! Agents are tracked in a local cache at `~/.mirrord/warm_agents.json`.
+
+use std::{
+    env::home_dir,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::SeekFrom,
+    ops::Not,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use fs4::tokio::AsyncFileExt;
+use mirrord_analytics::NullReporter;
+use mirrord_config::{
+    LayerConfig,
+    config::{ConfigContext, ConfigError},
+};
+use mirrord_kube::api::{
+    kubernetes::{AgentKubernetesConnectInfo, KubernetesAPI},
+    runtime::RuntimeData,
+};
+use mirrord_progress::{Progress, ProgressTracker};
+use mirrord_protocol_io::{Client, Connection};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs,
+    io::{self, AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+};
+use tracing::Level;
+
+use crate::{
+    AgentArgs, AgentCommand, AgentPrewarmArgs, CliResult,
+    connection::{ConnectData, create_and_connect},
+    diagnose::ping,
+};
+
+/// "~/.mirrord/warm_agents.json"
+///
+/// [`None`] when the home directory can't be determined, which disables warm agents.
+static WARM_AGENTS_PATH: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    let path = home_dir().map(|home| home.join(".mirrord").join("warm_agents.json"));
+    if path.is_none() {
+        tracing::warn!("Could not determine home directory; warm agents are disabled");
+    }
+    path
+});
+
+/// How long we wait for a warm agent to answer the health ping before we consider it gone.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Identifies the sessions that can share a warm agent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct WarmAgentKey {
+    /// Target path, or `targetless`.
+    target: String,
+    namespace: Option<String>,
+    /// Node on which the target runs, [`None`] for targetless agents.
+    node: Option<String>,
+    /// Hash of everything else that affects the agent: the agent config, the cluster and the
+    /// target container.
+    ///
+    /// Computed with [`DefaultHasher`], which is only stable within a single mirrord build. This
+    /// is fine, as a mismatch only means that a new agent is created.
+    config_hash: String,
+}
+
+impl WarmAgentKey {
+    pub(crate) fn new(config: &LayerConfig, runtime_data: Option<&RuntimeData>) -> Self {
+        let target = config
+            .target
+            .path
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "targetless".to_owned());
+
+        let mut hasher = DefaultHasher::new();
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        serde_json::to_string(&config.agent)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        config.kubeconfig.hash(&mut hasher);
+        config.kube_context.hash(&mut hasher);
+        runtime_data
+            .map(|data| data.container_id.as_str())
+            .hash(&mut hasher);
+
+        Self {
+            target,
+            namespace: config.target.namespace.clone(),
+            node: runtime_data.map(|data| data.node_name.clone()),
+            config_hash: format!("{:016x}", hasher.finish()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WarmAgentEntry {
+    key: WarmAgentKey,
+    connect_info: AgentKubernetesConnectInfo,
+    /// Seconds since the Unix epoch.
+    registered_at: u64,
+}
+
+/// Contents of `~/.mirrord/warm_agents.json`.
+///
+/// Any error when loading the file results in an empty cache, worst case we create a new agent.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct WarmAgentCache {
+    #[serde(default)]
+    agents: Vec<WarmAgentEntry>,
+}
+
+impl WarmAgentCache {
+    /// Opens the cache file with an exclusive lock and loads the cache from it.
+    ///
+    /// The lock must be held across the whole read-modify-write, so that concurrent sessions
+    /// don't lose each other's changes. It is released when the returned file is unlocked or
+    /// dropped.
+    async fn lock(path: &Path) -> io::Result<(Self, fs::File)> {
+        if let Some(parent) = path.parent()
+            && parent.exists().not()
+        {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .await?;
+        file.lock_exclusive()?;
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await?;
+        let cache = serde_json::from_slice(&contents)
+            .inspect_err(|error| tracing::trace!(%error, "Could not load warm agents cache"))
+            .unwrap_or_default();
+
+        Ok((cache, file))
+    }
+
+    /// Replaces the contents of the `file` locked with [`WarmAgentCache::lock`].
+    async fn save(&self, file: &mut fs::File) -> io::Result<()> {
+        let contents = serde_json::to_vec(self)?;
+
+        file.seek(SeekFrom::Start(0)).await?;
+        file.set_len(0).await?;
+        file.write_all(&contents).await?;
+        file.flush().await
+    }
+}
+
+/// Looks up a warm agent for the given key and connects to it.
+///
+/// The agent is removed from the cache, unless it answers a health ping. The connection used for
+/// the ping is returned, so that it can be used for the session.
+///
+/// A healthy agent stays in the cache, so concurrent sessions with the same key share it. This is
+/// safe, as the agent serves any number of clients, each with its own state. The agent stays
+/// alive until all of them are gone for `agent.warm.idle_ttl`.
+///
+/// The cache stays locked until the ping is done.
+#[tracing::instrument(level = Level::TRACE, skip(k8s_api))]
+pub(crate) async fn take(
+    k8s_api: &KubernetesAPI,
+    key: &WarmAgentKey,
+) -> Option<(AgentKubernetesConnectInfo, Connection<Client>)> {
+    let path = WARM_AGENTS_PATH.as_deref()?;
+
+    take_from(path, key, |connect_info| async move {
+        let stream = k8s_api
+            .create_connection_portforward(connect_info)
+            .await
+            .ok()?;
+        let mut connection = Connection::<Client>::from_stream(stream);
+        ping(&mut connection).await.ok()?;
+        Some(connection)
+    })
+    .await
+}
+
+/// [`take`] from the cache at `path`, with `connect` connecting to the agent and pinging it.
+async fn take_from<C, F>(
+    path: &Path,
+    key: &WarmAgentKey,
+    connect: impl FnOnce(AgentKubernetesConnectInfo) -> F,
+) -> Option<(AgentKubernetesConnectInfo, C)>
+where
+    F: Future<Output = Option<C>>,
+{
+    let (mut cache, mut file) = WarmAgentCache::lock(path)
+        .await
+        .inspect_err(|error| tracing::debug!(%error, "Failed to lock warm agents cache"))
+        .ok()?;
+    let position = cache.agents.iter().position(|entry| entry.key == *key)?;
+    let connect_info = cache.agents[position].connect_info.clone();
+
+    let taken = match tokio::time::timeout(PING_TIMEOUT, connect(connect_info.clone())).await {
+        Ok(Some(connection)) => Some((connect_info, connection)),
+        _ => {
+            tracing::debug!(
+                ?connect_info,
+                "Warm agent is gone, removing it from the cache"
+            );
+            cache.agents.remove(position);
+            cache
+                .save(&mut file)
+                .await
+                .inspect_err(|error| tracing::debug!(%error, "Failed to save warm agents cache"))
+                .ok();
+            None
+        }
+    };
+
+    file.unlock()
+        .inspect_err(|error| tracing::debug!(%error, "Failed to unlock warm agents cache"))
+        .ok();
+
+    taken
+}
+
+/// Registers the agent in the cache, replacing any agent previously registered with the same key.
+///
+/// Does nothing when warm agents are disabled, see [`WARM_AGENTS_PATH`].
+#[tracing::instrument(level = Level::TRACE, err)]
+pub(crate) async fn register(
+    key: WarmAgentKey,
+    connect_info: AgentKubernetesConnectInfo,
+) -> io::Result<()> {
+    match WARM_AGENTS_PATH.as_deref() {
+        Some(path) => register_at(path, key, connect_info).await,
+        None => Ok(()),
+    }
+}
+
+/// [`register`] in the cache at `path`.
+async fn register_at(
+    path: &Path,
+    key: WarmAgentKey,
+    connect_info: AgentKubernetesConnectInfo,
+) -> io::Result<()> {
+    let (mut cache, mut file) = WarmAgentCache::lock(path).await?;
+    cache.agents.retain(|entry| entry.key != key);
+    cache.agents.push(WarmAgentEntry {
+        key,
+        connect_info,
+        registered_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+    });
+
+    cache.save(&mut file).await?;
+    file.unlock()
+}
+
+/// Handles the `mirrord agent` command.
+pub(crate) async fn agent_command(args: AgentArgs) -> CliResult<()> {
+    match args.command {
+        AgentCommand::Prewarm(args) => prewarm(*args).await,
+    }
+}
+
+/// Starts a warm agent, or checks that one is already running, for the given target and config.
+///
+/// The operator is disabled, as warm agents are only used when mirrord creates the agent itself.
+async fn prewarm(args: AgentPrewarmArgs) -> CliResult<()> {
+    let mut progress = ProgressTracker::from_env("mirrord agent prewarm");
+
+    let mut cfg_context = ConfigContext::default()
+        .override_envs(args.target.as_env_vars())
+        .override_envs(args.agent.as_env_vars())
+        .override_env("MIRRORD_AGENT_WARM", "true")
+        .override_env("MIRRORD_OPERATOR_ENABLE", "false")
+        .override_env_opt("MIRRORD_KUBE_CONTEXT", args.context.as_ref())
+        .override_env_opt(LayerConfig::FILE_PATH_ENV, args.config_file.as_ref());
+    let mut config = LayerConfig::resolve(&mut cfg_context)?;
+    crate::profile::apply_profile_if_configured(&mut config, &progress).await?;
+
+    if config.agent.ephemeral {
+        return Err(ConfigError::Conflict(
+            "`agent.warm` is not supported with `agent.ephemeral`".to_owned(),
+        )
+        .into());
+    }
+
+    let ConnectData { mut connection, .. } = create_and_connect(
+        &mut config,
+        &mut progress,
+        &mut NullReporter::default(),
+        None,
+        None,
+        None,
+    )
+    .await?;
+
+    // The agent exits if it does not get its first client in time, so we connect once and make
+    // sure that it responds. After we disconnect, it stays idle for `agent.warm.idle_ttl`.
+    ping(&mut connection).await?;
+
+    progress.success(Some(&format!(
+        "agent is warm for the next {}s",
+        config.agent.warm.idle_ttl
+    )));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mirrord_config::{
+        LayerFileConfig,
+        agent::AgentFileConfig,
+        config::{ConfigContext, MirrordConfig},
+    };
+    use mirrord_kube::api::kubernetes::AgentKubernetesConnectInfo;
+
+    use super::{WarmAgentKey, register_at, take_from};
+
+    fn config(agent: AgentFileConfig) -> mirrord_config::LayerConfig {
+        LayerFileConfig {
+            agent: Some(agent),
+            ..Default::default()
+        }
+        .generate_config(&mut ConfigContext::default().strict_env(true))
+        .unwrap()
+    }
+
+    /// Agents created with a different agent config must not be reused.
+    #[test]
+    fn key_depends_on_agent_config() {
+        let default = config(Default::default());
+        let privileged = config(AgentFileConfig {
+            privileged: Some(true),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            WarmAgentKey::new(&default, None),
+            WarmAgentKey::new(&default, None)
+        );
+        assert_ne!(
+            WarmAgentKey::new(&default, None),
+            WarmAgentKey::new(&privileged, None)
+        );
+    }
+
+    /// A healthy agent stays registered after it's taken, so it can be shared. An agent that
+    /// doesn't answer is dropped from the cache until it's registered again.
+    #[tokio::test]
+    async fn take_register_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".mirrord").join("warm_agents.json");
+        let key = WarmAgentKey::new(&config(Default::default()), None);
+        let agent = |pod_name: &str| AgentKubernetesConnectInfo {
+            pod_name: pod_name.to_owned(),
+            pod_namespace: "default".to_owned(),
+            agent_port: 44128,
+        };
+
+        assert!(
+            take_from(&path, &key, |_| async { Some(()) })
+                .await
+                .is_none()
+        );
+
+        register_at(&path, key.clone(), agent("first"))
+            .await
+            .unwrap();
+        register_at(&path, key.clone(), agent("second"))
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let (taken, ()) = take_from(&path, &key, |_| async { Some(()) })
+                .await
+                .unwrap();
+            assert_eq!(taken, agent("second"));
+        }
+
+        assert!(
+            take_from(&path, &key, |_| async { None::<()> })
+                .await
+                .is_none()
+        );
+        assert!(
+            take_from(&path, &key, |_| async { Some(()) })
+                .await
+                .is_none()
+        );
+
+        register_at(&path, key.clone(), agent("third"))
+            .await
+            .unwrap();
+        let (taken, ()) = take_from(&path, &key, |_| async { Some(()) })
+            .await
+            .unwrap();
+        assert_eq!(taken, agent("third"));
+    }
+}
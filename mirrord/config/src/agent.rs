@@ -338,10 +338,31 @@ pub struct AgentConfig {
     /// If not set, the agent will try to detect the correct backend at runtime.
     pub nftables: Option<bool>,
 
+    /// ### agent.nftables_netlink {#agent-nftables_netlink}
+    ///
+    /// Makes the agent program the traffic redirection rules directly over netlink, in its own
+    /// nftables table, instead of running the iptables binaries. This avoids depending on the
+    /// iptables binaries and their modes, and makes rule updates atomic.
+    ///
+    /// Not used when the agent needs to exclude itself from a service mesh, as mesh rules are
+    /// managed with iptables.
+    ///
+    /// Defaults to `false`.
+    #[config(default = false, unstable)]
+    pub nftables_netlink: bool,
+
     /// ### agent.dns {#agent-dns}
     #[config(nested)]
     pub dns: AgentDnsConfig,
 
+    /// ### agent.quotas {#agent-quotas}
+    #[config(nested)]
+    pub quotas: AgentQuotasConfig,
+
+    /// ### agent.warm {#agent-warm}
+    #[config(nested)]
+    pub warm: AgentWarmConfig,
+
     /// ### agent.labels {#agent-labels}
     ///
     /// Allows setting up custom labels for the agent Job and Pod.
@@ -619,6 +640,7 @@ impl MirrordConfig for AgentImageFileConfig {
 impl CollectAnalytics for &AgentConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("ephemeral", self.ephemeral);
+        analytics.add("warm", self.warm.enabled);
     }
 }
 
@@ -710,6 +732,92 @@ pub struct AgentDnsConfig {
     pub attempts: Option<u32>,
 }
 
+/// Limits on the resources that the agent uses on behalf of a single mirrord session.
+///
+/// When a session exceeds a quota, the offending operations fail, and a warning explaining which
+/// quota was hit is shown in mirrord's output.
+///
+/// ```json
+/// {
+///   "agent": {
+///     "quotas": {
+///       "max_open_files": 512,
+///       "max_outgoing_connections": 256,
+///       "dns_requests_per_second": 100,
+///       "outgoing_bandwidth": 10485760
+///     }
+///   }
+/// }
+/// ```
+#[derive(MirrordConfig, Default, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[config(derive = "JsonSchema")]
+#[cfg_attr(test, config(derive = "PartialEq, Eq"))]
+pub struct AgentQuotasConfig {
+    /// ### agent.quotas.max_open_files {#agent-quotas-max_open_files}
+    ///
+    /// Maximum number of remote files and directories the session can have open at the same
+    /// time. Opening more fails with `EMFILE`.
+    pub max_open_files: Option<u32>,
+
+    /// ### agent.quotas.max_outgoing_connections {#agent-quotas-max_outgoing_connections}
+    ///
+    /// Maximum number of outgoing connections (TCP, UDP and unix sockets) the session can have
+    /// open at the same time. Connecting more fails with `EAGAIN`.
+    pub max_outgoing_connections: Option<u32>,
+
+    /// ### agent.quotas.dns_requests_per_second {#agent-quotas-dns_requests_per_second}
+    ///
+    /// Maximum number of remote DNS requests (including reverse lookups) the session can make
+    /// per second. Requests over the limit fail.
+    pub dns_requests_per_second: Option<u32>,
+
+    /// ### agent.quotas.outgoing_bandwidth {#agent-quotas-outgoing_bandwidth}
+    ///
+    /// Maximum rate, in bytes per second, at which the session can send data through its
+    /// outgoing connections. Writes over the limit are delayed, not failed.
+    pub outgoing_bandwidth: Option<u64>,
+}
+
+/// Keeps the agent running after the session ends, so that the next session can reuse it instead
+/// of waiting for a new agent to start.
+///
+/// Reused agents are tracked in `~/.mirrord/warm_agents.json`, and are picked only for sessions
+/// with the same target, node and agent configuration. Before reusing an agent, mirrord checks
+/// that it still responds.
+///
+/// Not supported with [`agent.ephemeral`](#agent-ephemeral).
+///
+/// ```json
+/// {
+///   "agent": {
+///     "warm": {
+///       "enabled": true,
+///       "idle_ttl": 600
+///     }
+///   }
+/// }
+/// ```
+#[derive(MirrordConfig, PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+#[config(derive = "JsonSchema")]
+#[cfg_attr(test, config(derive = "PartialEq, Eq"))]
+pub struct AgentWarmConfig {
+    /// ### agent.warm.enabled {#agent-warm-enabled}
+    ///
+    /// Enables reusing warm agents.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_AGENT_WARM", default = false)]
+    pub enabled: bool,
+
+    /// ### agent.warm.idle_ttl {#agent-warm-idle_ttl}
+    ///
+    /// How long (in seconds) the agent keeps running after all sessions using it have ended.
+    ///
+    /// Defaults to `300`.
+    #[config(default = 300)]
+    pub idle_ttl: u64,
+}
+
 #[cfg(test)]
 #[allow(clippy::too_many_arguments)]
 mod tests {
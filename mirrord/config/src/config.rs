@@ -1,5 +1,7 @@
 pub mod context;
 pub mod deprecated;
+pub mod explain;
+pub mod extends;
 pub mod from_env;
 pub mod source;
 pub mod unstable;
@@ -96,6 +98,11 @@ pub enum FromFileError {
     ParseToml(#[from] toml::de::Error),
     ParseJson(#[from] serde_json::Error),
     ParseYaml(#[from] serde_yaml::Error),
+    ExtendsCycle(PathBuf),
+    Extends {
+        path: PathBuf,
+        error: Box<FromFileError>,
+    },
 }
 
 impl From<tera::Error> for FromFileError {
@@ -141,6 +148,16 @@ impl fmt::Display for FromFileError {
                 f.write_str("failed to read the file")?;
                 error
             }
+            Self::ExtendsCycle(path) => {
+                return write!(f, "`extends` cycle detected at {}", path.display());
+            }
+            Self::Extends { path, error } => {
+                return write!(
+                    f,
+                    "failed to load extended file {}: {error}",
+                    path.display()
+                );
+            }
         };
 
         let mut source = error.source();
@@ -7,6 +7,8 @@ use std::{
     ops::Not,
 };
 
+use super::extends::ValueSources;
+
 /// Context for generating and verifying a [`MirrordConfig`](super::MirrordConfig).
 ///
 /// See:
@@ -30,6 +32,9 @@ pub struct ConfigContext {
 
     /// Warnings collected during config verification.
     warnings: Vec<String>,
+
+    /// Files that provided the config values, filled when reading the config file.
+    value_sources: ValueSources,
 }
 
 impl ConfigContext {
@@ -142,6 +147,19 @@ impl ConfigContext {
         self.warnings
     }
 
+    /// Stores the files that provided the config values.
+    pub(crate) fn set_value_sources(&mut self, sources: ValueSources) {
+        self.value_sources = sources;
+    }
+
+    /// Returns the files that provided the config values, keyed by dotted value paths (e.g.
+    /// `agent.namespace`).
+    ///
+    /// Empty if the config was not read from a file.
+    pub fn value_sources(&self) -> &ValueSources {
+        &self.value_sources
+    }
+
     /// Returns whether this context stores any warnings.
     pub fn has_warnings(&self) -> bool {
         self.warnings.is_empty().not()
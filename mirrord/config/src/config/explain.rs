@@ -0,0 +1,243 @@
+//! Tracking where the values of a resolved [`LayerConfig`] come from, used by
+//! `mirrord config explain`.
+//!
+//! The config is resolved in stages (defaults, config files, environment, CLI arguments, magic,
+//! ...). After each stage, the whole config is snapshotted with [`ConfigExplanation::record`], and
+//! every value that changed since the previous snapshot is attributed to that stage.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::Not,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    LayerConfig,
+    config::{ConfigError, extends::ValueSources},
+};
+
+/// Dotted path of the [`EnvKey::Generated`](crate::env_key::EnvKey::Generated) value.
+///
+/// A new key is generated at every stage that doesn't provide one, and we don't want to
+/// attribute it to the last stage that happened to generate it.
+const GENERATED_KEY: &str = "key.Generated";
+
+/// Where a value of the resolved config comes from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValueOrigin {
+    /// Default value of the config field.
+    Default,
+    /// Set in a config file (the file itself, or one of the files it extends).
+    File { path: PathBuf },
+    /// Set with an environment variable.
+    Env,
+    /// Set with a command line argument.
+    Cli,
+    /// Set by one of the `feature.magic` presets, or derived from other values by mirrord.
+    Magic,
+    /// Set by a mirrord profile.
+    Profile { name: String },
+    /// Set by `feature.magic.auto_mount`, from the target's volume mounts.
+    AutoMount,
+}
+
+impl fmt::Display for ValueOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::File { path } => write!(f, "file {}", path.display()),
+            Self::Env => f.write_str("env"),
+            Self::Cli => f.write_str("cli"),
+            Self::Magic => f.write_str("magic"),
+            Self::Profile { name } => write!(f, "profile {name}"),
+            Self::AutoMount => f.write_str("auto_mount"),
+        }
+    }
+}
+
+/// A value of the resolved config, along with its [`ValueOrigin`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ExplainedValue {
+    pub value: Value,
+    pub origin: ValueOrigin,
+}
+
+/// All values of the resolved config, keyed by their dotted paths (e.g. `agent.namespace`).
+///
+/// Maps are flattened, all other values (including arrays) are kept whole.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct ConfigExplanation(BTreeMap<String, ExplainedValue>);
+
+impl ConfigExplanation {
+    /// Starts the explanation from the default config, all values get [`ValueOrigin::Default`].
+    pub fn new(defaults: &LayerConfig) -> Result<Self, ConfigError> {
+        let mut explanation = Self::default();
+        explanation.record(defaults, ValueOrigin::Default)?;
+        Ok(explanation)
+    }
+
+    /// Attributes all values that changed since the last snapshot to `origin`.
+    pub fn record(&mut self, config: &LayerConfig, origin: ValueOrigin) -> Result<(), ConfigError> {
+        self.record_with(config, |_| origin.clone())
+    }
+
+    /// Attributes all values that changed since the last snapshot to the config files in
+    /// `sources` (see
+    /// [`ConfigContext::value_sources`](super::context::ConfigContext::value_sources)), or to
+    /// `path` when the value can't be matched with any of the sources.
+    pub fn record_file(
+        &mut self,
+        config: &LayerConfig,
+        path: &Path,
+        sources: &ValueSources,
+    ) -> Result<(), ConfigError> {
+        self.record_with(config, |key| ValueOrigin::File {
+            path: source_of(key, sources).unwrap_or(path).to_owned(),
+        })
+    }
+
+    /// Iterates over all values of the config, in order of their paths.
+    pub fn values(&self) -> impl Iterator<Item = (&str, &ExplainedValue)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    fn record_with<F>(&mut self, config: &LayerConfig, origin: F) -> Result<(), ConfigError>
+    where
+        F: Fn(&str) -> ValueOrigin,
+    {
+        let snapshot = serde_json::to_value(config)
+            .map_err(|error| ConfigError::EncodeError(error.to_string()))?;
+        let mut values = BTreeMap::new();
+        flatten(snapshot, String::new(), &mut values);
+
+        let mut previous = std::mem::take(&mut self.0);
+        for (key, value) in values {
+            let origin = match previous.remove(&key) {
+                Some(explained) if explained.value == value || key == GENERATED_KEY => {
+                    explained.origin
+                }
+                _ => origin(&key),
+            };
+
+            self.0.insert(key, ExplainedValue { value, origin });
+        }
+
+        Ok(())
+    }
+}
+
+/// Finds the file that provided the value at `key`.
+///
+/// Paths in the config files don't always match the paths in the resolved config (e.g.
+/// `feature.network.incoming: "steal"` vs `feature.network.incoming.mode`), so we also check the
+/// parents and the children of `key`.
+fn source_of<'a>(key: &str, sources: &'a ValueSources) -> Option<&'a Path> {
+    let mut parent = Some(key);
+    while let Some(current) = parent {
+        if let Some(source) = sources.get(current) {
+            return Some(source);
+        }
+
+        parent = current.rsplit_once('.').map(|(parent, _)| parent);
+    }
+
+    let prefix = format!("{key}.");
+    sources
+        .range(prefix.clone()..)
+        .take_while(|(source_key, _)| source_key.starts_with(&prefix))
+        .map(|(_, source)| source.as_path())
+        .next()
+}
+
+/// Flattens maps in `value` into `values`, keyed by dotted paths.
+fn flatten(value: Value, key: String, values: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) if map.is_empty().not() => {
+            for (name, value) in map {
+                let key = if key.is_empty() {
+                    name
+                } else {
+                    format!("{key}.{name}")
+                };
+                flatten(value, key, values);
+            }
+        }
+        value => {
+            values.insert(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        LayerFileConfig,
+        config::{ConfigContext, MirrordConfig},
+    };
+
+    fn generate(config: LayerFileConfig) -> LayerConfig {
+        config
+            .generate_config(&mut ConfigContext::default().strict_env(true))
+            .unwrap()
+    }
+
+    #[test]
+    fn changed_values_get_new_origin() {
+        let mut explanation = ConfigExplanation::new(&generate(Default::default())).unwrap();
+
+        let mut config = generate(LayerFileConfig {
+            kube_context: Some("from-file".to_owned()),
+            ..Default::default()
+        });
+        let sources = BTreeMap::from([("kube_context".to_owned(), PathBuf::from("base.json"))]);
+        explanation
+            .record_file(&config, Path::new("mirrord.json"), &sources)
+            .unwrap();
+
+        config.agent.namespace = Some("from-cli".to_owned());
+        explanation.record(&config, ValueOrigin::Cli).unwrap();
+
+        let origins = explanation
+            .values()
+            .map(|(key, explained)| (key.to_owned(), explained.origin.clone()))
+            .collect::<BTreeMap<_, _>>();
+
+        assert_eq!(
+            origins["kube_context"],
+            ValueOrigin::File {
+                path: PathBuf::from("base.json")
+            }
+        );
+        assert_eq!(origins["agent.namespace"], ValueOrigin::Cli);
+        assert_eq!(origins["telemetry"], ValueOrigin::Default);
+        assert_eq!(origins[GENERATED_KEY], ValueOrigin::Default);
+    }
+
+    #[test]
+    fn source_of_matches_parents_and_children() {
+        let sources = BTreeMap::from([
+            (
+                "feature.network.incoming".to_owned(),
+                PathBuf::from("a.json"),
+            ),
+            ("target.path.deployment".to_owned(), PathBuf::from("b.json")),
+        ]);
+
+        assert_eq!(
+            source_of("feature.network.incoming.mode", &sources),
+            Some(Path::new("a.json"))
+        );
+        assert_eq!(
+            source_of("target.path", &sources),
+            Some(Path::new("b.json"))
+        );
+        assert_eq!(source_of("agent.ttl", &sources), None);
+    }
+}
@@ -0,0 +1,322 @@
+//! Support for the root [`extends`](crate::LayerConfig::extends) key, which builds a config on top
+//! of other config files.
+//!
+//! The files are merged as plain [`Value`]s, before being parsed into a
+//! [`LayerFileConfig`](crate::LayerFileConfig):
+//!
+//! 1. Files listed in `extends` are merged in order, each on top of the previous ones. A file's own
+//!    `extends` are merged right before the file itself.
+//! 2. The extending file is merged last, so its values win.
+//! 3. Maps are merged key by key, all other values (including arrays) replace what was there.
+//!
+//! Environment variables and CLI arguments are applied later, when generating the config, so they
+//! still take precedence over anything that comes from the files.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    ops::Not,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+use tera::Tera;
+
+use crate::{config::FromFileError, util::VecOrSingle};
+
+/// Name of the root key that lists the extended files.
+const EXTENDS_KEY: &str = "extends";
+
+/// Maps dotted paths of config values (e.g. `feature.network.incoming.mode`) to the file that
+/// provided them.
+pub type ValueSources = BTreeMap<String, PathBuf>;
+
+/// Parses a config file content into a [`Value`], based on the file extension.
+pub(crate) fn parse_value(path: &Path, content: &str) -> Result<Value, FromFileError> {
+    match path.extension().and_then(OsStr::to_str) {
+        // No Extension? assume json
+        Some("json") | None => Ok(serde_json::from_str(content)?),
+        Some("toml") => Ok(toml::from_str(content)?),
+        Some("yaml" | "yml") => Ok(serde_yaml::from_str(content)?),
+        ext => Err(FromFileError::InvalidExtension(ext.map(String::from))),
+    }
+}
+
+/// Whether the parsed config extends other files.
+pub(crate) fn has_extends(config: &Value) -> bool {
+    config
+        .get(EXTENDS_KEY)
+        .is_some_and(|extends| extends.is_null().not())
+}
+
+/// Returns [`ValueSources`] for a config that does not extend any other file.
+pub(crate) fn sources_of(config: &Value, path: &Path) -> ValueSources {
+    let mut sources = ValueSources::new();
+    record_sources(config, path, "", &mut sources);
+    sources
+}
+
+/// Merges the already parsed config from `path` with all the files it (transitively) extends.
+///
+/// The extended files are rendered with the same `tera_context` as the root file.
+pub(crate) fn resolve(
+    path: &Path,
+    config: Value,
+    tera_context: &tera::Context,
+) -> Result<(Value, ValueSources), FromFileError> {
+    let mut resolver = ExtendsResolver {
+        tera_context,
+        stack: Vec::new(),
+        merged: Value::Object(Map::new()),
+        sources: ValueSources::new(),
+    };
+
+    resolver
+        .stack
+        .push(path.canonicalize().unwrap_or_else(|_| path.to_owned()));
+    resolver.merge_file(path, config)?;
+
+    Ok((resolver.merged, resolver.sources))
+}
+
+struct ExtendsResolver<'a> {
+    tera_context: &'a tera::Context,
+    /// Files that are currently being resolved, used to detect cycles.
+    stack: Vec<PathBuf>,
+    merged: Value,
+    sources: ValueSources,
+}
+
+impl ExtendsResolver<'_> {
+    /// Merges the files extended by `config`, and then `config` itself.
+    fn merge_file(&mut self, path: &Path, mut config: Value) -> Result<(), FromFileError> {
+        let extends: Vec<String> = match config
+            .as_object_mut()
+            .and_then(|map| map.remove(EXTENDS_KEY))
+        {
+            Some(Value::Null) | None => Vec::new(),
+            Some(extends) => serde_json::from_value::<VecOrSingle<String>>(extends)?.into(),
+        };
+
+        let base_dir = path
+            .parent()
+            .filter(|parent| parent.as_os_str().is_empty().not())
+            .map(Path::to_path_buf)
+            .map_or_else(std::env::current_dir, Ok)?;
+
+        for extended in extends {
+            let extended = base_dir.join(expand_home(&extended));
+            self.load(&extended)
+                .map_err(|error| FromFileError::Extends {
+                    path: extended,
+                    error: Box::new(error),
+                })?;
+        }
+
+        merge(&mut self.merged, config, path, "", &mut self.sources);
+
+        Ok(())
+    }
+
+    /// Reads, renders and merges an extended file.
+    fn load(&mut self, path: &Path) -> Result<(), FromFileError> {
+        let canonical = path.canonicalize()?;
+        if self.stack.contains(&canonical) {
+            return Err(FromFileError::ExtendsCycle(canonical));
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let mut template_engine = Tera::default();
+        template_engine.add_raw_template("main", &content)?;
+        let rendered = template_engine.render("main", self.tera_context)?;
+        let config = parse_value(path, &rendered)?;
+
+        self.stack.push(canonical);
+        let result = self.merge_file(path, config);
+        self.stack.pop();
+
+        result
+    }
+}
+
+/// Replaces a leading `~` with the user's home directory.
+fn expand_home(path: &str) -> PathBuf {
+    #[cfg(windows)]
+    let home = std::env::var_os("USERPROFILE");
+    #[cfg(not(windows))]
+    let home = std::env::var_os("HOME");
+
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => rest,
+        _ => return PathBuf::from(path),
+    };
+
+    match home {
+        Some(home) => PathBuf::from(home).join(rest.trim_start_matches(['/', '\\'])),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Merges `overlay` into `base`, recording the source of every value taken from `overlay`.
+///
+/// `key` is the dotted path of `base` in the whole config.
+fn merge(base: &mut Value, overlay: Value, source: &Path, key: &str, sources: &mut ValueSources) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (name, value) in overlay {
+                let child_key = join_key(key, &name);
+                match base.get_mut(&name) {
+                    Some(existing) => merge(existing, value, source, &child_key, sources),
+                    None => {
+                        record_sources(&value, source, &child_key, sources);
+                        base.insert(name, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => {
+            let nested_prefix = format!("{key}.");
+            sources.retain(|existing, _| {
+                existing != key && existing.starts_with(&nested_prefix).not()
+            });
+            record_sources(&overlay, source, key, sources);
+            *base = overlay;
+        }
+    }
+}
+
+/// Records `source` for every non-map value in `value`.
+fn record_sources(value: &Value, source: &Path, key: &str, sources: &mut ValueSources) {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .for_each(|(name, value)| record_sources(value, source, &join_key(key, name), sources)),
+        _ => {
+            sources.insert(key.to_owned(), source.to_owned());
+        }
+    }
+}
+
+fn join_key(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{prefix}.{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn write(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn resolve_file(path: &Path) -> Result<(Value, ValueSources), FromFileError> {
+        let content = fs::read_to_string(path).unwrap();
+        resolve(path, parse_value(path, &content)?, &tera::Context::new())
+    }
+
+    #[test]
+    fn maps_merge_and_arrays_replace() {
+        let dir = TempDir::new().unwrap();
+        let base = write(
+            &dir,
+            "base.json",
+            r#"{
+                "agent": { "namespace": "base", "ttl": 10 },
+                "feature": { "fs": { "read_only": ["a", "b"] } }
+            }"#,
+        );
+        let extra = write(&dir, "extra.toml", "[agent]\nttl = 20\n");
+        let root = write(
+            &dir,
+            "root.yaml",
+            "extends: [base.json, extra.toml]\nfeature:\n  fs:\n    read_only: [c]\n",
+        );
+
+        let (merged, sources) = resolve_file(&root).unwrap();
+
+        assert_eq!(
+            merged,
+            json!({
+                "agent": { "namespace": "base", "ttl": 20 },
+                "feature": { "fs": { "read_only": ["c"] } }
+            })
+        );
+        assert_eq!(sources["agent.namespace"], base);
+        assert_eq!(sources["agent.ttl"], extra);
+        assert_eq!(sources["feature.fs.read_only"], root);
+    }
+
+    #[test]
+    fn nested_extends_are_relative_to_the_extending_file() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("shared")).unwrap();
+        write(&dir, "shared/common.json", r#"{ "operator": false }"#);
+        write(
+            &dir,
+            "shared/base.json",
+            r#"{ "extends": "common.json", "skip_build_tools": false }"#,
+        );
+        let root = write(&dir, "root.json", r#"{ "extends": ["shared/base.json"] }"#);
+
+        let (merged, _) = resolve_file(&root).unwrap();
+
+        assert_eq!(
+            merged,
+            json!({ "operator": false, "skip_build_tools": false })
+        );
+    }
+
+    #[test]
+    fn scalar_replaces_map() {
+        let dir = TempDir::new().unwrap();
+        write(
+            &dir,
+            "base.json",
+            r#"{ "feature": { "fs": { "mode": "read" } } }"#,
+        );
+        let root = write(
+            &dir,
+            "root.json",
+            r#"{ "extends": "base.json", "feature": { "fs": "write" } }"#,
+        );
+
+        let (merged, sources) = resolve_file(&root).unwrap();
+
+        assert_eq!(merged, json!({ "feature": { "fs": "write" } }));
+        assert!(sources.contains_key("feature.fs.mode").not());
+        assert_eq!(sources["feature.fs"], root);
+    }
+
+    #[test]
+    fn cycle_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "a.json", r#"{ "extends": "b.json" }"#);
+        write(&dir, "b.json", r#"{ "extends": "a.json" }"#);
+        let root = dir.path().join("a.json");
+
+        let error = resolve_file(&root).unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                FromFileError::Extends { error, .. }
+                    if matches!(
+                        error.as_ref(),
+                        FromFileError::Extends { error, .. }
+                            if matches!(error.as_ref(), FromFileError::ExtendsCycle(..))
+                    )
+            ),
+            "{error:?}"
+        );
+    }
+}
@@ -174,6 +174,18 @@ pub struct ExperimentalConfig {
     /// Defaults to `false` in mfT.
     #[config(default = None)]
     pub go_asmcgocall: Option<bool>,
+
+    /// ### _experimental_ compression {#experimental-compression}
+    ///
+    /// Compresses large messages (e.g. file reads, mirrored or stolen HTTP bodies) exchanged
+    /// with the agent using zstd. Trades CPU time for bandwidth, which helps on slow connections
+    /// to the cluster.
+    ///
+    /// Requires an agent that supports it, otherwise it has no effect.
+    ///
+    /// Defaults to `false`.
+    #[config(default = false)]
+    pub compression: bool,
 }
 
 impl CollectAnalytics for &ExperimentalConfig {
@@ -199,6 +211,7 @@ impl CollectAnalytics for &ExperimentalConfig {
         if let Some(go_asmcgocall) = self.go_asmcgocall {
             analytics.add("go_asmcgocall", go_asmcgocall);
         }
+        analytics.add("compression", self.compression);
     }
 }
 
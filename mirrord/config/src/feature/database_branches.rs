@@ -1,4 +1,9 @@
-use std::{borrow::Cow, collections::BTreeMap, ops::Deref, path::PathBuf};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    ops::{Deref, Not},
+    path::{Component, Path, PathBuf},
+};
 
 use mirrord_analytics::{Analytics, CollectAnalytics};
 use mirrord_config_derive::MirrordConfig;
@@ -113,6 +118,7 @@ impl<T: JsonSchema> JsonSchema for SingleOrVec<T> {
 pub mod clickhouse;
 pub mod dynamodb;
 pub mod generic;
+pub mod local;
 pub mod mariadb;
 pub mod mongodb;
 pub mod mssql;
@@ -128,6 +134,7 @@ pub use dynamodb::{
     DynamodbBranchCollectionCopyConfig, DynamodbBranchConfig, DynamodbBranchCopyConfig,
 };
 pub use generic::{GenericBranchConfig, GenericReadinessConfig};
+pub use local::{BranchLocation, LocalBranchConfig};
 pub use mariadb::{MariadbBranchConfig, MariadbBranchCopyConfig, MariadbBranchTableCopyConfig};
 pub use mongodb::{
     MongodbBranchCollectionCopyConfig, MongodbBranchConfig, MongodbBranchCopyConfig,
@@ -166,18 +173,149 @@ pub enum SqlBranchMigrationsConfig {
         #[serde(default, skip_serializing_if = "Option::is_none")]
         image: Option<String>,
     },
+
+    /// Apply migrations with [Liquibase](https://docs.liquibase.com).
+    Liquibase {
+        /// Local directory holding the changelog and the files it includes.
+        ///
+        /// Resolved relative to the working directory.
+        path: PathBuf,
+        /// Changelog file, relative to `path`. Defaults to `changelog.xml`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        changelog: Option<String>,
+        /// Container image override for the migration runner.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+    },
+
+    /// Apply migrations with [golang-migrate](https://github.com/golang-migrate/migrate).
+    #[serde(rename = "golang_migrate")]
+    GolangMigrate {
+        /// Local directory holding the `*.up.sql` and `*.down.sql` files.
+        ///
+        /// Resolved relative to the working directory.
+        path: PathBuf,
+        /// Container image override for the migration runner.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+    },
+
+    /// Apply migrations with [sqlx](https://github.com/launchbadge/sqlx/tree/main/sqlx-cli).
+    ///
+    /// Not supported for `mssql` branches.
+    Sqlx {
+        /// Local directory holding the migration files.
+        ///
+        /// Resolved relative to the working directory.
+        path: PathBuf,
+        /// Container image override for the migration runner.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+    },
+
+    /// Apply migrations with [Alembic](https://alembic.sqlalchemy.org).
+    ///
+    /// Alembic migrations import the project's Python code, so there is no default image.
+    Alembic {
+        /// Local directory holding `alembic.ini` and the migration environment.
+        ///
+        /// Resolved relative to the working directory.
+        path: PathBuf,
+        /// Container image with Python, Alembic and the project's dependencies.
+        image: String,
+    },
+
+    /// Apply versioned migrations with [Atlas](https://atlasgo.io).
+    Atlas {
+        /// Local directory holding the migration files and `atlas.sum`.
+        ///
+        /// Resolved relative to the working directory.
+        path: PathBuf,
+        /// Container image override for the migration runner.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+    },
+
+    /// Run an arbitrary command, with the branch connection string in the `env` variable.
+    ///
+    /// With `image`, the command runs in the cluster next to the branch. Without it, the mirrord
+    /// CLI runs it locally before starting the application.
+    Command {
+        /// The command and its arguments.
+        command: Vec<String>,
+        /// Container image to run the command in.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+        /// Local directory to make available to the command, in its working directory.
+        ///
+        /// Requires `image`, local commands run in the current working directory.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<PathBuf>,
+        /// Name of the env var holding the branch connection string. Defaults to
+        /// `DATABASE_URL`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        env: Option<String>,
+    },
 }
 
 impl SqlBranchMigrationsConfig {
-    fn verify(&self, base: &DatabaseBranchBaseConfig) -> Result<(), ConfigError> {
-        if base.name.is_some() {
-            Ok(())
-        } else {
-            const MESSAGE: &str = "`feature.db_branches[].migrations` requires `feature.db_branches[].name` to be set.";
+    /// Env var holding the branch connection string for the `command` flavor, when the config
+    /// doesn't set `env`.
+    pub const DEFAULT_COMMAND_ENV: &str = "DATABASE_URL";
 
-            Err(ConfigError::Conflict(MESSAGE.to_owned()))
-        }
+    /// Whether the migrations run on the user's machine instead of in the cluster.
+    pub fn is_local(&self) -> bool {
+        matches!(self, Self::Command { image: None, .. })
     }
+
+    /// `engine` is the `type` of the branch, e.g. `pg`.
+    fn verify(&self, base: &DatabaseBranchBaseConfig, engine: &str) -> Result<(), ConfigError> {
+        let conflict = match self {
+            _ if self.is_local().not() && base.name.is_none() => {
+                "`feature.db_branches[].migrations` requires `feature.db_branches[].name` to be set."
+            }
+            Self::Liquibase {
+                changelog: Some(changelog),
+                ..
+            } if is_nested_relative(Path::new(changelog)).not() => {
+                "`feature.db_branches[].migrations.changelog` must be a relative path inside \
+                 `feature.db_branches[].migrations.path`."
+            }
+            Self::Sqlx { .. } if engine == "mssql" => {
+                "`feature.db_branches[].migrations` flavor `sqlx` is not supported for `mssql` \
+                 branches."
+            }
+            Self::Command { command, .. } if command.is_empty() => {
+                "`feature.db_branches[].migrations.command` must not be empty."
+            }
+            Self::Command {
+                image: None,
+                path: Some(_),
+                ..
+            } => {
+                "`feature.db_branches[].migrations.path` requires \
+                 `feature.db_branches[].migrations.image` for the `command` flavor."
+            }
+            Self::Command { env: Some(env), .. } if env.is_empty() || env.contains(['=', '\0']) => {
+                "`feature.db_branches[].migrations.env` must be a valid env var name."
+            }
+            Self::Flyway { .. }
+            | Self::Liquibase { .. }
+            | Self::GolangMigrate { .. }
+            | Self::Sqlx { .. }
+            | Self::Alembic { .. }
+            | Self::Atlas { .. }
+            | Self::Command { .. } => return Ok(()),
+        };
+
+        Err(ConfigError::Conflict(conflict.to_owned()))
+    }
+}
+
+/// Whether `path` is relative and stays inside the directory it's resolved against.
+fn is_nested_relative(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
 }
 
 /// IAM authentication for the source database.
@@ -395,32 +533,47 @@ impl DatabaseBranchesConfig {
 
                     cfg.migrations
                         .as_ref()
-                        .map(|migrations| migrations.verify(&cfg.base))
+                        .map(|migrations| migrations.verify(&cfg.base, "mariadb"))
                         .transpose()?;
                 }
-                DatabaseBranchConfig::Mongodb(cfg) => cfg.base.verify()?,
+                DatabaseBranchConfig::Mongodb(cfg) => {
+                    cfg.base.verify()?;
+                    verify_location(cfg.location, cfg.local.as_ref(), false, false)?;
+                }
                 DatabaseBranchConfig::Mssql(cfg) => {
                     cfg.base.verify()?;
 
                     cfg.migrations
                         .as_ref()
-                        .map(|migrations| migrations.verify(&cfg.base))
+                        .map(|migrations| migrations.verify(&cfg.base, "mssql"))
                         .transpose()?;
                 }
                 DatabaseBranchConfig::Mysql(cfg) => {
                     cfg.base.verify()?;
+                    verify_location(
+                        cfg.location,
+                        cfg.local.as_ref(),
+                        cfg.migrations.is_some(),
+                        cfg.iam_auth.is_some(),
+                    )?;
 
                     cfg.migrations
                         .as_ref()
-                        .map(|migrations| migrations.verify(&cfg.base))
+                        .map(|migrations| migrations.verify(&cfg.base, "mysql"))
                         .transpose()?;
                 }
                 DatabaseBranchConfig::Pg(cfg) => {
                     cfg.base.verify()?;
+                    verify_location(
+                        cfg.location,
+                        cfg.local.as_ref(),
+                        cfg.migrations.is_some(),
+                        cfg.iam_auth.is_some(),
+                    )?;
 
                     cfg.migrations
                         .as_ref()
-                        .map(|migrations| migrations.verify(&cfg.base))
+                        .map(|migrations| migrations.verify(&cfg.base, "pg"))
                         .transpose()?;
                 }
                 DatabaseBranchConfig::Redis(cfg) => match &**cfg {
@@ -430,11 +583,95 @@ impl DatabaseBranchesConfig {
                 DatabaseBranchConfig::Spanner(cfg) => cfg.base.verify()?,
             }
         }
+
+        let mut local_ports = HashSet::new();
+        if let Some(port) = self
+            .0
+            .iter()
+            .filter_map(DatabaseBranchConfig::local_port)
+            .find(|port| local_ports.insert(*port).not())
+        {
+            return Err(ConfigError::Conflict(format!(
+                "`feature.db_branches[].local.port` must be unique across local branches, \
+                 port {port} is used more than once."
+            )));
+        }
+
         Ok(())
     }
 }
 
+/// Checks that `local` is only given for local branches, and that local branches don't use
+/// features that only the operator implements.
+fn verify_location(
+    location: BranchLocation,
+    local: Option<&LocalBranchConfig>,
+    has_migrations: bool,
+    has_iam_auth: bool,
+) -> Result<(), ConfigError> {
+    let conflict = match location {
+        BranchLocation::Remote if local.is_some() => {
+            "`feature.db_branches[].local` requires `feature.db_branches[].location` to be `local`."
+        }
+        BranchLocation::Local if has_migrations => {
+            "`feature.db_branches[].migrations` is not supported for local branches."
+        }
+        BranchLocation::Local if has_iam_auth => {
+            "`feature.db_branches[].iam_auth` is not supported for local branches."
+        }
+        BranchLocation::Remote | BranchLocation::Local => return Ok(()),
+    };
+
+    Err(ConfigError::Conflict(conflict.to_owned()))
+}
+
 impl DatabaseBranchConfig {
+    /// Whether this branch runs on the user's machine, in which case the operator never sees
+    /// it.
+    pub fn is_local(&self) -> bool {
+        match self {
+            DatabaseBranchConfig::Mongodb(cfg) => cfg.location == BranchLocation::Local,
+            DatabaseBranchConfig::Mysql(cfg) => cfg.location == BranchLocation::Local,
+            DatabaseBranchConfig::Pg(cfg) => cfg.location == BranchLocation::Local,
+            DatabaseBranchConfig::Redis(cfg) => matches!(&**cfg, RedisBranchConfig::Local(_)),
+            DatabaseBranchConfig::Clickhouse(_)
+            | DatabaseBranchConfig::Dynamodb(_)
+            | DatabaseBranchConfig::Generic(_)
+            | DatabaseBranchConfig::Mariadb(_)
+            | DatabaseBranchConfig::Mssql(_)
+            | DatabaseBranchConfig::Spanner(_) => false,
+        }
+    }
+
+    /// Port on the user's machine that this branch binds to, [`None`] for remote branches.
+    ///
+    /// Defaults to the standard port of the engine.
+    pub fn local_port(&self) -> Option<u16> {
+        if self.is_local().not() {
+            return None;
+        }
+
+        let (local, default_port) = match self {
+            DatabaseBranchConfig::Mongodb(cfg) => (cfg.local.as_ref(), 27017),
+            DatabaseBranchConfig::Mysql(cfg) => (cfg.local.as_ref(), 3306),
+            DatabaseBranchConfig::Pg(cfg) => (cfg.local.as_ref(), 5432),
+            DatabaseBranchConfig::Redis(cfg) => {
+                return match &**cfg {
+                    RedisBranchConfig::Local(local) => Some(local.local.port),
+                    RedisBranchConfig::Remote(_) => None,
+                };
+            }
+            DatabaseBranchConfig::Clickhouse(_)
+            | DatabaseBranchConfig::Dynamodb(_)
+            | DatabaseBranchConfig::Generic(_)
+            | DatabaseBranchConfig::Mariadb(_)
+            | DatabaseBranchConfig::Mssql(_)
+            | DatabaseBranchConfig::Spanner(_) => return None,
+        };
+
+        Some(local.and_then(|local| local.port).unwrap_or(default_port))
+    }
+
     /// The shared base config of this branch, when the variant has one (local Redis
     /// branches don't - they never reach the operator).
     pub fn base(&self) -> Option<&DatabaseBranchBaseConfig> {
@@ -455,6 +692,22 @@ impl DatabaseBranchConfig {
         }
     }
 
+    /// The schema migrations of this branch, for the SQL engines that have them.
+    pub fn migrations(&self) -> Option<&SqlBranchMigrationsConfig> {
+        match self {
+            DatabaseBranchConfig::Mariadb(cfg) => cfg.migrations.as_ref(),
+            DatabaseBranchConfig::Mssql(cfg) => cfg.migrations.as_ref(),
+            DatabaseBranchConfig::Mysql(cfg) => cfg.migrations.as_ref(),
+            DatabaseBranchConfig::Pg(cfg) => cfg.migrations.as_ref(),
+            DatabaseBranchConfig::Clickhouse(_)
+            | DatabaseBranchConfig::Dynamodb(_)
+            | DatabaseBranchConfig::Generic(_)
+            | DatabaseBranchConfig::Mongodb(_)
+            | DatabaseBranchConfig::Redis(_)
+            | DatabaseBranchConfig::Spanner(_) => None,
+        }
+    }
+
     /// Names of target-pod env vars that the operator uses to redirect this branch's
     /// connection. Locally overriding any of these (via `feature.env.override`) would
     /// fight the operator's redirection, so [`LayerConfig::verify`] rejects such configs.
@@ -464,6 +717,11 @@ impl DatabaseBranchConfig {
         let mut keys = Vec::new();
 
         match self {
+            // The CLI itself overrides these for local branches.
+            DatabaseBranchConfig::Mongodb(_)
+            | DatabaseBranchConfig::Mysql(_)
+            | DatabaseBranchConfig::Pg(_)
+                if self.is_local() => {}
             DatabaseBranchConfig::Clickhouse(cfg) => {
                 cfg.base.connection.collect_env_keys(&mut keys)
             }
@@ -639,18 +897,41 @@ impl ConnectionParamsVars {
 ///
 /// #### feature.db_branches[].migrations (type: mysql, mariadb, pg, mssql, clickhouse) {#feature-db_branches-sql-migrations}
 ///
-/// Schema migrations to run on the branch after it is created. Currently supports
-/// [Flyway](https://documentation.red-gate.com/flyway):
+/// Schema migrations to run on the branch after it is created. The `flavor` picks the migration
+/// tool:
 ///
 /// ```json
 /// { "migrations": { "flavor": "flyway", "path": "./migrations" } }
 /// ```
 ///
+/// - `flyway`: [Flyway](https://documentation.red-gate.com/flyway).
+/// - `liquibase`: [Liquibase](https://docs.liquibase.com), `changelog` sets the changelog file
+///   relative to `path` (defaults to `changelog.xml`).
+/// - `golang_migrate`: [golang-migrate](https://github.com/golang-migrate/migrate).
+/// - `sqlx`: [sqlx](https://github.com/launchbadge/sqlx/tree/main/sqlx-cli), not supported for
+///   `mssql` branches.
+/// - `alembic`: [Alembic](https://alembic.sqlalchemy.org), `path` holds `alembic.ini`, and `image`
+///   is required, as it needs the project's dependencies.
+/// - `atlas`: [Atlas](https://atlasgo.io) versioned migrations.
+///
+/// Each of these takes:
+///
 /// - `path`: local directory holding the migration files, resolved relative to the working
 ///   directory.
 /// - `image`: optional container image override for the migration runner.
 ///
-/// Requires [`name`](#feature-db_branches-sql-name) to be set.
+/// The `command` flavor runs any command, with the branch connection string in the `env`
+/// variable (defaults to `DATABASE_URL`):
+///
+/// ```json
+/// { "migrations": { "flavor": "command", "command": ["npx", "prisma", "migrate", "deploy"] } }
+/// ```
+///
+/// With `image`, the command runs in the cluster, and `path` is copied to its working directory.
+/// Without `image`, the mirrord CLI runs the command locally before starting the application,
+/// reaching the branch through the agent.
+///
+/// Requires [`name`](#feature-db_branches-sql-name) to be set, except for local commands.
 #[derive(Clone, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
 pub enum DatabaseBranchConfig {
@@ -1662,6 +1943,8 @@ mod tests {
             connection_settings: Default::default(),
             iam_auth: None,
             migrations: None,
+            location: Default::default(),
+            local: None,
         }))
     }
 
@@ -1735,4 +2018,132 @@ mod tests {
         )));
         assert_eq!(branch.connection_env_keys(), vec!["REDIS_URL"]);
     }
+
+    /// A local pg branch parses, is not redirected by the operator, and rejects operator-only
+    /// features.
+    #[test]
+    fn local_pg_branch() {
+        let json = r#"{
+            "type": "pg",
+            "location": "local",
+            "connection": { "url": { "type": "env", "variable": "DATABASE_URL" } },
+            "local": { "port": 15432 }
+        }"#;
+        let branch: DatabaseBranchConfig = serde_json::from_str(json).unwrap();
+        assert!(branch.is_local());
+        assert!(branch.connection_env_keys().is_empty());
+
+        let mut context = config::ConfigContext::default();
+        DatabaseBranchesConfig(vec![branch.clone()])
+            .verify(&mut context)
+            .unwrap();
+
+        let DatabaseBranchConfig::Pg(mut pg) = branch else {
+            panic!("expected pg branch");
+        };
+        assert_eq!(pg.local.as_ref().and_then(|local| local.port), Some(15432));
+
+        pg.migrations = Some(SqlBranchMigrationsConfig::Flyway {
+            path: "./migrations".into(),
+            image: None,
+        });
+        pg.base.name = Some("app".to_owned());
+        assert!(matches!(
+            DatabaseBranchesConfig(vec![DatabaseBranchConfig::Pg(pg)]).verify(&mut context),
+            Err(ConfigError::Conflict(_))
+        ));
+    }
+
+    /// Two local branches can't bind to the same port, including the default ones.
+    #[test]
+    fn local_branches_port_conflict() {
+        let branch = |json: &str| serde_json::from_str::<DatabaseBranchConfig>(json).unwrap();
+        let pg = branch(
+            r#"{
+                "type": "pg",
+                "location": "local",
+                "connection": { "url": { "type": "env", "variable": "DATABASE_URL" } }
+            }"#,
+        );
+        let other_pg = branch(
+            r#"{
+                "type": "pg",
+                "location": "local",
+                "connection": { "url": { "type": "env", "variable": "OTHER_DATABASE_URL" } },
+                "local": { "port": 15432 }
+            }"#,
+        );
+        let verify = |branches: Vec<DatabaseBranchConfig>| {
+            DatabaseBranchesConfig(branches).verify(&mut config::ConfigContext::default())
+        };
+
+        assert_eq!(pg.local_port(), Some(5432));
+        verify(vec![pg.clone(), other_pg.clone()]).unwrap();
+        assert!(matches!(
+            verify(vec![pg.clone(), pg]),
+            Err(ConfigError::Conflict(_))
+        ));
+        assert!(matches!(
+            verify(vec![other_pg.clone(), other_pg]),
+            Err(ConfigError::Conflict(_))
+        ));
+    }
+
+    /// Each migration flavor parses, and `verify` rejects invalid flavor configs.
+    #[test]
+    fn migration_flavors() {
+        let verify = |kind: &str, migrations: &str| {
+            let json = format!(
+                r#"{{
+                    "type": "{kind}",
+                    "name": "app",
+                    "connection": {{ "url": {{ "type": "env", "variable": "DATABASE_URL" }} }},
+                    "migrations": {migrations}
+                }}"#
+            );
+            let branch: DatabaseBranchConfig = serde_json::from_str(&json).unwrap();
+            DatabaseBranchesConfig(vec![branch]).verify(&mut config::ConfigContext::default())
+        };
+
+        for migrations in [
+            r#"{ "flavor": "liquibase", "path": "./db", "changelog": "changelog.yaml" }"#,
+            r#"{ "flavor": "golang_migrate", "path": "./migrations" }"#,
+            r#"{ "flavor": "sqlx", "path": "./migrations" }"#,
+            r#"{ "flavor": "alembic", "path": ".", "image": "my-app:latest" }"#,
+            r#"{ "flavor": "atlas", "path": "./migrations" }"#,
+            r#"{ "flavor": "command", "command": ["npx", "prisma", "migrate", "deploy"] }"#,
+            r#"{ "flavor": "command", "command": ["./migrate.sh"], "image": "my-app:latest", "path": "./scripts" }"#,
+        ] {
+            verify("pg", migrations).unwrap();
+        }
+
+        for (kind, migrations) in [
+            ("mssql", r#"{ "flavor": "sqlx", "path": "./migrations" }"#),
+            (
+                "pg",
+                r#"{ "flavor": "liquibase", "path": "./db", "changelog": "../changelog.xml" }"#,
+            ),
+            ("pg", r#"{ "flavor": "command", "command": [] }"#),
+            (
+                "pg",
+                r#"{ "flavor": "command", "command": ["./migrate.sh"], "path": "./scripts" }"#,
+            ),
+            (
+                "pg",
+                r#"{ "flavor": "command", "command": ["./migrate.sh"], "env": "A=B" }"#,
+            ),
+        ] {
+            assert!(
+                matches!(verify(kind, migrations), Err(ConfigError::Conflict(_))),
+                "{migrations} should be rejected for {kind}"
+            );
+        }
+
+        assert!(
+            serde_json::from_str::<SqlBranchMigrationsConfig>(
+                r#"{ "flavor": "alembic", "path": "." }"#
+            )
+            .is_err()
+        );
+    }
 }
@@ -0,0 +1,55 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::container::ContainerRuntime;
+
+/// Where a database branch runs.
+///
+/// - `remote` (default): The branch is created in the cluster by the mirrord operator.
+/// - `local`: The branch is a container on the user's machine, spawned and seeded by the mirrord
+///   CLI. The seed data is read from the source database through the agent.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BranchLocation {
+    #[default]
+    Remote,
+    Local,
+}
+
+impl BranchLocation {
+    pub fn is_remote(&self) -> bool {
+        matches!(self, Self::Remote)
+    }
+}
+
+/// Configuration for a local database branch container.
+///
+/// The container image is taken from `image`, or built from the official image of the engine and
+/// `version`.
+///
+/// Example:
+/// ```json
+/// {
+///   "type": "pg",
+///   "location": "local",
+///   "version": "16",
+///   "connection": { "url": { "type": "env", "variable": "DATABASE_URL" } },
+///   "local": { "port": 15432, "container_runtime": "podman" }
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LocalBranchConfig {
+    /// Local port to bind the branch to. Defaults to the standard port of the engine.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+
+    /// Which container runtime to use (Docker, Podman, or nerdctl).
+    #[serde(default)]
+    pub container_runtime: ContainerRuntime,
+
+    /// Custom path to the container command.
+    /// If not provided, uses the runtime name from PATH (e.g., "docker").
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_command: Option<String>,
+}
@@ -3,7 +3,7 @@ use std::collections::BTreeMap;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::DatabaseBranchBaseConfig;
+use super::{BranchLocation, DatabaseBranchBaseConfig, LocalBranchConfig};
 
 /// When configuring a branch for MongoDB, set `type` to `mongodb`.
 #[derive(Clone, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize)]
@@ -14,6 +14,20 @@ pub struct MongodbBranchConfig {
 
     #[serde(default)]
     pub copy: MongodbBranchCopyConfig,
+
+    /// #### feature.db_branches[].location (type: mongodb) {#feature-db_branches-mongodb-location}
+    ///
+    /// Where the branch runs: `remote` (default) creates it in the cluster through the mirrord
+    /// operator, `local` spawns a container on this machine and seeds it from the source
+    /// database through the agent, honoring `copy`.
+    #[serde(default, skip_serializing_if = "BranchLocation::is_remote")]
+    pub location: BranchLocation,
+
+    /// #### feature.db_branches[].local (type: mongodb) {#feature-db_branches-mongodb-local}
+    ///
+    /// Local container configuration, used with `location: local`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local: Option<LocalBranchConfig>,
 }
 
 /// Users can choose from the following copy mode to bootstrap their MongoDB branch database:
@@ -3,7 +3,10 @@ use std::collections::BTreeMap;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::{DatabaseBranchBaseConfig, IamAuthConfig, SqlBranchMigrationsConfig};
+use super::{
+    BranchLocation, DatabaseBranchBaseConfig, IamAuthConfig, LocalBranchConfig,
+    SqlBranchMigrationsConfig,
+};
 
 /// When configuring a branch for MySQL, set `type` to `mysql`.
 #[derive(Clone, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize)]
@@ -27,6 +30,20 @@ pub struct MysqlBranchConfig {
     /// Documented on `DatabaseBranchConfig` (shared across SQL engines).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub migrations: Option<SqlBranchMigrationsConfig>,
+
+    /// #### feature.db_branches[].location (type: mysql) {#feature-db_branches-mysql-location}
+    ///
+    /// Where the branch runs: `remote` (default) creates it in the cluster through the mirrord
+    /// operator, `local` spawns a container on this machine and seeds it from the source
+    /// database through the agent, honoring `copy`.
+    #[serde(default, skip_serializing_if = "BranchLocation::is_remote")]
+    pub location: BranchLocation,
+
+    /// #### feature.db_branches[].local (type: mysql) {#feature-db_branches-mysql-local}
+    ///
+    /// Local container configuration, used with `location: local`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local: Option<LocalBranchConfig>,
 }
 
 /// Users can choose from the following copy mode to bootstrap their MySQL branch database.
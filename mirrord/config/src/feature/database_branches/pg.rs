@@ -3,7 +3,10 @@ use std::collections::BTreeMap;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::{DatabaseBranchBaseConfig, IamAuthConfig, SqlBranchMigrationsConfig};
+use super::{
+    BranchLocation, DatabaseBranchBaseConfig, IamAuthConfig, LocalBranchConfig,
+    SqlBranchMigrationsConfig,
+};
 
 /// When configuring a branch for PostgreSQL, set `type` to `pg`.
 #[derive(Clone, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize)]
@@ -43,6 +46,20 @@ pub struct PgBranchConfig {
     /// Documented on `DatabaseBranchConfig` (shared across SQL engines).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub migrations: Option<SqlBranchMigrationsConfig>,
+
+    /// #### feature.db_branches[].location (type: pg) {#feature-db_branches-pg-location}
+    ///
+    /// Where the branch runs: `remote` (default) creates it in the cluster through the mirrord
+    /// operator, `local` spawns a container on this machine and seeds it from the source
+    /// database through the agent, honoring `copy`.
+    #[serde(default, skip_serializing_if = "BranchLocation::is_remote")]
+    pub location: BranchLocation,
+
+    /// #### feature.db_branches[].local (type: pg) {#feature-db_branches-pg-local}
+    ///
+    /// Local container configuration, used with `location: local`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local: Option<LocalBranchConfig>,
 }
 
 /// Users can choose from the following copy mode to bootstrap their PostgreSQL branch database.
@@ -42,6 +42,67 @@ pub struct MagicConfig {
     #[config(default = true)]
     pub aws: bool,
 
+    /// #### feature.magic.gcp {#feature-magic-gcp}
+    ///
+    /// Google Cloud client libraries prefer local credentials (`GOOGLE_APPLICATION_CREDENTIALS`,
+    /// or the `gcloud` application default credentials in `~/.config/gcloud`) over the metadata
+    /// server, so the remote pod's Workload Identity is never used while they are present.
+    ///
+    /// When enabled, mirrord makes local Google Cloud configuration unavailable to the process
+    /// by:
+    /// - Unsetting `GOOGLE_APPLICATION_CREDENTIALS` and `CLOUDSDK_CONFIG`.
+    /// - Mapping `~/.config/gcloud` to a temporary directory.
+    ///
+    /// This allows the remote pod's Workload Identity to be used through the metadata server.
+    ///
+    /// Disable this only if you intentionally need local Google Cloud credentials inside the
+    /// local mirrord process.
+    ///
+    /// Defaults to `true`.
+    #[config(default = true)]
+    pub gcp: bool,
+
+    /// #### feature.magic.azure {#feature-magic-azure}
+    ///
+    /// Azure SDKs try local credentials (service principal secrets in `AZURE_*` environment
+    /// variables, or the Azure CLI login in `~/.azure`) before the remote pod's managed or
+    /// workload identity.
+    ///
+    /// When enabled, mirrord makes local Azure configuration unavailable to the process by:
+    /// - Unsetting `AZURE_CLIENT_SECRET`, `AZURE_CLIENT_CERTIFICATE_PATH`,
+    ///   `AZURE_CLIENT_CERTIFICATE_PASSWORD`, `AZURE_USERNAME`, `AZURE_PASSWORD` and
+    ///   `AZURE_CONFIG_DIR`.
+    /// - Mapping `~/.azure` to a temporary directory.
+    ///
+    /// `AZURE_CLIENT_ID`, `AZURE_TENANT_ID`, `AZURE_FEDERATED_TOKEN_FILE` and
+    /// `AZURE_AUTHORITY_HOST` are kept, as Azure Workload Identity sets them in the remote pod.
+    ///
+    /// Disable this only if you intentionally need local Azure credentials inside the local
+    /// mirrord process.
+    ///
+    /// Defaults to `true`.
+    #[config(default = true)]
+    pub azure: bool,
+
+    /// #### feature.magic.kube {#feature-magic-kube}
+    ///
+    /// Kubernetes clients prefer a kubeconfig (`KUBECONFIG`, or `~/.kube/config`) over the
+    /// in-cluster config, so a process that talks to the Kubernetes API would use your local
+    /// credentials instead of the remote pod's service account.
+    ///
+    /// When enabled, mirrord makes local Kubernetes configuration unavailable to the process by:
+    /// - Unsetting `KUBECONFIG`.
+    /// - Mapping `~/.kube` to a temporary directory.
+    ///
+    /// The client then falls back to the in-cluster config, reading the service account token
+    /// mounted in the remote pod.
+    ///
+    /// Note that this also affects tools like `kubectl` when they're run with mirrord.
+    ///
+    /// Defaults to `false`.
+    #[config(default = false)]
+    pub kube: bool,
+
     /// #### feature.magic.auto_mount {#feature-magic-auto_mount}
     ///
     /// Kubernetes mounts ConfigMaps, Secrets, and volumes (e.g. PVCs) into the target container at
@@ -93,6 +154,9 @@ pub struct MagicConfig {
 impl CollectAnalytics for &MagicConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("aws", self.aws);
+        analytics.add("gcp", self.gcp);
+        analytics.add("azure", self.azure);
+        analytics.add("kube", self.kube);
         analytics.add("auto_mount", self.auto_mount);
         analytics.add("turbo", self.turbo);
     }
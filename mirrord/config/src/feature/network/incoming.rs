@@ -1,9 +1,12 @@
 use std::{collections::HashSet, fmt, ops::Not, str::FromStr};
 
 use bimap::BiMap;
+use fallback::IncomingFallback;
 use mirrord_analytics::{AnalyticValue, Analytics, CollectAnalytics};
+use rewrite::IncomingRewrite;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize, de, ser, ser::SerializeSeq as _};
+use shadow::IncomingShadow;
 use thiserror::Error;
 use tls_delivery::LocalTlsDelivery;
 
@@ -15,7 +18,10 @@ use crate::{
     util::{MirrordToggleableConfig, ToggleableConfig},
 };
 
+pub mod fallback;
 pub mod http_filter;
+pub mod rewrite;
+pub mod shadow;
 pub mod tls_delivery;
 
 use http_filter::*;
@@ -138,6 +144,9 @@ impl MirrordConfig for IncomingFileConfig {
                 ports: advanced.ports.map(|ports| ports.into_iter().collect()),
                 https_delivery: advanced.https_delivery,
                 tls_delivery: advanced.tls_delivery,
+                fallback: advanced.fallback,
+                shadow: advanced.shadow,
+                rewrite: advanced.rewrite,
             },
         };
 
@@ -344,6 +353,24 @@ pub struct IncomingAdvancedFileConfig {
     /// (Operator Only): configures how mirrord delivers stolen TLS traffic
     /// to the local application.
     pub tls_delivery: Option<LocalTlsDelivery>,
+
+    /// ### fallback
+    ///
+    /// Passes stolen HTTP requests through to their original destination, when the local
+    /// application fails to handle them.
+    pub fallback: Option<IncomingFallback>,
+
+    /// ### shadow
+    ///
+    /// Compares the responses of the local application to mirrored HTTP requests with the
+    /// responses of the remote service.
+    pub shadow: Option<IncomingShadow>,
+
+    /// ### rewrite
+    ///
+    /// Rewrites stolen HTTP requests before they are delivered to the local application, and its
+    /// responses on the way back.
+    pub rewrite: Option<IncomingRewrite>,
 }
 
 fn serialize_bi_map<S>(map: &BiMap<u16, u16>, serializer: S) -> Result<S::Ok, S::Error>
@@ -521,6 +548,80 @@ pub struct IncomingConfig {
     /// (Operator Only): configures how mirrord delivers stolen TLS traffic
     /// to the local application.
     pub tls_delivery: Option<LocalTlsDelivery>,
+
+    /// ##### feature.network.incoming.fallback {#feature-network-incoming-fallback}
+    ///
+    /// Passes stolen HTTP requests through to their original destination, when the local
+    /// application fails to handle them. Only does something when
+    /// [`feature.network.incoming.mode`](#feature-network-incoming-mode) is set as `"steal"`.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "fallback": {
+    ///           "timeout_ms": 5000,
+    ///           "status_codes": [502, 503, 504]
+    ///         }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub fallback: Option<IncomingFallback>,
+
+    /// ##### feature.network.incoming.shadow {#feature-network-incoming-shadow}
+    ///
+    /// Compares the responses of the local application to mirrored HTTP requests with the
+    /// responses of the remote service, and reports the mismatches. Only does something when
+    /// [`feature.network.incoming.mode`](#feature-network-incoming-mode) is set as `"mirror"`.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "mirror",
+    ///         "shadow": {
+    ///           "ignore_paths": ["/timestamp"],
+    ///           "ignore_headers": ["x-request-id"]
+    ///         }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub shadow: Option<IncomingShadow>,
+
+    /// ##### feature.network.incoming.rewrite {#feature-network-incoming-rewrite}
+    ///
+    /// Rewrites stolen HTTP requests before they are delivered to the local application, and the
+    /// local application's responses on the way back. Only does something when
+    /// [`feature.network.incoming.mode`](#feature-network-incoming-mode) is set as `"steal"`.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "rewrite": {
+    ///           "request": {
+    ///             "path_prefix": [{ "from": "/api/users", "to": "/users" }],
+    ///             "host": "localhost"
+    ///           },
+    ///           "response": {
+    ///             "cookie_domain": ""
+    ///           }
+    ///         }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub rewrite: Option<IncomingRewrite>,
 }
 
 impl IncomingConfig {
@@ -715,6 +816,9 @@ impl CollectAnalytics for &IncomingConfig {
         analytics.add("ignore_localhost", self.ignore_localhost);
         analytics.add("ignore_ports_count", self.ignore_ports.len());
         analytics.add("http", &self.http_filter);
+        analytics.add("fallback", self.fallback.is_some());
+        analytics.add("shadow", self.shadow.is_some());
+        analytics.add("rewrite", self.rewrite.is_some());
     }
 }
 
@@ -737,6 +841,9 @@ mod tests {
             ports: None,
             https_delivery: None,
             tls_delivery: None,
+            fallback: None,
+            shadow: None,
+            rewrite: None,
         }))
     }
 
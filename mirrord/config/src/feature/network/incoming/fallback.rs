@@ -0,0 +1,44 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Stolen HTTP requests that the local application fails to handle can be passed through to their
+/// original destination in the cluster, as if they were never stolen. This way, the clients of
+/// the remote service are not affected when your local application is down or restarting.
+///
+/// A request falls back to its original destination when:
+///
+/// 1. mirrord fails to connect to the local application;
+/// 2. The local application does not respond in
+///    [`timeout_ms`](#feature-network-incoming-fallback-timeout_ms);
+/// 3. The local application responds with one of the
+///    [`status_codes`](#feature-network-incoming-fallback-status_codes).
+///
+/// Only requests with bodies not bigger than the agent's HTTP body buffer (64KiB by default) can
+/// fall back, and HTTP upgrades never do.
+///
+/// ```json
+/// {
+///   "timeout_ms": 5000,
+///   "status_codes": [502, 503, 504]
+/// }
+/// ```
+///
+/// Requires the mirrord-agent to support mirrord-protocol version 1.33.0 or newer.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct IncomingFallback {
+    /// ##### feature.network.incoming.fallback.timeout_ms {#feature-network-incoming-fallback-timeout_ms}
+    ///
+    /// How long to wait for the local application's response, in milliseconds.
+    ///
+    /// If not set, mirrord waits indefinitely.
+    pub timeout_ms: Option<u64>,
+
+    /// ##### feature.network.incoming.fallback.status_codes {#feature-network-incoming-fallback-status_codes}
+    ///
+    /// Response status codes that make the request fall back to its original destination.
+    ///
+    /// Defaults to `[]`.
+    #[serde(default)]
+    pub status_codes: Vec<u16>,
+}
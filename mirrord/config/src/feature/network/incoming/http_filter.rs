@@ -4,8 +4,8 @@ use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
 use mirrord_protocol::tcp::{
     Filter, HTTP_BODY_JSON_FILTER_VERSION, HTTP_COMPOSITE_FILTER_VERSION,
-    HTTP_HEADER_JQ_FILTER_VERSION, HTTP_METHOD_FILTER_VERSION, HttpBodyFilter, HttpFilter,
-    HttpMethodFilter, JqQuery, JsonPathQuery,
+    HTTP_HEADER_JQ_FILTER_VERSION, HTTP_METHOD_FILTER_VERSION, HTTP_WEBSOCKET_FILTER_VERSION,
+    HttpBodyFilter, HttpFilter, HttpMethodFilter, JqQuery, JsonPathQuery, WebSocketFilter,
 };
 use schemars::JsonSchema;
 use semver::{Version, VersionReq};
@@ -89,6 +89,17 @@ use crate::{
 /// }
 /// ```
 ///
+/// To steal only WebSocket connections of a given subprotocol, whose first message
+/// subscribes to a given channel:
+/// ```json
+/// {
+///   "websocket_filter": {
+///     "subprotocol": "^graphql-transport-ws$",
+///     "initial_message": "\"channel\":\\s*\"orders\""
+///   }
+/// }
+/// ```
+///
 /// If you want to steal HTTP requests that match **any** of the patterns specified, use `any_of`.
 /// For example, this filter steals HTTP requests to `/api/my-endpoint`, or requests whose
 /// `baggage` header contains `mirrord-session={{ key }}`.
@@ -151,6 +162,12 @@ pub struct HttpFilterConfig {
     #[config(env = "MIRRORD_HTTP_HEADER_FILTER_JQ")]
     pub header_filter_jq: Option<String>,
 
+    /// ##### feature.network.incoming.http_filter.websocket_filter {#feature-network-incoming-http-websocket-filter}
+    ///
+    /// Matches only WebSocket upgrade requests, based on the subprotocols offered by the client
+    /// and/or the first message it sends.
+    pub websocket_filter: Option<WebSocketFilterConfig>,
+
     /// ##### feature.network.incoming.http_filter.all_of {#feature-network-incoming-http_filter-all_of}
     ///
     /// An array of HTTP filters.
@@ -210,6 +227,7 @@ impl HttpFilterConfig {
             || self.any_of.is_some()
             || self.body_filter.is_some()
             || self.header_filter_jq.is_some()
+            || self.websocket_filter.is_some()
     }
 
     /// Rejects configured filter values that are present but empty.
@@ -229,6 +247,10 @@ impl HttpFilterConfig {
             body_filter.ensure_no_empty_strings("body_filter")?;
         }
 
+        if let Some(websocket_filter) = &self.websocket_filter {
+            websocket_filter.ensure_no_empty_strings("websocket_filter")?;
+        }
+
         if let Some(filters) = &self.all_of {
             for (index, filter) in filters.iter().enumerate() {
                 filter.ensure_no_empty_strings(&format!("all_of[{index}]"))?;
@@ -249,7 +271,7 @@ impl HttpFilterConfig {
         agent_protocol_version: Option<Version>,
     ) -> Result<(), ConfigError> {
         #![allow(clippy::type_complexity)]
-        static REQUIREMENTS: [(fn(&HttpFilterConfig) -> bool, &LazyLock<VersionReq>, &str); 4] = [
+        static REQUIREMENTS: [(fn(&HttpFilterConfig) -> bool, &LazyLock<VersionReq>, &str); 5] = [
             (
                 HttpFilterConfig::is_composite,
                 &HTTP_COMPOSITE_FILTER_VERSION,
@@ -270,6 +292,11 @@ impl HttpFilterConfig {
                 &HTTP_HEADER_JQ_FILTER_VERSION,
                 "JQ header filters",
             ),
+            (
+                HttpFilterConfig::has_websocket_filter,
+                &HTTP_WEBSOCKET_FILTER_VERSION,
+                "WebSocket filters",
+            ),
         ];
 
         for (validator, version, what) in REQUIREMENTS {
@@ -323,6 +350,45 @@ impl HttpFilterConfig {
             })
     }
 
+    fn has_websocket_filter(&self) -> bool {
+        self.websocket_filter.is_some()
+            || self.all_of.as_ref().is_some_and(|composite| {
+                composite
+                    .iter()
+                    .any(|f| matches!(f, InnerFilter::WebSocket { .. }))
+            })
+            || self.any_of.as_ref().is_some_and(|composite| {
+                composite
+                    .iter()
+                    .any(|f| matches!(f, InnerFilter::WebSocket { .. }))
+            })
+    }
+
+    /// Whether any of the WebSocket filters matches on the initial message.
+    ///
+    /// These filters are supported only in steal mode.
+    pub fn has_websocket_initial_message_filter(&self) -> bool {
+        let inner_has = |filters: &Option<Vec<InnerFilter>>| {
+            filters.iter().flatten().any(|filter| {
+                matches!(
+                    filter,
+                    InnerFilter::WebSocket {
+                        websocket: WebSocketFilterConfig {
+                            initial_message: Some(..),
+                            ..
+                        }
+                    }
+                )
+            })
+        };
+
+        self.websocket_filter
+            .as_ref()
+            .is_some_and(|filter| filter.initial_message.is_some())
+            || inner_has(&self.all_of)
+            || inner_has(&self.any_of)
+    }
+
     fn has_json_body_filter(&self) -> bool {
         matches!(self.body_filter, Some(BodyFilter::Json { .. }))
             || self.all_of.as_ref().is_some_and(|composite| {
@@ -362,6 +428,7 @@ impl HttpFilterConfig {
                 method_filter: None,
                 body_filter: None,
                 header_filter_jq: None,
+                websocket_filter: None,
                 all_of: None,
                 any_of: None,
                 ports: _,
@@ -373,6 +440,7 @@ impl HttpFilterConfig {
                 method_filter: None,
                 body_filter: None,
                 header_filter_jq: None,
+                websocket_filter: None,
                 all_of: None,
                 any_of: None,
                 ports: _,
@@ -384,6 +452,7 @@ impl HttpFilterConfig {
                 method_filter: Some(method),
                 body_filter: None,
                 header_filter_jq: None,
+                websocket_filter: None,
                 all_of: None,
                 any_of: None,
                 ports: _,
@@ -395,6 +464,7 @@ impl HttpFilterConfig {
                 method_filter: None,
                 body_filter: Some(filter),
                 header_filter_jq: None,
+                websocket_filter: None,
                 all_of: None,
                 any_of: None,
                 ports: _,
@@ -406,6 +476,7 @@ impl HttpFilterConfig {
                 method_filter: None,
                 body_filter: None,
                 header_filter_jq: Some(filter),
+                websocket_filter: None,
                 all_of: None,
                 any_of: None,
                 ports: _,
@@ -419,6 +490,7 @@ impl HttpFilterConfig {
                 method_filter: None,
                 body_filter: None,
                 header_filter_jq: None,
+                websocket_filter: None,
                 all_of: Some(filters),
                 any_of: None,
                 ports: _,
@@ -430,6 +502,21 @@ impl HttpFilterConfig {
                 method_filter: None,
                 body_filter: None,
                 header_filter_jq: None,
+                websocket_filter: Some(filter),
+                all_of: None,
+                any_of: None,
+                ports: _,
+            } => Ok(HttpFilter::WebSocket(
+                filter.as_protocol_websocket_filter()?,
+            )),
+
+            HttpFilterConfig {
+                path_filter: None,
+                header_filter: None,
+                method_filter: None,
+                body_filter: None,
+                header_filter_jq: None,
+                websocket_filter: None,
                 all_of: None,
                 any_of: Some(filters),
                 ports: _,
@@ -459,6 +546,9 @@ impl HttpFilterConfig {
                 InnerFilter::HeaderJq { query } => Ok(HttpFilter::HeaderJq(
                     JqQuery::new(query).map_err(HttpFilterParseError::Jq)?,
                 )),
+                InnerFilter::WebSocket { websocket } => Ok(HttpFilter::WebSocket(
+                    websocket.as_protocol_websocket_filter()?,
+                )),
             })
             .collect::<Result<Vec<_>, HttpFilterParseError>>()?;
 
@@ -515,6 +605,80 @@ pub enum InnerFilter {
     HeaderJq {
         query: String,
     },
+
+    /// ##### feature.network.incoming.inner_filter.websocket_filter {#feature-network-incoming-inner-websocket-filter}
+    ///
+    /// Matches only WebSocket upgrade requests, see
+    /// [`websocket_filter`](#feature-network-incoming-http-websocket-filter).
+    ///
+    /// Example:
+    /// ```json
+    /// { "websocket": { "subprotocol": "^mqtt$" } }
+    /// ```
+    WebSocket {
+        websocket: WebSocketFilterConfig,
+    },
+}
+
+/// Filter for WebSocket upgrade requests.
+///
+/// At least one of the fields must be set. When both are set, both must match.
+#[derive(PartialEq, Eq, Clone, Debug, JsonSchema, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebSocketFilterConfig {
+    /// ##### feature.network.incoming.http_filter.websocket_filter.subprotocol {#feature-network-incoming-http-websocket-filter-subprotocol}
+    ///
+    /// Supports regexes validated by the
+    /// [`fancy-regex`](https://docs.rs/fancy-regex/latest/fancy_regex/) crate.
+    ///
+    /// Case-insensitive. Matched against each subprotocol offered by the client in the
+    /// `Sec-WebSocket-Protocol` header.
+    pub subprotocol: Option<String>,
+
+    /// ##### feature.network.incoming.http_filter.websocket_filter.initial_message {#feature-network-incoming-http-websocket-filter-initial-message}
+    ///
+    /// Supports regexes validated by the
+    /// [`fancy-regex`](https://docs.rs/fancy-regex/latest/fancy_regex/) crate.
+    ///
+    /// Matched against the first text or binary message sent by the client after the upgrade
+    /// (binary messages are converted to UTF-8 lossily).
+    ///
+    /// To see the message, the mirrord-agent completes the WebSocket handshake with the client
+    /// on its own, selecting the first offered subprotocol, and waits for the message for at most
+    /// [`agent.max_body_buffer_timeout`](#agent-max_body_buffer_timeout) milliseconds.
+    /// The message can be at most [`agent.max_body_buffer_size`](#agent-max_body_buffer_size)
+    /// bytes long.
+    ///
+    /// Only supported when [`feature.network.incoming.mode`](#feature-network-incoming-mode) is
+    /// `"steal"`.
+    pub initial_message: Option<String>,
+}
+
+impl WebSocketFilterConfig {
+    /// Checks WebSocket filter strings for empty values.
+    ///
+    /// The prefix keeps errors pointing at the right nested config field.
+    fn ensure_no_empty_strings(&self, prefix: &str) -> Result<(), HttpFilterValidationError> {
+        if self.subprotocol.is_none() && self.initial_message.is_none() {
+            return Err(HttpFilterValidationError::EmptyWebSocketFilter {
+                field: prefix.to_owned(),
+            });
+        }
+
+        ensure_non_empty(self.subprotocol.as_deref(), format!("{prefix}.subprotocol"))?;
+        ensure_non_empty(
+            self.initial_message.as_deref(),
+            format!("{prefix}.initial_message"),
+        )
+    }
+
+    /// Converts this config into the protocol-level [`WebSocketFilter`].
+    pub fn as_protocol_websocket_filter(&self) -> Result<WebSocketFilter, Box<fancy_regex::Error>> {
+        Ok(WebSocketFilter {
+            subprotocol: self.subprotocol.clone().map(Filter::new).transpose()?,
+            initial_message: self.initial_message.clone().map(Filter::new).transpose()?,
+        })
+    }
 }
 
 /// Currently only JSON body filtering is supported.
@@ -662,6 +826,9 @@ impl InnerFilter {
             InnerFilter::HeaderJq { query } => {
                 ensure_non_empty(Some(query.as_str()), format!("{prefix}.query"))
             }
+            InnerFilter::WebSocket { websocket } => {
+                websocket.ensure_no_empty_strings(&format!("{prefix}.websocket"))
+            }
         }
     }
 }
@@ -681,6 +848,9 @@ fn ensure_non_empty(value: Option<&str>, field: String) -> Result<(), HttpFilter
 pub enum HttpFilterValidationError {
     #[error("HTTP filter `{field}` cannot be an empty string")]
     EmptyString { field: String },
+
+    #[error("WebSocket filter `{field}` must set `subprotocol`, `initial_message` or both")]
+    EmptyWebSocketFilter { field: String },
 }
 
 impl MirrordToggleableConfig for HttpFilterFileConfig {
@@ -705,6 +875,7 @@ impl MirrordToggleableConfig for HttpFilterFileConfig {
         let any_of = None;
 
         let body_filter = None;
+        let websocket_filter = None;
 
         let ports = FromEnv::new("MIRRORD_HTTP_FILTER_PORTS")
             .source_value(context)
@@ -716,6 +887,7 @@ impl MirrordToggleableConfig for HttpFilterFileConfig {
             method_filter,
             body_filter,
             header_filter_jq,
+            websocket_filter,
             all_of,
             any_of,
             ports,
@@ -727,6 +899,7 @@ impl CollectAnalytics for &HttpFilterConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("header_filter", self.header_filter.is_some());
         analytics.add("path_filter", self.path_filter.is_some());
+        analytics.add("websocket_filter", self.has_websocket_filter());
         analytics.add("ports", self.count_filtered_ports());
     }
 }
@@ -0,0 +1,248 @@
+use std::{collections::BTreeMap, ops::Not};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigError, Result};
+
+/// Rewrites stolen HTTP requests before they are delivered to the local application, and the
+/// local application's responses before they are sent back to the remote client. This way, apps
+/// that sit behind an ingress with path-based routing can run locally without code changes.
+///
+/// ```json
+/// {
+///   "request": {
+///     "headers": {
+///       "set": { "x-forwarded-prefix": "/api" },
+///       "remove": ["x-envoy-original-path"]
+///     },
+///     "path_prefix": [{ "from": "/api/users", "to": "/users" }],
+///     "host": "localhost"
+///   },
+///   "response": {
+///     "headers": {
+///       "remove": ["server"]
+///     },
+///     "cookie_domain": "example.com"
+///   }
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct IncomingRewrite {
+    /// ##### feature.network.incoming.rewrite.request {#feature-network-incoming-rewrite-request}
+    ///
+    /// Rules applied to the stolen requests, before they are delivered to the local application.
+    #[serde(default)]
+    pub request: RequestRewrite,
+
+    /// ##### feature.network.incoming.rewrite.response {#feature-network-incoming-rewrite-response}
+    ///
+    /// Rules applied to the local application's responses, before they are sent back to the
+    /// remote client.
+    #[serde(default)]
+    pub response: ResponseRewrite,
+}
+
+/// Rules applied to the stolen HTTP requests.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RequestRewrite {
+    /// ##### feature.network.incoming.rewrite.request.headers {#feature-network-incoming-rewrite-request-headers}
+    ///
+    /// Header rules applied to the request.
+    #[serde(default)]
+    pub headers: HeaderRewrite,
+
+    /// ##### feature.network.incoming.rewrite.request.path_prefix {#feature-network-incoming-rewrite-request-path_prefix}
+    ///
+    /// Replaces the prefix of the request path. The rules are checked in order, and only the
+    /// first matching rule is applied.
+    ///
+    /// A prefix matches only at a path segment boundary, so `/api` matches `/api` and
+    /// `/api/users`, but not `/apiary`.
+    ///
+    /// Defaults to `[]`.
+    #[serde(default)]
+    pub path_prefix: Vec<PathPrefixRewrite>,
+
+    /// ##### feature.network.incoming.rewrite.request.host {#feature-network-incoming-rewrite-request-host}
+    ///
+    /// Replaces the `Host` of the request (the `host` header and the URI authority, if present).
+    pub host: Option<String>,
+}
+
+/// Rules applied to the local application's HTTP responses.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ResponseRewrite {
+    /// ##### feature.network.incoming.rewrite.response.headers {#feature-network-incoming-rewrite-response-headers}
+    ///
+    /// Header rules applied to the response.
+    #[serde(default)]
+    pub headers: HeaderRewrite,
+
+    /// ##### feature.network.incoming.rewrite.response.cookie_domain {#feature-network-incoming-rewrite-response-cookie_domain}
+    ///
+    /// Replaces the `Domain` attribute of every `Set-Cookie` header in the response.
+    ///
+    /// When set to an empty string, the `Domain` attribute is removed, which makes the cookies
+    /// host-only.
+    pub cookie_domain: Option<String>,
+}
+
+/// Header rules, applied in order: `remove`, `set`, `add`.
+///
+/// Header names are case-insensitive.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderRewrite {
+    /// ###### set
+    ///
+    /// Headers to set, replacing all existing values.
+    ///
+    /// Defaults to `{}`.
+    #[serde(default)]
+    pub set: BTreeMap<String, String>,
+
+    /// ###### add
+    ///
+    /// Headers to add, preserving the existing values.
+    ///
+    /// Defaults to `{}`.
+    #[serde(default)]
+    pub add: BTreeMap<String, String>,
+
+    /// ###### remove
+    ///
+    /// Headers to remove.
+    ///
+    /// Defaults to `[]`.
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Replaces the path prefix `from` with `to`.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PathPrefixRewrite {
+    /// ###### from
+    ///
+    /// Prefix to replace, must start with `/`.
+    pub from: String,
+
+    /// ###### to
+    ///
+    /// Replacement prefix, must start with `/`.
+    pub to: String,
+}
+
+impl IncomingRewrite {
+    pub fn verify(&self) -> Result<(), ConfigError> {
+        self.request
+            .headers
+            .verify("feature.network.incoming.rewrite.request.headers")?;
+        self.response
+            .headers
+            .verify("feature.network.incoming.rewrite.response.headers")?;
+
+        for rule in &self.request.path_prefix {
+            for prefix in [&rule.from, &rule.to] {
+                if prefix.starts_with('/').not() {
+                    return Err(ConfigError::InvalidValue {
+                        name: "feature.network.incoming.rewrite.request.path_prefix".into(),
+                        provided: prefix.clone(),
+                        error: "path prefix must start with `/`".into(),
+                    });
+                }
+            }
+        }
+
+        if let Some(host) = &self.request.host
+            && (host.is_empty() || is_valid_header_value(host).not())
+        {
+            return Err(ConfigError::InvalidValue {
+                name: "feature.network.incoming.rewrite.request.host".into(),
+                provided: host.clone(),
+                error: "not a valid host".into(),
+            });
+        }
+
+        if let Some(domain) = &self.response.cookie_domain
+            && (domain.contains(';') || is_valid_header_value(domain).not())
+        {
+            return Err(ConfigError::InvalidValue {
+                name: "feature.network.incoming.rewrite.response.cookie_domain".into(),
+                provided: domain.clone(),
+                error: "not a valid cookie domain".into(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl HeaderRewrite {
+    fn verify(&self, field: &'static str) -> Result<(), ConfigError> {
+        let names = self
+            .set
+            .keys()
+            .chain(self.add.keys())
+            .chain(self.remove.iter());
+        for name in names {
+            if is_valid_header_name(name).not() {
+                return Err(ConfigError::InvalidValue {
+                    name: field.into(),
+                    provided: name.clone(),
+                    error: "not a valid HTTP header name".into(),
+                });
+            }
+        }
+
+        for value in self.set.values().chain(self.add.values()) {
+            if is_valid_header_value(value).not() {
+                return Err(ConfigError::InvalidValue {
+                    name: field.into(),
+                    provided: value.clone(),
+                    error: "not a valid HTTP header value".into(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks whether the given string is an HTTP token, as defined in RFC 9110.
+fn is_valid_header_name(name: &str) -> bool {
+    name.is_empty().not()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Checks whether the given string contains only visible ASCII characters, spaces and tabs.
+fn is_valid_header_value(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|b| b == b'\t' || (b' '..=b'~').contains(&b))
+}
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::valid(r#"{"request": {"headers": {"set": {"x-prefix": "/api"}}, "path_prefix": [{"from": "/api", "to": "/"}]}}"#, true)]
+    #[case::bad_header_name(r#"{"request": {"headers": {"remove": ["x prefix"]}}}"#, false)]
+    #[case::bad_header_value(r#"{"response": {"headers": {"add": {"x-a": "b\nc"}}}}"#, false)]
+    #[case::bad_prefix(r#"{"request": {"path_prefix": [{"from": "api", "to": "/"}]}}"#, false)]
+    #[case::bad_cookie_domain(r#"{"response": {"cookie_domain": "a.com; Secure"}}"#, false)]
+    #[case::strip_cookie_domain(r#"{"response": {"cookie_domain": ""}}"#, true)]
+    fn verify(#[case] config: &str, #[case] valid: bool) {
+        let rewrite: IncomingRewrite = serde_json::from_str(config).unwrap();
+        assert_eq!(rewrite.verify().is_ok(), valid);
+    }
+}
@@ -0,0 +1,50 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Compares the responses of the local application to mirrored HTTP requests with the responses
+/// of the remote service, and reports every mismatch. Use it to validate a refactor against real
+/// traffic, without affecting the clients of the remote service.
+///
+/// Responses are compared by:
+///
+/// 1. Status code;
+/// 2. Headers, except for `date`, `content-length`, `transfer-encoding`, `connection`,
+///    `keep-alive`, and the [`ignore_headers`](#feature-network-incoming-shadow-ignore_headers);
+/// 3. Body. JSON bodies are compared by value, skipping the
+///    [`ignore_paths`](#feature-network-incoming-shadow-ignore_paths). Other bodies are compared
+///    byte by byte.
+///
+/// Bodies bigger than the agent's HTTP body buffer (64KiB by default) are not compared.
+///
+/// Mismatches are reported through the session monitor, and a summary is logged when the session
+/// ends.
+///
+/// ```json
+/// {
+///   "ignore_paths": ["/timestamp", "/items/*/id"],
+///   "ignore_headers": ["x-request-id"]
+/// }
+/// ```
+///
+/// Requires the mirrord-agent to support mirrord-protocol version 1.34.0 or newer.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct IncomingShadow {
+    /// ##### feature.network.incoming.shadow.ignore_paths {#feature-network-incoming-shadow-ignore_paths}
+    ///
+    /// [JSON pointers](https://datatracker.ietf.org/doc/html/rfc6901) to the parts of JSON bodies
+    /// that should not be compared, e.g. generated IDs or timestamps. A `*` segment matches any
+    /// object key or array index.
+    ///
+    /// Defaults to `[]`.
+    #[serde(default)]
+    pub ignore_paths: Vec<String>,
+
+    /// ##### feature.network.incoming.shadow.ignore_headers {#feature-network-incoming-shadow-ignore_headers}
+    ///
+    /// Names of the response headers that should not be compared (case insensitive).
+    ///
+    /// Defaults to `[]`.
+    #[serde(default)]
+    pub ignore_headers: Vec<String>,
+}
@@ -151,4 +151,13 @@ pub struct InternalProxyConfig {
     /// ```
     #[config(default = 60)]
     pub process_logging_interval: u64,
+
+    /// <!--${internal}-->
+    ///
+    /// Whether the internal proxy keeps port subscriptions in the agent after the process that
+    /// made them exits, so that the next process listening on the same port takes them over.
+    ///
+    /// Set by `mirrord up` for services that are restarted on file changes.
+    #[config(default = false)]
+    pub keep_subscriptions: bool,
 }
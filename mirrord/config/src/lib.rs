@@ -52,7 +52,12 @@ use tracing::warn;
 use crate::{
     agent::AgentConfig,
     ci::CiConfig,
-    config::{FromFileError, source::MirrordConfigSource},
+    config::{
+        FromFileError,
+        explain::{ConfigExplanation, ValueOrigin},
+        extends,
+        source::MirrordConfigSource,
+    },
     container::ContainerConfig,
     env_key::EnvKey,
     external_proxy::ExternalProxyConfig,
@@ -213,6 +218,28 @@ pub const MIRRORD_LAYER_WAIT_FOR_DEBUGGER: &str = "MIRRORD_LAYER_WAIT_FOR_DEBUGG
 #[config(map_to = "LayerFileConfig", derive = "JsonSchema")]
 #[cfg_attr(test, config(derive = "PartialEq"))]
 pub struct LayerConfig {
+    /// ## extends {#root-extends}
+    ///
+    /// Other config files that this config builds on. Accepts a single path, or an array of paths.
+    ///
+    /// Relative paths are resolved against the directory of the file that extends them, and a
+    /// leading `~` is replaced with the home directory. Extended files can extend other files,
+    /// as long as there are no cycles.
+    ///
+    /// The files are merged in order, and this config is merged last:
+    /// - objects are merged key by key;
+    /// - any other value (including an array) replaces the previous one.
+    ///
+    /// Environment variables and command line arguments still override values from all of the
+    /// files.
+    ///
+    /// ```json
+    /// {
+    ///   "extends": ["~/.mirrord/team.json", "./local.json"]
+    /// }
+    /// ```
+    pub extends: Option<VecOrSingle<String>>,
+
     /// ## accept_invalid_certificates {#root-accept_invalid_certificates}
     ///
     /// Controls whether or not mirrord accepts invalid TLS certificates (e.g. self-signed
@@ -538,11 +565,55 @@ impl LayerConfig {
         } else {
             LayerFileConfig::default().generate_config(context)?
         };
-        config.apply_magic();
-        config.reflect_outgoing_filter_in_dns(context);
+        config.post_process(context);
         Ok(config)
     }
 
+    /// Resolves the config like [`LayerConfig::resolve`], and explains where each of its values
+    /// comes from.
+    ///
+    /// Env overrides in `context` are attributed to [`ValueOrigin::Cli`], as this is how the CLI
+    /// passes its arguments, while the process environment is attributed to [`ValueOrigin::Env`].
+    pub fn resolve_explained(
+        context: &mut ConfigContext,
+    ) -> Result<(Self, ConfigExplanation), ConfigError> {
+        let defaults = LayerFileConfig::default()
+            .generate_config(&mut ConfigContext::default().strict_env(true))?;
+        let mut explanation = ConfigExplanation::new(&defaults)?;
+
+        let file_config = match context.get_env(Self::FILE_PATH_ENV) {
+            Ok(path) => {
+                let file_config = LayerFileConfig::from_path(&path, context)?;
+                let from_file = file_config
+                    .clone()
+                    .generate_config(&mut ConfigContext::default().strict_env(true))?;
+                explanation.record_file(&from_file, Path::new(&path), context.value_sources())?;
+                file_config
+            }
+            Err(_) => LayerFileConfig::default(),
+        };
+
+        let from_env = file_config
+            .clone()
+            .generate_config(&mut ConfigContext::default())?;
+        explanation.record(&from_env, ValueOrigin::Env)?;
+
+        let mut config = file_config.generate_config(context)?;
+        explanation.record(&config, ValueOrigin::Cli)?;
+
+        config.post_process(context);
+        explanation.record(&config, ValueOrigin::Magic)?;
+
+        Ok((config, explanation))
+    }
+
+    /// Adjustments that [`LayerConfig::resolve`] and [`LayerConfig::resolve_explained`] apply on
+    /// top of the generated config.
+    fn post_process(&mut self, context: &mut ConfigContext) {
+        self.apply_magic();
+        self.reflect_outgoing_filter_in_dns(context);
+    }
+
     /// Mirrors host names from [`feature.network.outgoing.filter`](OutgoingFilterConfig)
     /// into [`feature.network.dns.filter`](DnsFilterConfig), so that a name routed to one
     /// side (local app vs remote pod) is also resolved on that same side.
@@ -610,31 +681,62 @@ impl LayerConfig {
 
     /// Applies the presets in `feature.magic` to the config, modifying it in-place.
     fn apply_magic(&mut self) {
-        if self.feature.magic.aws {
-            let mut unset: Vec<String> = self
-                .feature
-                .env
-                .unset
-                .take()
-                .map(Vec::from)
-                .unwrap_or_default();
-            unset.push("AWS_PROFILE".to_owned());
-            self.feature.env.unset = Some(VecOrSingle::Multiple(unset));
-
-            if let Some(home) = util::home_dir_for_path_mapping() {
-                let tmpdir = std::env::var("TMPDIR").unwrap_or_else(|_| "/tmp".to_owned());
-                let pattern = format!("^{}/\\.aws(/.*)?", regex::escape(&home));
-                let replacement = format!("{tmpdir}/.aws$1");
-                self.feature
-                    .fs
-                    .mapping
-                    .get_or_insert_with(HashMap::new)
-                    .entry(pattern)
-                    .or_insert(replacement);
+        let magic = &self.feature.magic;
+        let credentials = [
+            (magic.aws, ".aws", &["AWS_PROFILE"][..]),
+            (
+                magic.gcp,
+                ".config/gcloud",
+                &["GOOGLE_APPLICATION_CREDENTIALS", "CLOUDSDK_CONFIG"][..],
+            ),
+            (
+                magic.azure,
+                ".azure",
+                &[
+                    "AZURE_CLIENT_SECRET",
+                    "AZURE_CLIENT_CERTIFICATE_PATH",
+                    "AZURE_CLIENT_CERTIFICATE_PASSWORD",
+                    "AZURE_USERNAME",
+                    "AZURE_PASSWORD",
+                    "AZURE_CONFIG_DIR",
+                ][..],
+            ),
+            (magic.kube, ".kube", &["KUBECONFIG"][..]),
+        ];
+
+        for (enabled, dir, envs) in credentials {
+            if enabled {
+                self.hide_local_credentials(dir, envs);
             }
         }
     }
 
+    /// Unsets `envs` and maps `dir` (relative to the home directory) to a temporary directory, so
+    /// that the local credentials are not available to the process.
+    fn hide_local_credentials(&mut self, dir: &str, envs: &[&str]) {
+        let mut unset: Vec<String> = self
+            .feature
+            .env
+            .unset
+            .take()
+            .map(Vec::from)
+            .unwrap_or_default();
+        unset.extend(envs.iter().map(|env| (*env).to_owned()));
+        self.feature.env.unset = Some(VecOrSingle::Multiple(unset));
+
+        if let Some(home) = util::home_dir_for_path_mapping() {
+            let tmpdir = std::env::var("TMPDIR").unwrap_or_else(|_| "/tmp".to_owned());
+            let pattern = format!("^{}/{}(/.*)?", regex::escape(&home), regex::escape(dir));
+            let replacement = format!("{tmpdir}/{dir}$1");
+            self.feature
+                .fs
+                .mapping
+                .get_or_insert_with(HashMap::new)
+                .entry(pattern)
+                .or_insert(replacement);
+        }
+    }
+
     /// Adds the target container's volume-mount paths to
     /// [`feature.fs.read_only`](feature::fs::advanced::FsConfig), so files mounted into the remote
     /// pod (ConfigMaps, Secrets, PVCs) are read from the remote pod while writes stay local.
@@ -728,6 +830,13 @@ impl LayerConfig {
             );
         }
 
+        if self.agent.ephemeral && self.agent.warm.enabled {
+            context.add_warning(
+                "`agent.warm` is ignored when using an ephemeral container for the agent."
+                    .to_owned(),
+            );
+        }
+
         if matches!(
             self.feature.network.outgoing.filter,
             Some(OutgoingFilterConfig::Remote(_))
@@ -747,6 +856,7 @@ impl LayerConfig {
             http_filter.all_of.is_some(),
             http_filter.any_of.is_some(),
             http_filter.body_filter.is_some(),
+            http_filter.websocket_filter.is_some(),
         ]
         .into_iter()
         .filter(|used| *used)
@@ -775,6 +885,15 @@ impl LayerConfig {
                 error: Box::new(error),
             })?;
 
+        if http_filter.has_websocket_initial_message_filter()
+            && matches!(self.feature.network.incoming.mode, IncomingMode::Mirror)
+        {
+            Err(ConfigError::Conflict(
+                "WebSocket `initial_message` HTTP filters are supported only in the steal mode"
+                    .to_owned(),
+            ))?
+        }
+
         let verify_body_filter = |filter: &BodyFilter| match filter {
             BodyFilter::Json { query, .. } => {
                 // Only need to verify `query` as `matches` is later
@@ -842,6 +961,10 @@ impl LayerConfig {
             (None, None) => {}
         }
 
+        if let Some(rewrite) = &self.feature.network.incoming.rewrite {
+            rewrite.verify()?;
+        }
+
         if !self.feature.copy_target.enabled
             && self
                 .target
@@ -853,8 +976,19 @@ impl LayerConfig {
             Err(ConfigError::TargetJobWithoutCopyTarget)?
         }
 
+        if let Some(selector) = &self.target.selector {
+            if self.target.path.is_some() {
+                return Err(ConfigError::Conflict(
+                    "Cannot use both `target.path` and `target.selector` at the same time".into(),
+                ));
+            }
+
+            selector.verify()?;
+        }
+
         let is_targetless = match self.target.path.as_ref() {
             Some(Target::Targetless) => true,
+            None if self.target.selector.is_some() => false,
             None => context.is_empty_target_final(),
             _ => false,
         };
@@ -1266,7 +1400,18 @@ impl LayerFileConfig {
         );
 
         let rendered = template_engine.render("main", &tera_context)?;
+        let value = extends::parse_value(path, &rendered)?;
+
+        if extends::has_extends(&value) {
+            let (merged, sources) = extends::resolve(path, value, &tera_context)?;
+            context.set_value_sources(sources);
+            return Ok(serde_json::from_value::<Self>(merged)?);
+        }
 
+        context.set_value_sources(extends::sources_of(&value, path));
+
+        // Parse the rendered content directly, so that errors point to the right place in the
+        // file.
         match path.extension().and_then(OsStr::to_str) {
             // No Extension? assume json
             Some("json") | None => Ok(serde_json::from_str::<Self>(&rendered)?),
@@ -1555,6 +1700,7 @@ mod tests {
 
         let expect = LayerFileConfig {
             key: None,
+            extends: None,
             accept_invalid_certificates: Some(false),
             kubeconfig: None,
             telemetry: None,
@@ -1564,6 +1710,7 @@ mod tests {
                     container: None,
                 })),
                 namespace: Some("default".to_owned()),
+                selector: None,
             }),
             skip_processes: None,
             skip_extra_build_tools: None,
@@ -1608,6 +1755,9 @@ mod tests {
                             ports: None,
                             https_delivery: Default::default(),
                             tls_delivery: Default::default(),
+                            fallback: None,
+                            shadow: None,
+                            rewrite: None,
                         }),
                     ))),
                     outgoing: Some(ToggleableConfig::Config(OutgoingFileConfig {
@@ -2471,4 +2621,40 @@ mod tests {
     fn magic_auto_mount_defaults_to_enabled() {
         assert!(default_config().feature.magic.auto_mount);
     }
+
+    /// Each enabled credential preset unsets its env vars, and `feature.magic.kube` is opt-in.
+    #[test]
+    fn magic_credentials_unset_envs() {
+        let unset = |config: &LayerConfig| {
+            config
+                .feature
+                .env
+                .unset
+                .clone()
+                .map(Vec::from)
+                .unwrap_or_default()
+        };
+
+        let mut config = default_config();
+        let defaults = unset(&config);
+        for env in [
+            "AWS_PROFILE",
+            "GOOGLE_APPLICATION_CREDENTIALS",
+            "AZURE_CLIENT_SECRET",
+        ] {
+            assert!(defaults.iter().any(|unset| unset == env), "{env} not unset");
+        }
+        assert!(defaults.iter().any(|unset| unset == "KUBECONFIG").not());
+        // Needed by Azure Workload Identity in the remote pod.
+        assert!(
+            defaults
+                .iter()
+                .any(|unset| unset == "AZURE_CLIENT_ID")
+                .not()
+        );
+
+        config.feature.magic.kube = true;
+        config.apply_magic();
+        assert!(unset(&config).iter().any(|unset| unset == "KUBECONFIG"));
+    }
 }
@@ -1,9 +1,11 @@
 use std::{borrow::Cow, fmt, str::FromStr};
 
 use cron_job::CronJobTarget;
+use daemon_set::DaemonSetTarget;
 use mirrord_analytics::CollectAnalytics;
 use replica_set::ReplicaSetTarget;
 use schemars::{JsonSchema, Schema, SchemaGenerator};
+use selector::TargetSelector;
 use serde::{Deserialize, Serialize};
 use strum_macros::{EnumDiscriminants, EnumString};
 
@@ -22,11 +24,13 @@ use crate::{
 };
 
 pub mod cron_job;
+pub mod daemon_set;
 pub mod deployment;
 pub mod job;
 pub mod pod;
 pub mod replica_set;
 pub mod rollout;
+pub mod selector;
 pub mod service;
 pub mod stateful_set;
 
@@ -49,6 +53,7 @@ pub enum TargetFileConfig {
         #[schemars(schema_with = "make_simple_target_custom_schema")]
         path: Option<Target>,
         namespace: Option<String>,
+        selector: Option<TargetSelector>,
     },
 }
 
@@ -78,6 +83,7 @@ fn make_simple_target_custom_schema(generator: &mut SchemaGenerator) -> Schema {
 /// - `cronjob/{cronjob-name}[/container/{container-name}]`;
 /// - `statefulset/{statefulset-name}[/container/{container-name}]`;
 /// - `service/{service-name}[/container/{container-name}]`;
+/// - `daemonset/{daemonset-name}[/container/{container-name}][/node/{node-name}]`;
 ///
 /// Please note that:
 ///
@@ -162,6 +168,8 @@ pub struct TargetConfig {
     ///   Operator)
     /// - `service/{service-name}[/container/{container-name}]`; (requires mirrord Operator)
     /// - `replicaset/{replicaset-name}[/container/{container-name}]`; (requires mirrord Operator)
+    /// - `daemonset/{daemonset-name}[/container/{container-name}][/node/{node-name}]`; (`node`
+    ///   picks the pod running on the given node)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<Target>,
 
@@ -174,6 +182,28 @@ pub struct TargetConfig {
     /// Defaults to the Kubernetes user's default namespace (defined in Kubernetes context).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub namespace: Option<String>,
+
+    /// ### target.selector {#target-selector}
+    ///
+    /// Selects the target pod by its labels, instead of its name.
+    ///
+    /// Mutually exclusive with [`target.path`](#target-path).
+    ///
+    /// ```json
+    /// {
+    ///   "target": {
+    ///     "selector": {
+    ///       "labels": { "app": "api", "tier": "web" },
+    ///       "strategy": "newest"
+    ///     },
+    ///     "namespace": "bear-namespace"
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Supported strategies are `newest` (default), `oldest`, `random` and `least-loaded`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selector: Option<TargetSelector>,
 }
 
 impl Default for TargetFileConfig {
@@ -208,15 +238,23 @@ impl MirrordConfig for TargetFileConfig {
     /// Generate the final config object, out of the configuration parsed from a configuration file,
     /// factoring in environment variables (which are also set by the front end - CLI/IDE-plugin).
     fn generate_config(self, context: &mut ConfigContext) -> Result<Self::Generated> {
-        let (path_from_conf_file, namespace_from_conf_file) = match self {
-            TargetFileConfig::Simple(path) => (path, None),
-            TargetFileConfig::Advanced { path, namespace } => (path, namespace),
+        let (path_from_conf_file, namespace_from_conf_file, selector) = match self {
+            TargetFileConfig::Simple(path) => (path, None, None),
+            TargetFileConfig::Advanced {
+                path,
+                namespace,
+                selector,
+            } => (path, namespace, selector),
         };
 
         // Env overrides configuration if both there.
         let path = Self::get_target_path_from_env(context)?.or(path_from_conf_file);
         let namespace = Self::get_target_namespace_from_env(context)?.or(namespace_from_conf_file);
-        Ok(TargetConfig { path, namespace })
+        Ok(TargetConfig {
+            path,
+            namespace,
+            selector,
+        })
     }
 }
 
@@ -239,6 +277,7 @@ mirrord-layer failed to parse the provided target!
     >> `statefulset/{statefulset-name}[/container/{container-name}]`;
     >> `service/{service-name}[/container/{container-name}]`;
     >> `replicaset/{replicaset-name}[/container/{container-name}]`;
+    >> `daemonset/{daemonset-name}[/container/{container-name}][/node/{node-name}]`;
 
 - Note:
     >> specifying container name is optional, defaults to a container chosen by mirrord
@@ -265,6 +304,7 @@ mirrord-layer failed to parse the provided target!
 /// - `statefulset/{statefulset-name}[/container/{container-name}]`;
 /// - `service/{service-name}[/container/{container-name}]`;
 /// - `replicaset/{replicaset-name}[/container/{container-name}]`;
+/// - `daemonset/{daemonset-name}[/container/{container-name}][/node/{node-name}]`;
 ///
 /// Used to derive `TargetType` via the strum crate
 #[warn(clippy::wildcard_enum_match_arm)]
@@ -311,6 +351,10 @@ pub enum Target {
     /// [ReplicaSet](https://kubernetes.io/docs/concepts/workloads/controllers/replicaset/).
     ReplicaSet(replica_set::ReplicaSetTarget),
 
+    /// <!--${internal}-->
+    /// [DaemonSet](https://kubernetes.io/docs/concepts/workloads/controllers/daemonset/).
+    DaemonSet(daemon_set::DaemonSetTarget),
+
     /// <!--${internal}-->
     /// Spawn a new pod.
     Targetless,
@@ -343,6 +387,9 @@ impl JsonSchema for Target {
             schema_gen
                 .subschema_for::<replica_set::ReplicaSetTarget>()
                 .to_value(),
+            schema_gen
+                .subschema_for::<daemon_set::DaemonSetTarget>()
+                .to_value(),
             serde_json::json!({ "enum": ["targetless"] }),
         ];
 
@@ -375,6 +422,9 @@ impl FromStr for Target {
             Some("replicaset") => {
                 replica_set::ReplicaSetTarget::from_split(&mut split).map(Target::ReplicaSet)
             }
+            Some("daemonset") | Some("ds") => {
+                daemon_set::DaemonSetTarget::from_split(&mut split).map(Target::DaemonSet)
+            }
             _ => Err(ConfigError::InvalidTarget(format!(
                 "Provided target: {target} is unsupported. Did you remember to add a prefix, e.g. pod/{target}? \n{FAIL_PARSE_DEPLOYMENT_OR_POD}",
             ))),
@@ -397,6 +447,7 @@ impl Target {
             Target::StatefulSet(t) => t.container = Some(container),
             Target::Service(t) => t.container = Some(container),
             Target::ReplicaSet(t) => t.container = Some(container),
+            Target::DaemonSet(t) => t.container = Some(container),
             Target::Job(t) => t.container = Some(container),
             Target::CronJob(t) => t.container = Some(container),
             Target::Targetless => {}
@@ -428,6 +479,7 @@ impl fmt::Display for TargetType {
             TargetType::StatefulSet => "statefulset",
             TargetType::Service => "service",
             TargetType::ReplicaSet => "replicaset",
+            TargetType::DaemonSet => "daemonset",
         };
 
         f.write_str(stringified)
@@ -446,6 +498,7 @@ impl TargetType {
             Self::StatefulSet,
             Self::Service,
             Self::ReplicaSet,
+            Self::DaemonSet,
         ]
         .into_iter()
     }
@@ -453,7 +506,10 @@ impl TargetType {
     pub fn compatible_with(&self, config: &FeatureConfig) -> bool {
         match self {
             Self::Targetless | Self::Rollout => !config.copy_target.enabled,
-            Self::Pod => !(config.copy_target.enabled && config.copy_target.scale_down),
+            // Daemon sets cannot be scaled down.
+            Self::Pod | Self::DaemonSet => {
+                !(config.copy_target.enabled && config.copy_target.scale_down)
+            }
             Self::Job | Self::CronJob => config.copy_target.enabled,
             Self::Service => !config.copy_target.enabled,
             Self::Deployment | Self::StatefulSet | Self::ReplicaSet => true,
@@ -519,6 +575,38 @@ impl_target_display!(StatefulSetTarget, stateful_set, "statefulset");
 impl_target_display!(ServiceTarget, service, "service");
 impl_target_display!(ReplicaSetTarget, replica_set, "replicaset");
 
+impl TargetDisplay for DaemonSetTarget {
+    fn type_(&self) -> &str {
+        "daemonset"
+    }
+
+    fn name(&self) -> &str {
+        self.daemon_set.as_str()
+    }
+
+    fn container(&self) -> Option<&String> {
+        self.container.as_ref()
+    }
+}
+
+impl fmt::Display for DaemonSetTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}{}{}",
+            self.type_(),
+            self.name(),
+            self.container()
+                .map(|name| format!("/container/{name}"))
+                .unwrap_or_default(),
+            self.node
+                .as_ref()
+                .map(|node| format!("/node/{node}"))
+                .unwrap_or_default()
+        )
+    }
+}
+
 impl fmt::Display for Target {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -531,6 +619,7 @@ impl fmt::Display for Target {
             Target::StatefulSet(target) => target.fmt(f),
             Target::Service(target) => target.fmt(f),
             Target::ReplicaSet(target) => target.fmt(f),
+            Target::DaemonSet(target) => target.fmt(f),
         }
     }
 }
@@ -547,6 +636,7 @@ impl TargetDisplay for Target {
             Target::StatefulSet(target) => target.type_(),
             Target::Service(target) => target.type_(),
             Target::ReplicaSet(target) => target.type_(),
+            Target::DaemonSet(target) => target.type_(),
         }
     }
 
@@ -561,6 +651,7 @@ impl TargetDisplay for Target {
             Target::StatefulSet(target) => target.name(),
             Target::Service(target) => target.name(),
             Target::ReplicaSet(target) => target.name(),
+            Target::DaemonSet(target) => target.name(),
         }
     }
 
@@ -575,6 +666,7 @@ impl TargetDisplay for Target {
             Target::StatefulSet(target) => target.container(),
             Target::Service(target) => target.container(),
             Target::ReplicaSet(target) => target.container(),
+            Target::DaemonSet(target) => target.container(),
         }
     }
 }
@@ -593,6 +685,9 @@ bitflags::bitflags! {
         const STATEFUL_SET = 128;
         const SERVICE = 256;
         const REPLICA_SET = 512;
+        const SELECTOR = 1024;
+        const DAEMON_SET = 2048;
+        const NODE = 4096;
     }
 }
 
@@ -602,6 +697,9 @@ impl CollectAnalytics for &TargetConfig {
         if self.namespace.is_some() {
             flags |= TargetAnalyticFlags::NAMESPACE;
         }
+        if self.selector.is_some() {
+            flags |= TargetAnalyticFlags::SELECTOR;
+        }
         if let Some(path) = &self.path {
             match path {
                 Target::Pod(target) => {
@@ -652,6 +750,15 @@ impl CollectAnalytics for &TargetConfig {
                         flags |= TargetAnalyticFlags::CONTAINER;
                     }
                 }
+                Target::DaemonSet(target) => {
+                    flags |= TargetAnalyticFlags::DAEMON_SET;
+                    if target.container.is_some() {
+                        flags |= TargetAnalyticFlags::CONTAINER;
+                    }
+                    if target.node.is_some() {
+                        flags |= TargetAnalyticFlags::NODE;
+                    }
+                }
                 Target::Targetless => {
                     // Targetless is essentially 0, so no need to set any flags.
                 }
@@ -665,14 +772,15 @@ impl CollectAnalytics for &TargetConfig {
 mod tests {
     use rstest::rstest;
 
-    use super::*;
+    use super::{selector::SelectorStrategy, *};
     use crate::config::{ConfigContext, MirrordConfig};
 
     #[rstest]
     #[case(None, None,
         TargetConfig {
             path: None,
-            namespace: None
+            namespace: None,
+            selector: None
         }
     )] // Nothing specified - no target config (targetless mode).
     #[case(
@@ -680,7 +788,8 @@ mod tests {
         Some("ns"),
         TargetConfig{
             path: None,
-            namespace: Some("ns".to_owned())
+            namespace: Some("ns".to_owned()),
+            selector: None
         }
     )] // Namespace without target - error.
     #[case(
@@ -688,7 +797,8 @@ mod tests {
         None,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "foo".to_owned(), container: None})),
-            namespace: None
+            namespace: None,
+            selector: None
         }
     )] // Only pod specified
     #[case(
@@ -699,7 +809,8 @@ mod tests {
                 pod: "foo".to_owned(),
                 container: Some("bar".to_owned())
             })),
-            namespace: None
+            namespace: None,
+            selector: None
         }
     )] // Pod and container specified.
     #[case(
@@ -707,7 +818,8 @@ mod tests {
         Some("baz"),
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "foo".to_owned(), container: None})),
-            namespace: Some("baz".to_owned())
+            namespace: Some("baz".to_owned()),
+            selector: None
         }
     )] // Pod and namespace specified.
     #[case(
@@ -718,7 +830,8 @@ mod tests {
                 rollout: "foo".to_owned(),
                 container: None
             })),
-            namespace: None
+            namespace: None,
+            selector: None
         }
     )] // Rollout specified.
     fn default(
@@ -744,7 +857,8 @@ mod tests {
         r#"{ "namespace": "my-test-namespace" }"#,
         TargetConfig {
             path: None,
-            namespace: Some("my-test-namespace".to_owned())
+            namespace: Some("my-test-namespace".to_owned()),
+            selector: None
         }
     )]
     // simple variant of file config - path string, not an object.
@@ -752,7 +866,8 @@ mod tests {
         r#""pod/my-cool-pod""#,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "my-cool-pod".to_owned(), container: None})),
-            namespace: None
+            namespace: None,
+            selector: None
         }
     )]
     // advanced variant of file config.
@@ -760,7 +875,8 @@ mod tests {
         r#"{ "path": "pod/my-cool-pod" }"#,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "my-cool-pod".to_owned(), container: None})),
-            namespace: None
+            namespace: None,
+            selector: None
         }
     )]
     // advanced variant of file config, with object as path.
@@ -772,7 +888,26 @@ mod tests {
         }"#,
         TargetConfig{
             path: Some(Target::Pod(PodTarget {pod: "my-cool-pod".to_owned(), container: None})),
-            namespace: None
+            namespace: None,
+            selector: None
+        }
+    )]
+    // advanced variant of file config, with a selector.
+    #[case(
+        r#"{
+            "selector": {
+                "labels": { "app": "api" },
+                "strategy": "oldest"
+            }
+        }"#,
+        TargetConfig{
+            path: None,
+            namespace: None,
+            selector: Some(TargetSelector {
+                labels: [("app".to_owned(), "api".to_owned())].into(),
+                strategy: SelectorStrategy::Oldest,
+                container: None,
+            })
         }
     )]
     fn parse_target_config_from_json(
@@ -11,7 +11,10 @@ use std::{
 use bincode::{Decode, Encode};
 use mirrord_protocol::{
     FileRequest, FileResponse, GetEnvVarsRequest, Port, RemoteResult,
-    dns::{GetAddrInfoRequestV2, GetAddrInfoResponse},
+    dns::{
+        DnsQueryRequest, DnsQueryResponse, GetAddrInfoRequestV2, GetAddrInfoResponse,
+        ReverseDnsLookupRequest, ReverseDnsLookupResponse,
+    },
     file::*,
     outgoing::SocketAddress,
     tcp::{MirrorType, StealType},
@@ -51,6 +54,10 @@ pub enum LayerToProxyMessage {
     Incoming(IncomingRequest),
     /// Fetch environment variables from the target.
     GetEnv(GetEnvVarsRequest),
+    /// A raw DNS query.
+    DnsQuery(DnsQueryRequest),
+    /// A reverse DNS lookup (IP address to hostname).
+    ReverseDnsLookup(ReverseDnsLookupRequest),
 }
 
 /// Layer process information
@@ -323,6 +330,10 @@ pub enum ProxyToLayerMessage {
     Incoming(IncomingResponse),
     /// A response to layer's [`LayerToProxyMessage::GetEnv`].
     GetEnv(RemoteResult<HashMap<String, String>>),
+    /// A response to layer's [`DnsQueryRequest`].
+    DnsQuery(DnsQueryResponse),
+    /// A response to layer's [`ReverseDnsLookupRequest`].
+    ReverseDnsLookup(RemoteResult<ReverseDnsLookupResponse>),
     /// Internal proxy encountered a fatal error.
     ProxyFailed {
         agent_reported: bool,
@@ -630,6 +641,20 @@ impl_request!(
     res_path = ProxyToLayerMessage::GetEnv,
 );
 
+impl_request!(
+    req = DnsQueryRequest,
+    res = DnsQueryResponse,
+    req_path = LayerToProxyMessage::DnsQuery,
+    res_path = ProxyToLayerMessage::DnsQuery,
+);
+
+impl_request!(
+    req = ReverseDnsLookupRequest,
+    res = RemoteResult<ReverseDnsLookupResponse>,
+    req_path = LayerToProxyMessage::ReverseDnsLookup,
+    res_path = ProxyToLayerMessage::ReverseDnsLookup,
+);
+
 impl_request!(
     req = RenameRequest,
     res = RemoteResult<()>,
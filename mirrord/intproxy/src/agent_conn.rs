@@ -1,7 +1,13 @@
 //! Implementation of `proxy <-> agent` connection through [`mpsc`](tokio::sync::mpsc) channels
 //! created in different mirrord crates.
 
-use std::{fmt, io, net::SocketAddr, ops::ControlFlow, path::PathBuf, time::Duration};
+use std::{
+    fmt, io,
+    net::SocketAddr,
+    ops::{ControlFlow, Not},
+    path::PathBuf,
+    time::Duration,
+};
 
 use mirrord_analytics::{NullReporter, Reporter};
 use mirrord_config::{LayerConfig, container::MIRRORD_EXTERNAL_PROXY_HOSTNAME};
@@ -14,8 +20,14 @@ use mirrord_operator::{
     },
     types::{RECONNECT_NOT_POSSIBLE_CODE, RECONNECT_NOT_POSSIBLE_REASON},
 };
-#[cfg(test)]
-use mirrord_protocol::DaemonMessage;
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage,
+    compression::COMPRESSION_VERSION,
+    resume::{
+        ClientSessionResume, DaemonSessionResume, ResumeSessionRequest, ResumeToken,
+        SESSION_RESUME_VERSION,
+    },
+};
 #[cfg(test)]
 use mirrord_protocol_io::ConnectionOutput;
 use mirrord_protocol_io::{Client, Connection, ProtocolError};
@@ -60,6 +72,40 @@ pub enum AgentConnectionError {
     ProtocolError(#[from] ProtocolError),
 }
 
+/// Errors that can occur when the internal proxy tries to resume the session on a new connection,
+/// see [`mirrord_protocol::resume`].
+#[derive(Error, Debug)]
+pub enum SessionResumeError {
+    /// Making the new connection failed.
+    #[error(transparent)]
+    Connection(#[from] AgentConnectionError),
+    /// The agent did not respond to [`ClientSessionResume::Resume`] in time.
+    #[error("agent did not respond to the session resume request in time")]
+    Timeout,
+    /// The agent closed the new connection without responding to [`ClientSessionResume::Resume`].
+    #[error("agent closed the connection")]
+    ConnectionClosed,
+    /// The agent no longer has the session.
+    #[error("agent session expired")]
+    Expired,
+    /// The agent responded to [`ClientSessionResume::Resume`] with an unexpected message.
+    #[error("agent sent an unexpected message: {0:?}")]
+    UnexpectedMessage(Box<DaemonMessage>),
+    /// Messages that the agent did not receive are no longer available.
+    #[error("messages that need to be sent again to the agent are no longer available")]
+    ReplayUnavailable,
+}
+
+impl SessionResumeError {
+    /// Whether resuming the session can succeed on another attempt.
+    fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Connection(..) | Self::Timeout | Self::ConnectionClosed
+        )
+    }
+}
+
 /// Directive for the proxy on how to connect to the agent.
 #[derive(Debug, Clone, Serialize, EnumDiscriminants)]
 #[cfg_attr(not(test), derive(Deserialize))]
@@ -141,6 +187,17 @@ pub enum AgentConnectionMessage {
     RequestReconnect,
 }
 
+/// State of session resumption in the [`AgentConnection`], see [`mirrord_protocol::resume`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionResume {
+    /// The connection does not allow for session resumption.
+    Unsupported,
+    /// Session resumption will be enabled after the [`mirrord_protocol`] version is negotiated.
+    Supported,
+    /// Session resumption is enabled, the session can be resumed with this token.
+    Enabled(ResumeToken),
+}
+
 /// Handles logic of the `proxy <-> agent` connection as a [`BackgroundTask`].
 ///
 /// # Note
@@ -152,9 +209,19 @@ pub enum AgentConnectionMessage {
 pub struct AgentConnection {
     pub connection: Connection<Client>,
     pub reconnect: ReconnectFlow,
+    pub session_resume: SessionResume,
+    /// Whether [`mirrord_protocol::compression`] can be enabled on this connection.
+    ///
+    /// With the operator, this requires the operator itself to support
+    /// [`COMPRESSION_VERSION`], as it relays the messages to the agent. The external proxy does
+    /// not tell us what it is connected to, so we never enable compression there.
+    pub compression: bool,
 }
 
 impl AgentConnection {
+    /// How long we wait for the agent to respond to [`ClientSessionResume::Resume`].
+    const SESSION_RESUME_TIMEOUT: Duration = Duration::from_secs(10);
+
     /// Creates a new agent connection based on the provided [`LayerConfig`] and optional
     /// [`AgentConnectInfo`].
     #[tracing::instrument(level = Level::INFO, skip(config, analytics), ret, err)]
@@ -164,6 +231,18 @@ impl AgentConnection {
         analytics: &mut R,
     ) -> Result<Self, AgentConnectionError> {
         let kind = connect_info.discriminant();
+        let session_resume = match &connect_info {
+            AgentConnectInfo::DirectKubernetes(..) => SessionResume::Supported,
+            _ => SessionResume::Unsupported,
+        };
+        let compression = match &connect_info {
+            AgentConnectInfo::Operator(session) => session
+                .operator_protocol_version
+                .as_ref()
+                .is_some_and(|version| COMPRESSION_VERSION.matches(version)),
+            AgentConnectInfo::ExternalProxy { .. } => false,
+            _ => true,
+        };
 
         let (connection, reconnect) = match connect_info {
             AgentConnectInfo::Operator(session) => {
@@ -232,7 +311,13 @@ impl AgentConnection {
 
             AgentConnectInfo::DirectKubernetes(connect_info) => {
                 let conn = portforward::create_connection(config, connect_info.clone()).await?;
-                (conn, ReconnectFlow::Break(kind))
+                (
+                    conn,
+                    ReconnectFlow::ConnectInfo {
+                        config: Box::new(config.clone()),
+                        connect_info: AgentConnectInfo::DirectKubernetes(connect_info),
+                    },
+                )
             }
 
             #[cfg(test)]
@@ -252,6 +337,8 @@ impl AgentConnection {
         Ok(Self {
             connection,
             reconnect,
+            session_resume,
+            compression,
         })
     }
 
@@ -262,9 +349,67 @@ impl AgentConnection {
         Ok(Self {
             connection,
             reconnect: ReconnectFlow::Break(AgentConnectInfoDiscriminants::DirectKubernetes),
+            session_resume: SessionResume::Unsupported,
+            compression: true,
         })
     }
 
+    /// Makes a new connection to the agent, and continues the session of the `previous`
+    /// connection on it.
+    #[tracing::instrument(level = Level::INFO, skip(previous, token, config), err)]
+    async fn resume(
+        previous: &Connection<Client>,
+        token: ResumeToken,
+        config: &LayerConfig,
+        connect_info: &AgentConnectInfo,
+    ) -> Result<Self, SessionResumeError> {
+        let mut resumed =
+            Self::new(config, connect_info.clone(), &mut NullReporter::default()).await?;
+
+        resumed
+            .connection
+            .tx_handle()
+            .send(ClientMessage::SessionResume(ClientSessionResume::Resume(
+                ResumeSessionRequest {
+                    token,
+                    received: previous.received(),
+                    replayable_from: previous.replayable_from(),
+                },
+            )))
+            .await;
+
+        let response =
+            tokio::time::timeout(Self::SESSION_RESUME_TIMEOUT, resumed.connection.recv())
+                .await
+                .map_err(|_| SessionResumeError::Timeout)?
+                .ok_or(SessionResumeError::ConnectionClosed)?;
+
+        match response {
+            DaemonMessage::SessionResume(DaemonSessionResume::Resumed { received }) => {
+                if resumed.connection.take_over(previous, received).not() {
+                    return Err(SessionResumeError::ReplayUnavailable);
+                }
+
+                resumed.session_resume = SessionResume::Enabled(token);
+                Ok(resumed)
+            }
+            DaemonMessage::SessionResume(DaemonSessionResume::Expired) => {
+                Err(SessionResumeError::Expired)
+            }
+            other => Err(SessionResumeError::UnexpectedMessage(Box::new(other))),
+        }
+    }
+
+    /// Retry strategy for making a new connection to the agent.
+    ///
+    /// 1s, 2s, 4s, 8s, 8s, ...
+    fn reconnect_strategy() -> impl Iterator<Item = Duration> {
+        ExponentialBackoff::from_millis(2)
+            .factor(500)
+            .max_delay(Duration::from_secs(8))
+            .take(10)
+    }
+
     pub fn reconnectable(&self) -> bool {
         self.reconnect.reconnectable()
     }
@@ -274,6 +419,8 @@ impl fmt::Debug for AgentConnection {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("AgentConnection")
             .field("reconnect", &self.reconnect)
+            .field("session_resume", &self.session_resume)
+            .field("compression", &self.compression)
             .finish()
     }
 }
@@ -312,7 +459,24 @@ impl BackgroundTask for AgentConnection {
                         tracing::error!("failed to receive message from the {}, inner task down", self.reconnect.kind());
                         break Err(AgentConnectionTaskError::ChannelError(self.reconnect.kind()));
                     }
-                    Some(msg) => message_bus.send(ProxyMessage::FromAgent(msg)).await,
+                    Some(DaemonMessage::SessionResume(DaemonSessionResume::Token(token))) => {
+                        tracing::debug!("session resumption enabled");
+                        self.session_resume = SessionResume::Enabled(token);
+                    }
+                    Some(msg) => {
+                        if let DaemonMessage::SwitchProtocolVersionResponse(version) = &msg
+                            && self.session_resume == SessionResume::Supported
+                            && SESSION_RESUME_VERSION.matches(version)
+                        {
+                            self.connection.enable_replay();
+                            self.connection
+                                .tx_handle()
+                                .send(ClientMessage::SessionResume(ClientSessionResume::Enable))
+                                .await;
+                        }
+
+                        message_bus.send(ProxyMessage::FromAgent(msg)).await
+                    }
                 },
             }
         }
@@ -344,15 +508,59 @@ impl RestartableBackgroundTask for AgentConnection {
                     }
                 }
 
+                if let SessionResume::Enabled(token) = self.session_resume {
+                    message_bus
+                        .send(ProxyMessage::ConnectionRefresh(ConnectionRefresh::Resuming))
+                        .await;
+
+                    let resumed = RetryIf::start(
+                        Self::reconnect_strategy(),
+                        || async {
+                            message_bus
+                                .closed_token()
+                                .run_until_cancelled(AgentConnection::resume(
+                                    &self.connection,
+                                    token,
+                                    config,
+                                    connect_info,
+                                ))
+                                .await
+                                .transpose()
+                                .inspect_err(|error| {
+                                    tracing::error!(
+                                        error = %Report::new(error),
+                                        "Failed to resume the session with the {}",
+                                        connect_info.discriminant(),
+                                    );
+                                })
+                        },
+                        SessionResumeError::is_transient,
+                    )
+                    .await;
+
+                    match resumed {
+                        Ok(Some(connection)) => {
+                            *self = connection;
+                            message_bus
+                                .send(ProxyMessage::ConnectionRefresh(ConnectionRefresh::Resumed))
+                                .await;
+
+                            return ControlFlow::Continue(());
+                        }
+                        Ok(None) => {
+                            return ControlFlow::Break(AgentConnectionTaskError::ChannelError(
+                                self.reconnect.kind(),
+                            ));
+                        }
+                        // Fall back to a new session.
+                        Err(..) => {}
+                    }
+                }
+
                 message_bus
                     .send(ProxyMessage::ConnectionRefresh(ConnectionRefresh::Start))
                     .await;
 
-                // 1s, 2s, 4s, 8s, 8s, ...
-                let retry_strategy = ExponentialBackoff::from_millis(2)
-                    .factor(500)
-                    .max_delay(Duration::from_secs(8))
-                    .take(10);
                 // Unless the operator responded with explicit 410 (meaning that the session is
                 // permanently gone), we can still retry.
                 let can_retry = |error: &AgentConnectionError| match error {
@@ -367,7 +575,7 @@ impl RestartableBackgroundTask for AgentConnection {
                 };
 
                 let connection = RetryIf::start(
-                    retry_strategy,
+                    Self::reconnect_strategy(),
                     || async {
                         message_bus
                             .closed_token()
@@ -391,7 +599,12 @@ impl RestartableBackgroundTask for AgentConnection {
                 .await;
 
                 match connection {
-                    Ok(Some(connection)) => {
+                    Ok(Some(mut connection)) => {
+                        // Session resumption is enabled again after the protocol version is
+                        // negotiated.
+                        if self.session_resume != SessionResume::Unsupported {
+                            connection.session_resume = SessionResume::Supported;
+                        }
                         *self = connection;
                         message_bus
                             .send(ProxyMessage::ConnectionRefresh(ConnectionRefresh::End(
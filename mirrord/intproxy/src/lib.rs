@@ -13,7 +13,11 @@ use layer_conn::LayerConnection;
 use layer_initializer::LayerInitializer;
 use main_tasks::{FromLayer, LayerForked, MainTaskId, ProxyMessage, ToLayer};
 use mirrord_config::{
-    experimental::ExperimentalConfig, feature::network::incoming::tls_delivery::LocalTlsDelivery,
+    experimental::ExperimentalConfig,
+    feature::network::incoming::{
+        fallback::IncomingFallback, rewrite::IncomingRewrite, shadow::IncomingShadow,
+        tls_delivery::LocalTlsDelivery,
+    },
 };
 use mirrord_intproxy_protocol::{
     IncomingRequest, LayerId, LayerToProxyMessage, LocalMessage, MessageId, OutgoingRequest,
@@ -21,6 +25,7 @@ use mirrord_intproxy_protocol::{
 };
 use mirrord_protocol::{
     CLIENT_READY_FOR_LOGS, ClientMessage, DaemonMessage, FileRequest, LogLevel,
+    compression::{COMPRESSION_VERSION, Compression},
 };
 use mirrord_protocol_io::{Client, TxHandle};
 use ping_pong::{PingPong, PingPongMessage};
@@ -147,6 +152,12 @@ pub struct IntProxy {
     /// Send handle for the agent connection
     agent_tx: TxHandle<Client>,
 
+    /// Whether to enable [`mirrord_protocol::compression`] on the agent connection, when the
+    /// agent supports it.
+    ///
+    /// Only set when enabled in the config, and allowed by [`AgentConnection::compression`].
+    compression: bool,
+
     /// Session monitor event sender
     monitor_tx: MonitorTx,
 }
@@ -175,6 +186,10 @@ impl IntProxy {
         listener: TcpListener,
         file_buffer_size: u64,
         https_delivery: LocalTlsDelivery,
+        incoming_fallback: Option<IncomingFallback>,
+        incoming_shadow: Option<IncomingShadow>,
+        incoming_rewrite: Option<IncomingRewrite>,
+        keep_subscriptions: bool,
         intervals: IntProxyIntervals,
         experimental: &ExperimentalConfig,
         monitor_tx: MonitorTx,
@@ -190,6 +205,7 @@ impl IntProxy {
         );
 
         let agent_conn_reconnectable = agent_conn.reconnectable();
+        let compression = experimental.compression && agent_conn.compression;
 
         // We need to negotiate mirrord-protocol version
         // before we can process layers' requests.
@@ -228,6 +244,10 @@ impl IntProxy {
             IncomingProxy::new(
                 Duration::from_millis(experimental.idle_local_http_connection_timeout),
                 https_delivery,
+                incoming_fallback,
+                incoming_shadow,
+                incoming_rewrite,
+                keep_subscriptions,
                 monitor_tx.clone(),
             ),
             MainTaskId::IncomingProxy,
@@ -274,6 +294,7 @@ impl IntProxy {
             connected_layers: HashMap::new(),
             process_logging_interval,
             agent_tx,
+            compression,
             monitor_tx,
         }
     }
@@ -590,6 +611,18 @@ impl IntProxy {
                     .send(SimpleProxyMessage::AddrInfoRes(msg))
                     .await
             }
+            DaemonMessage::DnsQuery(msg) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::DnsQueryRes(msg))
+                    .await
+            }
+            DaemonMessage::ReverseDnsLookup(msg) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::ReverseDnsRes(msg))
+                    .await
+            }
             DaemonMessage::Tcp(msg) => {
                 self.task_txs
                     .incoming
@@ -614,6 +647,13 @@ impl IntProxy {
                     self.agent_tx.send(ClientMessage::ReadyForLogs).await;
                 }
 
+                if self.compression && COMPRESSION_VERSION.matches(&protocol_version) {
+                    self.agent_tx
+                        .send(ClientMessage::EnableCompression(Compression::default()))
+                        .await;
+                    self.agent_tx.set_compression(Some(Compression::default()));
+                }
+
                 self.task_txs
                     .files
                     .send(FilesProxyMessage::ProtocolVersion(protocol_version.clone()))
@@ -658,9 +698,10 @@ impl IntProxy {
                     .send(SimpleProxyMessage::GetEnvRes(res.map(Into::into)))
                     .await
             }
+            // Session resumption messages are handled in the agent connection task.
             message @ DaemonMessage::PauseTarget(_)
             | message @ DaemonMessage::Vpn(_)
-            | message @ DaemonMessage::ReverseDnsLookup(_) => {
+            | message @ DaemonMessage::SessionResume(_) => {
                 Err(ProxyRuntimeError::UnexpectedAgentMessage(
                     UnexpectedAgentMessage(message.into()),
                 ))?;
@@ -744,6 +785,24 @@ impl IntProxy {
                     .send(SimpleProxyMessage::GetEnvReq(message_id, layer_id, req))
                     .await
             }
+            LayerToProxyMessage::DnsQuery(req) => {
+                self.monitor_tx.emit(MonitorEvent::DnsQuery {
+                    host: req.name.clone(),
+                });
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::DnsQueryReq(message_id, layer_id, req))
+                    .await
+            }
+            LayerToProxyMessage::ReverseDnsLookup(req) => {
+                self.monitor_tx.emit(MonitorEvent::DnsQuery {
+                    host: req.ip_address.to_string(),
+                });
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::ReverseDnsReq(message_id, layer_id, req))
+                    .await
+            }
             other => Err(ProxyRuntimeError::UnexpectedLayerMessage(other))?,
         }
 
@@ -829,6 +888,8 @@ impl IntProxy {
                     .send(AgentConnectionMessage::RequestReconnect)
                     .await;
             }
+            // Messages sent during the resume attempt go through the new connection.
+            ConnectionRefresh::Resuming | ConnectionRefresh::Resumed => {}
         }
 
         Ok(())
@@ -855,8 +916,9 @@ mod test {
         ClientMessage, DaemonMessage, ErrorKindInternal, FileRequest, FileResponse, RemoteIOError,
         ResponseError, VERSION,
         dns::{AddressFamily, GetAddrInfoRequestV2, GetAddrInfoResponse, SockType},
-        file::{OpenFileRequest, StatFsRequestV2},
+        file::{OpenFileRequest, OpenFileResponse, StatFsRequestV2},
         outgoing::{LayerConnectV2, SocketAddress, tcp::LayerTcpOutgoing},
+        resume::{ClientSessionResume, DaemonSessionResume, ResumeToken},
         tcp::{
             ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, DaemonTcp,
             HttpRequestMetadata, HttpResponse, IncomingTrafficTransportType, InternalHttpBodyFrame,
@@ -878,6 +940,7 @@ mod test {
         IntProxy, IntProxyIntervals,
         agent_conn::{
             AgentConnectInfo, AgentConnectInfoDiscriminants, AgentConnection, ReconnectFlow,
+            SessionResume,
         },
         session_monitor::{MonitorTx, chaos::ChaosWatcherRx},
     };
@@ -901,6 +964,8 @@ mod test {
         let agent_conn = AgentConnection {
             connection,
             reconnect: ReconnectFlow::Break(AgentConnectInfoDiscriminants::DirectKubernetes),
+            session_resume: SessionResume::Unsupported,
+            compression: true,
         };
 
         let (_, chaos_rx) = watch::channel(Default::default());
@@ -910,6 +975,10 @@ mod test {
             listener,
             4096,
             Default::default(),
+            None,
+            None,
+            None,
+            false,
             IntProxyIntervals {
                 ping: IntProxy::PING_INTERVAL,
                 process_logging: Duration::from_secs(60),
@@ -1026,6 +1095,8 @@ mod test {
         let agent_conn = AgentConnection {
             connection,
             reconnect: ReconnectFlow::Break(AgentConnectInfoDiscriminants::DirectKubernetes),
+            session_resume: SessionResume::Unsupported,
+            compression: true,
         };
 
         let (_, chaos_rx) = watch::channel(Default::default());
@@ -1035,6 +1106,10 @@ mod test {
             listener,
             4096,
             Default::default(),
+            None,
+            None,
+            None,
+            false,
             IntProxyIntervals {
                 ping: IntProxy::PING_INTERVAL,
                 process_logging: Duration::from_secs(60),
@@ -1126,6 +1201,8 @@ mod test {
         let agent_conn = AgentConnection {
             connection,
             reconnect: ReconnectFlow::Break(AgentConnectInfoDiscriminants::DirectKubernetes),
+            session_resume: SessionResume::Unsupported,
+            compression: true,
         };
 
         let (_, chaos_rx) = watch::channel(Default::default());
@@ -1135,6 +1212,10 @@ mod test {
             listener,
             4096,
             Default::default(),
+            None,
+            None,
+            None,
+            false,
             IntProxyIntervals {
                 ping: IntProxy::PING_INTERVAL,
                 process_logging: Duration::from_secs(60),
@@ -1161,6 +1242,10 @@ mod test {
     }
 
     async fn setup_reconnect_test() -> ReconnectTestSetup {
+        setup_reconnect_test_with(SessionResume::Unsupported).await
+    }
+
+    async fn setup_reconnect_test_with(session_resume: SessionResume) -> ReconnectTestSetup {
         let listener = TcpListener::bind("127.0.0.1:0".parse::<SocketAddr>().unwrap())
             .await
             .unwrap();
@@ -1171,13 +1256,14 @@ mod test {
             .generate_config(&mut Default::default())
             .unwrap();
 
-        let agent_conn = AgentConnection::new(
+        let mut agent_conn = AgentConnection::new(
             &config,
             AgentConnectInfo::Dummy(conn_tx),
             &mut NullReporter::default(),
         )
         .await
         .unwrap();
+        agent_conn.session_resume = session_resume;
 
         let conn = TcpStream::connect(listener.local_addr().unwrap())
             .await
@@ -1213,6 +1299,10 @@ mod test {
             listener,
             4096,
             Default::default(),
+            None,
+            None,
+            None,
+            false,
             IntProxyIntervals {
                 ping: IntProxy::PING_INTERVAL,
                 process_logging: Duration::from_secs(60),
@@ -1270,6 +1360,166 @@ mod test {
             .unwrap();
     }
 
+    /// Like [`next_proxy_msg`], but also counts all messages received from the proxy, like the
+    /// agent does for session resumption.
+    async fn next_proxy_msg_counted(
+        to_proxy: &mpsc::Sender<DaemonMessage>,
+        from_proxy: &ConnectionOutput<Client>,
+        received: &mut u64,
+    ) -> ClientMessage {
+        loop {
+            let message = from_proxy.next().await.unwrap();
+            *received += 1;
+            match message {
+                ClientMessage::Ping => to_proxy.send(DaemonMessage::Pong).await.unwrap(),
+                ClientMessage::ReadyForLogs => (),
+                other => return other,
+            }
+        }
+    }
+
+    /// Negotiates the protocol version and enables session resumption with the given token.
+    ///
+    /// Returns how many messages were received from the proxy.
+    async fn enable_session_resume(
+        to_proxy: &mpsc::Sender<DaemonMessage>,
+        from_proxy: &ConnectionOutput<Client>,
+        token: ResumeToken,
+    ) -> u64 {
+        let mut received = 0;
+
+        assert_eq!(
+            next_proxy_msg_counted(to_proxy, from_proxy, &mut received).await,
+            ClientMessage::SwitchProtocolVersion(VERSION.clone()),
+        );
+        to_proxy
+            .send(DaemonMessage::SwitchProtocolVersionResponse(
+                mirrord_protocol::VERSION.clone(),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            next_proxy_msg_counted(to_proxy, from_proxy, &mut received).await,
+            ClientMessage::SessionResume(ClientSessionResume::Enable),
+        );
+        to_proxy
+            .send(DaemonMessage::SessionResume(DaemonSessionResume::Token(
+                token,
+            )))
+            .await
+            .unwrap();
+
+        received
+    }
+
+    /// Verifies that [`IntProxy`] resumes the session after the connection is lost, and that the
+    /// file request that was lost with the connection is sent again and answered.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn resume_during_fileop() {
+        let ReconnectTestSetup {
+            mut conn_rx,
+            mut from_layer,
+            mut to_layer,
+        } = setup_reconnect_test_with(SessionResume::Supported).await;
+
+        let token = ResumeToken(42);
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+        let mut received = enable_session_resume(&to_proxy, &from_proxy, token).await;
+
+        let file_request = FileRequest::Open(OpenFileRequest {
+            path: "/some/file".into(),
+            open_options: Default::default(),
+        });
+        from_layer
+            .send(&LocalMessage {
+                message_id: 0,
+                inner: LayerToProxyMessage::File(file_request.clone()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            next_proxy_msg_counted(&to_proxy, &from_proxy, &mut received).await,
+            ClientMessage::FileRequest(file_request.clone())
+        );
+
+        // The file request is lost with the connection.
+        received -= 1;
+        drop(to_proxy);
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+        match from_proxy.next().await.unwrap() {
+            ClientMessage::SessionResume(ClientSessionResume::Resume(request)) => {
+                assert_eq!(request.token, token);
+                assert_eq!(request.replayable_from, 1);
+            }
+            other => panic!("unexpected client message from the proxy: {other:?}"),
+        }
+        to_proxy
+            .send(DaemonMessage::SessionResume(DaemonSessionResume::Resumed {
+                received,
+            }))
+            .await
+            .unwrap();
+
+        // The protocol version is not negotiated again.
+        assert_eq!(
+            next_proxy_msg(&to_proxy, &from_proxy).await,
+            ClientMessage::FileRequest(file_request)
+        );
+        to_proxy
+            .send(DaemonMessage::File(FileResponse::Open(Ok(
+                OpenFileResponse { fd: 1 },
+            ))))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            to_layer.receive().await,
+            Ok(Some(LocalMessage {
+                message_id: 0,
+                inner: ProxyToLayerMessage::File(FileResponse::Open(Ok(OpenFileResponse {
+                    fd: 1
+                })))
+            }))
+        ));
+    }
+
+    /// Verifies that [`IntProxy`] starts a new session when the agent session expired.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn resume_expired_session() {
+        let ReconnectTestSetup {
+            mut conn_rx,
+            // Keep the connection so intproxy doesn't exit
+            from_layer: _from_layer,
+            to_layer: _,
+        } = setup_reconnect_test_with(SessionResume::Supported).await;
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+        enable_session_resume(&to_proxy, &from_proxy, ResumeToken(42)).await;
+        drop(to_proxy);
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+        assert!(matches!(
+            from_proxy.next().await,
+            Some(ClientMessage::SessionResume(ClientSessionResume::Resume(
+                ..
+            )))
+        ));
+        to_proxy
+            .send(DaemonMessage::SessionResume(DaemonSessionResume::Expired))
+            .await
+            .unwrap();
+
+        // New session, with a new token.
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+        enable_session_resume(&to_proxy, &from_proxy, ResumeToken(43)).await;
+    }
+
     /// Verifies that [`IntProxy`] reconnects and restores state (port subscriptions) correctly
     #[tokio::test]
     #[rstest::rstest]
@@ -144,6 +144,16 @@ pub enum ConnectionRefresh {
     Start,
     End(TxHandle<Client>),
     Request,
+    /// Start of an attempt to resume the session on a new connection, see
+    /// [`mirrord_protocol::resume`].
+    ///
+    /// If the attempt fails, it is followed by [`ConnectionRefresh::Start`].
+    Resuming,
+    /// The session was resumed on a new connection.
+    ///
+    /// The agent kept our state, and [`TxHandle`]s of the previous connection now send messages
+    /// through the new one.
+    Resumed,
 }
 
 impl ConnectionRefresh {
@@ -155,6 +165,8 @@ impl ConnectionRefresh {
             Self::Start => Self::Start,
             Self::End(tx) => Self::End(tx.another()),
             Self::Request => Self::Request,
+            Self::Resuming => Self::Resuming,
+            Self::Resumed => Self::Resumed,
         }
     }
 }
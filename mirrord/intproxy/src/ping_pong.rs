@@ -134,6 +134,14 @@ impl BackgroundTask for PingPong {
                                 self.ticker.reset();
                                 message_bus.set_agent_tx(new_agent_tx);
                             }
+                            ConnectionRefresh::Resuming => {
+                                self.awaiting_pongs = 0;
+                                self.reconnecting = true;
+                            }
+                            ConnectionRefresh::Resumed => {
+                                self.reconnecting = false;
+                                self.ticker.reset();
+                            }
                             ConnectionRefresh::Request => {}
                         }
                     }
@@ -1189,7 +1189,10 @@ impl FilesProxy {
             ConnectionRefresh::End(tx_handle) => {
                 message_bus.set_agent_tx(tx_handle);
             }
-            ConnectionRefresh::Request => {}
+            // After the session is resumed, the agent still has our state.
+            ConnectionRefresh::Request
+            | ConnectionRefresh::Resuming
+            | ConnectionRefresh::Resumed => {}
         }
     }
 }
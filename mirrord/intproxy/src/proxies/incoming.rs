@@ -21,7 +21,10 @@ use http::{ClientStore, ResponseMode, StreamingBody};
 use http_gateway::HttpGatewayTask;
 use hyper::{HeaderMap, Method, Uri};
 use metadata_store::MetadataStore;
-use mirrord_config::feature::network::incoming::tls_delivery::LocalTlsDelivery;
+use mirrord_config::feature::network::incoming::{
+    fallback::IncomingFallback, rewrite::IncomingRewrite, shadow::IncomingShadow,
+    tls_delivery::LocalTlsDelivery,
+};
 use mirrord_intproxy_protocol::{
     ConnMetadataRequest, ConnMetadataResponse, IncomingRequest, IncomingResponse, LayerId,
     ListeningOn, MessageId, PortSubscription, ProxyToLayerMessage,
@@ -30,13 +33,15 @@ use mirrord_protocol::{
     ClientMessage, ConnectionId, RequestId, ResponseError,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestErrorV1, ChunkedRequestErrorV2,
-        DaemonTcp, HttpRequest, HttpRequestMetadata, IncomingTrafficTransportType,
-        InternalHttpBodyFrame, InternalHttpRequest, LayerTcp, LayerTcpSteal, NewTcpConnectionV1,
-        NewTcpConnectionV2,
+        DaemonTcp, HTTP_FALLBACK_VERSION, HTTP_SHADOW_VERSION, HttpRequest, HttpRequestFallback,
+        HttpRequestMetadata, IncomingTrafficTransportType, InternalHttpBodyFrame,
+        InternalHttpRequest, LayerTcp, LayerTcpSteal, NewTcpConnectionV1, NewTcpConnectionV2,
     },
 };
 use rand::seq::IndexedRandom;
+use rewrite::HttpRewriter;
 use semver::Version;
+use shadow::{ShadowComparator, ShadowOutcome};
 use tasks::{HttpGatewayId, HttpOut, InProxyTask, InProxyTaskError, InProxyTaskMessage};
 use tcp_proxy::{LocalTcpConnection, TcpProxyTask};
 use thiserror::Error;
@@ -59,6 +64,8 @@ pub mod http;
 mod http_gateway;
 mod metadata_store;
 pub mod port_subscription_ext;
+mod rewrite;
+mod shadow;
 mod subscriptions;
 pub mod tasks;
 mod tcp_proxy;
@@ -193,6 +200,18 @@ pub struct IncomingProxy {
     client_store: ClientStore,
     /// For connecting to the user application's server with TLS.
     tls_setup: Option<Arc<LocalTlsSetup>>,
+    /// When to hand stolen HTTP requests back to the agent, so that it passes them through to
+    /// their original destination.
+    ///
+    /// Only used if the agent supports [`HTTP_FALLBACK_VERSION`].
+    fallback: Option<IncomingFallback>,
+    /// Compares the responses of the user application to mirrored HTTP requests with the remote
+    /// ones.
+    ///
+    /// Only used if the agent supports [`HTTP_SHADOW_VERSION`].
+    shadow: Option<ShadowComparator>,
+    /// Rewrites stolen HTTP requests and the responses of the user application.
+    rewriter: Option<Arc<HttpRewriter>>,
     /// Each mirrored/stolen remote connection is mapped to a [`TcpProxyTask`].
     ///
     /// Each entry here maps to a connection that is in progress both locally and remotely.
@@ -239,11 +258,15 @@ impl IncomingProxy {
     pub fn new(
         idle_local_http_connection_timeout: Duration,
         https_delivery: LocalTlsDelivery,
+        fallback: Option<IncomingFallback>,
+        shadow: Option<IncomingShadow>,
+        rewrite: Option<IncomingRewrite>,
+        keep_subscriptions: bool,
         monitor_tx: MonitorTx,
     ) -> Self {
         let tls_setup = LocalTlsSetup::from_config(https_delivery);
         Self {
-            subscriptions: Default::default(),
+            subscriptions: SubscriptionsManager::new(keep_subscriptions),
             metadata_store: Default::default(),
             response_mode: Default::default(),
             client_store: ClientStore::new_with_timeout(
@@ -251,6 +274,9 @@ impl IncomingProxy {
                 tls_setup.clone(),
             ),
             tls_setup,
+            fallback,
+            shadow: shadow.as_ref().map(ShadowComparator::new),
+            rewriter: rewrite.as_ref().map(HttpRewriter::new).map(Arc::new),
             tcp_proxies: Default::default(),
             http_gateways: Default::default(),
             tasks: None,
@@ -325,6 +351,30 @@ impl IncomingProxy {
         let listening_on = subscription.listening_on.clone();
         tracing::info!(%listening_on, "Forwarding HTTP request");
 
+        let fallback = self.fallback.clone().filter(|_| is_steal).filter(|_| {
+            self.protocol_version
+                .as_ref()
+                .is_some_and(|version| HTTP_FALLBACK_VERSION.matches(version))
+        });
+
+        let shadow = match self.shadow.as_mut() {
+            Some(shadow)
+                if is_steal.not()
+                    && self
+                        .protocol_version
+                        .as_ref()
+                        .is_some_and(|version| HTTP_SHADOW_VERSION.matches(version)) =>
+            {
+                shadow.request_started(
+                    connection_id,
+                    &request.internal_request.method,
+                    &request.internal_request.uri,
+                );
+                true
+            }
+            _ => false,
+        };
+
         let tx = self.tasks.as_mut().unwrap().register(
             HttpGatewayTask::new(
                 request,
@@ -332,6 +382,9 @@ impl IncomingProxy {
                 is_steal.then_some(self.response_mode),
                 listening_on,
                 transport,
+                fallback,
+                shadow,
+                self.rewriter.clone().filter(|_| is_steal),
             ),
             if is_steal {
                 InProxyTask::StealHttpGateway(id)
@@ -705,6 +758,15 @@ impl IncomingProxy {
                     message_bus.send(msg).await;
                 }
             }
+
+            DaemonTcp::HttpResponseShadow(shadow) => {
+                let outcome = self.shadow.as_mut().and_then(|comparator| {
+                    comparator.remote_response(shadow.connection_id, shadow.response)
+                });
+                if let Some(outcome) = outcome {
+                    self.report_shadow_outcome(outcome);
+                }
+            }
         }
 
         Ok(())
@@ -720,6 +782,11 @@ impl IncomingProxy {
         match message {
             IncomingProxyMessage::LayerRequest(message_id, layer_id, req) => match req {
                 IncomingRequest::PortSubscribe(subscribe) => {
+                    if let Some(msg) = self.subscriptions.replace_released(&subscribe.subscription)
+                    {
+                        message_bus.send_agent(msg).await;
+                    }
+
                     let msg = self.subscriptions.layer_subscribed(
                         layer_id,
                         message_id,
@@ -776,6 +843,21 @@ impl IncomingProxy {
 
             IncomingProxyMessage::AgentProtocolVersion(protocol_version) => {
                 self.response_mode = ResponseMode::from(&protocol_version);
+
+                if self.shadow.is_some() && HTTP_SHADOW_VERSION.matches(&protocol_version) {
+                    message_bus
+                        .send_agent(ClientMessage::Tcp(LayerTcp::ShadowHttpResponses))
+                        .await;
+                }
+
+                if self.fallback.is_some() && HTTP_FALLBACK_VERSION.matches(&protocol_version) {
+                    message_bus
+                        .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::HttpRequestFallback(
+                            HttpRequestFallback::Enable,
+                        )))
+                        .await;
+                }
+
                 self.protocol_version.replace(protocol_version);
 
                 if self.restore_subscriptions_on_protocol_version_switch {
@@ -800,6 +882,9 @@ impl IncomingProxy {
                         self.http_gateways.mirror.clear();
                         self.http_gateways.steal.clear();
                         self.tasks.as_mut().unwrap().clear();
+                        if let Some(shadow) = self.shadow.as_mut() {
+                            shadow.clear();
+                        }
 
                         // Reset protocol version since we'll need another negotiation
                         // round for the new connection.
@@ -813,7 +898,10 @@ impl IncomingProxy {
                             .unwrap()
                             .set_agent_tx(message_bus.clone_agent_tx());
                     }
-                    ConnectionRefresh::Request => {}
+                    // After the session is resumed, the agent still has our state.
+                    ConnectionRefresh::Request
+                    | ConnectionRefresh::Resuming
+                    | ConnectionRefresh::Resumed => {}
                 }
             }
         }
@@ -923,6 +1011,18 @@ impl IncomingProxy {
                 }
             }
 
+            TaskUpdate::Message(InProxyTaskMessage::Http(HttpOut::ShadowResponse(response))) => {
+                // The gateway might already be removed, as the agent closes mirrored connections
+                // as soon as the request body is done.
+                let outcome = self
+                    .shadow
+                    .as_mut()
+                    .and_then(|comparator| comparator.local_response(id.connection_id, response));
+                if let Some(outcome) = outcome {
+                    self.report_shadow_outcome(outcome);
+                }
+            }
+
             TaskUpdate::Message(InProxyTaskMessage::Http(message)) => {
                 let exists = self
                     .http_gateways
@@ -954,10 +1054,38 @@ impl IncomingProxy {
                             .get_mut(is_steal)
                             .insert(id.connection_id, proxy);
                     }
+                    HttpOut::ShadowResponse(..) => {
+                        unreachable!("shadow responses are handled before this match")
+                    }
                 }
             }
         }
     }
+
+    /// Reports the result of comparing the local and the remote response to a mirrored HTTP
+    /// request, along with the updated summary of the shadow mode.
+    fn report_shadow_outcome(&self, outcome: ShadowOutcome) {
+        if let Some(mismatch) = outcome.mismatch {
+            tracing::warn!(
+                method = mismatch.method,
+                path = mismatch.path,
+                differences = ?mismatch.differences,
+                "Local response does not match the remote one",
+            );
+
+            self.monitor_tx.emit(MonitorEvent::ShadowMismatch {
+                method: mismatch.method,
+                path: mismatch.path,
+                differences: mismatch.differences,
+            });
+        }
+
+        self.monitor_tx.emit(MonitorEvent::ShadowSummary {
+            compared: outcome.summary.compared,
+            mismatched: outcome.summary.mismatched,
+            skipped: outcome.summary.skipped,
+        });
+    }
 }
 
 impl BackgroundTask for IncomingProxy {
@@ -972,14 +1100,18 @@ impl BackgroundTask for IncomingProxy {
             None => self.tasks = Some(BackgroundTasks::new(message_bus.clone_agent_tx())),
         };
 
-        loop {
+        let result = loop {
             tokio::select! {
                 msg = message_bus.recv() =>  match msg {
                     None => {
                         tracing::debug!("Message bus closed, exiting");
                         break Ok(());
                     },
-                    Some(message) => self.handle_message(message, message_bus).await?,
+                    Some(message) => {
+                        if let Err(error) = self.handle_message(message, message_bus).await {
+                            break Err(error);
+                        }
+                    }
                 },
 
                 Some((id, update)) = self.tasks.as_mut().unwrap().next() => match id {
@@ -997,7 +1129,13 @@ impl BackgroundTask for IncomingProxy {
                     }
                 },
             }
+        };
+
+        if let Some(shadow) = &self.shadow {
+            shadow.log_summary();
         }
+
+        result
     }
 }
 
@@ -1,4 +1,4 @@
-use std::{fmt, io, net::SocketAddr, ops::Not};
+use std::{fmt, io, net::SocketAddr, ops::Not, time::Duration};
 
 use hyper::{
     Request, Response, StatusCode, Version,
@@ -102,6 +102,12 @@ pub enum LocalHttpError {
 
     #[error("failed to prepare TLS client configuration: {0}")]
     TlsSetupError(#[from] LocalTlsSetupError),
+
+    #[error("the local application's HTTP server did not respond in {}ms", .0.as_millis())]
+    ResponseTimeout(Duration),
+
+    #[error("the local application's HTTP server responded with {0}, configured for fallback")]
+    FallbackStatus(StatusCode),
 }
 
 impl LocalHttpError {
@@ -111,7 +117,9 @@ impl LocalHttpError {
         match self {
             Self::SocketSetupFailed(..)
             | Self::UnsupportedHttpVersion(..)
-            | Self::TlsSetupError(..) => false,
+            | Self::TlsSetupError(..)
+            | Self::ResponseTimeout(..)
+            | Self::FallbackStatus(..) => false,
             Self::ConnectTcpFailed(..) | Self::ConnectTlsFailed(..) => true,
             Self::HandshakeFailed(err) | Self::SendFailed(err) | Self::ReadBodyFailed(err) => (err
                 .is_parse()
@@ -2,19 +2,23 @@ use std::{
     collections::VecDeque,
     convert::Infallible,
     fmt,
-    ops::ControlFlow,
+    ops::{ControlFlow, Not},
+    sync::Arc,
     time::{Duration, Instant},
 };
 
+use bytes::BytesMut;
 use http_body_util::BodyExt;
 use hyper::{StatusCode, body::Incoming, http::response::Parts};
+use mirrord_config::feature::network::incoming::fallback::IncomingFallback;
 use mirrord_intproxy_protocol::ListeningOn;
 use mirrord_nightly_polyfill::error::Report;
 use mirrord_protocol::{
     ClientMessage, Payload,
     batched_body::BatchedBody,
     tcp::{
-        ChunkedRequestBodyV1, ChunkedRequestErrorV1, ChunkedResponse, HttpRequest, HttpResponse,
+        ChunkedRequestBodyV1, ChunkedRequestErrorV1, ChunkedResponse, HttpRequest,
+        HttpRequestFallback, HttpRequestFallbackRequest, HttpResponse,
         IncomingTrafficTransportType, InternalHttpBody, InternalHttpBodyFrame,
         InternalHttpResponse, LayerTcpSteal,
     },
@@ -26,6 +30,8 @@ use tracing::Level;
 use super::{
     ListeningOnExt,
     http::{ClientStore, LocalHttpError, ResponseMode, StreamingBody, mirrord_error_response},
+    rewrite::HttpRewriter,
+    shadow::{LocalResponse, ShadowComparator},
     tasks::{HttpOut, InProxyTaskMessage},
 };
 use crate::background_tasks::{BackgroundTask, MessageBus};
@@ -49,6 +55,19 @@ pub struct HttpGatewayTask {
     listening_on: ListeningOn,
     /// How to transport the HTTP request to the server.
     transport: IncomingTrafficTransportType,
+    /// Set if we should hand the request back to the agent when the user application fails to
+    /// handle it.
+    ///
+    /// [`None`] if this is a mirrored request, or the agent does not support
+    /// [`HttpRequestFallback`]s.
+    fallback: Option<IncomingFallback>,
+    /// Set if this is a mirrored request, and we should report the response to the
+    /// [`IncomingProxy`](super::IncomingProxy) with [`HttpOut::ShadowResponse`].
+    shadow: bool,
+    /// Rewrites the local application's response, if this is a stolen request.
+    ///
+    /// The request itself is rewritten in [`HttpGatewayTask::new`].
+    rewriter: Option<Arc<HttpRewriter>>,
 }
 
 impl fmt::Debug for HttpGatewayTask {
@@ -58,28 +77,59 @@ impl fmt::Debug for HttpGatewayTask {
             .field("response_mode", &self.response_mode)
             .field("listening_on", &self.listening_on)
             .field("transport", &self.transport)
+            .field("fallback", &self.fallback)
+            .field("shadow", &self.shadow)
+            .field("rewriter", &self.rewriter)
             .finish()
     }
 }
 
 impl HttpGatewayTask {
     /// Creates a new gateway task.
+    ///
+    /// If `rewriter` is given, the request is rewritten here, before any send attempt.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        request: HttpRequest<StreamingBody>,
+        mut request: HttpRequest<StreamingBody>,
         client_store: ClientStore,
         response_mode: Option<ResponseMode>,
         listening_on: ListeningOn,
         transport: IncomingTrafficTransportType,
+        fallback: Option<IncomingFallback>,
+        shadow: bool,
+        rewriter: Option<Arc<HttpRewriter>>,
     ) -> Self {
+        if let Some(rewriter) = &rewriter {
+            rewriter.rewrite_request(&mut request.internal_request);
+        }
+
         Self {
             request,
             client_store,
             response_mode,
             listening_on,
             transport,
+            fallback,
+            shadow,
+            rewriter,
         }
     }
 
+    /// Whether the request should be handed back to the agent, given that sending it to the user
+    /// application failed with this error.
+    fn falls_back(&self, error: &LocalHttpError) -> bool {
+        self.fallback.is_some()
+            && matches!(
+                error,
+                LocalHttpError::ConnectTcpFailed(..)
+                    | LocalHttpError::ConnectTlsFailed(..)
+                    | LocalHttpError::HandshakeFailed(..)
+                    | LocalHttpError::SendFailed(..)
+                    | LocalHttpError::ResponseTimeout(..)
+                    | LocalHttpError::FallbackStatus(..)
+            )
+    }
+
     /// Handles the response if we operate in [`ResponseMode::Chunked`].
     ///
     /// # Returns
@@ -246,12 +296,31 @@ impl HttpGatewayTask {
                 &self.request.internal_request.uri,
             )
             .await?;
-        let mut response = client.send_request(self.request.clone()).await?;
+        let response = client.send_request(self.request.clone());
+        let timeout = self
+            .fallback
+            .as_ref()
+            .and_then(|fallback| fallback.timeout_ms)
+            .map(Duration::from_millis);
+        let mut response = match timeout {
+            Some(timeout) => time::timeout(timeout, response)
+                .await
+                .map_err(|_| LocalHttpError::ResponseTimeout(timeout))??,
+            None => response.await?,
+        };
+        if let Some(fallback) = &self.fallback
+            && fallback.status_codes.contains(&response.status().as_u16())
+        {
+            return Err(LocalHttpError::FallbackStatus(response.status()));
+        }
         let on_upgrade = (response.status() == StatusCode::SWITCHING_PROTOCOLS).then(|| {
             tracing::debug!("Detected an HTTP upgrade");
             hyper::upgrade::on(&mut response)
         });
-        let (parts, mut body) = response.into_parts();
+        let (mut parts, mut body) = response.into_parts();
+        if let Some(rewriter) = &self.rewriter {
+            rewriter.rewrite_response(&mut parts.headers);
+        }
 
         let flow = match self.response_mode {
             Some(ResponseMode::Basic) => {
@@ -324,14 +393,36 @@ impl HttpGatewayTask {
             }
             None => {
                 let start = Instant::now();
+                let mut body_copy = self.shadow.then(BytesMut::new);
                 while let Some(frame) = body.frame().await {
-                    frame.map_err(LocalHttpError::ReadBodyFailed)?;
+                    let frame = frame.map_err(LocalHttpError::ReadBodyFailed)?;
+                    if let Some(data) = frame.data_ref()
+                        && let Some(copy) = body_copy.as_mut()
+                    {
+                        if copy.len() + data.len() > ShadowComparator::MAX_BODY_SIZE {
+                            body_copy = None;
+                        } else {
+                            copy.extend_from_slice(data);
+                        }
+                    }
                 }
                 tracing::debug!(
                     ?body,
                     elapsed_ms = start.elapsed().as_millis(),
                     "Collected the whole response body",
                 );
+
+                if self.shadow {
+                    let response = LocalResponse {
+                        status: parts.status,
+                        headers: parts.headers,
+                        body: body_copy.map(BytesMut::freeze),
+                    };
+                    message_bus
+                        .send(HttpOut::ShadowResponse(Ok(response)))
+                        .await;
+                }
+
                 ControlFlow::Continue(())
             }
         };
@@ -391,7 +482,11 @@ impl BackgroundTask for HttpGatewayTask {
             match send_result {
                 None | Some(Ok(())) => return Ok(()),
                 Some(Err(error)) => {
-                    let backoff = error.can_retry().then(|| backoffs.next()).flatten();
+                    // When the request can fall back to its original destination, there is no
+                    // point in keeping the remote client waiting.
+                    let backoff = (error.can_retry() && self.falls_back(&error).not())
+                        .then(|| backoffs.next())
+                        .flatten();
 
                     let Some(backoff) = backoff else {
                         tracing::warn!(
@@ -432,7 +527,21 @@ impl BackgroundTask for HttpGatewayTask {
         // If we send an error response here IncomingProxy may hit an
         // unreachable!() and panic as it doesn't expect responses in
         // mirror mode
-        if self.response_mode.is_some() {
+        if self.response_mode.is_some() && self.falls_back(&error) {
+            tracing::info!(
+                error = %Report::new(&error),
+                "Passing the HTTP request through to its original destination",
+            );
+
+            message_bus
+                .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::HttpRequestFallback(
+                    HttpRequestFallback::Request(HttpRequestFallbackRequest {
+                        connection_id: self.request.connection_id,
+                        request_id: self.request.request_id,
+                    }),
+                )))
+                .await;
+        } else if self.response_mode.is_some() {
             let response = mirrord_error_response(
                 Report::new(error).pretty(true),
                 self.request.version(),
@@ -446,6 +555,10 @@ impl BackgroundTask for HttpGatewayTask {
                     response,
                 )))
                 .await;
+        } else if self.shadow {
+            message_bus
+                .send(HttpOut::ShadowResponse(Err(Report::new(error).to_string())))
+                .await;
         }
 
         Ok(())
@@ -691,6 +804,9 @@ mod test {
                 } else {
                     IncomingTrafficTransportType::Tcp
                 },
+                None,
+                false,
+                None,
             );
             tasks.register(gateway, 0, 8)
         };
@@ -853,6 +969,9 @@ mod test {
                 response_mode,
                 ListeningOn::Socket(addr),
                 IncomingTrafficTransportType::Tcp,
+                None,
+                false,
+                None,
             ),
             (),
             8,
@@ -1014,6 +1133,9 @@ mod test {
                 Some(ResponseMode::Basic),
                 ListeningOn::Socket(addr),
                 IncomingTrafficTransportType::Tcp,
+                None,
+                false,
+                None,
             ),
             (),
             8,
@@ -1094,6 +1216,9 @@ mod test {
                 Some(ResponseMode::Basic),
                 ListeningOn::Socket(addr),
                 IncomingTrafficTransportType::Tcp,
+                None,
+                false,
+                None,
             ),
             0,
             8,
@@ -1105,6 +1230,9 @@ mod test {
                 Some(ResponseMode::Basic),
                 ListeningOn::Socket(addr),
                 IncomingTrafficTransportType::Tcp,
+                None,
+                false,
+                None,
             ),
             1,
             8,
@@ -1132,4 +1260,261 @@ mod test {
             }
         }
     }
+
+    #[derive(Debug, Clone, Copy)]
+    enum FallbackReason {
+        ConnectionRefused,
+        Timeout,
+        Status,
+    }
+
+    /// Verifies that [`HttpGatewayTask`] hands the request back to the agent with
+    /// [`HttpRequestFallback::Request`], when the user application fails to handle it.
+    #[rstest]
+    #[case::connection_refused(FallbackReason::ConnectionRefused)]
+    #[case::timeout(FallbackReason::Timeout)]
+    #[case::status(FallbackReason::Status)]
+    #[tokio::test]
+    async fn falls_back_to_original_destination(#[case] reason: FallbackReason) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        match reason {
+            FallbackReason::ConnectionRefused => std::mem::drop(listener),
+            FallbackReason::Timeout | FallbackReason::Status => {
+                tokio::spawn(async move {
+                    let service = service_fn(move |_req: Request<Incoming>| async move {
+                        if matches!(reason, FallbackReason::Timeout) {
+                            time::sleep(Duration::from_secs(10)).await;
+                        }
+                        let mut response = Response::new(Empty::<Bytes>::new());
+                        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                        Ok::<_, Infallible>(response)
+                    });
+
+                    let (connection, _) = listener.accept().await.unwrap();
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(connection), service)
+                        .await;
+                });
+            }
+        }
+
+        let request = HttpRequest {
+            connection_id: 0,
+            request_id: 0,
+            port: 80,
+            internal_request: InternalHttpRequest {
+                method: Method::GET,
+                uri: "/".parse().unwrap(),
+                headers: Default::default(),
+                version: Version::HTTP_11,
+                body: Default::default(),
+            },
+        };
+
+        let (connection, _, proxy_rx) = Connection::dummy();
+
+        let mut tasks: BackgroundTasks<(), InProxyTaskMessage, Infallible> =
+            BackgroundTasks::new(connection.tx_handle());
+
+        let _gateway = tasks.register(
+            HttpGatewayTask::new(
+                request,
+                ClientStore::new_with_timeout(Duration::from_secs(1), Default::default()),
+                Some(ResponseMode::Chunked),
+                ListeningOn::Socket(addr),
+                IncomingTrafficTransportType::Tcp,
+                Some(IncomingFallback {
+                    timeout_ms: Some(100),
+                    status_codes: vec![StatusCode::SERVICE_UNAVAILABLE.as_u16()],
+                }),
+                false,
+                None,
+            ),
+            (),
+            8,
+        );
+
+        match proxy_rx.next().await.unwrap() {
+            ClientMessage::TcpSteal(LayerTcpSteal::HttpRequestFallback(
+                HttpRequestFallback::Request(request),
+            )) => {
+                assert_eq!(
+                    request,
+                    HttpRequestFallbackRequest {
+                        connection_id: 0,
+                        request_id: 0,
+                    }
+                );
+            }
+            other => panic!("unexpected message: {other:?}"),
+        }
+
+        match tasks.next().await.unwrap().1 {
+            TaskUpdate::Finished(Ok(())) => {}
+            other => panic!("unexpected task update: {other:?}"),
+        }
+    }
+
+    /// Verifies that [`HttpGatewayTask`] reports the response of the user application to a
+    /// mirrored request with [`HttpOut::ShadowResponse`].
+    #[tokio::test]
+    async fn reports_shadow_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let service = service_fn(|_req: Request<Incoming>| async {
+                let mut response =
+                    Response::new(http_body_util::Full::new(Bytes::from_static(b"hello")));
+                *response.status_mut() = StatusCode::ACCEPTED;
+                Ok::<_, Infallible>(response)
+            });
+
+            let (connection, _) = listener.accept().await.unwrap();
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(connection), service)
+                .await;
+        });
+
+        let request = HttpRequest {
+            connection_id: 0,
+            request_id: 0,
+            port: 80,
+            internal_request: InternalHttpRequest {
+                method: Method::GET,
+                uri: "/".parse().unwrap(),
+                headers: Default::default(),
+                version: Version::HTTP_11,
+                body: Default::default(),
+            },
+        };
+
+        let (connection, _, _proxy_rx) = Connection::dummy();
+
+        let mut tasks: BackgroundTasks<(), InProxyTaskMessage, Infallible> =
+            BackgroundTasks::new(connection.tx_handle());
+
+        let _gateway = tasks.register(
+            HttpGatewayTask::new(
+                request,
+                ClientStore::new_with_timeout(Duration::from_secs(1), Default::default()),
+                None,
+                ListeningOn::Socket(addr),
+                IncomingTrafficTransportType::Tcp,
+                None,
+                true,
+                None,
+            ),
+            (),
+            8,
+        );
+
+        match tasks.next().await.unwrap().1 {
+            TaskUpdate::Message(InProxyTaskMessage::Http(HttpOut::ShadowResponse(Ok(
+                response,
+            )))) => {
+                assert_eq!(response.status, StatusCode::ACCEPTED);
+                assert_eq!(response.body, Some(Bytes::from_static(b"hello")));
+            }
+            other => panic!("unexpected task update: {other:?}"),
+        }
+
+        match tasks.next().await.unwrap().1 {
+            TaskUpdate::Finished(Ok(())) => {}
+            other => panic!("unexpected task update: {other:?}"),
+        }
+    }
+
+    /// Verifies that [`HttpGatewayTask`] applies the [`HttpRewriter`] to the stolen request and
+    /// to the response of the user application.
+    #[tokio::test]
+    async fn rewrites_request_and_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let service = service_fn(|req: Request<Incoming>| async move {
+                assert_eq!(req.uri(), "/users?id=1");
+                assert_eq!(req.headers().get(header::HOST).unwrap(), "localhost");
+                let response = Response::builder()
+                    .header(header::SET_COOKIE, "a=b; Domain=localhost")
+                    .header(header::SERVER, "local")
+                    .body(Empty::<Bytes>::new())
+                    .unwrap();
+                Ok::<_, Infallible>(response)
+            });
+
+            let (connection, _) = listener.accept().await.unwrap();
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(connection), service)
+                .await;
+        });
+
+        let request = HttpRequest {
+            connection_id: 0,
+            request_id: 0,
+            port: 80,
+            internal_request: InternalHttpRequest {
+                method: Method::GET,
+                uri: "/api/users?id=1".parse().unwrap(),
+                headers: [(header::HOST, HeaderValue::from_static("example.com"))]
+                    .into_iter()
+                    .collect(),
+                version: Version::HTTP_11,
+                body: Default::default(),
+            },
+        };
+
+        let rewrite = serde_json::from_value(serde_json::json!({
+            "request": {
+                "path_prefix": [{ "from": "/api", "to": "/" }],
+                "host": "localhost"
+            },
+            "response": {
+                "headers": { "remove": ["server"] },
+                "cookie_domain": "example.com"
+            }
+        }))
+        .unwrap();
+
+        let (connection, _, proxy_rx) = Connection::dummy();
+
+        let mut tasks: BackgroundTasks<(), InProxyTaskMessage, Infallible> =
+            BackgroundTasks::new(connection.tx_handle());
+
+        let _gateway = tasks.register(
+            HttpGatewayTask::new(
+                request,
+                ClientStore::new_with_timeout(Duration::from_secs(1), Default::default()),
+                Some(ResponseMode::Basic),
+                ListeningOn::Socket(addr),
+                IncomingTrafficTransportType::Tcp,
+                None,
+                false,
+                Some(Arc::new(HttpRewriter::new(&rewrite))),
+            ),
+            (),
+            8,
+        );
+
+        match proxy_rx.next().await.unwrap() {
+            ClientMessage::TcpSteal(LayerTcpSteal::HttpResponse(response)) => {
+                let headers = &response.internal_response.headers;
+                assert_eq!(response.internal_response.status, StatusCode::OK);
+                assert_eq!(
+                    headers.get(header::SET_COOKIE).unwrap(),
+                    "a=b; Domain=example.com"
+                );
+                assert!(headers.get(header::SERVER).is_none());
+            }
+            other => panic!("unexpected message: {other:?}"),
+        }
+
+        match tasks.next().await.unwrap().1 {
+            TaskUpdate::Finished(Ok(())) => {}
+            other => panic!("unexpected task update: {other:?}"),
+        }
+    }
 }
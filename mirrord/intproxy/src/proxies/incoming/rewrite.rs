@@ -0,0 +1,323 @@
+//! Rewriting of stolen HTTP requests and their responses, see [`IncomingRewrite`].
+
+use std::ops::Not;
+
+use hyper::{
+    HeaderMap, Uri,
+    header::{HOST, HeaderName, HeaderValue, SET_COOKIE},
+    http::uri::{Authority, PathAndQuery},
+};
+use mirrord_config::feature::network::incoming::rewrite::{HeaderRewrite, IncomingRewrite};
+use mirrord_protocol::tcp::InternalHttpRequest;
+
+/// Parsed [`HeaderRewrite`].
+#[derive(Debug)]
+struct HeaderRules {
+    remove: Vec<HeaderName>,
+    set: Vec<(HeaderName, HeaderValue)>,
+    add: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl HeaderRules {
+    /// Parses the given config.
+    ///
+    /// The config is verified in [`IncomingRewrite::verify`], so invalid entries are only logged
+    /// and skipped here.
+    fn new(config: &HeaderRewrite) -> Self {
+        let parse_name = |name: &String| {
+            HeaderName::try_from(name.as_str())
+                .inspect_err(
+                    |error| tracing::warn!(%name, %error, "Invalid header name in rewrite rules"),
+                )
+                .ok()
+        };
+        let parse_entry = |(name, value): (&String, &String)| {
+            let value = HeaderValue::try_from(value.as_str())
+                .inspect_err(
+                    |error| tracing::warn!(%value, %error, "Invalid header value in rewrite rules"),
+                )
+                .ok()?;
+            Some((parse_name(name)?, value))
+        };
+
+        Self {
+            remove: config.remove.iter().filter_map(parse_name).collect(),
+            set: config.set.iter().filter_map(parse_entry).collect(),
+            add: config.add.iter().filter_map(parse_entry).collect(),
+        }
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        for name in &self.remove {
+            headers.remove(name);
+        }
+
+        for (name, value) in &self.set {
+            headers.insert(name.clone(), value.clone());
+        }
+
+        for (name, value) in &self.add {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+}
+
+/// Applies the [`IncomingRewrite`] rules to stolen HTTP requests, before they are delivered to
+/// the local application, and to the local application's responses.
+#[derive(Debug)]
+pub struct HttpRewriter {
+    request_headers: HeaderRules,
+    /// Path prefix rules, as `(from, to)` pairs.
+    path_prefix: Vec<(String, String)>,
+    host: Option<HeaderValue>,
+    response_headers: HeaderRules,
+    cookie_domain: Option<String>,
+}
+
+impl HttpRewriter {
+    pub fn new(config: &IncomingRewrite) -> Self {
+        Self {
+            request_headers: HeaderRules::new(&config.request.headers),
+            path_prefix: config
+                .request
+                .path_prefix
+                .iter()
+                .map(|rule| (rule.from.clone(), rule.to.clone()))
+                .collect(),
+            host: config.request.host.as_deref().and_then(|host| {
+                HeaderValue::try_from(host)
+                    .inspect_err(
+                        |error| tracing::warn!(host, %error, "Invalid host in rewrite rules"),
+                    )
+                    .ok()
+            }),
+            response_headers: HeaderRules::new(&config.response.headers),
+            cookie_domain: config.response.cookie_domain.clone(),
+        }
+    }
+
+    /// Rewrites the request that is about to be delivered to the local application.
+    ///
+    /// The path and the host are rewritten first, so that the header rules can override the
+    /// `host` header.
+    pub fn rewrite_request<B>(&self, request: &mut InternalHttpRequest<B>) {
+        let path = self.rewrite_path(request.uri.path());
+        let authority = self
+            .host
+            .as_ref()
+            .filter(|_| request.uri.authority().is_some())
+            .and_then(|host| Authority::try_from(host.as_bytes()).ok());
+
+        if path.is_some() || authority.is_some() {
+            let mut parts = request.uri.clone().into_parts();
+
+            if let Some(path) = path {
+                let path_and_query = match request.uri.query() {
+                    Some(query) => format!("{path}?{query}"),
+                    None => path,
+                };
+                parts.path_and_query = PathAndQuery::try_from(path_and_query).ok();
+            }
+
+            if let Some(authority) = authority {
+                parts.authority = Some(authority);
+            }
+
+            match Uri::from_parts(parts) {
+                Ok(uri) => request.uri = uri,
+                Err(error) => {
+                    tracing::warn!(uri = %request.uri, %error, "Failed to rewrite the request URI")
+                }
+            }
+        }
+
+        if let Some(host) = &self.host {
+            request.headers.insert(HOST, host.clone());
+        }
+
+        self.request_headers.apply(&mut request.headers);
+    }
+
+    /// Rewrites the headers of the local application's response.
+    pub fn rewrite_response(&self, headers: &mut HeaderMap) {
+        if let Some(domain) = &self.cookie_domain {
+            let cookies = headers
+                .get_all(SET_COOKIE)
+                .iter()
+                .map(|cookie| Self::rewrite_cookie_domain(cookie, domain))
+                .collect::<Vec<_>>();
+            headers.remove(SET_COOKIE);
+            for cookie in cookies {
+                headers.append(SET_COOKIE, cookie);
+            }
+        }
+
+        self.response_headers.apply(headers);
+    }
+
+    /// Applies the first matching path prefix rule to the given path.
+    ///
+    /// Returns [`None`] if no rule matches.
+    fn rewrite_path(&self, path: &str) -> Option<String> {
+        self.path_prefix.iter().find_map(|(from, to)| {
+            let rest = path.strip_prefix(from.as_str())?;
+            let at_boundary = rest.is_empty() || rest.starts_with('/') || from.ends_with('/');
+            if at_boundary.not() {
+                return None;
+            }
+
+            let rest = rest.trim_start_matches('/');
+            let path = match (to.ends_with('/'), rest.is_empty()) {
+                (_, true) => to.clone(),
+                (true, false) => format!("{to}{rest}"),
+                (false, false) => format!("{to}/{rest}"),
+            };
+
+            Some(path)
+        })
+    }
+
+    /// Replaces the `Domain` attribute of the given `Set-Cookie` header value, or removes it if
+    /// `domain` is empty.
+    ///
+    /// Values that are not valid UTF-8 are returned unchanged.
+    fn rewrite_cookie_domain(cookie: &HeaderValue, domain: &str) -> HeaderValue {
+        let Ok(value) = cookie.to_str() else {
+            return cookie.clone();
+        };
+
+        let mut attributes = value
+            .split(';')
+            .map(str::trim)
+            .filter(|attribute| {
+                attribute
+                    .get(..7)
+                    .is_some_and(|name| name.eq_ignore_ascii_case("domain="))
+                    .not()
+            })
+            .map(ToOwned::to_owned)
+            .collect::<Vec<_>>();
+        if domain.is_empty().not() {
+            attributes.push(format!("Domain={domain}"));
+        }
+
+        HeaderValue::try_from(attributes.join("; ")).unwrap_or_else(|_| cookie.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::{HeaderMap, Method, Version, header::HeaderValue};
+    use mirrord_config::feature::network::incoming::rewrite::{
+        IncomingRewrite, PathPrefixRewrite, RequestRewrite, ResponseRewrite,
+    };
+    use mirrord_protocol::tcp::InternalHttpRequest;
+    use rstest::rstest;
+
+    use super::HttpRewriter;
+
+    fn request(uri: &str, headers: &[(&'static str, &'static str)]) -> InternalHttpRequest<()> {
+        InternalHttpRequest {
+            method: Method::GET,
+            uri: uri.parse().unwrap(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+                .collect(),
+            version: Version::HTTP_11,
+            body: (),
+        }
+    }
+
+    #[rstest]
+    #[case("/api", "/")]
+    #[case("/api/", "/")]
+    #[case("/api/users?id=1", "/users?id=1")]
+    #[case("/apiary", "/apiary")]
+    #[case("/v1/items", "/v2/items")]
+    #[case("/other", "/other")]
+    fn rewrites_path_prefix(#[case] uri: &str, #[case] expected: &str) {
+        let rewriter = HttpRewriter::new(&IncomingRewrite {
+            request: RequestRewrite {
+                path_prefix: vec![
+                    PathPrefixRewrite {
+                        from: "/api".into(),
+                        to: "/".into(),
+                    },
+                    PathPrefixRewrite {
+                        from: "/v1/".into(),
+                        to: "/v2".into(),
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let mut request = request(uri, &[]);
+        rewriter.rewrite_request(&mut request);
+        assert_eq!(request.uri, expected);
+    }
+
+    #[test]
+    fn rewrites_host_and_headers() {
+        let config: IncomingRewrite = serde_json::from_value(serde_json::json!({
+            "request": {
+                "host": "localhost:8080",
+                "headers": {
+                    "set": { "x-prefix": "/api" },
+                    "add": { "x-tag": "local" },
+                    "remove": ["x-envoy-original-path"]
+                }
+            }
+        }))
+        .unwrap();
+        let rewriter = HttpRewriter::new(&config);
+
+        let mut request = request(
+            "http://example.com/users",
+            &[
+                ("host", "example.com"),
+                ("x-prefix", "/old"),
+                ("x-tag", "remote"),
+                ("x-envoy-original-path", "/api/users"),
+            ],
+        );
+        rewriter.rewrite_request(&mut request);
+
+        assert_eq!(request.uri, "http://localhost:8080/users");
+        assert_eq!(request.headers.get("host").unwrap(), "localhost:8080");
+        assert_eq!(request.headers.get("x-prefix").unwrap(), "/api");
+        assert_eq!(
+            request.headers.get_all("x-tag").iter().collect::<Vec<_>>(),
+            ["remote", "local"],
+        );
+        assert!(request.headers.get("x-envoy-original-path").is_none());
+    }
+
+    #[rstest]
+    #[case::replace("example.com", "a=b; Path=/; Domain=example.com")]
+    #[case::strip("", "a=b; Path=/")]
+    fn rewrites_cookie_domain(#[case] domain: &str, #[case] expected: &str) {
+        let rewriter = HttpRewriter::new(&IncomingRewrite {
+            response: ResponseRewrite {
+                cookie_domain: Some(domain.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+
+        let mut headers = HeaderMap::new();
+        headers.append(
+            "set-cookie",
+            HeaderValue::from_static("a=b; Path=/; domain=localhost"),
+        );
+        headers.append("set-cookie", HeaderValue::from_static("a=b;Path=/"));
+        rewriter.rewrite_response(&mut headers);
+
+        assert_eq!(
+            headers.get_all("set-cookie").iter().collect::<Vec<_>>(),
+            [expected, expected],
+        );
+    }
+}
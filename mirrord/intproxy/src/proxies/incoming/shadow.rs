@@ -0,0 +1,510 @@
+//! Shadow mode of the [`IncomingProxy`](super::IncomingProxy), see [`IncomingShadow`].
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    ops::Not,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use hyper::{HeaderMap, Method, StatusCode, Uri, header::HeaderName};
+use mirrord_config::feature::network::incoming::shadow::IncomingShadow;
+use mirrord_protocol::{ConnectionId, Payload, tcp::InternalHttpResponse};
+use serde_json::Value;
+
+/// Response of the local application to a mirrored HTTP request, collected by the
+/// [`HttpGatewayTask`](super::http_gateway::HttpGatewayTask).
+#[derive(Debug)]
+pub struct LocalResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// [`None`] if the body exceeded [`ShadowComparator::MAX_BODY_SIZE`].
+    pub body: Option<Bytes>,
+}
+
+/// Response of the original destination to a mirrored HTTP request, captured by the agent.
+pub type RemoteResponse = InternalHttpResponse<Option<Payload>>;
+
+/// Differences between the local and the remote response to a mirrored HTTP request.
+#[derive(Debug)]
+pub struct ShadowMismatch {
+    pub method: String,
+    pub path: String,
+    pub differences: Vec<String>,
+}
+
+/// How many response pairs were compared so far, reported to the session monitor.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShadowSummary {
+    /// How many response pairs were compared.
+    pub compared: usize,
+    /// How many of the compared response pairs did not match.
+    pub mismatched: usize,
+    /// How many responses could not be compared, because the agent failed to capture them, or
+    /// one of them did not arrive in [`ShadowComparator::PENDING_TIMEOUT`].
+    pub skipped: usize,
+}
+
+/// Result of pairing the local and the remote response to a mirrored HTTP request.
+#[derive(Debug)]
+pub struct ShadowOutcome {
+    /// [`None`] if the responses match, or could not be compared.
+    pub mismatch: Option<ShadowMismatch>,
+    pub summary: ShadowSummary,
+}
+
+/// A mirrored HTTP request that waits for one of its responses.
+struct PendingShadow {
+    started_at: Instant,
+    method: String,
+    path: String,
+    local: Option<Result<LocalResponse, String>>,
+    /// Inner [`None`] means that the agent failed to capture the response.
+    remote: Option<Option<RemoteResponse>>,
+}
+
+/// Pairs the local responses to mirrored HTTP requests with the remote ones, and compares them.
+pub struct ShadowComparator {
+    /// Lowercase names of the headers excluded from the comparison.
+    ignore_headers: HashSet<String>,
+    /// Parsed [`IncomingShadow::ignore_paths`].
+    ignore_paths: Vec<Vec<String>>,
+    pending: HashMap<ConnectionId, PendingShadow>,
+    summary: ShadowSummary,
+}
+
+impl ShadowComparator {
+    /// Max size of the local response body that we keep for the comparison.
+    pub const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+    /// How long we wait for the second response to a mirrored HTTP request, before we drop the
+    /// first one.
+    const PENDING_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Max number of differences reported for a single response pair.
+    const MAX_DIFFERENCES: usize = 16;
+
+    /// Headers that are not related to the response contents.
+    const DEFAULT_IGNORE_HEADERS: [&str; 5] = [
+        "date",
+        "content-length",
+        "transfer-encoding",
+        "connection",
+        "keep-alive",
+    ];
+
+    pub fn new(config: &IncomingShadow) -> Self {
+        let ignore_headers = Self::DEFAULT_IGNORE_HEADERS
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .chain(config.ignore_headers.iter().map(|name| name.to_lowercase()))
+            .collect();
+
+        Self {
+            ignore_headers,
+            ignore_paths: config
+                .ignore_paths
+                .iter()
+                .map(|pointer| parse_pointer(pointer))
+                .collect(),
+            pending: Default::default(),
+            summary: Default::default(),
+        }
+    }
+
+    /// Starts waiting for the responses to a mirrored HTTP request.
+    ///
+    /// Drops the requests that waited longer than [`Self::PENDING_TIMEOUT`], e.g. because the
+    /// local application never responded.
+    pub fn request_started(&mut self, connection_id: ConnectionId, method: &Method, uri: &Uri) {
+        let now = Instant::now();
+        let pending_before = self.pending.len();
+        self.pending
+            .retain(|_, pending| now.duration_since(pending.started_at) < Self::PENDING_TIMEOUT);
+        self.summary.skipped += pending_before - self.pending.len();
+
+        self.pending.insert(
+            connection_id,
+            PendingShadow {
+                started_at: now,
+                method: method.to_string(),
+                path: uri.path().to_owned(),
+                local: None,
+                remote: None,
+            },
+        );
+    }
+
+    /// Stores the local response, and compares it with the remote one if it's already here.
+    pub fn local_response(
+        &mut self,
+        connection_id: ConnectionId,
+        response: Result<LocalResponse, String>,
+    ) -> Option<ShadowOutcome> {
+        self.pending.get_mut(&connection_id)?.local = Some(response);
+        self.try_compare(connection_id)
+    }
+
+    /// Stores the remote response, and compares it with the local one if it's already here.
+    pub fn remote_response(
+        &mut self,
+        connection_id: ConnectionId,
+        response: Option<RemoteResponse>,
+    ) -> Option<ShadowOutcome> {
+        self.pending.get_mut(&connection_id)?.remote = Some(response);
+        self.try_compare(connection_id)
+    }
+
+    /// Drops all pending requests, e.g. when the agent connection is refreshed.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Logs how many responses were compared and how many of them did not match.
+    pub fn log_summary(&self) {
+        let ShadowSummary {
+            compared,
+            mismatched,
+            skipped,
+        } = self.summary;
+
+        if mismatched > 0 {
+            tracing::warn!(
+                compared,
+                mismatched,
+                skipped,
+                "Shadow mode found mismatched responses",
+            );
+        } else {
+            tracing::info!(
+                compared,
+                skipped,
+                "Shadow mode found no mismatched responses",
+            );
+        }
+    }
+
+    fn try_compare(&mut self, connection_id: ConnectionId) -> Option<ShadowOutcome> {
+        let pending = self.pending.get(&connection_id)?;
+        if pending.local.is_none() || pending.remote.is_none() {
+            return None;
+        }
+
+        let PendingShadow {
+            method,
+            path,
+            started_at: _,
+            local: Some(local),
+            remote: Some(remote),
+        } = self.pending.remove(&connection_id)?
+        else {
+            return None;
+        };
+
+        let Some(remote) = remote else {
+            self.summary.skipped += 1;
+            return Some(ShadowOutcome {
+                mismatch: None,
+                summary: self.summary,
+            });
+        };
+
+        let differences = match local {
+            Ok(local) => self.compare(&local, &remote),
+            Err(error) => vec![format!("local application failed: {error}")],
+        };
+
+        self.summary.compared += 1;
+        let mismatch = differences.is_empty().not().then_some(ShadowMismatch {
+            method,
+            path,
+            differences,
+        });
+        if mismatch.is_some() {
+            self.summary.mismatched += 1;
+        }
+
+        Some(ShadowOutcome {
+            mismatch,
+            summary: self.summary,
+        })
+    }
+
+    fn compare(&self, local: &LocalResponse, remote: &RemoteResponse) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if local.status != remote.status {
+            differences.push(format!(
+                "status: local {}, remote {}",
+                local.status, remote.status
+            ));
+        }
+
+        let names = local
+            .headers
+            .keys()
+            .chain(remote.headers.keys())
+            .map(HeaderName::as_str)
+            .filter(|name| self.ignore_headers.contains(*name).not())
+            .collect::<BTreeSet<_>>();
+        for name in names {
+            let local_values = local.headers.get_all(name).iter().collect::<Vec<_>>();
+            let remote_values = remote.headers.get_all(name).iter().collect::<Vec<_>>();
+            if local_values != remote_values {
+                differences.push(format!(
+                    "header `{name}`: local {local_values:?}, remote {remote_values:?}"
+                ));
+            }
+        }
+
+        if let (Some(local_body), Some(remote_body)) = (&local.body, &remote.body) {
+            let json = serde_json::from_slice::<Value>(local_body)
+                .ok()
+                .zip(serde_json::from_slice::<Value>(remote_body).ok());
+            match json {
+                Some((local_json, remote_json)) => {
+                    self.compare_json(&mut Vec::new(), &local_json, &remote_json, &mut differences);
+                }
+                None if *local_body != remote_body.0 => {
+                    differences.push(format!(
+                        "body: local {} bytes, remote {} bytes, contents differ",
+                        local_body.len(),
+                        remote_body.len()
+                    ));
+                }
+                None => {}
+            }
+        }
+
+        differences.truncate(Self::MAX_DIFFERENCES);
+        differences
+    }
+
+    /// Compares two JSON values recursively, skipping the [`Self::ignore_paths`].
+    fn compare_json(
+        &self,
+        path: &mut Vec<String>,
+        local: &Value,
+        remote: &Value,
+        differences: &mut Vec<String>,
+    ) {
+        if differences.len() >= Self::MAX_DIFFERENCES || self.is_ignored(path) {
+            return;
+        }
+
+        match (local, remote) {
+            (Value::Object(local), Value::Object(remote)) => {
+                let mut keys = local.keys().chain(remote.keys()).collect::<Vec<_>>();
+                keys.sort();
+                keys.dedup();
+                for key in keys {
+                    path.push(key.clone());
+                    match (local.get(key), remote.get(key)) {
+                        (Some(local), Some(remote)) => {
+                            self.compare_json(path, local, remote, differences)
+                        }
+                        (Some(..), None) if self.is_ignored(path).not() => differences.push(
+                            format!("body at `{}`: missing remotely", format_pointer(path)),
+                        ),
+                        (None, Some(..)) if self.is_ignored(path).not() => differences.push(
+                            format!("body at `{}`: missing locally", format_pointer(path)),
+                        ),
+                        _ => {}
+                    }
+                    path.pop();
+                }
+            }
+            (Value::Array(local_items), Value::Array(remote_items))
+                if local_items.len() == remote_items.len() =>
+            {
+                for (index, (local, remote)) in local_items.iter().zip(remote_items).enumerate() {
+                    path.push(index.to_string());
+                    self.compare_json(path, local, remote, differences);
+                    path.pop();
+                }
+            }
+            (local, remote) if local != remote => {
+                differences.push(format!(
+                    "body at `{}`: local {local}, remote {remote}",
+                    format_pointer(path)
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    fn is_ignored(&self, path: &[String]) -> bool {
+        self.ignore_paths.iter().any(|pointer| {
+            pointer.len() == path.len()
+                && pointer
+                    .iter()
+                    .zip(path)
+                    .all(|(expected, segment)| expected == "*" || expected == segment)
+        })
+    }
+}
+
+/// Splits a JSON pointer into unescaped segments.
+fn parse_pointer(pointer: &str) -> Vec<String> {
+    pointer
+        .split('/')
+        .skip(1)
+        .map(|segment| segment.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+/// Builds a JSON pointer from the given segments.
+fn format_pointer(path: &[String]) -> String {
+    path.iter()
+        .map(|segment| format!("/{}", segment.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Not;
+
+    use hyper::{HeaderMap, Method, StatusCode, Version, header::HeaderValue};
+    use mirrord_config::feature::network::incoming::shadow::IncomingShadow;
+
+    use super::{LocalResponse, RemoteResponse, ShadowComparator};
+
+    fn local(
+        status: StatusCode,
+        headers: &[(&'static str, &'static str)],
+        body: &str,
+    ) -> LocalResponse {
+        LocalResponse {
+            status,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+                .collect(),
+            body: Some(body.to_owned().into()),
+        }
+    }
+
+    fn remote(
+        status: StatusCode,
+        headers: &[(&'static str, &'static str)],
+        body: &str,
+    ) -> RemoteResponse {
+        RemoteResponse {
+            status,
+            version: Version::HTTP_11,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+                .collect::<HeaderMap>(),
+            body: Some(body.to_owned().into_bytes().into()),
+        }
+    }
+
+    fn compare(
+        config: &IncomingShadow,
+        local: LocalResponse,
+        remote: RemoteResponse,
+    ) -> Vec<String> {
+        let mut comparator = ShadowComparator::new(config);
+        comparator.request_started(0, &Method::GET, &"/test".parse().unwrap());
+        assert!(comparator.local_response(0, Ok(local)).is_none());
+        comparator
+            .remote_response(0, Some(remote))
+            .expect("both responses are here")
+            .mismatch
+            .map(|mismatch| mismatch.differences)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn matching_responses() {
+        let differences = compare(
+            &Default::default(),
+            local(
+                StatusCode::OK,
+                &[("content-type", "application/json"), ("date", "yesterday")],
+                r#"{"a": 1, "b": [1, 2]}"#,
+            ),
+            remote(
+                StatusCode::OK,
+                &[("content-type", "application/json"), ("date", "today")],
+                r#"{"b": [1, 2], "a": 1}"#,
+            ),
+        );
+        assert!(differences.is_empty(), "{differences:?}");
+    }
+
+    #[test]
+    fn mismatched_responses() {
+        let differences = compare(
+            &Default::default(),
+            local(
+                StatusCode::OK,
+                &[("x-version", "2")],
+                r#"{"a": 1, "b": {"c": true}}"#,
+            ),
+            remote(
+                StatusCode::CREATED,
+                &[("x-version", "1")],
+                r#"{"a": 2, "b": {}, "d": null}"#,
+            ),
+        );
+        assert_eq!(
+            differences,
+            vec![
+                "status: local 200 OK, remote 201 Created".to_owned(),
+                r#"header `x-version`: local ["2"], remote ["1"]"#.to_owned(),
+                "body at `/a`: local 1, remote 2".to_owned(),
+                "body at `/b/c`: missing remotely".to_owned(),
+                "body at `/d`: missing locally".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignored_paths_and_headers() {
+        let config = IncomingShadow {
+            ignore_paths: vec!["/items/*/id".to_owned(), "/timestamp".to_owned()],
+            ignore_headers: vec!["X-Request-Id".to_owned()],
+        };
+        let differences = compare(
+            &config,
+            local(
+                StatusCode::OK,
+                &[("x-request-id", "abc")],
+                r#"{"items": [{"id": 1, "name": "x"}], "timestamp": 10}"#,
+            ),
+            remote(
+                StatusCode::OK,
+                &[("x-request-id", "def")],
+                r#"{"items": [{"id": 2, "name": "x"}], "timestamp": 20}"#,
+            ),
+        );
+        assert!(differences.is_empty(), "{differences:?}");
+    }
+
+    #[test]
+    fn stale_requests_are_dropped() {
+        let mut comparator = ShadowComparator::new(&Default::default());
+        comparator.request_started(0, &Method::GET, &"/old".parse().unwrap());
+        comparator.pending.get_mut(&0).unwrap().started_at -= ShadowComparator::PENDING_TIMEOUT;
+
+        comparator.request_started(1, &Method::GET, &"/new".parse().unwrap());
+        assert!(comparator.pending.contains_key(&0).not());
+        assert!(comparator.pending.contains_key(&1));
+        assert_eq!(comparator.summary.skipped, 1);
+    }
+
+    #[test]
+    fn non_json_bodies() {
+        let differences = compare(
+            &Default::default(),
+            local(StatusCode::OK, &[], "hello"),
+            remote(StatusCode::OK, &[], "hello there"),
+        );
+        assert_eq!(
+            differences,
+            vec!["body: local 5 bytes, remote 11 bytes, contents differ".to_owned()]
+        );
+    }
+}
@@ -1,9 +1,12 @@
-use std::collections::{HashMap, hash_map::Entry};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    ops::Not,
+};
 
 use futures::future::Either;
 use mirrord_intproxy_protocol::{
-    IncomingResponse, LayerId, ListeningOn, MessageId, PortSubscribe, PortUnsubscribe,
-    ProxyToLayerMessage,
+    IncomingResponse, LayerId, ListeningOn, MessageId, PortSubscribe, PortSubscription,
+    PortUnsubscribe, ProxyToLayerMessage,
 };
 use mirrord_protocol::{BlockedAction, ClientMessage, Port, RemoteResult, ResponseError};
 use semver::Version;
@@ -33,6 +36,9 @@ pub struct Subscription {
     active_source: Source,
     /// Whether this subscription is confirmed.
     confirmed: bool,
+    /// Whether all layers released this subscription, and it's only kept in the agent for the
+    /// next layer that subscribes to the same port.
+    released: bool,
 }
 
 impl Subscription {
@@ -49,6 +55,7 @@ impl Subscription {
                 queued_sources: Default::default(),
                 active_source: source,
                 confirmed: false,
+                released: false,
             },
             message,
         )
@@ -57,6 +64,9 @@ impl Subscription {
     /// Overwrites the active subscription [`Source`].
     /// Returns a message to be sent to the layer.
     /// Returns [`None`] if this subscription is still waiting for confirmation.
+    ///
+    /// If this subscription was released, the previous [`Source`] is dropped instead of queued,
+    /// as its listener is gone.
     fn push_source(&mut self, source: Source) -> Option<ToLayer> {
         let message = if self.confirmed {
             // Agent already confirmed this subscription.
@@ -71,7 +81,9 @@ impl Subscription {
         };
 
         let previous_source = std::mem::replace(&mut self.active_source, source);
-        self.queued_sources.push(previous_source);
+        if std::mem::take(&mut self.released).not() {
+            self.queued_sources.push(previous_source);
+        }
 
         message
     }
@@ -121,8 +133,13 @@ impl Subscription {
     }
 
     /// Removed a source from this subscription.
-    /// If this source is the last one, returns [`Err`] with a message to be sent to the agent.
-    fn remove_source(mut self, listening_on: &ListeningOn) -> Result<Self, Box<ClientMessage>> {
+    /// If this source is the last one, returns [`Err`] with a message to be sent to the agent,
+    /// unless `keep_released` is set, in which case the subscription is marked as released.
+    fn remove_source(
+        mut self,
+        listening_on: &ListeningOn,
+        keep_released: bool,
+    ) -> Result<Self, Box<ClientMessage>> {
         let queue_size = self.queued_sources.len();
         self.queued_sources
             .retain(|source| source.request.listening_on != *listening_on);
@@ -139,6 +156,10 @@ impl Subscription {
                 self.active_source = next_in_queue;
                 Ok(self)
             }
+            None if keep_released => {
+                self.released = true;
+                Ok(self)
+            }
             None => Err(Box::new(
                 self.active_source
                     .request
@@ -170,9 +191,19 @@ impl Subscription {
 pub struct SubscriptionsManager {
     remote_ports: RemoteResources<(Port, ListeningOn)>,
     subscriptions: HashMap<Port, Subscription>,
+    /// Whether to keep subscriptions in the agent after all layers release them, so that a
+    /// restarted process takes them over without missing traffic in between.
+    keep_released: bool,
 }
 
 impl SubscriptionsManager {
+    pub fn new(keep_released: bool) -> Self {
+        Self {
+            keep_released,
+            ..Default::default()
+        }
+    }
+
     /// Returns active [`PortSubscribe`] request for the given [`Port`].
     pub fn get(&self, port: Port) -> Option<&PortSubscribe> {
         self.subscriptions
@@ -219,6 +250,25 @@ impl SubscriptionsManager {
         }
     }
 
+    /// Drops a released subscription of the same port, if the new `subscription` does not match
+    /// it, so that the agent is subscribed again with the new one.
+    /// Optionally returns a message to be sent to the agent.
+    pub fn replace_released(&mut self, subscription: &PortSubscription) -> Option<ClientMessage> {
+        let port = subscription.port();
+        let existing = self.subscriptions.get(&port)?;
+        if existing.released.not() || existing.active_source.request.subscription == *subscription {
+            return None;
+        }
+
+        self.subscriptions.remove(&port).map(|released| {
+            released
+                .active_source
+                .request
+                .subscription
+                .wrap_agent_unsubscribe()
+        })
+    }
+
     /// Unregisters a subscription from this struct.
     /// Optionally returns a message to be sent to the agent.
     #[tracing::instrument(level = Level::INFO, skip(self), ret)]
@@ -236,7 +286,7 @@ impl SubscriptionsManager {
 
         let subscription = self.subscriptions.remove(&request.port)?;
 
-        match subscription.remove_source(&request.listening_on) {
+        match subscription.remove_source(&request.listening_on, self.keep_released) {
             Ok(subscription) => {
                 self.subscriptions.insert(request.port, subscription);
                 None
@@ -320,7 +370,7 @@ impl SubscriptionsManager {
             .remove_all(layer_id)
             .filter_map(|(port, listening_on)| {
                 let subscription = self.subscriptions.remove(&port)?;
-                match subscription.remove_source(&listening_on) {
+                match subscription.remove_source(&listening_on, self.keep_released) {
                     Ok(subscription) => {
                         self.subscriptions.insert(port, subscription);
                         None
@@ -344,7 +394,7 @@ impl SubscriptionsManager {
 #[cfg(test)]
 mod test {
     use mirrord_intproxy_protocol::PortSubscription;
-    use mirrord_protocol::tcp::{LayerTcp, MirrorType};
+    use mirrord_protocol::tcp::{LayerTcp, MirrorType, StealType};
 
     use super::*;
 
@@ -555,4 +605,68 @@ mod test {
             .unwrap();
         assert!(responses.is_empty(), "{responses:?}");
     }
+
+    #[test]
+    fn released_subscription_taken_over() {
+        let old_listener: ListeningOn = "127.0.0.1:1111"
+            .parse::<std::net::SocketAddr>()
+            .unwrap()
+            .into();
+        let new_listener: ListeningOn = "127.0.0.1:2222"
+            .parse::<std::net::SocketAddr>()
+            .unwrap()
+            .into();
+        let subscription = PortSubscription::Mirror(MirrorType::All(80));
+
+        let mut manager = SubscriptionsManager::new(true);
+        manager.layer_subscribed(
+            LayerId(0),
+            0,
+            PortSubscribe {
+                listening_on: old_listener,
+                subscription: subscription.clone(),
+            },
+            None,
+        );
+        manager.agent_responded(Ok(80)).unwrap();
+
+        let messages = manager.layer_closed(LayerId(0));
+        assert!(messages.is_empty(), "{messages:?}");
+        assert!(manager.get(80).is_some());
+
+        assert!(manager.replace_released(&subscription).is_none());
+        let response = manager.layer_subscribed(
+            LayerId(1),
+            0,
+            PortSubscribe {
+                listening_on: new_listener.clone(),
+                subscription,
+            },
+            None,
+        );
+        assert!(
+            matches!(
+                response,
+                Some(Either::Left(ProxyMessage::ToLayer(ToLayer {
+                    layer_id: LayerId(1),
+                    message: ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Ok(()))),
+                    message_id: 0,
+                })))
+            ),
+            "{response:?}"
+        );
+        assert_eq!(manager.get(80).unwrap().listening_on, new_listener);
+
+        let message = manager.layer_closed(LayerId(1));
+        assert!(message.is_empty(), "{message:?}");
+        let message = manager.replace_released(&PortSubscription::Steal(StealType::All(80)));
+        assert!(
+            matches!(
+                message,
+                Some(ClientMessage::Tcp(LayerTcp::PortUnsubscribe(80)))
+            ),
+            "{message:?}"
+        );
+        assert!(manager.get(80).is_none());
+    }
 }
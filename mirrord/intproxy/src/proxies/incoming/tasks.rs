@@ -4,7 +4,7 @@ use hyper::{Version, upgrade::OnUpgrade};
 use mirrord_protocol::{ConnectionId, Port, RequestId};
 use thiserror::Error;
 
-use super::tls::LocalTlsSetupError;
+use super::{shadow::LocalResponse, tls::LocalTlsSetupError};
 
 /// Messages produced by the [`BackgroundTask`](crate::background_tasks::BackgroundTask)s used in
 /// the [`IncomingProxy`](super::IncomingProxy).
@@ -27,6 +27,11 @@ impl fmt::Debug for InProxyTaskMessage {
 #[derive(Debug)]
 pub enum HttpOut {
     Upgraded(OnUpgrade),
+    /// Response of the local application to a mirrored HTTP request, or the reason why we failed
+    /// to get it.
+    ///
+    /// Produced only in shadow mode.
+    ShadowResponse(Result<LocalResponse, String>),
 }
 
 impl From<HttpOut> for InProxyTaskMessage {
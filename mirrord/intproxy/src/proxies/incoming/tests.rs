@@ -65,6 +65,10 @@ async fn http_request_terminates_on_remote_close(#[case] steal_type: StealType)
     let proxy = IncomingProxy::new(
         Duration::from_secs(3),
         Default::default(),
+        None,
+        None,
+        None,
+        false,
         crate::session_monitor::MonitorTx::disabled(),
     );
     let mut background_tasks: BackgroundTasks<(), ProxyMessage, IncomingProxyError> =
@@ -677,7 +677,10 @@ impl OutgoingProxy {
             ConnectionRefresh::End(tx_handle) => {
                 message_bus.set_agent_tx(tx_handle);
             }
-            ConnectionRefresh::Request => {}
+            // After the session is resumed, the agent still has our state.
+            ConnectionRefresh::Request
+            | ConnectionRefresh::Resuming
+            | ConnectionRefresh::Resumed => {}
         }
     }
 
@@ -7,7 +7,11 @@ use mirrord_intproxy_protocol::{LayerId, MessageId, ProxyToLayerMessage};
 use mirrord_protocol::{
     ClientMessage, DaemonMessage, DnsLookupError, GetEnvVarsRequest, RemoteResult,
     ResolveErrorKindInternal, ResponseError,
-    dns::{ADDRINFO_V2_VERSION, AddressFamily, GetAddrInfoRequestV2, GetAddrInfoResponse},
+    dns::{
+        ADDRINFO_V2_VERSION, AddressFamily, DNS_QUERY_VERSION, DnsQueryRequest, DnsQueryResponse,
+        GetAddrInfoRequestV2, GetAddrInfoResponse, REVERSE_DNS_VERSION, ReverseDnsLookupRequest,
+        ReverseDnsLookupResponse,
+    },
 };
 use semver::Version;
 use thiserror::Error;
@@ -27,6 +31,10 @@ pub enum SimpleProxyMessage {
     AddrInfoRes(GetAddrInfoResponse),
     GetEnvReq(MessageId, LayerId, GetEnvVarsRequest),
     GetEnvRes(RemoteResult<HashMap<String, String>>),
+    DnsQueryReq(MessageId, LayerId, DnsQueryRequest),
+    DnsQueryRes(DnsQueryResponse),
+    ReverseDnsReq(MessageId, LayerId, ReverseDnsLookupRequest),
+    ReverseDnsRes(RemoteResult<ReverseDnsLookupResponse>),
     /// Protocol version was negotiated with the agent.
     ProtocolVersion(Version),
     ConnectionRefresh(ConnectionRefresh),
@@ -49,6 +57,8 @@ pub enum SimpleProxyError {
 pub enum AgentLostSimpleResponseKind {
     AddrInfo,
     GetEnv,
+    DnsQuery,
+    ReverseDns,
 }
 
 /// Lightweight (no allocations) [`ProxyMessage`] to be returned when connection with the
@@ -63,6 +73,18 @@ impl AgentLostSimpleResponse {
     pub fn get_env(layer_id: LayerId, message_id: MessageId) -> Self {
         AgentLostSimpleResponse(AgentLostSimpleResponseKind::GetEnv, layer_id, message_id)
     }
+
+    pub fn dns_query(layer_id: LayerId, message_id: MessageId) -> Self {
+        AgentLostSimpleResponse(AgentLostSimpleResponseKind::DnsQuery, layer_id, message_id)
+    }
+
+    pub fn reverse_dns(layer_id: LayerId, message_id: MessageId) -> Self {
+        AgentLostSimpleResponse(
+            AgentLostSimpleResponseKind::ReverseDns,
+            layer_id,
+            message_id,
+        )
+    }
 }
 
 impl From<AgentLostSimpleResponse> for ToLayer {
@@ -75,6 +97,12 @@ impl From<AgentLostSimpleResponse> for ToLayer {
                 ProxyToLayerMessage::GetAddrInfo(GetAddrInfoResponse(Err(error)))
             }
             AgentLostSimpleResponseKind::GetEnv => ProxyToLayerMessage::GetEnv(Err(error)),
+            AgentLostSimpleResponseKind::DnsQuery => {
+                ProxyToLayerMessage::DnsQuery(DnsQueryResponse(Err(error)))
+            }
+            AgentLostSimpleResponseKind::ReverseDns => {
+                ProxyToLayerMessage::ReverseDnsLookup(Err(error))
+            }
         };
 
         ToLayer {
@@ -93,6 +121,10 @@ pub struct SimpleProxy {
     addr_info_reqs: RequestQueue,
     /// For [`GetEnvVarsRequest`]s.
     get_env_reqs: RequestQueue,
+    /// For [`DnsQueryRequest`]s.
+    dns_query_reqs: RequestQueue,
+    /// For [`ReverseDnsLookupRequest`]s.
+    reverse_dns_reqs: RequestQueue,
     /// [`mirrord_protocol`] version negotiated with the agent.
     /// Determines whether we can use `GetAddrInfoRequestV2`.
     protocol_version: Option<Version>,
@@ -107,6 +139,20 @@ impl SimpleProxy {
         self.protocol_version.replace(version);
     }
 
+    /// Returns whether [`mirrord_protocol`] version allows for a [`DnsQueryRequest`].
+    fn dns_query_supported(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| DNS_QUERY_VERSION.matches(version))
+    }
+
+    /// Returns whether [`mirrord_protocol`] version allows for a [`ReverseDnsLookupRequest`].
+    fn reverse_dns_supported(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| REVERSE_DNS_VERSION.matches(version))
+    }
+
     /// Returns whether [`mirrord_protocol`] version allows for a V2 addrinfo request.
     fn addr_info_v2(&self) -> bool {
         self.protocol_version
@@ -146,12 +192,39 @@ impl SimpleProxy {
                         .await;
                 }
 
+                tracing::debug!(
+                    num_responses = self.dns_query_reqs.len(),
+                    "Flushing error responses to DnsQueryRequests"
+                );
+                while let Some((message_id, layer_id)) = self.dns_query_reqs.pop_front() {
+                    message_bus
+                        .send(ToLayer::from(AgentLostSimpleResponse::dns_query(
+                            layer_id, message_id,
+                        )))
+                        .await;
+                }
+
+                tracing::debug!(
+                    num_responses = self.reverse_dns_reqs.len(),
+                    "Flushing error responses to ReverseDnsLookupRequests"
+                );
+                while let Some((message_id, layer_id)) = self.reverse_dns_reqs.pop_front() {
+                    message_bus
+                        .send(ToLayer::from(AgentLostSimpleResponse::reverse_dns(
+                            layer_id, message_id,
+                        )))
+                        .await;
+                }
+
                 // Reset protocol version since we'll need another negotiation
                 // round for the new connection.
                 self.protocol_version = None;
             }
             ConnectionRefresh::End(tx_handle) => message_bus.set_agent_tx(tx_handle),
-            ConnectionRefresh::Request => {}
+            // After the session is resumed, the agent still has our state.
+            ConnectionRefresh::Request
+            | ConnectionRefresh::Resuming
+            | ConnectionRefresh::Resumed => {}
         }
     }
 }
@@ -229,6 +302,68 @@ impl BackgroundTask for SimpleProxy {
                         })
                         .await
                 }
+                SimpleProxyMessage::DnsQueryReq(message_id, layer_id, req) => {
+                    if self.dns_query_supported() {
+                        self.dns_query_reqs.push_back(message_id, layer_id);
+                        message_bus.send_agent(ClientMessage::DnsQuery(req)).await;
+                    } else {
+                        message_bus
+                            .send(ToLayer {
+                                message_id,
+                                message: ProxyToLayerMessage::DnsQuery(DnsQueryResponse(Err(
+                                    ResponseError::NotImplemented,
+                                ))),
+                                layer_id,
+                            })
+                            .await;
+                    }
+                }
+                SimpleProxyMessage::DnsQueryRes(res) => {
+                    let (message_id, layer_id) =
+                        self.dns_query_reqs.pop_front().ok_or_else(|| {
+                            UnexpectedAgentMessage(DaemonMessage::DnsQuery(res.clone()).into())
+                        })?;
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            message: ProxyToLayerMessage::DnsQuery(res),
+                            layer_id,
+                        })
+                        .await;
+                }
+                SimpleProxyMessage::ReverseDnsReq(message_id, layer_id, req) => {
+                    if self.reverse_dns_supported() {
+                        self.reverse_dns_reqs.push_back(message_id, layer_id);
+                        message_bus
+                            .send_agent(ClientMessage::ReverseDnsLookup(req))
+                            .await;
+                    } else {
+                        message_bus
+                            .send(ToLayer {
+                                message_id,
+                                message: ProxyToLayerMessage::ReverseDnsLookup(Err(
+                                    ResponseError::NotImplemented,
+                                )),
+                                layer_id,
+                            })
+                            .await;
+                    }
+                }
+                SimpleProxyMessage::ReverseDnsRes(res) => {
+                    let (message_id, layer_id) =
+                        self.reverse_dns_reqs.pop_front().ok_or_else(|| {
+                            UnexpectedAgentMessage(
+                                DaemonMessage::ReverseDnsLookup(res.clone()).into(),
+                            )
+                        })?;
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            message: ProxyToLayerMessage::ReverseDnsLookup(res),
+                            layer_id,
+                        })
+                        .await;
+                }
                 SimpleProxyMessage::ProtocolVersion(version) => self.set_protocol_version(version),
                 SimpleProxyMessage::ConnectionRefresh(new_agent_tx) => {
                     self.handle_connection_refresh(message_bus, new_agent_tx)
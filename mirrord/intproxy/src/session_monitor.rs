@@ -53,6 +53,16 @@ pub enum MonitorEvent {
     LayerDisconnected {
         pid: u32,
     },
+    ShadowMismatch {
+        method: String,
+        path: String,
+        differences: Vec<String>,
+    },
+    ShadowSummary {
+        compared: usize,
+        mismatched: usize,
+        skipped: usize,
+    },
 }
 
 /// Wrapper around an optional broadcast sender for session monitor events.
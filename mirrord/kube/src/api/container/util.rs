@@ -57,6 +57,26 @@ pub(super) fn agent_env(agent: &AgentConfig, params: &ContainerParams) -> Vec<En
         env.push(envs::NFTABLES.as_k8s_spec(&nftables));
     }
 
+    if agent.nftables_netlink {
+        env.push(envs::NFTABLES_NETLINK.as_k8s_spec(&true));
+    }
+
+    if let Some(max_open_files) = agent.quotas.max_open_files {
+        env.push(envs::QUOTA_MAX_OPEN_FILES.as_k8s_spec(&max_open_files));
+    }
+
+    if let Some(max_connections) = agent.quotas.max_outgoing_connections {
+        env.push(envs::QUOTA_MAX_OUTGOING_CONNECTIONS.as_k8s_spec(&max_connections));
+    }
+
+    if let Some(dns_requests) = agent.quotas.dns_requests_per_second {
+        env.push(envs::QUOTA_DNS_REQUESTS_PER_SECOND.as_k8s_spec(&dns_requests));
+    }
+
+    if let Some(bandwidth) = agent.quotas.outgoing_bandwidth {
+        env.push(envs::QUOTA_OUTGOING_BANDWIDTH.as_k8s_spec(&bandwidth));
+    }
+
     if let Some(attempts) = agent.dns.attempts {
         env.push(envs::DNS_ATTEMPTS.as_k8s_spec(&attempts));
     }
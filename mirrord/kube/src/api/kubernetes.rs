@@ -231,6 +231,22 @@ impl KubernetesAPI {
             .create_agent_params(target_config, container_config)
             .await?;
 
+        self.create_agent_with_params(progress, params, runtime_data, network_config)
+            .await
+    }
+
+    /// Creates an agent with params prepared in [`KubernetesAPI::create_agent_params`].
+    #[tracing::instrument(level = "trace", skip(self, progress))]
+    pub async fn create_agent_with_params<P>(
+        &self,
+        progress: &mut P,
+        params: ContainerParams,
+        runtime_data: Option<RuntimeData>,
+        network_config: Option<&mut NetworkConfig>,
+    ) -> Result<AgentKubernetesConnectInfo, KubeApiError>
+    where
+        P: Progress,
+    {
         if let Some(RuntimeData {
             guessed_container,
             container_name,
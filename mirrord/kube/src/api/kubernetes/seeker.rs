@@ -5,7 +5,7 @@ use itertools::join;
 use k8s_openapi::{
     ClusterResourceScope, Metadata, NamespaceResourceScope,
     api::{
-        apps::v1::{Deployment, ReplicaSet, StatefulSet},
+        apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
         batch::v1::{CronJob, Job},
         core::v1::{Pod, Service},
     },
@@ -23,22 +23,26 @@ pub struct KubeResourceSeeker<'a> {
     pub client: &'a kube::Client,
     pub namespace: &'a str,
     pub copy_target: bool,
+    /// Optional label selector (e.g. `app=api,tier=web`) applied to all listed resources.
+    pub label_selector: Option<&'a str>,
 }
 
 impl KubeResourceSeeker<'_> {
     /// Returns all resource types that don't require the operator to operate ie. [`Pod`],
-    /// [`Deployment`] and [`Rollout`]
+    /// [`Deployment`], [`Rollout`] and [`DaemonSet`]
     pub async fn all_open_source(&self) -> Result<Vec<String>> {
-        let (pods, deployments, rollouts) = tokio::try_join!(
+        let (pods, deployments, rollouts, daemonsets) = tokio::try_join!(
             self.pods(),
             self.deployments(),
-            self.simple_list_resource::<Rollout>("rollout")
+            self.simple_list_resource::<Rollout>("rollout"),
+            self.simple_list_resource::<DaemonSet>("daemonset"),
         )?;
 
         Ok(pods
             .into_iter()
             .chain(deployments)
             .chain(rollouts)
+            .chain(daemonsets)
             .collect())
     }
 
@@ -50,9 +54,20 @@ impl KubeResourceSeeker<'_> {
     /// 5. [`Job`]s
     /// 6. [`Service`]s
     /// 7. [`ReplicaSet`]s
-    /// 8. [`Pod`]s
+    /// 8. [`DaemonSet`]s
+    /// 9. [`Pod`]s
     pub async fn all(&self) -> Result<Vec<String>> {
-        let (pods, deployments, rollouts, jobs, cronjobs, statefulsets, services, replicasets) = tokio::try_join!(
+        let (
+            pods,
+            deployments,
+            rollouts,
+            jobs,
+            cronjobs,
+            statefulsets,
+            services,
+            replicasets,
+            daemonsets,
+        ) = tokio::try_join!(
             self.pods(),
             self.simple_list_resource::<Deployment>("deployment"),
             self.simple_list_resource::<Rollout>("rollout"),
@@ -61,6 +76,7 @@ impl KubeResourceSeeker<'_> {
             self.simple_list_resource::<StatefulSet>("statefulset"),
             self.simple_list_resource::<Service>("service"),
             self.simple_list_resource::<ReplicaSet>("replicaset"),
+            self.simple_list_resource::<DaemonSet>("daemonset"),
         )?;
 
         Ok(deployments
@@ -71,6 +87,7 @@ impl KubeResourceSeeker<'_> {
             .chain(jobs)
             .chain(services)
             .chain(replicasets)
+            .chain(daemonsets)
             .chain(pods)
             .collect())
     }
@@ -120,6 +137,7 @@ impl KubeResourceSeeker<'_> {
             TargetType::ReplicaSet if operator_active => {
                 self.simple_list_resource::<ReplicaSet>("replicaset").await
             }
+            TargetType::DaemonSet => self.simple_list_resource::<DaemonSet>("daemonset").await,
             TargetType::Targetless => Err(KubeApiError::InvalidTargetType(resource_type)),
             resource_type if !operator_active => {
                 Err(KubeApiError::TargetTypeRequiresOperator(resource_type))
@@ -160,9 +178,9 @@ impl KubeResourceSeeker<'_> {
 
         // `copy_target` can be used on dead resources.
         if self.copy_target {
-            self.list_all_namespaced(None, None)
+            self.list_all_namespaced(None, self.label_selector)
         } else {
-            self.list_all_namespaced(Some("status.phase=Running"), None)
+            self.list_all_namespaced(Some("status.phase=Running"), self.label_selector)
         }
         .try_filter(|pod| std::future::ready(self.copy_target || check_pod_status(pod)))
         .try_filter_map(|pod| std::future::ready(Ok(create_pod_container_map(pod))))
@@ -194,7 +212,7 @@ impl KubeResourceSeeker<'_> {
                 .unwrap_or(false)
         }
 
-        self.list_all_namespaced::<Deployment>(None, None)
+        self.list_all_namespaced::<Deployment>(None, self.label_selector)
             .filter(|response| std::future::ready(response.is_ok()))
             .try_filter(|deployment| {
                 std::future::ready(self.copy_target || check_deployment_replicas(deployment))
@@ -220,7 +238,7 @@ impl KubeResourceSeeker<'_> {
             + Metadata
             + Send,
     {
-        self.list_all_namespaced::<R>(None, None)
+        self.list_all_namespaced::<R>(None, self.label_selector)
             .filter(|response| std::future::ready(response.is_ok()))
             .try_filter_map(|rollout| {
                 std::future::ready(Ok(rollout
@@ -15,7 +15,7 @@ use k8s_openapi::{
 };
 use kube::{Api, Client, Resource, api::ListParams};
 use mirrord_agent_env::mesh::MeshVendor;
-use mirrord_config::target::Target;
+use mirrord_config::target::{Target, daemon_set::DaemonSetTarget};
 use serde::de::DeserializeOwned;
 use thiserror::Error;
 use tracing::Level;
@@ -34,6 +34,7 @@ use crate::{
 };
 
 pub mod cron_job;
+pub mod daemon_set;
 pub mod deployment;
 pub mod job;
 pub mod pod;
@@ -469,6 +470,14 @@ pub trait RuntimeDataFromLabels {
     fn name(&self) -> Cow<'_, str>;
 
     fn container(&self) -> Option<&str>;
+
+    /// Name of the node, on which the target pod must run.
+    ///
+    /// Only [`DaemonSetTarget`](mirrord_config::target::daemon_set::DaemonSetTarget)s can be
+    /// narrowed down to a node.
+    fn node(&self) -> Option<&str> {
+        None
+    }
 }
 
 impl<T> RuntimeDataProvider for T
@@ -479,7 +488,7 @@ where
         let api: Api<<Self as RuntimeDataFromLabels>::Resource> =
             get_k8s_resource_api(client, namespace);
         let resource = api.get(&self.name()).await?;
-        let pods = Self::get_pods(&resource, client).await?;
+        let mut pods = Self::get_pods(&resource, client).await?;
 
         if pods.is_empty() {
             return Err(KubeApiError::invalid_state(
@@ -488,6 +497,19 @@ where
             ));
         }
 
+        if let Some(node) = self.node() {
+            pods.retain(|pod| {
+                pod.spec.as_ref().and_then(|spec| spec.node_name.as_deref()) == Some(node)
+            });
+
+            if pods.is_empty() {
+                return Err(KubeApiError::invalid_state(
+                    &resource,
+                    format_args!("no pod matching the labels runs on node `{node}`"),
+                ));
+            }
+        }
+
         pods.iter()
             .filter_map(|pod| RuntimeData::from_pod(pod, self.container()).ok())
             .next()
@@ -511,6 +533,7 @@ impl RuntimeDataProvider for Target {
             Target::StatefulSet(target) => target.runtime_data(client, namespace).await,
             Target::Service(target) => target.runtime_data(client, namespace).await,
             Target::ReplicaSet(target) => target.runtime_data(client, namespace).await,
+            Target::DaemonSet(target) => target.runtime_data(client, namespace).await,
             Target::Targetless => Err(KubeApiError::MissingRuntimeData),
         }
     }
@@ -527,6 +550,15 @@ impl RuntimeDataProvider for ResolvedTarget<true> {
             Self::StatefulSet(target) => target.runtime_data(client, namespace).await,
             Self::Service(target) => target.runtime_data(client, namespace).await,
             Self::ReplicaSet(target) => target.runtime_data(client, namespace).await,
+            Self::DaemonSet(target, node) => {
+                DaemonSetTarget {
+                    daemon_set: target.name().into_owned(),
+                    container: target.container.clone(),
+                    node: node.clone(),
+                }
+                .runtime_data(client, namespace)
+                .await
+            }
             Self::Targetless(_) => Err(KubeApiError::MissingRuntimeData),
         }
     }
@@ -92,6 +92,10 @@ pub enum KubeApiError {
     /// Apiserver returned non-u16 version numbers
     #[error("apiserver returned invalid {field} version number: {data}")]
     InvalidVersionNumber { field: &'static str, data: String },
+
+    /// No running and ready pod matches the `target.selector` labels.
+    #[error("no running and ready pod matches the target selector `{0}`")]
+    NoPodMatchesSelector(String),
 }
 
 impl KubeApiError {
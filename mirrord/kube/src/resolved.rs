@@ -1,14 +1,17 @@
 use std::{borrow::Cow, collections::BTreeMap};
 
 use k8s_openapi::api::{
-    apps::v1::{Deployment, ReplicaSet, StatefulSet},
+    apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
     batch::v1::{CronJob, Job},
     core::v1::{
         EnvFromSource, EnvVar, PersistentVolumeClaim, Pod, PodSpec, PodTemplateSpec, Service,
     },
 };
-use kube::{Client, Resource, ResourceExt};
-use mirrord_config::target::Target;
+use kube::{Client, Resource, ResourceExt, api::ListParams};
+use mirrord_config::target::{
+    Target,
+    selector::{SelectorStrategy, TargetSelector},
+};
 use tracing::Level;
 
 use super::{
@@ -18,11 +21,13 @@ use super::{
 use crate::api::{kubernetes::rollout::Rollout, runtime::RuntimeDataFromLabels};
 
 pub mod cron_job;
+pub mod daemon_set;
 pub mod deployment;
 pub mod job;
 pub mod pod;
 pub mod replica_set;
 pub mod rollout;
+mod selector;
 pub mod service;
 pub mod stateful_set;
 
@@ -45,6 +50,11 @@ pub enum ResolvedTarget<const CHECKED: bool> {
     StatefulSet(ResolvedResource<StatefulSet>),
     Service(ResolvedResource<Service>),
     ReplicaSet(ResolvedResource<ReplicaSet>),
+    DaemonSet(
+        ResolvedResource<DaemonSet>,
+        /// Name of the node, on which the target pod runs.
+        Option<String>,
+    ),
 
     /// [`Pod`] is a special case, in that it does not implement [`RuntimeDataFromLabels`],
     /// and instead we implement a `runtime_data` method directly in its
@@ -103,6 +113,9 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::ReplicaSet(ResolvedResource { resource, .. }) => {
                 resource.metadata.name.as_deref()
             }
+            ResolvedTarget::DaemonSet(ResolvedResource { resource, .. }, _) => {
+                resource.metadata.name.as_deref()
+            }
             ResolvedTarget::Targetless(_) => None,
         }
     }
@@ -117,6 +130,7 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::StatefulSet(ResolvedResource { resource, .. }) => resource.name_any(),
             ResolvedTarget::Service(ResolvedResource { resource, .. }) => resource.name_any(),
             ResolvedTarget::ReplicaSet(ResolvedResource { resource, .. }) => resource.name_any(),
+            ResolvedTarget::DaemonSet(ResolvedResource { resource, .. }, _) => resource.name_any(),
             ResolvedTarget::Targetless(..) => "targetless".to_owned(),
         }
     }
@@ -147,6 +161,9 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::ReplicaSet(ResolvedResource { resource, .. }) => {
                 resource.metadata.namespace.as_deref()
             }
+            ResolvedTarget::DaemonSet(ResolvedResource { resource, .. }, _) => {
+                resource.metadata.namespace.as_deref()
+            }
             ResolvedTarget::Targetless(namespace) => Some(namespace),
         }
     }
@@ -168,6 +185,9 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::ReplicaSet(ResolvedResource { resource, .. }) => {
                 resource.metadata.labels
             }
+            ResolvedTarget::DaemonSet(ResolvedResource { resource, .. }, _) => {
+                resource.metadata.labels
+            }
             ResolvedTarget::Targetless(_) => None,
         }
     }
@@ -182,6 +202,7 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::StatefulSet(_) => "statefulset",
             ResolvedTarget::Service(_) => "service",
             ResolvedTarget::ReplicaSet(_) => "replicaset",
+            ResolvedTarget::DaemonSet(..) => "daemonset",
             ResolvedTarget::Targetless(_) => "targetless",
         }
     }
@@ -196,12 +217,23 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             | ResolvedTarget::StatefulSet(ResolvedResource { container, .. })
             | ResolvedTarget::Service(ResolvedResource { container, .. })
             | ResolvedTarget::Pod(ResolvedResource { container, .. })
-            | ResolvedTarget::ReplicaSet(ResolvedResource { container, .. }) => {
+            | ResolvedTarget::ReplicaSet(ResolvedResource { container, .. })
+            | ResolvedTarget::DaemonSet(ResolvedResource { container, .. }, _) => {
                 container.as_deref()
             }
             ResolvedTarget::Targetless(..) => None,
         }
     }
+
+    /// Name of the node, on which the target pod runs.
+    ///
+    /// Only [`ResolvedTarget::DaemonSet`] can be narrowed down to a node.
+    pub fn node(&self) -> Option<&str> {
+        match self {
+            ResolvedTarget::DaemonSet(_, node) => node.as_deref(),
+            _ => None,
+        }
+    }
 }
 
 impl ResolvedTarget<false> {
@@ -294,6 +326,19 @@ impl ResolvedTarget<false> {
                         container: target.container.clone(),
                     })
                 }),
+            Target::DaemonSet(target) => get_k8s_resource_api::<DaemonSet>(client, namespace)
+                .get(&target.daemon_set)
+                .await
+                .map(Box::new)
+                .map(|resource| {
+                    ResolvedTarget::DaemonSet(
+                        ResolvedResource {
+                            resource,
+                            container: target.container.clone(),
+                        },
+                        target.node.clone(),
+                    )
+                }),
             Target::Targetless => Ok(ResolvedTarget::Targetless(
                 namespace.unwrap_or("default").to_owned(),
             )),
@@ -302,6 +347,49 @@ impl ResolvedTarget<false> {
         Ok(target)
     }
 
+    /// Picks a target pod from the pods that match the given [`TargetSelector`].
+    ///
+    /// Only running and ready pods are considered. When the [`SelectorStrategy::LeastLoaded`]
+    /// strategy is used and the metrics API is not available, falls back to the newest pod.
+    #[tracing::instrument(level = Level::DEBUG, skip(client), ret, err)]
+    pub async fn from_selector(
+        client: &Client,
+        selector: &TargetSelector,
+        namespace: Option<&str>,
+    ) -> Result<Self, KubeApiError> {
+        let label_selector = selector.label_selector();
+        let pods = get_k8s_resource_api::<Pod>(client, namespace)
+            .list(&ListParams::default().labels(&label_selector))
+            .await?
+            .items
+            .into_iter()
+            .filter(selector::is_ready)
+            .collect::<Vec<_>>();
+
+        let cpu_usage = match selector.strategy {
+            SelectorStrategy::LeastLoaded if pods.len() > 1 => {
+                selector::fetch_cpu_usage(client, selector, namespace)
+                    .await
+                    .inspect_err(|error| {
+                        tracing::warn!(
+                            %error,
+                            "Failed to fetch pod metrics, falling back to the newest pod"
+                        )
+                    })
+                    .ok()
+            }
+            _ => None,
+        };
+
+        let pod = selector::select_pod(pods, selector.strategy, cpu_usage.as_ref())
+            .ok_or(KubeApiError::NoPodMatchesSelector(label_selector))?;
+
+        Ok(ResolvedTarget::Pod(ResolvedResource {
+            resource: Box::new(pod),
+            container: selector.container.clone(),
+        }))
+    }
+
     /// Checks if the target can be used via the mirrord Operator.
     ///
     /// This is implemented in the CLI only to improve the UX (skip roundtrip to the operator).
@@ -316,6 +404,8 @@ impl ResolvedTarget<false> {
     /// 4. [`ResolvedTarget::Targetless`] - no check (not applicable)
     /// 5. [`ResolvedTarget::Service`] - the target container, if specified, is found in at least
     ///    one of the pods
+    /// 6. [`ResolvedTarget::DaemonSet`] - the target container, if specified, is found in the spec,
+    ///    and one of the pods runs on the target node, if specified
     #[tracing::instrument(level = Level::DEBUG, skip(client), ret, err)]
     pub async fn assert_valid_mirrord_target(
         self,
@@ -472,6 +562,50 @@ impl ResolvedTarget<false> {
                 }))
             }
 
+            ResolvedTarget::DaemonSet(
+                ResolvedResource {
+                    resource,
+                    container,
+                },
+                node,
+            ) => {
+                if let Some(container) = &container {
+                    // verify that the container exists
+                    resource
+                        .spec
+                        .as_ref()
+                        .and_then(|spec| spec.template.spec.as_ref())
+                        .ok_or_else(|| KubeApiError::missing_field(resource.as_ref(), ".spec.template.spec"))?
+                        .containers
+                        .iter()
+                        .find(|c| c.name == *container)
+                        .ok_or_else(|| KubeApiError::invalid_state(resource.as_ref(), format_args!("specified pod template does not contain target container `{container}`")))?;
+                }
+
+                if let Some(node) = &node {
+                    // verify that the daemon set runs a pod on the node
+                    let runs_on_node = ResolvedResource::<DaemonSet>::get_pods(&resource, client)
+                        .await?
+                        .iter()
+                        .filter_map(|pod| pod.spec.as_ref()?.node_name.as_deref())
+                        .any(|pod_node| pod_node == node);
+                    if !runs_on_node {
+                        return Err(KubeApiError::invalid_state(
+                            resource.as_ref(),
+                            format_args!("no pod matching the labels runs on node `{node}`"),
+                        ));
+                    }
+                }
+
+                Ok(ResolvedTarget::DaemonSet(
+                    ResolvedResource {
+                        resource,
+                        container,
+                    },
+                    node,
+                ))
+            }
+
             ResolvedTarget::Targetless(namespace) => {
                 // no check needed here
                 Ok(ResolvedTarget::Targetless(namespace))
@@ -582,6 +716,9 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
                 .as_ref()?
                 .spec
                 .as_ref(),
+            ResolvedTarget::DaemonSet(inner, _) => {
+                inner.resource.spec.as_ref()?.template.spec.as_ref()
+            }
             ResolvedTarget::Pod(inner) => inner.resource.spec.as_ref(),
             ResolvedTarget::Targetless(..) => None,
         }
@@ -617,6 +754,9 @@ impl<const CHECKED: bool> ResolvedTarget<CHECKED> {
             ResolvedTarget::ReplicaSet(inner) => Some(Cow::Borrowed(
                 inner.resource.spec.as_ref()?.template.as_ref()?,
             )),
+            ResolvedTarget::DaemonSet(inner, _) => {
+                Some(Cow::Borrowed(&inner.resource.spec.as_ref()?.template))
+            }
             ResolvedTarget::Pod(inner) => Some(Cow::Owned(PodTemplateSpec {
                 metadata: Some(inner.resource.metadata.clone()),
                 spec: inner.resource.spec.clone(),
@@ -0,0 +1,172 @@
+//! Picking the target pod for a [`TargetSelector`].
+
+use std::collections::HashMap;
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::{
+    Api, Client,
+    api::{ApiResource, DynamicObject, GroupVersionKind, ListParams},
+};
+use mirrord_config::target::selector::{SelectorStrategy, TargetSelector};
+use rand::seq::IndexedRandom;
+
+use crate::error::Result;
+
+/// Whether the pod is running and passes its readiness checks.
+pub(super) fn is_ready(pod: &Pod) -> bool {
+    pod.status.as_ref().is_some_and(|status| {
+        status.phase.as_deref() == Some("Running")
+            && status.conditions.as_ref().is_some_and(|conditions| {
+                conditions
+                    .iter()
+                    .any(|condition| condition.type_ == "Ready" && condition.status == "True")
+            })
+    })
+}
+
+/// Fetches the CPU usage (in nanocores) of the pods matching the [`TargetSelector`], from the
+/// Kubernetes metrics API.
+pub(super) async fn fetch_cpu_usage(
+    client: &Client,
+    selector: &TargetSelector,
+    namespace: Option<&str>,
+) -> Result<HashMap<String, u64>> {
+    let resource = ApiResource::from_gvk_with_plural(
+        &GroupVersionKind::gvk("metrics.k8s.io", "v1beta1", "PodMetrics"),
+        "pods",
+    );
+    let api: Api<DynamicObject> = match namespace {
+        Some(namespace) => Api::namespaced_with(client.clone(), namespace, &resource),
+        None => Api::default_namespaced_with(client.clone(), &resource),
+    };
+
+    let metrics = api
+        .list(&ListParams::default().labels(&selector.label_selector()))
+        .await?;
+
+    let usage = metrics
+        .items
+        .into_iter()
+        .filter_map(|metrics| {
+            let name = metrics.metadata.name?;
+            let usage = metrics
+                .data
+                .get("containers")?
+                .as_array()?
+                .iter()
+                .filter_map(|container| container.pointer("/usage/cpu")?.as_str())
+                .filter_map(parse_cpu_nanocores)
+                .sum();
+
+            Some((name, usage))
+        })
+        .collect();
+
+    Ok(usage)
+}
+
+/// Parses a Kubernetes CPU quantity (e.g. `250m`, `1`, `123456n`) into nanocores.
+fn parse_cpu_nanocores(quantity: &str) -> Option<u64> {
+    let (number, multiplier) = [('n', 1.0), ('u', 1e3), ('m', 1e6)]
+        .into_iter()
+        .find_map(|(suffix, multiplier)| Some((quantity.strip_suffix(suffix)?, multiplier)))
+        .unwrap_or((quantity, 1e9));
+
+    let nanocores = number.parse::<f64>().ok()? * multiplier;
+    (nanocores >= 0.0).then_some(nanocores as u64)
+}
+
+/// Picks the target pod from the given candidates, using the [`SelectorStrategy`].
+///
+/// `cpu_usage` is used only with [`SelectorStrategy::LeastLoaded`]. When it's [`None`], we fall
+/// back to [`SelectorStrategy::Newest`].
+pub(super) fn select_pod(
+    mut pods: Vec<Pod>,
+    strategy: SelectorStrategy,
+    cpu_usage: Option<&HashMap<String, u64>>,
+) -> Option<Pod> {
+    // Newest first, so that ties in the least loaded strategy are resolved in favour of the
+    // newest pod.
+    pods.sort_by_key(|pod| {
+        std::cmp::Reverse(
+            pod.metadata
+                .creation_timestamp
+                .as_ref()
+                .map(|timestamp| timestamp.0),
+        )
+    });
+
+    match (strategy, cpu_usage) {
+        (SelectorStrategy::Newest, _) | (SelectorStrategy::LeastLoaded, None) => {
+            pods.into_iter().next()
+        }
+        (SelectorStrategy::Oldest, _) => pods.pop(),
+        (SelectorStrategy::Random, _) => pods.choose(&mut rand::rng()).cloned(),
+        (SelectorStrategy::LeastLoaded, Some(cpu_usage)) => pods.into_iter().min_by_key(|pod| {
+            pod.metadata
+                .name
+                .as_ref()
+                .and_then(|name| cpu_usage.get(name))
+                .copied()
+                .unwrap_or(u64::MAX)
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use k8s_openapi::{
+        api::core::v1::Pod, apimachinery::pkg::apis::meta::v1::Time, jiff::Timestamp,
+    };
+    use kube::api::ObjectMeta;
+    use mirrord_config::target::selector::SelectorStrategy;
+    use rstest::rstest;
+
+    use super::{parse_cpu_nanocores, select_pod};
+
+    fn pod(name: &str, created_at_secs: i64) -> Pod {
+        Pod {
+            metadata: ObjectMeta {
+                name: Some(name.to_owned()),
+                creation_timestamp: Some(Time(Timestamp::from_second(created_at_secs).unwrap())),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case("250m", Some(250_000_000))]
+    #[case("2", Some(2_000_000_000))]
+    #[case("0.5", Some(500_000_000))]
+    #[case("1500u", Some(1_500_000))]
+    #[case("123456n", Some(123_456))]
+    #[case("", None)]
+    #[case("abc", None)]
+    fn parses_cpu_quantity(#[case] quantity: &str, #[case] expected: Option<u64>) {
+        assert_eq!(parse_cpu_nanocores(quantity), expected);
+    }
+
+    #[rstest]
+    #[case(SelectorStrategy::Newest, false, "new")]
+    #[case(SelectorStrategy::Oldest, false, "old")]
+    #[case(SelectorStrategy::LeastLoaded, true, "middle")]
+    #[case(SelectorStrategy::LeastLoaded, false, "new")]
+    fn selects_pod(
+        #[case] strategy: SelectorStrategy,
+        #[case] with_metrics: bool,
+        #[case] expected: &str,
+    ) {
+        let pods = vec![pod("middle", 20), pod("old", 10), pod("new", 30)];
+        let cpu_usage = HashMap::from([
+            ("old".to_owned(), 300),
+            ("middle".to_owned(), 100),
+            ("new".to_owned(), 200),
+        ]);
+
+        let selected = select_pod(pods, strategy, with_metrics.then_some(&cpu_usage)).unwrap();
+        assert_eq!(selected.metadata.name.as_deref(), Some(expected));
+    }
+}
@@ -566,6 +566,45 @@ pub fn getaddrinfo_error_code(fail: HookError) -> i32 {
     }
 }
 
+/// `h_errno` codes from `netdb.h`, same on Linux and macOS.
+#[cfg(unix)]
+mod h_errno {
+    pub const HOST_NOT_FOUND: i32 = 1;
+    pub const TRY_AGAIN: i32 = 2;
+    pub const NO_RECOVERY: i32 = 3;
+    pub const NO_DATA: i32 = 4;
+}
+
+/// Converts a [`HookError`] into an `h_errno` code, to be reported from `res_query`,
+/// `gethostbyaddr` and friends.
+///
+/// Like in [`getaddrinfo_error_code`], non-DNS failures still go through the generic hook-error
+/// side effects.
+#[cfg(unix)]
+pub fn h_errno_code(fail: HookError) -> i32 {
+    match fail {
+        HookError::ResponseError(ResponseError::DnsLookup(DnsLookupError { kind })) => {
+            use mirrord_protocol::ResolveErrorKindInternal;
+
+            match kind {
+                // `NXDOMAIN`, the name does not exist.
+                ResolveErrorKindInternal::NoRecordsFound(3) => h_errno::HOST_NOT_FOUND,
+                ResolveErrorKindInternal::NoRecordsFound(_) => h_errno::NO_DATA,
+                ResolveErrorKindInternal::Timeout | ResolveErrorKindInternal::NoConnections => {
+                    h_errno::TRY_AGAIN
+                }
+                _ => h_errno::NO_RECOVERY,
+            }
+        }
+        HookError::DNSNoName => h_errno::HOST_NOT_FOUND,
+        other => {
+            // The conversion has side effects - logs, possible gracefull exit, etc.
+            let _ = i64::from(other);
+            h_errno::NO_RECOVERY
+        }
+    }
+}
+
 /// mapping based on - <https://man7.org/linux/man-pages/man3/errno.3.html>
 impl From<HookError> for i64 {
     fn from(fail: HookError) -> Self {
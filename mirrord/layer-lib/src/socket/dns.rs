@@ -7,7 +7,10 @@ pub mod windows;
 use std::net::IpAddr;
 
 use libc::c_int;
-use mirrord_protocol::dns::{AddressFamily, GetAddrInfoRequestV2, LookupRecord, SockType};
+use mirrord_protocol::dns::{
+    AddressFamily, DnsQueryRequest, DnsRecord, GetAddrInfoRequestV2, LookupRecord,
+    ReverseDnsLookupRequest, SockType,
+};
 
 use crate::{
     error::HookResult,
@@ -67,6 +70,46 @@ pub fn remote_getaddrinfo(
     Ok(result)
 }
 
+/// Handles the remote communication part of `res_query` and friends, queries the agent for DNS
+/// records of any type and class.
+///
+/// When `search` is set, the agent applies the search domains from the remote
+/// `/etc/resolv.conf` to `name`.
+#[mirrord_layer_macro::instrument(
+    level = tracing::Level::TRACE,
+    ret,
+    err(level = tracing::Level::TRACE)
+)]
+pub fn remote_dns_query(
+    name: String,
+    class: u16,
+    record_type: u16,
+    search: bool,
+) -> HookResult<Vec<DnsRecord>> {
+    let records = make_proxy_request_with_response(DnsQueryRequest {
+        name,
+        record_type,
+        class,
+        search,
+    })?
+    .0?;
+
+    Ok(records)
+}
+
+/// Handles the remote communication part of `getnameinfo` and `gethostbyaddr`, asks the agent for
+/// the hostname of `ip_address`.
+#[mirrord_layer_macro::instrument(
+    level = tracing::Level::TRACE,
+    ret,
+    err(level = tracing::Level::TRACE)
+)]
+pub fn remote_reverse_dns_lookup(ip_address: IpAddr) -> HookResult<String> {
+    let response = make_proxy_request_with_response(ReverseDnsLookupRequest { ip_address })??;
+
+    Ok(response.hostname?)
+}
+
 /// Perform remote DNS resolution via ProxyConnection using mirrord protocol
 /// wrapper around `remote_getaddrinfo` with common parameters
 pub fn remote_dns_resolve_via_proxy(hostname: &str) -> HookResult<Vec<(String, std::net::IpAddr)>> {
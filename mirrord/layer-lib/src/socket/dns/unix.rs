@@ -8,6 +8,8 @@ use std::{
     sync::{LazyLock, Mutex},
 };
 
+use libc::c_int;
+use mirrord_protocol::{ResponseError, dns::DnsRecord};
 use socket2::SockAddr;
 use tracing::{trace, warn};
 
@@ -15,9 +17,15 @@ use crate::{
     detour::{Bypass, Detour, OptionExt},
     error::HookError,
     setup::setup,
-    socket::remote_getaddrinfo,
+    socket::{dns::remote_dns_query, remote_getaddrinfo},
 };
 
+/// Size of the DNS message header.
+const DNS_HEADER_LEN: usize = 12;
+
+/// Maximum length of a single label in a DNS name.
+const DNS_MAX_LABEL_LEN: usize = 63;
+
 /// Here we keep addr infos that we allocated so we'll know when to use the original
 /// freeaddrinfo function and when to use our implementation
 pub static MANAGED_ADDRINFO: LazyLock<Mutex<HashSet<usize>>> =
@@ -147,3 +155,192 @@ pub fn getaddrinfo(
 
     Detour::Success(result)
 }
+
+/// Retrieves DNS records from the remote host, and writes them into `answer` as a DNS response
+/// message, like `res_query` (or `res_search` when `search` is set) does.
+///
+/// Returns the length of the message. When the message does not fit in `answer`, it is truncated
+/// and has the `TC` flag set.
+///
+/// # Protocol
+///
+/// `-layer` sends a [`DnsQueryRequest`](mirrord_protocol::dns::DnsQueryRequest) to the `-agent`,
+/// which resolves it with its own resolver.
+#[mirrord_layer_macro::instrument(level = "trace", ret, skip(answer))]
+pub fn res_query(
+    rawish_name: Option<&CStr>,
+    class: c_int,
+    record_type: c_int,
+    search: bool,
+    answer: &mut [u8],
+) -> Detour<c_int> {
+    let name: String = rawish_name
+        .bypass(Bypass::NullNode)?
+        .to_str()
+        .map_err(|fail| {
+            warn!("Failed converting `name` from `CStr` with {:#?}", fail);
+
+            Bypass::CStrConversion
+        })?
+        .into();
+
+    let (Ok(class), Ok(record_type)) = (u16::try_from(class), u16::try_from(record_type)) else {
+        return Detour::Error(HookError::DNSNoName);
+    };
+
+    setup().dns_selector().check_query(&name, 0)?;
+
+    let records = match remote_dns_query(name.clone(), class, record_type, search) {
+        Err(HookError::ResponseError(ResponseError::NotImplemented)) => {
+            return Detour::Bypass(Bypass::NotImplemented);
+        }
+        result => result?,
+    };
+
+    // With `search`, the agent may have queried a name extended with one of the search domains.
+    let query_name = records
+        .first()
+        .map(|record| record.name.as_str())
+        .unwrap_or(&name);
+    let mut message = encode_dns_response(query_name, class, record_type, &records)
+        .ok_or(HookError::DNSNoName)?;
+
+    if message.len() > answer.len() {
+        message.truncate(answer.len());
+        // Sets the `TC` (truncated) flag, the second byte of the flags.
+        if let Some(flags) = message.get_mut(2) {
+            *flags |= 0b10;
+        }
+    }
+
+    let answer_len = message.len();
+    answer
+        .get_mut(..answer_len)
+        .ok_or(HookError::DNSNoName)?
+        .copy_from_slice(&message);
+
+    Detour::Success(c_int::try_from(answer_len).unwrap_or(c_int::MAX))
+}
+
+/// Sets `h_errno`, which is used to report errors from `res_query` and friends.
+pub fn set_h_errno(code: c_int) {
+    #[cfg(target_os = "linux")]
+    unsafe extern "C" {
+        fn __h_errno_location() -> *mut c_int;
+    }
+
+    #[cfg(target_os = "macos")]
+    unsafe extern "C" {
+        static mut h_errno: c_int;
+    }
+
+    // Safety: `h_errno` is a (thread local on Linux) variable that lives for the whole program.
+    #[cfg(target_os = "linux")]
+    unsafe {
+        *__h_errno_location() = code;
+    }
+    #[cfg(target_os = "macos")]
+    unsafe {
+        h_errno = code;
+    }
+}
+
+/// Encodes a DNS response message with a single question and the given answer `records`.
+///
+/// Names are never compressed. Returns [`None`] if any of the names is invalid.
+fn encode_dns_response(
+    query_name: &str,
+    class: u16,
+    record_type: u16,
+    records: &[DnsRecord],
+) -> Option<Vec<u8>> {
+    let mut message = Vec::with_capacity(DNS_HEADER_LEN);
+
+    // ID, flags (`QR`, `RD` and `RA`), 1 question, answers, no authority and additional records.
+    message.extend_from_slice(&0u16.to_be_bytes());
+    message.extend_from_slice(&0x8180u16.to_be_bytes());
+    message.extend_from_slice(&1u16.to_be_bytes());
+    message.extend_from_slice(&u16::try_from(records.len()).ok()?.to_be_bytes());
+    message.extend_from_slice(&[0; 4]);
+
+    encode_dns_name(query_name, &mut message)?;
+    message.extend_from_slice(&record_type.to_be_bytes());
+    message.extend_from_slice(&class.to_be_bytes());
+
+    for record in records {
+        encode_dns_name(&record.name, &mut message)?;
+        message.extend_from_slice(&record.record_type.to_be_bytes());
+        message.extend_from_slice(&record.class.to_be_bytes());
+        message.extend_from_slice(&record.ttl.to_be_bytes());
+        message.extend_from_slice(&u16::try_from(record.data.len()).ok()?.to_be_bytes());
+        message.extend_from_slice(&record.data);
+    }
+
+    Some(message)
+}
+
+/// Encodes a DNS name in the uncompressed wire format.
+///
+/// The trailing dot is optional, the name is always treated as fully qualified.
+fn encode_dns_name(name: &str, buffer: &mut Vec<u8>) -> Option<()> {
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() {
+            // Only the root name can be empty.
+            if name.trim_end_matches('.').is_empty() {
+                break;
+            }
+
+            return None;
+        }
+
+        if label.len() > DNS_MAX_LABEL_LEN {
+            return None;
+        }
+
+        buffer.push(label.len() as u8);
+        buffer.extend_from_slice(label.as_bytes());
+    }
+
+    buffer.push(0);
+
+    Some(())
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_protocol::dns::{DNS_CLASS_IN, DnsRecord};
+
+    use super::encode_dns_response;
+
+    /// Verifies the encoding of a DNS response to an `SRV` query.
+    #[test]
+    fn encode_srv_response() {
+        let record = DnsRecord {
+            name: "_kafka._tcp.svc.".into(),
+            record_type: 33,
+            class: DNS_CLASS_IN,
+            ttl: 30,
+            data: vec![0, 10, 0, 5, 0x23, 0x84, 1, b'b', 0],
+        };
+
+        let message = encode_dns_response("_kafka._tcp.svc", DNS_CLASS_IN, 33, &[record]).unwrap();
+
+        let name = b"\x06_kafka\x04_tcp\x03svc\x00";
+        let mut expected = vec![0, 0, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0];
+        expected.extend_from_slice(name);
+        expected.extend_from_slice(&[0, 33, 0, 1]);
+        expected.extend_from_slice(name);
+        expected.extend_from_slice(&[0, 33, 0, 1, 0, 0, 0, 30, 0, 9]);
+        expected.extend_from_slice(&[0, 10, 0, 5, 0x23, 0x84, 1, b'b', 0]);
+
+        assert_eq!(message, expected);
+    }
+
+    /// Verifies that invalid names are rejected.
+    #[test]
+    fn encode_invalid_name() {
+        assert!(encode_dns_response("a..b", DNS_CLASS_IN, 16, &[]).is_none());
+        assert!(encode_dns_response(&"a".repeat(64), DNS_CLASS_IN, 16, &[]).is_none());
+        assert!(encode_dns_response(".", DNS_CLASS_IN, 2, &[]).is_some());
+    }
+}
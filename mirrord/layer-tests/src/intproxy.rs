@@ -72,6 +72,10 @@ impl TestIntProxy {
                 listener,
                 0,
                 Default::default(),
+                None,
+                None,
+                None,
+                false,
                 IntProxyIntervals {
                     ping: Duration::from_secs(60),
                     process_logging: Duration::from_secs(60),
@@ -544,8 +544,9 @@ fn sip_only_layer_start(
 /// - `enabled_file_ops`: replaces [`libc`] file-ish calls with our own from [`file::hooks`], see
 ///   `FsConfig::is_active`, and [`hooks::enable_file_hooks`](file::hooks::enable_file_hooks);
 ///
-/// - `enabled_remote_dns`: replaces [`libc::getaddrinfo`] and [`libc::freeaddrinfo`] when this is
-///   `true`, see [`NetworkConfig`](mirrord_config::feature::network::NetworkConfig), and
+/// - `enabled_remote_dns`: replaces [`libc::getaddrinfo`], [`libc::freeaddrinfo`], and the
+///   `res_query` family of functions when this is `true`, see
+///   [`NetworkConfig`](mirrord_config::feature::network::NetworkConfig), and
 ///   [`hooks::enable_socket_hooks`](socket::hooks::enable_socket_hooks).
 #[mirrord_layer_macro::instrument(level = tracing::Level::TRACE)]
 fn enable_hooks(state: &LayerSetup) {
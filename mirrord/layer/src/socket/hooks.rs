@@ -1,18 +1,21 @@
 use alloc::ffi::CString;
 use core::{cmp, ffi::CStr};
-use std::{collections::HashSet, os::unix::io::RawFd, sync::LazyLock};
+use std::{collections::HashSet, os::unix::io::RawFd, ptr, sync::LazyLock};
 
-use libc::{c_char, c_int, c_void, hostent, size_t, sockaddr, socklen_t, ssize_t};
+use libc::{c_char, c_int, c_uchar, c_void, hostent, size_t, sockaddr, socklen_t, ssize_t};
 #[cfg(target_os = "macos")]
 use libc::{c_uint, iovec, sa_endpoints_t, sae_associd_t, sae_connid_t};
 use mirrord_config::experimental::ExperimentalConfig;
 #[cfg(target_os = "macos")]
 use mirrord_layer_lib::socket::apple_dnsinfo::*;
 use mirrord_layer_lib::{
-    detour::{Detour, DetourGuard},
-    error::getaddrinfo_error_code,
+    detour::{Bypass, Detour, DetourGuard},
+    error::{getaddrinfo_error_code, h_errno_code},
     mutex::Mutex,
-    socket::ops::socket,
+    socket::{
+        dns::unix::{res_query, set_h_errno},
+        ops::socket,
+    },
 };
 use mirrord_layer_macro::{hook_fn, hook_guard_fn};
 use nix::errno::Errno;
@@ -155,6 +158,159 @@ unsafe extern "C" fn gethostbyname_detour(raw_name: *const c_char) -> *mut hoste
     }
 }
 
+/// Turns the raw address parameter of `gethostbyaddr` and `gethostbyaddr_r` into a slice.
+unsafe fn raw_address_bytes<'a>(
+    raw_address: *const c_void,
+    address_length: socklen_t,
+) -> Option<&'a [u8]> {
+    unsafe {
+        (!raw_address.is_null())
+            .then(|| std::slice::from_raw_parts(raw_address.cast(), address_length as usize))
+    }
+}
+
+/// Hook for `gethostbyaddr`, resolves the hostname of the address through the agent.
+///
+/// Like [`gethostbyname_detour`], the result is kept in a `static` [`libc::hostent`], and errors
+/// are reported through `h_errno`.
+#[hook_guard_fn]
+unsafe extern "C" fn gethostbyaddr_detour(
+    raw_address: *const c_void,
+    address_length: socklen_t,
+    family: c_int,
+) -> *mut hostent {
+    unsafe {
+        let result = match raw_address_bytes(raw_address, address_length) {
+            Some(address) => gethostbyaddr(address, family),
+            None => Detour::Bypass(Bypass::NullNode),
+        };
+
+        match result {
+            Detour::Success(entry) => entry,
+            Detour::Bypass(_) => FN_GETHOSTBYADDR(raw_address, address_length, family),
+            Detour::Error(error) => {
+                set_h_errno(h_errno_code(error));
+                ptr::null_mut()
+            }
+        }
+    }
+}
+
+/// Converts the result of resolving a [`HostEntry`] into the return value of the reentrant
+/// `gethostbyname_r` and `gethostbyaddr_r`, writing the entry into the user's `buffer`.
+///
+/// Like in glibc, a name that could not be resolved is reported with a null `result` and the
+/// `h_errnop` code, while the function itself still returns `0`.
+#[cfg(target_os = "linux")]
+unsafe fn hostent_r_result(
+    entry: Detour<HostEntry>,
+    ret: *mut hostent,
+    buffer: *mut c_char,
+    buffer_len: size_t,
+    result: *mut *mut hostent,
+    h_errnop: *mut c_int,
+    bypass: impl FnOnce() -> c_int,
+) -> c_int {
+    /// `h_errno` for errors that are reported through `errno`.
+    const NETDB_INTERNAL: c_int = -1;
+
+    unsafe {
+        let (code, h_errno) = match entry {
+            Detour::Success(entry) => match entry.write_into(&mut *ret, buffer, buffer_len) {
+                Ok(()) => {
+                    *result = ret;
+                    return 0;
+                }
+                Err(errno) => {
+                    Errno::set_raw(errno as c_int);
+                    (errno as c_int, NETDB_INTERNAL)
+                }
+            },
+            Detour::Bypass(_) => return bypass(),
+            Detour::Error(error) => (0, h_errno_code(error)),
+        };
+
+        *result = ptr::null_mut();
+        if let Some(h_errnop) = h_errnop.as_mut() {
+            *h_errnop = h_errno;
+        }
+
+        code
+    }
+}
+
+/// Hook for `gethostbyname_r`, the reentrant version of `gethostbyname` from glibc.
+///
+/// The resolved [`HostEntry`] is written into the user's `buffer`.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn gethostbyname_r_detour(
+    raw_name: *const c_char,
+    ret: *mut hostent,
+    buffer: *mut c_char,
+    buffer_len: size_t,
+    result: *mut *mut hostent,
+    h_errnop: *mut c_int,
+) -> c_int {
+    unsafe {
+        let bypass = || FN_GETHOSTBYNAME_R(raw_name, ret, buffer, buffer_len, result, h_errnop);
+        if ret.is_null() || result.is_null() {
+            return bypass();
+        }
+
+        let rawish_name = (!raw_name.is_null()).then(|| CStr::from_ptr(raw_name));
+        hostent_r_result(
+            gethostbyname_entry(rawish_name),
+            ret,
+            buffer,
+            buffer_len,
+            result,
+            h_errnop,
+            bypass,
+        )
+    }
+}
+
+/// Hook for `gethostbyaddr_r`, the reentrant version of `gethostbyaddr` from glibc.
+///
+/// The resolved [`HostEntry`] is written into the user's `buffer`.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn gethostbyaddr_r_detour(
+    raw_address: *const c_void,
+    address_length: socklen_t,
+    family: c_int,
+    ret: *mut hostent,
+    buffer: *mut c_char,
+    buffer_len: size_t,
+    result: *mut *mut hostent,
+    h_errnop: *mut c_int,
+) -> c_int {
+    unsafe {
+        let bypass = || {
+            FN_GETHOSTBYADDR_R(
+                raw_address,
+                address_length,
+                family,
+                ret,
+                buffer,
+                buffer_len,
+                result,
+                h_errnop,
+            )
+        };
+        if ret.is_null() || result.is_null() {
+            return bypass();
+        }
+
+        let entry = match raw_address_bytes(raw_address, address_length) {
+            Some(address) => gethostbyaddr_entry(address, family),
+            None => Detour::Bypass(Bypass::NullNode),
+        };
+        hostent_r_result(entry, ret, buffer, buffer_len, result, h_errnop, bypass)
+    }
+}
+
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn accept_detour(
     sockfd: c_int,
@@ -357,6 +513,173 @@ unsafe extern "C" fn getaddrinfo_detour(
     }
 }
 
+/// Hook for `getnameinfo`, resolves the hostname of `raw_address` through the agent.
+///
+/// Only the host part is resolved remotely, the service part (if requested) is still filled by
+/// the original function.
+#[hook_guard_fn]
+unsafe extern "C" fn getnameinfo_detour(
+    raw_address: *const sockaddr,
+    address_length: socklen_t,
+    host: *mut c_char,
+    host_length: socklen_t,
+    service: *mut c_char,
+    service_length: socklen_t,
+    flags: c_int,
+) -> c_int {
+    unsafe {
+        let call_original = |host: *mut c_char, host_length: socklen_t| {
+            FN_GETNAMEINFO(
+                raw_address,
+                address_length,
+                host,
+                host_length,
+                service,
+                service_length,
+                flags,
+            )
+        };
+
+        if raw_address.is_null() || host.is_null() || host_length == 0 {
+            return call_original(host, host_length);
+        }
+
+        match getnameinfo(raw_address, address_length, flags) {
+            Detour::Success(hostname) => {
+                let hostname = hostname.as_bytes_with_nul();
+                if hostname.len() > host_length as usize {
+                    return libc::EAI_OVERFLOW;
+                }
+
+                if !service.is_null() && service_length != 0 {
+                    let result = call_original(ptr::null_mut(), 0);
+                    if result != 0 {
+                        return result;
+                    }
+                }
+
+                host.copy_from_nonoverlapping(hostname.as_ptr().cast(), hostname.len());
+                0
+            }
+            Detour::Bypass(_) => call_original(host, host_length),
+            Detour::Error(error) => getaddrinfo_error_code(error),
+        }
+    }
+}
+
+/// Turns the raw pointer parameters of `res_query` and friends into Rust types and calls
+/// [`res_query`].
+///
+/// # Warning:
+/// - `raw_name` and/or `answer` might be null!
+unsafe fn res_query_common(
+    raw_name: *const c_char,
+    class: c_int,
+    type_: c_int,
+    search: bool,
+    answer: *mut c_uchar,
+    anslen: c_int,
+) -> Detour<c_int> {
+    unsafe {
+        let rawish_name = (!raw_name.is_null()).then(|| CStr::from_ptr(raw_name));
+
+        let answer = match usize::try_from(anslen) {
+            Ok(anslen) if !answer.is_null() => std::slice::from_raw_parts_mut(answer, anslen),
+            _ => return Detour::Bypass(Bypass::EmptyBuffer),
+        };
+
+        res_query(rawish_name, class, type_, search, answer)
+    }
+}
+
+/// Converts the result of [`res_query_common`] into the return value of `res_query` and friends.
+///
+/// Errors are reported through `h_errno`.
+fn res_query_result(result: Detour<c_int>, bypass: impl FnOnce() -> c_int) -> c_int {
+    match result {
+        Detour::Success(answer_len) => answer_len,
+        Detour::Bypass(_) => bypass(),
+        Detour::Error(error) => {
+            set_h_errno(h_errno_code(error));
+            -1
+        }
+    }
+}
+
+/// Hook for `res_query`, resolves DNS records of any type through the agent.
+#[hook_guard_fn]
+unsafe extern "C" fn res_query_detour(
+    raw_name: *const c_char,
+    class: c_int,
+    type_: c_int,
+    answer: *mut c_uchar,
+    anslen: c_int,
+) -> c_int {
+    unsafe {
+        res_query_result(
+            res_query_common(raw_name, class, type_, false, answer, anslen),
+            || FN_RES_QUERY(raw_name, class, type_, answer, anslen),
+        )
+    }
+}
+
+/// Hook for `res_search`, like [`res_query_detour`], but the agent applies the search domains.
+#[hook_guard_fn]
+unsafe extern "C" fn res_search_detour(
+    raw_name: *const c_char,
+    class: c_int,
+    type_: c_int,
+    answer: *mut c_uchar,
+    anslen: c_int,
+) -> c_int {
+    unsafe {
+        res_query_result(
+            res_query_common(raw_name, class, type_, true, answer, anslen),
+            || FN_RES_SEARCH(raw_name, class, type_, answer, anslen),
+        )
+    }
+}
+
+/// Hook for `res_nquery`, the thread safe version of `res_query`.
+///
+/// The resolver state is ignored, as the query is resolved by the agent.
+#[hook_guard_fn]
+unsafe extern "C" fn res_nquery_detour(
+    state: *mut c_void,
+    raw_name: *const c_char,
+    class: c_int,
+    type_: c_int,
+    answer: *mut c_uchar,
+    anslen: c_int,
+) -> c_int {
+    unsafe {
+        res_query_result(
+            res_query_common(raw_name, class, type_, false, answer, anslen),
+            || FN_RES_NQUERY(state, raw_name, class, type_, answer, anslen),
+        )
+    }
+}
+
+/// Hook for `res_nsearch`, the thread safe version of `res_search`.
+///
+/// Used by Go's cgo resolver for `SRV` and `TXT` lookups.
+#[hook_guard_fn]
+unsafe extern "C" fn res_nsearch_detour(
+    state: *mut c_void,
+    raw_name: *const c_char,
+    class: c_int,
+    type_: c_int,
+    answer: *mut c_uchar,
+    anslen: c_int,
+) -> c_int {
+    unsafe {
+        res_query_result(
+            res_query_common(raw_name, class, type_, true, answer, anslen),
+            || FN_RES_NSEARCH(state, raw_name, class, type_, answer, anslen),
+        )
+    }
+}
+
 /// Deallocates a `*mut libc::addrinfo` that was previously allocated with `Box::new` in
 /// `getaddrinfo_detour` and converted into a raw pointer by `Box::into_raw`. Same thing must also
 /// be done for `addrinfo.ai_addr`.
@@ -829,6 +1152,41 @@ pub(crate) unsafe fn enable_socket_hooks(
                 FN_GETHOSTBYNAME
             );
 
+            replace!(
+                hook_manager,
+                "gethostbyaddr",
+                gethostbyaddr_detour,
+                FnGethostbyaddr,
+                FN_GETHOSTBYADDR
+            );
+
+            #[cfg(target_os = "linux")]
+            {
+                replace!(
+                    hook_manager,
+                    "gethostbyname_r",
+                    gethostbyname_r_detour,
+                    FnGethostbyname_r,
+                    FN_GETHOSTBYNAME_R
+                );
+
+                replace!(
+                    hook_manager,
+                    "gethostbyaddr_r",
+                    gethostbyaddr_r_detour,
+                    FnGethostbyaddr_r,
+                    FN_GETHOSTBYADDR_R
+                );
+            }
+
+            replace!(
+                hook_manager,
+                "getnameinfo",
+                getnameinfo_detour,
+                FnGetnameinfo,
+                FN_GETNAMEINFO
+            );
+
             replace!(
                 hook_manager,
                 "getaddrinfo",
@@ -844,6 +1202,37 @@ pub(crate) unsafe fn enable_socket_hooks(
                 FnFreeaddrinfo,
                 FN_FREEADDRINFO
             );
+
+            // In glibc, `__res_query` (and so on) are aliases of the same functions, so we hook
+            // only the public names.
+            replace!(
+                hook_manager,
+                "res_query",
+                res_query_detour,
+                FnRes_query,
+                FN_RES_QUERY
+            );
+            replace!(
+                hook_manager,
+                "res_search",
+                res_search_detour,
+                FnRes_search,
+                FN_RES_SEARCH
+            );
+            replace!(
+                hook_manager,
+                "res_nquery",
+                res_nquery_detour,
+                FnRes_nquery,
+                FN_RES_NQUERY
+            );
+            replace!(
+                hook_manager,
+                "res_nsearch",
+                res_nsearch_detour,
+                FnRes_nsearch,
+                FN_RES_NSEARCH
+            );
             #[cfg(target_os = "macos")]
             {
                 replace!(
@@ -3,7 +3,7 @@ use core::ffi::CStr;
 #[cfg(target_os = "macos")]
 use std::os::fd::BorrowedFd;
 use std::{
-    io,
+    io, iter, mem,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, TcpStream},
     ops::Not,
     os::{
@@ -15,7 +15,7 @@ use std::{
     sync::{Arc, OnceLock},
 };
 
-use libc::{AF_UNIX, c_int, c_void, hostent, sockaddr, socklen_t};
+use libc::{AF_UNIX, c_char, c_int, c_void, hostent, sockaddr, socklen_t};
 #[cfg(target_os = "macos")]
 use libc::{SAE_ASSOCID_ANY, c_uint, iovec, sa_endpoints_t, sae_associd_t, sae_connid_t, size_t};
 use mirrord_config::feature::network::incoming::{IncomingConfig, IncomingMode};
@@ -32,11 +32,14 @@ use mirrord_layer_lib::{
     proxy_connection::make_proxy_request_with_response,
     socket::{
         Bound, Connected, SocketAddrExt, SocketKind, SocketState,
-        dns::{remote_getaddrinfo, unix::getaddrinfo as getaddrinfo_lib},
+        dns::{
+            remote_getaddrinfo, remote_reverse_dns_lookup, unix::getaddrinfo as getaddrinfo_lib,
+        },
         ops::{ConnectResult, connect_common, connect_outgoing_common, nop_connect_fn},
     },
 };
 use mirrord_protocol::{
+    ResponseError,
     file::{OpenFileResponse, OpenOptionsInternal, ReadFileResponse},
     outgoing::SocketAddress,
 };
@@ -80,6 +83,122 @@ static mut GETHOSTBYNAME_HOSTENT: hostent = hostent {
     h_addr_list: ptr::null_mut(),
 };
 
+/// Holds the names and addresses that [`GETHOSTBYADDR_HOSTENT`] points to.
+static mut GETHOSTBYADDR_BUFFER: Vec<c_char> = Vec::new();
+
+/// Global static that the user will receive when calling [`gethostbyaddr`].
+static mut GETHOSTBYADDR_HOSTENT: hostent = hostent {
+    h_name: ptr::null_mut(),
+    h_aliases: ptr::null_mut(),
+    h_addrtype: 0,
+    h_length: 0,
+    h_addr_list: ptr::null_mut(),
+};
+
+/// The contents of a [`hostent`], resolved through the agent.
+///
+/// Unlike [`gethostbyname`], which keeps every part of the result in its own `static`, this is
+/// written into a single buffer with [`HostEntry::write_into`], so it can also fill the buffers
+/// that the user passes to the reentrant `gethostbyname_r` and `gethostbyaddr_r`.
+#[derive(Debug)]
+pub(super) struct HostEntry {
+    name: CString,
+    aliases: Vec<CString>,
+    /// Addresses of the same family.
+    addresses: Vec<IpAddr>,
+}
+
+impl HostEntry {
+    fn address_family(&self) -> c_int {
+        match self.addresses.first() {
+            Some(IpAddr::V6(_)) => libc::AF_INET6,
+            _ => libc::AF_INET,
+        }
+    }
+
+    fn address_len(&self) -> usize {
+        match self.addresses.first() {
+            Some(IpAddr::V6(_)) => mem::size_of::<Ipv6Addr>(),
+            _ => mem::size_of::<Ipv4Addr>(),
+        }
+    }
+
+    /// Size of the buffer required by [`HostEntry::write_into`], including the padding needed
+    /// to align the pointer lists.
+    pub(super) fn buffer_len(&self) -> usize {
+        let pointers =
+            (self.aliases.len() + 1 + self.addresses.len() + 1) * mem::size_of::<*mut c_char>();
+        let addresses = self.addresses.len() * self.address_len();
+        let strings = iter::once(&self.name)
+            .chain(&self.aliases)
+            .map(|string| string.as_bytes_with_nul().len())
+            .sum::<usize>();
+
+        mem::align_of::<*mut c_char>() - 1 + pointers + addresses + strings
+    }
+
+    /// Fills `entry` with pointers into `buffer`, where the null terminated alias and address
+    /// lists, the addresses and the names are copied to (in this order).
+    ///
+    /// Fails with [`Errno::ERANGE`] when `buffer` is too small, like `gethostbyname_r` does.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be valid for writes of `buffer_len` bytes.
+    pub(super) unsafe fn write_into(
+        &self,
+        entry: &mut hostent,
+        buffer: *mut c_char,
+        buffer_len: usize,
+    ) -> Result<(), Errno> {
+        if buffer.is_null() || buffer_len < self.buffer_len() {
+            return Err(Errno::ERANGE);
+        }
+
+        unsafe {
+            let padding = buffer.align_offset(mem::align_of::<*mut c_char>());
+            let aliases_list = buffer.add(padding).cast::<*mut c_char>();
+            let addresses_list = aliases_list.add(self.aliases.len() + 1);
+            let mut data = addresses_list
+                .add(self.addresses.len() + 1)
+                .cast::<c_char>();
+
+            let mut copy = |bytes: &[u8]| {
+                let start = data;
+                copy_nonoverlapping(bytes.as_ptr().cast(), start, bytes.len());
+                data = data.add(bytes.len());
+                start
+            };
+
+            for (index, address) in self.addresses.iter().enumerate() {
+                let address = match address {
+                    IpAddr::V4(address) => copy(&address.octets()),
+                    IpAddr::V6(address) => copy(&address.octets()),
+                };
+                addresses_list.add(index).write(address);
+            }
+            addresses_list
+                .add(self.addresses.len())
+                .write(ptr::null_mut());
+
+            for (index, alias) in self.aliases.iter().enumerate() {
+                aliases_list
+                    .add(index)
+                    .write(copy(alias.as_bytes_with_nul()));
+            }
+            aliases_list.add(self.aliases.len()).write(ptr::null_mut());
+
+            entry.h_name = copy(self.name.as_bytes_with_nul());
+            entry.h_aliases = aliases_list;
+            entry.h_addrtype = self.address_family();
+            entry.h_length = self.address_len() as c_int;
+            entry.h_addr_list = addresses_list;
+        }
+
+        Ok(())
+    }
+}
+
 /// Tries to bind the given socket to the requested address, with fallbacks.
 ///
 /// Tried addresses, in order:
@@ -874,6 +993,153 @@ pub(super) fn gethostbyname(raw_name: Option<&CStr>) -> Detour<*mut hostent> {
     Detour::Success(std::ptr::addr_of!(GETHOSTBYNAME_HOSTENT) as _)
 }
 
+/// Resolves a hostname into a [`HostEntry`] with its IPv4 addresses, for `gethostbyname_r`.
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(super) fn gethostbyname_entry(raw_name: Option<&CStr>) -> Detour<HostEntry> {
+    let name: String = raw_name
+        .bypass(Bypass::NullNode)?
+        .to_str()
+        .map_err(|fail| {
+            warn!("Failed converting `name` from `CStr` with {:#?}", fail);
+
+            Bypass::CStrConversion
+        })?
+        .into();
+
+    crate::setup().dns_selector().check_query(&name, 0)?;
+
+    let hosts_and_ips = remote_getaddrinfo(name.clone(), 0, 0, libc::AF_INET, 0, 0)?;
+
+    let mut aliases: Vec<CString> = Vec::new();
+    let mut addresses = Vec::new();
+    for (host, ip) in hosts_and_ips {
+        if ip.is_ipv6() {
+            trace!("ipv6 received - ignoring - {ip:?}");
+            continue;
+        }
+
+        addresses.push(ip);
+        if host != name
+            && aliases
+                .iter()
+                .all(|alias| alias.as_bytes() != host.as_bytes())
+        {
+            aliases.extend(CString::new(host).ok());
+        }
+    }
+
+    if addresses.is_empty() {
+        return Detour::Error(HookError::DNSNoName);
+    }
+
+    Detour::Success(HostEntry {
+        name: CString::new(name)?,
+        aliases,
+        addresses,
+    })
+}
+
+/// Converts the raw address of `gethostbyaddr` (`in_addr` or `in6_addr` bytes) into an
+/// [`IpAddr`].
+fn ip_from_raw_address(address: &[u8], family: c_int) -> Detour<IpAddr> {
+    let ip = match family {
+        libc::AF_INET => <[u8; 4]>::try_from(address).map(IpAddr::from),
+        libc::AF_INET6 => <[u8; 16]>::try_from(address).map(IpAddr::from),
+        other => return Detour::Bypass(Bypass::Domain(other)),
+    };
+
+    Detour::Success(ip.map_err(|_| Bypass::AddressConversion)?)
+}
+
+/// Resolves the hostname of `ip_address` through the agent, unless `feature.network.dns.filter`
+/// says that it should be resolved locally.
+///
+/// Fails with [`HookError::DNSNoName`] when the agent could not resolve the address.
+fn remote_reverse_lookup(ip_address: IpAddr, port: u16) -> Detour<String> {
+    crate::setup()
+        .dns_selector()
+        .check_query(&ip_address.to_string(), port)?;
+
+    match remote_reverse_dns_lookup(ip_address) {
+        Err(HookError::ResponseError(ResponseError::NotImplemented)) => {
+            Detour::Bypass(Bypass::NotImplemented)
+        }
+        Err(HookError::ResponseError(fail)) => {
+            trace!("Reverse DNS lookup of {ip_address} failed with {fail}");
+            Detour::Error(HookError::DNSNoName)
+        }
+        result => Detour::Success(result?),
+    }
+}
+
+/// Resolves an address into a [`HostEntry`] with its hostname, for `gethostbyaddr` and
+/// `gethostbyaddr_r`.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(super) fn gethostbyaddr_entry(address: &[u8], family: c_int) -> Detour<HostEntry> {
+    let ip_address = ip_from_raw_address(address, family)?;
+    let name = remote_reverse_lookup(ip_address, 0)?;
+
+    Detour::Success(HostEntry {
+        name: CString::new(name)?,
+        aliases: Vec::new(),
+        addresses: vec![ip_address],
+    })
+}
+
+/// Resolves the hostname of an address and sets the result to a static global, like the original
+/// `gethostbyaddr` does.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(super) fn gethostbyaddr(address: &[u8], family: c_int) -> Detour<*mut hostent> {
+    let entry = gethostbyaddr_entry(address, family)?;
+    let mut buffer = vec![0; entry.buffer_len()];
+
+    // The `Vec` allocation does not move when the `Vec` itself is moved into the `static`.
+    #[allow(static_mut_refs)]
+    unsafe {
+        entry
+            .write_into(
+                &mut GETHOSTBYADDR_HOSTENT,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+            )
+            .map_err(io::Error::from)?;
+        GETHOSTBYADDR_BUFFER = buffer;
+    }
+
+    Detour::Success(ptr::addr_of_mut!(GETHOSTBYADDR_HOSTENT))
+}
+
+/// Resolves the host part of `getnameinfo` through the agent.
+///
+/// When the agent cannot resolve the address, the numeric form of the address is returned
+/// instead, unless `NI_NAMEREQD` is set. The service part is left to the original
+/// `getnameinfo`.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(super) fn getnameinfo(
+    raw_address: *const sockaddr,
+    address_length: socklen_t,
+    flags: c_int,
+) -> Detour<CString> {
+    if flags & libc::NI_NUMERICHOST != 0 {
+        return Detour::Bypass(Bypass::LocalDns);
+    }
+
+    let address = SocketAddr::try_from_raw(raw_address, address_length)?;
+
+    let host = match remote_reverse_lookup(address.ip(), address.port()) {
+        Detour::Success(hostname) if flags & libc::NI_NOFQDN != 0 => {
+            hostname.split('.').next().unwrap_or_default().to_owned()
+        }
+        Detour::Error(HookError::DNSNoName) if flags & libc::NI_NAMEREQD == 0 => {
+            address.ip().to_string()
+        }
+        other => other?,
+    };
+
+    Detour::Success(CString::new(host)?)
+}
+
 /// Resolve hostname from remote host with caching for the result
 #[mirrord_layer_macro::instrument(level = "trace")]
 pub(super) fn gethostname() -> Detour<&'static CString> {
@@ -1439,3 +1705,40 @@ pub(super) fn getifaddrs() -> HookResult<*mut libc::ifaddrs> {
 
     Ok(original_head)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn host_entry_write_into() {
+        let entry = HostEntry {
+            name: CString::new("db.default.svc.cluster.local").unwrap(),
+            aliases: vec![CString::new("db").unwrap()],
+            addresses: vec![IpAddr::from([10, 0, 0, 1]), IpAddr::from([10, 0, 0, 2])],
+        };
+        let mut raw: hostent = unsafe { mem::zeroed() };
+
+        let mut buffer = vec![0 as c_char; entry.buffer_len() - 1];
+        assert_eq!(
+            unsafe { entry.write_into(&mut raw, buffer.as_mut_ptr(), buffer.len()) },
+            Err(Errno::ERANGE)
+        );
+
+        let mut buffer = vec![0 as c_char; entry.buffer_len()];
+        unsafe { entry.write_into(&mut raw, buffer.as_mut_ptr(), buffer.len()) }.unwrap();
+
+        unsafe {
+            assert_eq!(CStr::from_ptr(raw.h_name), entry.name.as_c_str());
+            assert_eq!(CStr::from_ptr(*raw.h_aliases), c"db");
+            assert!((*raw.h_aliases.add(1)).is_null());
+            assert_eq!(raw.h_addrtype, libc::AF_INET);
+            assert_eq!(raw.h_length, 4);
+            assert_eq!(
+                std::slice::from_raw_parts((*raw.h_addr_list.add(1)).cast::<u8>(), 4),
+                [10, 0, 0, 2]
+            );
+            assert!((*raw.h_addr_list.add(2)).is_null());
+        }
+    }
+}
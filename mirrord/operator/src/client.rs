@@ -21,8 +21,10 @@ use mirrord_auth::{
 };
 use mirrord_config::{
     LayerConfig,
-    feature::database_branches::{DatabaseBranchConfig, default_creation_timeout_secs},
-    target::Target,
+    feature::database_branches::{
+        DatabaseBranchConfig, SqlBranchMigrationsConfig, default_creation_timeout_secs,
+    },
+    target::{Target, daemon_set::DaemonSetTarget},
 };
 use mirrord_kube::{
     api::{
@@ -670,7 +672,12 @@ where
         // readiness. We only check the dialects whose feature has been advertised since it was
         // introduced (see `required_branching_feature`); the rest rely on the operator marking the
         // branch `Failed`.
-        for branch_config in layer_config.feature.db_branches.iter() {
+        for branch_config in layer_config
+            .feature
+            .db_branches
+            .iter()
+            .filter(|branch| branch.is_local().not())
+        {
             if let Some(feature) = required_branching_feature(branch_config)
                 && !self.operator.spec.supported_features().contains(&feature)
             {
@@ -765,9 +772,10 @@ where
                 !matches!(
                     branch_config,
                     mirrord_config::feature::database_branches::DatabaseBranchConfig::Generic(_)
-                ) && branch_config
-                    .base()
-                    .is_some_and(|base| base.image.is_some())
+                ) && branch_config.is_local().not()
+                    && branch_config
+                        .base()
+                        .is_some_and(|base| base.image.is_some())
             })
         {
             self.operator
@@ -775,6 +783,24 @@ where
                 .require_feature(NewOperatorFeature::DbBranchCustomImage)?;
         }
 
+        // Only Flyway migrations are understood by older operators. Local `command` migrations
+        // never reach the operator.
+        if layer_config
+            .feature
+            .db_branches
+            .iter()
+            .filter(|branch_config| branch_config.is_local().not())
+            .filter_map(|branch_config| branch_config.migrations())
+            .any(|migrations| {
+                !matches!(migrations, SqlBranchMigrationsConfig::Flyway { .. })
+                    && migrations.is_local().not()
+            })
+        {
+            self.operator
+                .spec
+                .require_feature(NewOperatorFeature::DbBranchMigrationRunners)?;
+        }
+
         let use_unified_crd = self
             .operator
             .spec
@@ -1334,7 +1360,12 @@ impl OperatorApi<PreparedClientCert> {
             .supported_features()
             .contains(&NewOperatorFeature::ProxyApi);
 
-        let branch_db_names = if layer_config.feature.db_branches.is_empty().not() {
+        let branch_db_names = if layer_config
+            .feature
+            .db_branches
+            .iter()
+            .any(|branch| branch.is_local().not())
+        {
             self.prepare_branch_dbs(layer_config, progress).await?
         } else {
             BranchDbNames::default()
@@ -1540,7 +1571,12 @@ impl OperatorApi<PreparedClientCert> {
         // - Multi-cluster: CRDs created on primary cluster. If Primary == Default, branching
         //   happens locally. If Primary != Default, a sync controller copies CRDs to Default where
         //   actual branching happens, then syncs status back.
-        let branch_db_names = if layer_config.feature.db_branches.is_empty().not() {
+        let branch_db_names = if layer_config
+            .feature
+            .db_branches
+            .iter()
+            .any(|branch| branch.is_local().not())
+        {
             self.prepare_branch_dbs(layer_config, progress).await?
         } else {
             BranchDbNames::default()
@@ -1860,6 +1896,10 @@ impl OperatorApi<PreparedClientCert> {
                 urlfied_name.push_str(".container.");
                 urlfied_name.push_str(target_container);
             }
+            if let Some(target_node) = target.node() {
+                urlfied_name.push_str(".node.");
+                urlfied_name.push_str(target_node);
+            }
             urlfied_name
         };
 
@@ -1899,6 +1939,13 @@ impl OperatorApi<PreparedClientCert> {
                     urlfied_name.push_str(".container.");
                     urlfied_name.push_str(container);
                 }
+                if let Target::DaemonSet(DaemonSetTarget {
+                    node: Some(node), ..
+                }) = target
+                {
+                    urlfied_name.push_str(".node.");
+                    urlfied_name.push_str(node);
+                }
             }
             urlfied_name
         };
@@ -30,7 +30,7 @@ impl Stream for OperatorConnection {
 
         let item = match std::task::ready!(this.0.poll_next_unpin(cx)) {
             Some(Ok(Message::Binary(msg))) => {
-                match bincode::decode_from_slice(&msg, bincode::config::standard()) {
+                match mirrord_protocol::compression::decode_from_slice(&msg) {
                     Ok((message, _)) => Some(Ok(message)),
                     Err(error) => Some(Err(OperatorConnectionError::DecodeError(error))),
                 }
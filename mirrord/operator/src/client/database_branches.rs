@@ -529,6 +529,8 @@ impl DatabaseBranchParams {
         let mut pg = HashMap::new();
         for branch_db_config in config.0.iter() {
             match branch_db_config {
+                // Local branches are spawned by the CLI.
+                _ if branch_db_config.is_local() => {}
                 DatabaseBranchConfig::Mongodb(mongodb_config) => {
                     let id = if let Some(id) = mongodb_config.base.id.clone() {
                         BranchDatabaseId::specified(id)
@@ -1344,6 +1346,11 @@ impl UnifiedDatabaseBranchParams {
 
         let mut branches = HashMap::new();
         for branch_db_config in config.0.iter_mut() {
+            // Local branches are spawned by the CLI.
+            if branch_db_config.is_local() {
+                continue;
+            }
+
             let (id_source, connection, migrations_config) = match branch_db_config {
                 DatabaseBranchConfig::Clickhouse(c) => (&c.base.id, &mut c.base.connection, None),
                 DatabaseBranchConfig::Pg(c) => {
@@ -1490,31 +1497,74 @@ fn read_migrations(
         return Ok(None);
     };
 
-    match config {
-        SqlBranchMigrationsConfig::Flyway { path, image } => {
-            let archive = build_migration_archive(path).map_err(|error| {
-                OperatorApiError::MigrationsRead {
-                    path: path.display().to_string(),
-                    error: error.to_string(),
-                }
+    let read_archive = |path: &std::path::Path| {
+        let archive =
+            build_migration_archive(path).map_err(|error| OperatorApiError::MigrationsRead {
+                path: path.display().to_string(),
+                error: error.to_string(),
             })?;
 
-            const LIMIT: usize = 1024 * 1024;
-
-            if archive.len() > LIMIT {
-                return Err(OperatorApiError::MigrationsTooLarge {
-                    path: path.display().to_string(),
-                    size: archive.len(),
-                    limit: LIMIT,
-                });
-            }
+        const LIMIT: usize = 1024 * 1024;
 
-            Ok(Some(MigrationsSpec::Flyway {
-                image: image.clone(),
-                archive: ByteString(archive),
-            }))
+        if archive.len() > LIMIT {
+            return Err(OperatorApiError::MigrationsTooLarge {
+                path: path.display().to_string(),
+                size: archive.len(),
+                limit: LIMIT,
+            });
         }
-    }
+
+        Ok(ByteString(archive))
+    };
+
+    let spec = match config {
+        SqlBranchMigrationsConfig::Flyway { path, image } => MigrationsSpec::Flyway {
+            image: image.clone(),
+            archive: read_archive(path)?,
+        },
+        SqlBranchMigrationsConfig::Liquibase {
+            path,
+            changelog,
+            image,
+        } => MigrationsSpec::Liquibase {
+            image: image.clone(),
+            changelog: changelog.clone(),
+            archive: read_archive(path)?,
+        },
+        SqlBranchMigrationsConfig::GolangMigrate { path, image } => MigrationsSpec::GolangMigrate {
+            image: image.clone(),
+            archive: read_archive(path)?,
+        },
+        SqlBranchMigrationsConfig::Sqlx { path, image } => MigrationsSpec::Sqlx {
+            image: image.clone(),
+            archive: read_archive(path)?,
+        },
+        SqlBranchMigrationsConfig::Alembic { path, image } => MigrationsSpec::Alembic {
+            image: image.clone(),
+            archive: read_archive(path)?,
+        },
+        SqlBranchMigrationsConfig::Atlas { path, image } => MigrationsSpec::Atlas {
+            image: image.clone(),
+            archive: read_archive(path)?,
+        },
+        // Local commands are run by the CLI once the session is up.
+        SqlBranchMigrationsConfig::Command { image: None, .. } => return Ok(None),
+        SqlBranchMigrationsConfig::Command {
+            command,
+            image: Some(image),
+            path,
+            env,
+        } => MigrationsSpec::Command {
+            image: image.clone(),
+            command: command.clone(),
+            env: env
+                .clone()
+                .unwrap_or_else(|| SqlBranchMigrationsConfig::DEFAULT_COMMAND_ENV.to_owned()),
+            archive: path.as_deref().map(read_archive).transpose()?,
+        },
+    };
+
+    Ok(Some(spec))
 }
 
 /// Builds a gzipped tar of a migration directory tree.
@@ -108,6 +108,8 @@ impl TargetCrd {
     /// - `DeploymentTarget { deployment: "nginx", container: None }` -> `deploy.nginx`;
     /// - `DeploymentTarget { deployment: "nginx", container: Some("pyrex") }` ->
     ///   `deploy.nginx.container.pyrex`;
+    /// - `DaemonSetTarget { daemon_set: "fluentd", container: None, node: Some("node-1") }` ->
+    ///   `daemonset.fluentd.node.node-1`;
     ///
     /// It's used to connect to a resource through the operator.
     ///
@@ -119,6 +121,11 @@ impl TargetCrd {
     /// `operator-crd::crd::policy::MirrordPolicySpec::target_path` and
     /// `operator-crd::crd::policy::MirrordClusterPolicySpec::target_path`).
     pub fn urlfied_name(target: &Target) -> String {
+        let node = match target {
+            Target::DaemonSet(target) => target.node.as_deref(),
+            _ => None,
+        };
+
         let (type_name, target, container) = match target {
             Target::Deployment(target) => ("deploy", &target.deployment, &target.container),
             Target::Pod(target) => ("pod", &target.pod, &target.container),
@@ -128,14 +135,22 @@ impl TargetCrd {
             Target::StatefulSet(target) => ("statefulset", &target.stateful_set, &target.container),
             Target::Service(target) => ("service", &target.service, &target.container),
             Target::ReplicaSet(target) => ("replicaset", &target.replica_set, &target.container),
+            Target::DaemonSet(target) => ("daemonset", &target.daemon_set, &target.container),
             Target::Targetless => return TARGETLESS_TARGET_NAME.to_owned(),
         };
 
-        if let Some(container) = container {
+        let mut name = if let Some(container) = container {
             format!("{}.{}.container.{}", type_name, target, container)
         } else {
             format!("{}.{}", type_name, target)
+        };
+
+        if let Some(node) = node {
+            name.push_str(".node.");
+            name.push_str(node);
         }
+
+        name
     }
 
     /// "targetless" ([`TARGETLESS_TARGET_NAME`]) if `None`,
@@ -155,6 +170,7 @@ impl TryFrom<TargetCrd> for TargetConfig {
         Ok(TargetConfig {
             path: Some(Target::try_from(crd.spec.target)?),
             namespace: crd.metadata.namespace,
+            selector: None,
         })
     }
 }
@@ -631,6 +647,11 @@ pub enum NewOperatorFeature {
     /// session just to run ping/pong.
     DiagnosticPing,
 
+    /// This operator runs SQL branch migrations with runners other than Flyway (`liquibase`,
+    /// `golang_migrate`, `sqlx`, `alembic`, `atlas` and `command`). Gated so the CLI can fail
+    /// fast on older operators, whose CRD schema would reject the unknown `migrations` variants.
+    DbBranchMigrationRunners,
+
     /// This variant is what a client sees when the operator includes a feature the client is not
     /// yet aware of, because it was introduced in a version newer than the client's.
     #[schemars(skip)]
@@ -682,6 +703,7 @@ impl Display for NewOperatorFeature {
             NewOperatorFeature::CopyTargetFilterIsolation => "copy target filter isolation",
             NewOperatorFeature::DbBranchCustomImage => "custom db branch image",
             NewOperatorFeature::DiagnosticPing => "diagnostic ping",
+            NewOperatorFeature::DbBranchMigrationRunners => "db branch migration runners",
             NewOperatorFeature::Unknown => "unknown feature",
         };
         f.write_str(name)
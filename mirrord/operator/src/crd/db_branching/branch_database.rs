@@ -96,6 +96,60 @@ pub enum MigrationsSpec {
         #[schemars(with = "String")]
         archive: ByteString,
     },
+    Liquibase {
+        /// Overrides the container image used to run the migrations.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+        /// Changelog file inside the archive. Defaults to `changelog.xml`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        changelog: Option<String>,
+        /// A gzipped tar of the migration files.
+        #[schemars(with = "String")]
+        archive: ByteString,
+    },
+    GolangMigrate {
+        /// Overrides the container image used to run the migrations.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+        /// A gzipped tar of the migration files.
+        #[schemars(with = "String")]
+        archive: ByteString,
+    },
+    Sqlx {
+        /// Overrides the container image used to run the migrations.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+        /// A gzipped tar of the migration files.
+        #[schemars(with = "String")]
+        archive: ByteString,
+    },
+    Alembic {
+        /// Container image with Alembic and the project's dependencies.
+        image: String,
+        /// A gzipped tar of the directory holding `alembic.ini`.
+        #[schemars(with = "String")]
+        archive: ByteString,
+    },
+    Atlas {
+        /// Overrides the container image used to run the migrations.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        image: Option<String>,
+        /// A gzipped tar of the migration files.
+        #[schemars(with = "String")]
+        archive: ByteString,
+    },
+    Command {
+        /// Container image to run the command in.
+        image: String,
+        /// The command and its arguments.
+        command: Vec<String>,
+        /// Name of the env var holding the branch connection string.
+        env: String,
+        /// A gzipped tar extracted to the command's working directory.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[schemars(with = "Option<String>")]
+        archive: Option<ByteString>,
+    },
 }
 
 /// Validated dialect configuration extracted from a [`BranchDatabaseSpec`].
@@ -3,7 +3,7 @@ use std::fmt;
 use k8s_openapi::{
     Resource,
     api::{
-        apps::v1::{Deployment, ReplicaSet, StatefulSet},
+        apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet},
         batch::v1::{CronJob, Job},
         core::v1::{Pod, Service},
     },
@@ -115,6 +115,12 @@ impl SessionTarget {
                 name: t.replica_set,
                 container: t.container?,
             }),
+            Target::DaemonSet(t) => Some(Self {
+                api_version: <DaemonSet as Resource>::API_VERSION.to_owned(),
+                kind: <DaemonSet as Resource>::KIND.to_owned(),
+                name: t.daemon_set,
+                container: t.container?,
+            }),
             Target::Targetless => None,
         }
     }
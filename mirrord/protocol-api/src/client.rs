@@ -2,23 +2,29 @@ use std::{
     net::SocketAddr,
     num::NonZeroUsize,
     ops::Not,
+    path::PathBuf,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
 };
 
-use futures::{FutureExt, future::Shared};
+use futures::{FutureExt, future::Shared, stream::BoxStream};
 use mirrord_protocol::{
     LogMessage,
+    file::{DirEntryInternal, OpenOptionsInternal},
     outgoing::UnixAddr,
     tcp::{
         HTTP_BODY_JSON_FILTER_VERSION, HTTP_COMPOSITE_FILTER_VERSION,
-        HTTP_HEADER_JQ_FILTER_VERSION, HTTP_METHOD_FILTER_VERSION, HttpBodyFilter, HttpFilter,
-        MIRROR_HTTP_FILTER_VERSION,
+        HTTP_HEADER_JQ_FILTER_VERSION, HTTP_METHOD_FILTER_VERSION, HTTP_WEBSOCKET_FILTER_VERSION,
+        HttpBodyFilter, HttpFilter, MIRROR_HTTP_FILTER_VERSION,
     },
 };
 use tokio::{
-    sync::{mpsc, oneshot},
+    runtime::Handle,
+    sync::{
+        mpsc::{self, error::TrySendError},
+        oneshot,
+    },
     task::{AbortHandle, JoinHandle},
 };
 use tokio_stream::wrappers::ReceiverStream;
@@ -27,8 +33,10 @@ pub use crate::client::{
     config::ClientConfig,
     connector::{Connection, ProtocolConnector},
     error::ClientError,
+    file::RemoteFile,
     request::ClientRequest,
     retrying::MirrordClientRetry,
+    udp::TunneledUdp,
 };
 use crate::{
     client::{
@@ -46,6 +54,7 @@ mod config;
 mod connector;
 mod enum_map;
 mod error;
+mod file;
 mod incoming;
 mod outbox;
 mod outgoing;
@@ -58,6 +67,7 @@ mod task;
 #[cfg(test)]
 mod test;
 mod tunnels;
+mod udp;
 
 /// Public client API of a [`mirrord_protocol`] session, possibly spanning multiple connections.
 ///
@@ -240,6 +250,12 @@ impl MirrordClient {
         }
     }
 
+    /// Creates a [`TunneledUdp`] socket, which makes outgoing UDP connections with
+    /// [`Self::connect_ip`] on demand.
+    pub fn udp_socket(&self) -> TunneledUdp {
+        TunneledUdp::new(self.clone_with_same_queue(), None)
+    }
+
     /// Makes an outgoing UNIX connection.
     ///
     /// Note that [`Fifo`]s used in the tunnel are subject to the limits set in the
@@ -293,6 +309,7 @@ impl MirrordClient {
                 }
                 HttpFilter::Method(..) => HTTP_METHOD_FILTER_VERSION.matches(version),
                 HttpFilter::HeaderJq(..) => HTTP_HEADER_JQ_FILTER_VERSION.matches(version),
+                HttpFilter::WebSocket(..) => HTTP_WEBSOCKET_FILTER_VERSION.matches(version),
                 HttpFilter::Composite { filters, .. } => {
                     HTTP_COMPOSITE_FILTER_VERSION.matches(version)
                         && filters.iter().all(|f| filter_supported(f, version))
@@ -361,6 +378,25 @@ impl MirrordClient {
         }
     }
 
+    /// Opens a remote file.
+    pub async fn open_file(
+        &self,
+        path: PathBuf,
+        open_options: OpenOptionsInternal,
+    ) -> ClientResult<RemoteFile> {
+        RemoteFile::open(self.clone_with_same_queue(), path, open_options).await
+    }
+
+    /// Creates a [`Stream`](futures::Stream) of remote directory entries.
+    ///
+    /// Entries are fetched in batches with
+    /// [`ReadDirBatchRequest`](mirrord_protocol::file::ReadDirBatchRequest), which requires
+    /// [`READDIR_BATCH_VERSION`](mirrord_protocol::file::READDIR_BATCH_VERSION). The stream ends
+    /// after the first error.
+    pub fn read_dir(&self, path: PathBuf) -> BoxStream<'static, ClientResult<DirEntryInternal>> {
+        file::read_dir(self.clone_with_same_queue(), path)
+    }
+
     /// Makes a [`SimpleRequest`].
     ///
     /// Note that the request can be transparently downgraded to fit the server's
@@ -392,6 +428,24 @@ impl MirrordClient {
             .await
             .ok();
     }
+
+    /// Synchronous version of [`Self::make_request_no_response`], for [`Drop`] implementations.
+    ///
+    /// If the queue is full, the request is sent from a spawned task.
+    pub(crate) fn make_request_no_response_on_drop<R: SimpleRequestNoResponse>(&self, request: R) {
+        let prepared = request.prepare();
+        let Err(TrySendError::Full(request)) = self
+            .request_tx
+            .try_send(ClientRequest::SimpleNoResponse(prepared))
+        else {
+            return;
+        };
+
+        if let Ok(runtime) = Handle::try_current() {
+            let request_tx = self.request_tx.clone();
+            runtime.spawn(async move { request_tx.send(request).await.ok() });
+        }
+    }
 }
 
 /// Trait guard - prevents implementing the sealed trait outside of this crate.
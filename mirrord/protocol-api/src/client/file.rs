@@ -0,0 +1,384 @@
+use std::{
+    fmt, io,
+    io::SeekFrom,
+    ops::Not,
+    path::PathBuf,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use futures::{FutureExt, StreamExt, TryStreamExt, future::BoxFuture, stream::BoxStream};
+use mirrord_protocol::{
+    Payload,
+    file::{
+        CloseDirRequest, CloseFileRequest, DirEntryInternal, FdOpenDirRequest, MetadataInternal,
+        OpenDirResponse, OpenFileRequest, OpenFileResponse, OpenOptionsInternal,
+        ReadDirBatchRequest, ReadDirBatchResponse, ReadFileResponse, ReadLimitedFileRequest,
+        SeekFileRequest, SeekFileResponse, SeekFromInternal, WriteFileRequest, WriteFileResponse,
+        WriteLimitedFileRequest, XstatRequest, XstatResponse,
+    },
+};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+use crate::client::{ClientError, MirrordClient, SimpleRequest, error::ClientResult};
+
+/// Upper limit for the amount of data requested with a single [`ReadLimitedFileRequest`].
+const READ_LIMIT: usize = 64 * 1024;
+
+/// Amount of entries requested with a single [`ReadDirBatchRequest`].
+const READ_DIR_BATCH_SIZE: usize = 128;
+
+/// File opened in the [`mirrord_protocol`] server.
+///
+/// Implements [`AsyncRead`], [`AsyncWrite`] and [`AsyncSeek`]. Each read and write makes a single
+/// request, so consider wrapping this in [`tokio::io::BufReader`] or [`tokio::io::BufWriter`].
+///
+/// # Position
+///
+/// The file position is tracked locally, and reads and writes are positional
+/// ([`ReadLimitedFileRequest`] and [`WriteLimitedFileRequest`]). The exception are files opened in
+/// append mode, where writes always go to the end of the file ([`WriteFileRequest`]), and do not
+/// move the position.
+///
+/// # Closing
+///
+/// The remote file is closed when this struct is dropped. The remote file descriptor does not
+/// survive reconnects, all operations after a reconnect fail with
+/// [`ClientError::LostFileDescriptor`].
+///
+/// Acquire with [`MirrordClient::open_file`].
+pub struct RemoteFile {
+    client: MirrordClient,
+    /// Remote file descriptor.
+    fd: u64,
+    /// Whether the file was opened in append mode.
+    append: bool,
+    position: u64,
+    /// Operation that was started in one of the `poll_*` methods, and is not yet complete.
+    pending: Option<Pending>,
+}
+
+/// Operation of a [`RemoteFile`] that is in progress.
+enum Pending {
+    Read(BoxFuture<'static, ClientResult<ReadFileResponse>>),
+    Write(BoxFuture<'static, ClientResult<WriteFileResponse>>),
+    Seek(BoxFuture<'static, ClientResult<SeekFileResponse>>),
+}
+
+impl RemoteFile {
+    pub(super) async fn open(
+        client: MirrordClient,
+        path: PathBuf,
+        open_options: OpenOptionsInternal,
+    ) -> ClientResult<Self> {
+        let OpenFileResponse { fd } = client
+            .make_request(OpenFileRequest { path, open_options })
+            .await?;
+
+        Ok(Self {
+            client,
+            fd,
+            append: open_options.append,
+            position: 0,
+            pending: None,
+        })
+    }
+
+    /// Returns the remote file descriptor.
+    pub fn fd(&self) -> u64 {
+        self.fd
+    }
+
+    /// Fetches the metadata of this file.
+    pub async fn metadata(&self) -> ClientResult<MetadataInternal> {
+        let XstatResponse { metadata } = self
+            .client
+            .make_request(XstatRequest {
+                path: None,
+                fd: Some(self.fd),
+                follow_symlink: true,
+            })
+            .await?;
+
+        Ok(metadata)
+    }
+
+    /// Makes a request that does not borrow this file, so that it can be stored in
+    /// [`Self::pending`].
+    fn request<R: SimpleRequest + 'static>(
+        &self,
+        request: R,
+    ) -> BoxFuture<'static, ClientResult<R::Response>> {
+        let client = self.client.clone_with_same_queue();
+        async move { client.make_request(request).await }.boxed()
+    }
+
+    /// Drives the [`Self::pending`] operation to completion.
+    ///
+    /// Used when the caller stopped polling the operation before it completed, and started a
+    /// different one. Results of writes and seeks are applied to the position, results of reads
+    /// are dropped.
+    fn poll_interrupted(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        match self.pending.as_mut() {
+            None => {}
+            Some(Pending::Read(future)) => {
+                let _ = ready!(future.poll_unpin(cx));
+            }
+            Some(Pending::Write(future)) => {
+                if let Ok(response) = ready!(future.poll_unpin(cx))
+                    && self.append.not()
+                {
+                    self.position += response.written_amount;
+                }
+            }
+            Some(Pending::Seek(future)) => {
+                if let Ok(response) = ready!(future.poll_unpin(cx)) {
+                    self.position = response.result_offset;
+                }
+            }
+        }
+
+        self.pending = None;
+        Poll::Ready(())
+    }
+}
+
+impl AsyncRead for RemoteFile {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            match this.pending.as_mut() {
+                None if buf.remaining() == 0 => return Poll::Ready(Ok(())),
+                None => {
+                    let future = this.request(ReadLimitedFileRequest {
+                        remote_fd: this.fd,
+                        buffer_size: buf.remaining().min(READ_LIMIT) as u64,
+                        start_from: this.position,
+                    });
+                    this.pending = Some(Pending::Read(future));
+                }
+                Some(Pending::Read(future)) => {
+                    let result = ready!(future.poll_unpin(cx));
+                    this.pending = None;
+
+                    let mut bytes = result.map_err(io::Error::other)?.bytes.0;
+                    // The read might have been started with a larger buffer.
+                    // As reads are positional, the rest is not lost.
+                    bytes.truncate(buf.remaining());
+                    buf.put_slice(&bytes);
+                    this.position += bytes.len() as u64;
+
+                    return Poll::Ready(Ok(()));
+                }
+                Some(..) => ready!(this.poll_interrupted(cx)),
+            }
+        }
+    }
+}
+
+impl AsyncWrite for RemoteFile {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        loop {
+            match this.pending.as_mut() {
+                None if buf.is_empty() => return Poll::Ready(Ok(0)),
+                None => {
+                    let write_bytes = Payload::from(buf.to_vec());
+                    let future = if this.append {
+                        this.request(WriteFileRequest {
+                            fd: this.fd,
+                            write_bytes,
+                        })
+                    } else {
+                        this.request(WriteLimitedFileRequest {
+                            remote_fd: this.fd,
+                            start_from: this.position,
+                            write_bytes,
+                        })
+                    };
+                    this.pending = Some(Pending::Write(future));
+                }
+                Some(Pending::Write(future)) => {
+                    let result = ready!(future.poll_unpin(cx));
+                    this.pending = None;
+
+                    let written = result.map_err(io::Error::other)?.written_amount;
+                    if this.append.not() {
+                        this.position += written;
+                    }
+
+                    return Poll::Ready(Ok(written as usize));
+                }
+                Some(..) => ready!(this.poll_interrupted(cx)),
+            }
+        }
+    }
+
+    /// Writes are not buffered, this only completes an interrupted operation.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.get_mut().poll_interrupted(cx));
+        Poll::Ready(Ok(()))
+    }
+
+    /// The remote file is closed when [`RemoteFile`] is dropped, this only completes an
+    /// interrupted operation.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl AsyncSeek for RemoteFile {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+
+        if this.pending.is_some() {
+            return Err(io::Error::other(
+                "other file operation is pending, call poll_complete before start_seek",
+            ));
+        }
+
+        match position {
+            SeekFrom::Start(offset) => this.position = offset,
+            SeekFrom::Current(offset) => {
+                this.position = this.position.checked_add_signed(offset).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "invalid seek to a negative or overflowing position",
+                    )
+                })?;
+            }
+            // We don't know the size of the file.
+            SeekFrom::End(offset) => {
+                let future = this.request(SeekFileRequest {
+                    fd: this.fd,
+                    seek_from: SeekFromInternal::End(offset),
+                });
+                this.pending = Some(Pending::Seek(future));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        let this = self.get_mut();
+
+        match this.pending.as_mut() {
+            None => {}
+            Some(Pending::Seek(future)) => {
+                let result = ready!(future.poll_unpin(cx));
+                this.pending = None;
+                this.position = result.map_err(io::Error::other)?.result_offset;
+            }
+            Some(..) => ready!(this.poll_interrupted(cx)),
+        }
+
+        Poll::Ready(Ok(this.position))
+    }
+}
+
+impl Drop for RemoteFile {
+    fn drop(&mut self) {
+        self.client
+            .make_request_no_response_on_drop(CloseFileRequest { fd: self.fd });
+    }
+}
+
+impl fmt::Debug for RemoteFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pending = self.pending.as_ref().map(|pending| match pending {
+            Pending::Read(..) => "read",
+            Pending::Write(..) => "write",
+            Pending::Seek(..) => "seek",
+        });
+
+        f.debug_struct("RemoteFile")
+            .field("fd", &self.fd)
+            .field("append", &self.append)
+            .field("position", &self.position)
+            .field("pending", &pending)
+            .finish()
+    }
+}
+
+/// Directory opened in the [`mirrord_protocol`] server.
+///
+/// Closed when dropped.
+struct RemoteDir {
+    client: MirrordClient,
+    /// Remote directory descriptor.
+    fd: u64,
+}
+
+impl RemoteDir {
+    async fn open(client: MirrordClient, path: PathBuf) -> ClientResult<Self> {
+        let file = RemoteFile::open(
+            client,
+            path,
+            OpenOptionsInternal {
+                read: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        // The file is closed when dropped, the directory stream does not depend on it.
+        let OpenDirResponse { fd } = file
+            .client
+            .make_request(FdOpenDirRequest { remote_fd: file.fd })
+            .await?;
+
+        Ok(Self {
+            client: file.client.clone_with_same_queue(),
+            fd,
+        })
+    }
+}
+
+impl Drop for RemoteDir {
+    fn drop(&mut self) {
+        self.client
+            .make_request_no_response_on_drop(CloseDirRequest { remote_fd: self.fd });
+    }
+}
+
+/// Implements [`MirrordClient::read_dir`].
+pub(super) fn read_dir(
+    client: MirrordClient,
+    path: PathBuf,
+) -> BoxStream<'static, ClientResult<DirEntryInternal>> {
+    let read_batch = |dir: Option<RemoteDir>| async move {
+        let Some(dir) = dir else {
+            return Ok(None);
+        };
+
+        let ReadDirBatchResponse { dir_entries, .. } = dir
+            .client
+            .make_request(ReadDirBatchRequest {
+                remote_fd: dir.fd,
+                amount: READ_DIR_BATCH_SIZE,
+            })
+            .await?;
+
+        // The server returns a partial batch only when it reaches the end of the directory.
+        let dir = (dir_entries.len() == READ_DIR_BATCH_SIZE).then_some(dir);
+        let entries = futures::stream::iter(dir_entries.into_iter().map(Ok));
+
+        Ok::<_, ClientError>(Some((entries, dir)))
+    };
+
+    futures::stream::once(RemoteDir::open(client, path))
+        .map_ok(move |dir| futures::stream::try_unfold(Some(dir), read_batch).try_flatten())
+        .try_flatten()
+        .boxed()
+}
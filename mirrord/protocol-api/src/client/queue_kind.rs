@@ -16,6 +16,7 @@ pub enum QueueKind {
     Dns,
     ReverseDns,
     Files,
+    DnsQuery,
 }
 
 impl EnumKey for QueueKind {
@@ -5,8 +5,8 @@ use mirrord_protocol::{LogMessage, outgoing::UnixAddr, tcp::HttpFilter};
 
 use crate::{
     client::{
-        ClientError, MirrordClient, SimpleRequest, error::ClientResult, incoming::IncomingMode,
-        outgoing::OutgoingMode,
+        ClientError, MirrordClient, SimpleRequest, TunneledUdp, error::ClientResult,
+        incoming::IncomingMode, outgoing::OutgoingMode,
     },
     traffic::{TunneledIncoming, TunneledOutgoing},
 };
@@ -59,6 +59,12 @@ pub trait MirrordClientRetry {
         timeout: Duration,
     ) -> impl Future<Output = ClientResult<TunneledOutgoing<UnixAddr>>> + Send;
 
+    /// Creates a [`TunneledUdp`] socket, which makes outgoing UDP connections with
+    /// [`MirrordClientRetry::connect_ip_retry`] on demand.
+    ///
+    /// See this trait's doc for more context.
+    fn udp_socket_retry(&self, timeout: Duration) -> TunneledUdp;
+
     /// Makes a retrying [`SimpleRequest`].
     ///
     /// See this trait's doc for more context.
@@ -106,6 +112,10 @@ impl MirrordClientRetry for MirrordClient {
         retry_op(|| self.connect_unix(addr.clone()), timeout).await
     }
 
+    fn udp_socket_retry(&self, timeout: Duration) -> TunneledUdp {
+        TunneledUdp::new(self.clone_with_same_queue(), Some(timeout))
+    }
+
     async fn make_request_retry<R: SimpleRequest + Clone + Sync>(
         &self,
         request: R,
@@ -4,9 +4,9 @@ use mirrord_protocol::{
     ClientMessage, DaemonMessage, FileRequest, FileResponse, GetEnvVarsRequest, RemoteEnvVars,
     RemoteResult,
     dns::{
-        ADDRINFO_V2_VERSION, DnsLookup, GetAddrInfoRequest, GetAddrInfoRequestV2,
-        GetAddrInfoResponse, REVERSE_DNS_VERSION, ReverseDnsLookupRequest,
-        ReverseDnsLookupResponse,
+        ADDRINFO_V2_VERSION, DNS_QUERY_VERSION, DnsLookup, DnsQueryRequest, DnsQueryResponse,
+        DnsRecord, GetAddrInfoRequest, GetAddrInfoRequestV2, GetAddrInfoResponse,
+        REVERSE_DNS_VERSION, ReverseDnsLookupRequest, ReverseDnsLookupResponse,
     },
     file::{
         AccessFileRequest, AccessFileResponse, COPYFILE_VERSION, CloseDirRequest, CloseFileRequest,
@@ -225,6 +225,42 @@ impl SimpleRequest for ReverseDnsLookupRequest {
     }
 }
 
+impl SimpleRequest for DnsQueryRequest {
+    type Response = Vec<DnsRecord>;
+
+    fn prepare(self, version: &semver::Version) -> ClientResult<Prepared<Self>> {
+        if DNS_QUERY_VERSION.matches(version).not() {
+            return Err(ClientError::NotSupported);
+        }
+
+        let (result_tx, result_rx) = oneshot::channel();
+        let result_handler: Box<dyn ResultHandler> =
+            Box::new(|result: ClientResult<DaemonMessage>| -> TaskResult<()> {
+                let result = match result {
+                    Err(error) => Err(error),
+                    Ok(DaemonMessage::DnsQuery(DnsQueryResponse(Ok(records)))) => Ok(records),
+                    Ok(DaemonMessage::DnsQuery(DnsQueryResponse(Err(error)))) => {
+                        Err(ClientError::Response(error))
+                    }
+                    Ok(other) => return Err(TaskError::unexpected_message(&other)),
+                };
+                result_tx.send(result).ok();
+                Ok(())
+            });
+
+        Ok(Prepared {
+            request: ClientMessage::DnsQuery(self),
+            queue_kind: QueueKind::DnsQuery,
+            result_handler,
+            result_rx,
+        })
+    }
+
+    fn is_stateless(&self) -> bool {
+        true
+    }
+}
+
 /// Trait for file requests that trigger a response from the server.
 pub trait FileRequestWithResponse: FileRequestExt + Sized {
     type Response;
@@ -352,11 +352,13 @@ impl<C: ProtocolConnector> ClientTask<C> {
             mut message @ (DaemonMessage::GetAddrInfoResponse(..)
             | DaemonMessage::GetEnvVarsResponse(..)
             | DaemonMessage::ReverseDnsLookup(..)
+            | DaemonMessage::DnsQuery(..)
             | DaemonMessage::File(..)) => {
                 let queue_kind = match &message {
                     DaemonMessage::GetAddrInfoResponse(..) => QueueKind::Dns,
                     DaemonMessage::GetEnvVarsResponse(..) => QueueKind::EnvVars,
                     DaemonMessage::ReverseDnsLookup(..) => QueueKind::ReverseDns,
+                    DaemonMessage::DnsQuery(..) => QueueKind::DnsQuery,
                     DaemonMessage::File(..) => QueueKind::Files,
                     _ => unreachable!(),
                 };
@@ -375,7 +377,8 @@ impl<C: ProtocolConnector> ClientTask<C> {
             message @ (DaemonMessage::SwitchProtocolVersionResponse(..)
             | DaemonMessage::Vpn(..)
             | DaemonMessage::PauseTarget(..)
-            | DaemonMessage::SeqpacketOutgoing(..)) => {
+            | DaemonMessage::SeqpacketOutgoing(..)
+            | DaemonMessage::SessionResume(..)) => {
                 return Err(TaskError::unexpected_message(&message));
             }
         };
@@ -496,6 +499,8 @@ impl<C: ProtocolConnector> ClientTask<C> {
                 | DaemonMessage::PauseTarget(_)
                 | DaemonMessage::Vpn(_)
                 | DaemonMessage::ReverseDnsLookup(_)
+                | DaemonMessage::DnsQuery(_)
+                | DaemonMessage::SessionResume(_)
                 | DaemonMessage::Pong) => return Err(TaskError::unexpected_message(&message)),
             }
         };
@@ -1,11 +1,12 @@
 use std::{
+    io::SeekFrom,
     net::{Ipv4Addr, SocketAddr},
     num::NonZeroUsize,
     ops::Not,
     time::Duration,
 };
 
-use futures::{SinkExt, StreamExt};
+use futures::{SinkExt, StreamExt, TryStreamExt};
 use mirrord_protocol::{
     ClientMessage, DaemonMessage, FileRequest, FileResponse, GetEnvVarsRequest, RemoteEnvVars,
     dns::{
@@ -13,10 +14,13 @@ use mirrord_protocol::{
         LookupRecord, ReverseDnsLookupRequest, ReverseDnsLookupResponse, SockType,
     },
     file::{
-        AccessFileRequest, AccessFileResponse, CloseDirRequest, CloseFileRequest, FdOpenDirRequest,
-        FsMetadataInternal, FsMetadataInternalV2, OpenDirResponse, OpenFileRequest,
-        OpenFileResponse, OpenOptionsInternal, ReadDirRequest, ReadDirResponse, STATFS_V2_VERSION,
-        STATFS_VERSION, StatFsRequestV2, XstatFsResponse, XstatFsResponseV2,
+        AccessFileRequest, AccessFileResponse, CloseDirRequest, CloseFileRequest, DirEntryInternal,
+        FdOpenDirRequest, FsMetadataInternal, FsMetadataInternalV2, OpenDirResponse,
+        OpenFileRequest, OpenFileResponse, OpenOptionsInternal, ReadDirBatchRequest,
+        ReadDirBatchResponse, ReadDirRequest, ReadDirResponse, ReadFileResponse,
+        ReadLimitedFileRequest, STATFS_V2_VERSION, STATFS_VERSION, SeekFileRequest,
+        SeekFileResponse, SeekFromInternal, StatFsRequestV2, WriteFileResponse,
+        WriteLimitedFileRequest, XstatFsResponse, XstatFsResponseV2,
     },
     outgoing::{
         DaemonConnect, DaemonConnectV2,
@@ -25,13 +29,14 @@ use mirrord_protocol::{
     tcp::{DaemonTcp, LayerTcpSteal, NewTcpConnectionV1, StealType},
 };
 use rstest::rstest;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
 use crate::client::{
     ClientConfig, ClientError, MirrordClient, MirrordClientRetry, error::TaskError,
     incoming::IncomingMode, outgoing::OutgoingMode, test::connector::TestConnector,
 };
 
-mod connector;
+pub(super) mod connector;
 
 /// Verifies [`GetAddrInfoRequestV2`] handling.
 #[rstest]
@@ -424,6 +429,202 @@ async fn file_ops_compat(
     tokio::join!(client_fut, server_fut);
 }
 
+/// Verifies [`RemoteFile`](crate::client::RemoteFile) reads, writes, seeks and closing on drop.
+#[tokio::test]
+async fn remote_file() {
+    let (connector, mut acceptor) = TestConnector::new_pair();
+
+    let client_fut = async {
+        let client = MirrordClient::new(
+            connector,
+            ClientConfig::cli(),
+            NonZeroUsize::new(32).unwrap(),
+        )
+        .await
+        .unwrap();
+        let mut file = client
+            .open_file(
+                "/some/file".into(),
+                OpenOptionsInternal {
+                    read: true,
+                    write: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        file.write_all(b"hello").await.unwrap();
+        assert_eq!(file.seek(SeekFrom::End(0)).await.unwrap(), 5);
+        assert_eq!(file.seek(SeekFrom::Current(-5)).await.unwrap(), 0);
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).await.unwrap();
+        assert_eq!(contents, "hello");
+    };
+
+    let server_fut = async {
+        let mut server = acceptor.accept(mirrord_protocol::VERSION.clone()).await;
+
+        match server.stream.next().await.unwrap() {
+            ClientMessage::FileRequest(FileRequest::Open(..)) => {}
+            other => panic!("unexpected message: {other:?}"),
+        }
+        server
+            .sink
+            .send(Ok(DaemonMessage::File(FileResponse::Open(Ok(
+                OpenFileResponse { fd: 0 },
+            )))))
+            .await
+            .unwrap();
+        match server.stream.next().await.unwrap() {
+            ClientMessage::FileRequest(FileRequest::WriteLimited(WriteLimitedFileRequest {
+                remote_fd: 0,
+                start_from: 0,
+                write_bytes,
+            })) => assert_eq!(write_bytes.0, b"hello".as_slice()),
+            other => panic!("unexpected message: {other:?}"),
+        }
+        server
+            .sink
+            .send(Ok(DaemonMessage::File(FileResponse::WriteLimited(Ok(
+                WriteFileResponse { written_amount: 5 },
+            )))))
+            .await
+            .unwrap();
+        match server.stream.next().await.unwrap() {
+            ClientMessage::FileRequest(FileRequest::Seek(SeekFileRequest {
+                fd: 0,
+                seek_from: SeekFromInternal::End(0),
+            })) => {}
+            other => panic!("unexpected message: {other:?}"),
+        }
+        server
+            .sink
+            .send(Ok(DaemonMessage::File(FileResponse::Seek(Ok(
+                SeekFileResponse { result_offset: 5 },
+            )))))
+            .await
+            .unwrap();
+        for (start_from, bytes) in [(0, &b"hello"[..]), (5, &b""[..])] {
+            match server.stream.next().await.unwrap() {
+                ClientMessage::FileRequest(FileRequest::ReadLimited(ReadLimitedFileRequest {
+                    remote_fd: 0,
+                    start_from: requested,
+                    ..
+                })) => assert_eq!(requested, start_from),
+                other => panic!("unexpected message: {other:?}"),
+            }
+            server
+                .sink
+                .send(Ok(DaemonMessage::File(FileResponse::ReadLimited(Ok(
+                    ReadFileResponse {
+                        bytes: bytes.to_vec().into(),
+                        read_amount: bytes.len() as u64,
+                    },
+                )))))
+                .await
+                .unwrap();
+        }
+        match server.stream.next().await.unwrap() {
+            ClientMessage::FileRequest(FileRequest::Close(CloseFileRequest { fd: 0 })) => {}
+            other => panic!("unexpected message: {other:?}"),
+        }
+        assert!(server.stream.next().await.is_none());
+    };
+
+    tokio::join!(client_fut, server_fut);
+}
+
+/// Verifies that [`MirrordClient::read_dir`] reads entries in batches and closes the directory.
+#[tokio::test]
+async fn read_dir() {
+    let (connector, mut acceptor) = TestConnector::new_pair();
+
+    let client_fut = async {
+        let client = MirrordClient::new(
+            connector,
+            ClientConfig::cli(),
+            NonZeroUsize::new(32).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let names = client
+            .read_dir("/some/dir".into())
+            .map_ok(|entry| entry.name)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(names, ["a", "b"]);
+    };
+
+    let server_fut = async {
+        let mut server = acceptor.accept(mirrord_protocol::VERSION.clone()).await;
+
+        match server.stream.next().await.unwrap() {
+            ClientMessage::FileRequest(FileRequest::Open(..)) => {}
+            other => panic!("unexpected message: {other:?}"),
+        }
+        server
+            .sink
+            .send(Ok(DaemonMessage::File(FileResponse::Open(Ok(
+                OpenFileResponse { fd: 0 },
+            )))))
+            .await
+            .unwrap();
+        match server.stream.next().await.unwrap() {
+            ClientMessage::FileRequest(FileRequest::FdOpenDir(FdOpenDirRequest {
+                remote_fd: 0,
+            })) => {}
+            other => panic!("unexpected message: {other:?}"),
+        }
+        server
+            .sink
+            .send(Ok(DaemonMessage::File(FileResponse::OpenDir(Ok(
+                OpenDirResponse { fd: 1 },
+            )))))
+            .await
+            .unwrap();
+        match server.stream.next().await.unwrap() {
+            ClientMessage::FileRequest(FileRequest::Close(CloseFileRequest { fd: 0 })) => {}
+            other => panic!("unexpected message: {other:?}"),
+        }
+        match server.stream.next().await.unwrap() {
+            ClientMessage::FileRequest(FileRequest::ReadDirBatch(ReadDirBatchRequest {
+                remote_fd: 1,
+                ..
+            })) => {}
+            other => panic!("unexpected message: {other:?}"),
+        }
+        let dir_entries = ["a", "b"]
+            .into_iter()
+            .enumerate()
+            .map(|(position, name)| DirEntryInternal {
+                inode: position as u64,
+                position: position as u64,
+                name: name.into(),
+                file_type: 0,
+            })
+            .collect();
+        server
+            .sink
+            .send(Ok(DaemonMessage::File(FileResponse::ReadDirBatch(Ok(
+                ReadDirBatchResponse { fd: 1, dir_entries },
+            )))))
+            .await
+            .unwrap();
+        match server.stream.next().await.unwrap() {
+            ClientMessage::FileRequest(FileRequest::CloseDir(CloseDirRequest { remote_fd: 1 })) => {
+            }
+            other => panic!("unexpected message: {other:?}"),
+        }
+        assert!(server.stream.next().await.is_none());
+    };
+
+    tokio::join!(client_fut, server_fut);
+}
+
 /// Verifies behavior of [`MirrordClient`] when the connection to the server is lost.
 #[rstest]
 #[tokio::test]
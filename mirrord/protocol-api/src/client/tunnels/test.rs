@@ -1,4 +1,4 @@
-use std::{num::NonZeroUsize, ops::Not, time::Duration};
+use std::{net::SocketAddr, num::NonZeroUsize, ops::Not, time::Duration};
 
 use bytes::Bytes;
 use futures::{SinkExt, StreamExt};
@@ -6,7 +6,11 @@ use http_body::Frame;
 use http_body_util::{BodyExt, Empty, Full, StreamBody, combinators::BoxBody};
 use hyper::{HeaderMap, StatusCode};
 use mirrord_protocol::{
-    ClientMessage, Payload,
+    ClientMessage, ConnectionId, DaemonMessage, Payload,
+    outgoing::{
+        DaemonConnect, DaemonConnectV2, DaemonRead, LayerWrite, SocketAddress,
+        udp::{DaemonUdpOutgoing, LayerUdpOutgoing},
+    },
     tcp::{
         ChunkedRequestBodyV1, ChunkedResponse, HttpResponse, InternalHttpBodyFrame,
         InternalHttpBodyNew, InternalHttpRequest, InternalHttpResponse, LayerTcpSteal, TcpData,
@@ -17,7 +21,11 @@ use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 
 use crate::{
-    client::tunnels::{TrafficTunnels, TunnelId, TunnelType},
+    client::{
+        ClientConfig, MirrordClient, MirrordClientRetry,
+        test::connector::{TestConnector, TestServer},
+        tunnels::{TrafficTunnels, TunnelId, TunnelType},
+    },
     traffic::{TunneledData, TunneledRequest},
 };
 
@@ -614,3 +622,160 @@ async fn http_request_body_after_close() {
 
     assert_tunnels_idle(&mut tunnels).await;
 }
+
+/// Expects an outgoing UDP connect request, and accepts it with the given connection id.
+///
+/// Returns the peer address.
+async fn accept_udp_connect(server: &mut TestServer, connection_id: ConnectionId) -> SocketAddr {
+    let connect = match server.stream.next().await.unwrap() {
+        ClientMessage::UdpOutgoing(LayerUdpOutgoing::ConnectV2(connect)) => connect,
+        other => panic!("unexpected message: {other:?}"),
+    };
+    let SocketAddress::Ip(peer) = connect.remote_address else {
+        panic!("unexpected address: {:?}", connect.remote_address);
+    };
+
+    server
+        .sink
+        .send(Ok(DaemonMessage::UdpOutgoing(
+            DaemonUdpOutgoing::ConnectV2(DaemonConnectV2 {
+                uid: connect.uid,
+                connect: Ok(DaemonConnect {
+                    connection_id,
+                    remote_address: connect.remote_address,
+                    local_address: "127.0.0.1:2136".parse::<SocketAddr>().unwrap().into(),
+                }),
+            }),
+        )))
+        .await
+        .unwrap();
+
+    peer
+}
+
+/// Expects a datagram sent through the given outgoing UDP connection.
+async fn expect_udp_write(server: &mut TestServer, connection_id: ConnectionId, data: Bytes) {
+    assert_eq!(
+        server.stream.next().await.unwrap(),
+        ClientMessage::UdpOutgoing(LayerUdpOutgoing::Write(LayerWrite {
+            connection_id,
+            bytes: data.into(),
+        })),
+    );
+}
+
+/// Sends a datagram through the given outgoing UDP connection.
+async fn send_udp_read(server: &mut TestServer, connection_id: ConnectionId, data: Bytes) {
+    server
+        .sink
+        .send(Ok(DaemonMessage::UdpOutgoing(DaemonUdpOutgoing::Read(Ok(
+            DaemonRead {
+                connection_id,
+                bytes: data.into(),
+            },
+        )))))
+        .await
+        .unwrap();
+}
+
+/// Verifies that [`TunneledUdp`](crate::client::TunneledUdp) maintains one tunnel per peer,
+/// and tags received datagrams with the peer address.
+#[tokio::test]
+async fn udp_send_to_recv_from() {
+    let dns: SocketAddr = "10.0.0.10:53".parse().unwrap();
+    let metrics: SocketAddr = "10.0.0.20:8125".parse().unwrap();
+    let (connector, mut acceptor) = TestConnector::new_pair();
+
+    let client_fut = async {
+        let client = MirrordClient::new(
+            connector,
+            ClientConfig::cli(),
+            NonZeroUsize::new(32).unwrap(),
+        )
+        .await
+        .unwrap();
+        let mut socket = client.udp_socket();
+
+        socket.send_to(PAYLOAD, dns).await.unwrap();
+        socket.send_to(PAYLOAD, metrics).await.unwrap();
+        socket.send_to(PAYLOAD, dns).await.unwrap();
+        // Dropped, should not produce any message.
+        socket.send_to(Bytes::new(), dns).await.unwrap();
+
+        let mut received = vec![
+            socket.recv_from().await.unwrap(),
+            socket.recv_from().await.unwrap(),
+        ];
+        received.sort_by_key(|(_, peer)| *peer);
+        assert_eq!(
+            received,
+            vec![
+                (Bytes::from_static(b"dns"), dns),
+                (Bytes::from_static(b"metrics"), metrics),
+            ]
+        );
+
+        socket.close(dns);
+        socket.close(metrics);
+        assert!(socket.recv_from().await.is_none());
+    };
+
+    let server_fut = async {
+        let mut server = acceptor.accept(mirrord_protocol::VERSION.clone()).await;
+
+        assert_eq!(accept_udp_connect(&mut server, 0).await, dns);
+        expect_udp_write(&mut server, 0, PAYLOAD).await;
+        assert_eq!(accept_udp_connect(&mut server, 1).await, metrics);
+        expect_udp_write(&mut server, 1, PAYLOAD).await;
+        expect_udp_write(&mut server, 0, PAYLOAD).await;
+
+        send_udp_read(&mut server, 1, Bytes::from_static(b"metrics")).await;
+        send_udp_read(&mut server, 0, Bytes::from_static(b"dns")).await;
+
+        server
+    };
+
+    tokio::join!(client_fut, server_fut);
+}
+
+/// Verifies that [`TunneledUdp`](crate::client::TunneledUdp) acquired with
+/// [`MirrordClientRetry::udp_socket_retry`] makes the tunnel again after a reconnect.
+#[tokio::test]
+async fn udp_retry_after_reconnect() {
+    let dns: SocketAddr = "10.0.0.10:53".parse().unwrap();
+    let (connector, mut acceptor) = TestConnector::new_pair();
+
+    let client_fut = async {
+        let client = MirrordClient::new(
+            connector,
+            ClientConfig::cli(),
+            NonZeroUsize::new(32).unwrap(),
+        )
+        .await
+        .unwrap();
+        let mut socket = client.udp_socket_retry(Duration::from_millis(500));
+
+        socket.send_to(PAYLOAD, dns).await.unwrap();
+        // Tunnel is lost with the connection.
+        assert!(socket.recv_from().await.is_none());
+
+        socket.send_to(PAYLOAD, dns).await.unwrap();
+        assert_eq!(socket.recv_from().await.unwrap(), (PAYLOAD, dns));
+    };
+
+    let server_fut = async {
+        let mut server = acceptor.accept(mirrord_protocol::VERSION.clone()).await;
+        assert_eq!(accept_udp_connect(&mut server, 0).await, dns);
+        expect_udp_write(&mut server, 0, PAYLOAD).await;
+        drop(server);
+
+        let mut server = acceptor.accept(mirrord_protocol::VERSION.clone()).await;
+        assert_eq!(accept_udp_connect(&mut server, 0).await, dns);
+        expect_udp_write(&mut server, 0, PAYLOAD).await;
+        send_udp_read(&mut server, 0, PAYLOAD).await;
+
+        server
+    };
+
+    tokio::join!(client_fut, server_fut);
+}
@@ -0,0 +1,99 @@
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use tokio_stream::StreamMap;
+
+use crate::{
+    client::{MirrordClient, MirrordClientRetry, error::ClientResult, outgoing::OutgoingMode},
+    fifo::{FifoSink, FifoStream},
+};
+
+/// UDP socket tunneled through [`mirrord_protocol`], with `send_to`/`recv_from` semantics.
+///
+/// [`mirrord_protocol`] only supports connected UDP sockets, so this socket maintains one outgoing
+/// UDP tunnel per peer address (see [`MirrordClient::connect_ip`]). A tunnel is made on the first
+/// datagram sent to its peer, and made again when the previous one was closed (e.g. after the
+/// client reconnected to the server).
+///
+/// Datagrams can only be received from peers to which this socket sent something.
+///
+/// Acquire with [`MirrordClient::udp_socket`] or [`MirrordClientRetry::udp_socket_retry`].
+#[derive(Debug)]
+pub struct TunneledUdp {
+    client: MirrordClient,
+    /// If set, tunnels are made with [`MirrordClientRetry::connect_ip_retry`].
+    retry_timeout: Option<Duration>,
+    /// Outbound halves of the tunnels.
+    sinks: HashMap<SocketAddr, FifoSink<Bytes>>,
+    /// Inbound halves of the tunnels.
+    ///
+    /// Finished streams are removed by the [`StreamMap`].
+    streams: StreamMap<SocketAddr, FifoStream<Bytes>>,
+}
+
+impl TunneledUdp {
+    pub(super) fn new(client: MirrordClient, retry_timeout: Option<Duration>) -> Self {
+        Self {
+            client,
+            retry_timeout,
+            sinks: Default::default(),
+            streams: Default::default(),
+        }
+    }
+
+    /// Sends a datagram to the given peer.
+    ///
+    /// Fails only when a new tunnel to the peer cannot be made. Like with a regular UDP socket,
+    /// success does not mean that the datagram was delivered.
+    ///
+    /// Empty datagrams cannot be sent through [`mirrord_protocol`], and are silently dropped.
+    pub async fn send_to(&mut self, data: Bytes, peer: SocketAddr) -> ClientResult<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(sink) = self.sinks.get_mut(&peer) {
+            if sink.send(data.clone()).await.is_ok() {
+                return Ok(());
+            }
+            self.close(peer);
+        }
+
+        let tunnel = match self.retry_timeout {
+            Some(timeout) => {
+                self.client
+                    .connect_ip_retry(peer, OutgoingMode::Udp, timeout)
+                    .await?
+            }
+            None => self.client.connect_ip(peer, OutgoingMode::Udp).await?,
+        };
+
+        let mut sink = tunnel.data.sink;
+        // The new tunnel can only be closed if we've already lost the connection again.
+        // The datagram is dropped then, and the tunnel will be made again on the next send.
+        let _ = sink.send(data).await;
+        self.sinks.insert(peer, sink);
+        self.streams.insert(peer, tunnel.data.stream);
+
+        Ok(())
+    }
+
+    /// Receives a datagram from any of the peers, along with the peer address.
+    ///
+    /// Returns [`None`] when there are no open tunnels, e.g. when nothing was sent yet, or the
+    /// connection with the server was lost. Sending another datagram makes the tunnel again.
+    pub async fn recv_from(&mut self) -> Option<(Bytes, SocketAddr)> {
+        let (peer, data) = self.streams.next().await?;
+        Some((data, peer))
+    }
+
+    /// Closes the tunnel to the given peer, if there is one.
+    ///
+    /// Tunnels are kept open until this socket is dropped, so this should be used to free the
+    /// remote resources of peers with whom we no longer talk.
+    pub fn close(&mut self, peer: SocketAddr) {
+        self.sinks.remove(&peer);
+        self.streams.remove(&peer);
+    }
+}
@@ -256,7 +256,9 @@ impl FileResponseExt for DaemonMessage {
             | Self::SwitchProtocolVersionResponse(_)
             | Self::Vpn(_)
             | Self::OperatorPing(_)
-            | Self::ReverseDnsLookup(_) => None,
+            | Self::ReverseDnsLookup(_)
+            | Self::DnsQuery(_)
+            | Self::SessionResume(_) => None,
         }
     }
 }
@@ -7,8 +7,8 @@ use std::{
     marker::PhantomData,
     ops::Not,
     sync::{
-        Arc, Mutex,
-        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
     },
     task::{Context, Poll},
 };
@@ -16,7 +16,10 @@ use std::{
 use actix_codec::{AsyncRead, AsyncWrite, Decoder, Encoder, Framed};
 use bytes::BytesMut;
 use futures::{Sink, SinkExt, Stream, StreamExt};
-use mirrord_protocol::{ClientMessage, DaemonMessage, ProtocolCodec};
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, ProtocolCodec,
+    compression::{self, Compression},
+};
 use rand::seq::IteratorRandom;
 use tokio::{
     pin, select,
@@ -178,13 +181,22 @@ impl<Type: ProtocolEndpoint> Connection<Type> {
     /// Cancel safe.
     #[inline]
     pub async fn recv(&mut self) -> Option<Type::InMsg> {
-        self.rx.recv().await
+        let message = self.rx.recv().await;
+        if message.is_some() {
+            self.shared_state.received.fetch_add(1, Ordering::Relaxed);
+        }
+        message
     }
 
     /// Poll the next message.
     #[inline]
     pub fn poll_recv(&mut self, cx: &mut Context) -> Poll<Option<Type::InMsg>> {
-        self.rx.poll_recv(cx)
+        let received = &self.shared_state.received;
+        self.rx.poll_recv(cx).map(|message| {
+            message.inspect(|_| {
+                received.fetch_add(1, Ordering::Relaxed);
+            })
+        })
     }
 
     /// Send a message.
@@ -255,7 +267,99 @@ impl<Type: ProtocolEndpoint> Connection<Type> {
         rx_other
     }
 
-    /// Destructure `self` into a `(TxHandle, Receiver)` pair
+    /// Starts keeping the last sent messages, so that they can be replayed on another connection
+    /// with [`Self::take_over`].
+    pub fn enable_replay(&self) {
+        self.shared_state
+            .log
+            .lock()
+            .unwrap()
+            .replay
+            .get_or_insert_default();
+    }
+
+    /// Returns how many messages were received with [`Self::recv`] and [`Self::poll_recv`] in
+    /// this session.
+    pub fn received(&self) -> u64 {
+        self.shared_state.received.load(Ordering::Relaxed)
+    }
+
+    /// Returns the sequence number of the oldest sent message that can still be replayed.
+    ///
+    /// Messages are numbered from 0, in the order they were sent in this session.
+    pub fn replayable_from(&self) -> u64 {
+        self.shared_state.log.lock().unwrap().replayable_from()
+    }
+
+    /// Continues the session of the `previous` connection on this connection.
+    ///
+    /// 1. Messages that were sent on the `previous` connection, starting from the one with sequence
+    ///    number `peer_received`, are sent again before any other messages.
+    /// 2. Messages that were queued on the `previous` connection, but not yet sent, are moved to
+    ///    this connection. Messages sent later with [`TxHandle`]s of the `previous` connection are
+    ///    redirected to this connection.
+    /// 3. Message counters, replay buffer and compression settings are moved from the `previous`
+    ///    connection.
+    ///
+    /// Returns `false` if the messages that need to be sent again are no longer available, or the
+    /// `previous` connection was already taken over. Nothing is changed then.
+    pub fn take_over(&self, previous: &Self, peer_received: u64) -> bool {
+        let previous = &previous.shared_state;
+
+        let mut previous_queues = previous.queues.lock().unwrap();
+        let mut previous_log = previous.log.lock().unwrap();
+        let Some(missed) = previous_log.replay_from(peer_received) else {
+            return false;
+        };
+        let missed = missed.cloned().collect::<Vec<_>>();
+        if previous
+            .taken_over_by
+            .set(self.shared_state.clone())
+            .is_err()
+        {
+            return false;
+        }
+
+        let mut queues_guard = self.shared_state.queues.lock().unwrap();
+        let queues = &mut *queues_guard;
+        queues.replay.extend(missed);
+        for (id, queue) in previous_queues.queues.drain() {
+            // Senders waiting for free capacity will notice the redirect.
+            queue.free.notify_waiters();
+
+            if queue.messages.is_empty() {
+                continue;
+            }
+
+            let target = queues.queues.entry(id).or_default();
+            if target.messages.is_empty() {
+                queues.ready.push(id);
+            }
+            target.used_bytes += queue.used_bytes;
+            target.messages.extend(queue.messages);
+        }
+        previous_queues.ready.clear();
+        drop(queues_guard);
+
+        *self.shared_state.log.lock().unwrap() = std::mem::take(&mut *previous_log);
+        self.shared_state
+            .received
+            .store(previous.received.load(Ordering::Relaxed), Ordering::Relaxed);
+        *self.shared_state.compression.lock().unwrap() = *previous.compression.lock().unwrap();
+        self.shared_state.next_queue_id.fetch_max(
+            previous.next_queue_id.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+
+        self.shared_state.nonempty.notify_one();
+
+        true
+    }
+
+    /// Destructure `self` into a `(TxHandle, Receiver)` pair.
+    ///
+    /// Messages received with the returned [`mpsc::Receiver`] are not counted in
+    /// [`Self::received`].
     #[inline]
     pub fn destructure(self) -> (TxHandle<Type>, mpsc::Receiver<Type::InMsg>) {
         (
@@ -283,7 +387,7 @@ where
     Type::OutMsg: bincode::Decode<()>,
 {
     pub async fn next(&self) -> Option<Type::OutMsg> {
-        bincode::decode_from_slice(&self.0.next().await, bincode::config::standard())
+        compression::decode_from_slice(&self.0.next().await)
             .ok()
             .map(|e| e.0)
     }
@@ -354,9 +458,68 @@ struct OutQueue {
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
 struct QueueId(usize);
 
+/// Returned from [`SharedState::try_push`] when the message was not pushed.
+enum PushError<Type: ProtocolEndpoint> {
+    /// The queue is full, the notification resolves when it has free capacity.
+    Full(Vec<u8>, OwnedNotified),
+    /// The connection was taken over, the message should be pushed to the successor.
+    TakenOver(Vec<u8>, Arc<SharedState<Type>>),
+}
+
 struct Queues {
     queues: HashMap<QueueId, OutQueue>,
     ready: Vec<QueueId>,
+    /// Messages sent again after [`Connection::take_over`], these go before all other messages.
+    replay: VecDeque<Vec<u8>>,
+}
+
+/// Counts sent messages, and keeps the last ones so that they can be sent again after
+/// [`Connection::take_over`].
+#[derive(Default)]
+struct SessionLog {
+    /// How many messages were sent in this session.
+    sent: u64,
+    /// Last sent messages, [`None`] if replay was not enabled with [`Connection::enable_replay`].
+    replay: Option<VecDeque<Vec<u8>>>,
+    /// Total size of the messages in [`Self::replay`].
+    replay_bytes: usize,
+}
+
+impl SessionLog {
+    /// Upper limit for [`Self::replay_bytes`].
+    const REPLAY_CAPACITY: usize = 16 * 1024 * 1024;
+
+    fn record(&mut self, encoded: &[u8]) {
+        self.sent += 1;
+
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        replay.push_back(encoded.to_vec());
+        self.replay_bytes += encoded.len();
+        while self.replay_bytes > Self::REPLAY_CAPACITY
+            && let Some(dropped) = replay.pop_front()
+        {
+            self.replay_bytes -= dropped.len();
+        }
+    }
+
+    fn replayable_from(&self) -> u64 {
+        let kept = self.replay.as_ref().map(VecDeque::len).unwrap_or_default();
+        self.sent - kept as u64
+    }
+
+    /// Returns the sent messages, starting from the one with sequence number `from`.
+    ///
+    /// Returns [`None`] if some of these are no longer available.
+    fn replay_from(&self, from: u64) -> Option<impl Iterator<Item = &Vec<u8>>> {
+        if from > self.sent {
+            return None;
+        }
+        let skip = from.checked_sub(self.replayable_from())?;
+
+        Some(self.replay.iter().flatten().skip(skip as usize))
+    }
 }
 
 struct SharedState<Type: ProtocolEndpoint> {
@@ -371,6 +534,16 @@ struct SharedState<Type: ProtocolEndpoint> {
     in_tx: mpsc::WeakSender<Type::InMsg>,
 
     next_queue_id: AtomicUsize,
+
+    /// Compression of outgoing messages, applied when they are pushed into the queues.
+    compression: Mutex<Option<Compression>>,
+
+    /// Counts sent messages, see [`Connection::take_over`].
+    log: Mutex<SessionLog>,
+    /// Counts messages received with [`Connection::recv`] and [`Connection::poll_recv`].
+    received: AtomicU64,
+    /// Set by [`Connection::take_over`], all new messages are pushed there.
+    taken_over_by: OnceLock<Arc<SharedState<Type>>>,
 }
 
 impl<Type: ProtocolEndpoint> fmt::Debug for SharedState<Type> {
@@ -391,11 +564,16 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
             queues: Mutex::new(Queues {
                 queues: HashMap::new(),
                 ready: Vec::new(),
+                replay: VecDeque::new(),
             }),
             nonempty: Arc::new(Notify::new()),
             in_tx,
             // 0 is reserved for the Connection struct
             next_queue_id: 1.into(),
+            compression: Default::default(),
+            log: Default::default(),
+            received: Default::default(),
+            taken_over_by: Default::default(),
         }
     }
 
@@ -403,13 +581,14 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
     /// creating it if it doesn't exist. If the queue is full, return
     /// the message and an `OwnedNotified` that will resolve when the
     /// queue has free capacity.
-    fn try_push(
-        &self,
-        queue_id: QueueId,
-        encoded: Vec<u8>,
-    ) -> Result<(), (Vec<u8>, OwnedNotified)> {
+    fn try_push(&self, queue_id: QueueId, encoded: Vec<u8>) -> Result<(), PushError<Type>> {
         let mut lock = self.queues.lock().unwrap();
 
+        // Checked under the lock, so that no message is left behind in the queues.
+        if let Some(successor) = self.taken_over_by.get() {
+            return Err(PushError::TakenOver(encoded, successor.clone()));
+        }
+
         // Garbage-collect unused queues
         if lock.queues.len() > 8 && lock.ready.len() < lock.queues.len() / 3 {
             // We can remove all empty ones because we know they wont be in `ready`
@@ -420,7 +599,10 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
         let queue = lock.queues.entry(queue_id).or_default();
 
         if queue.used_bytes > Self::MAX_CAPACITY {
-            return Err((encoded, queue.free.clone().notified_owned()));
+            return Err(PushError::Full(
+                encoded,
+                queue.free.clone().notified_owned(),
+            ));
         }
 
         queue.used_bytes += encoded.len();
@@ -442,15 +624,22 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
     /// Will wait for the queue to free up if necessary, resolves only
     /// when the message has been successfully pushed.
     async fn push(&self, id: QueueId, msg: Type::OutMsg) {
-        let mut encoded = bincode::encode_to_vec(msg, bincode::config::standard()).unwrap();
+        let settings = *self.compression.lock().unwrap();
+        let mut encoded = compression::encode_to_vec(msg, settings).unwrap();
 
+        let mut successor: Option<Arc<Self>> = None;
         loop {
-            match self.try_push(id, encoded) {
+            let state = successor.as_deref().unwrap_or(self);
+            match state.try_push(id, encoded) {
                 Ok(()) => break,
-                Err((r, notify)) => {
+                Err(PushError::Full(r, notify)) => {
                     encoded = r;
                     notify.await;
                 }
+                Err(PushError::TakenOver(r, next)) => {
+                    encoded = r;
+                    successor = Some(next);
+                }
             }
         }
     }
@@ -460,6 +649,15 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
     fn poll_next(&self) -> Option<Vec<u8>> {
         let mut lock = self.queues.lock().unwrap();
 
+        if self.taken_over_by.get().is_some() {
+            return None;
+        }
+
+        // These were already counted when sent for the first time.
+        if let Some(replayed) = lock.replay.pop_front() {
+            return Some(replayed);
+        }
+
         // If `ready` is empty then we have nothing to do.
         let key_idx = (0..lock.ready.len()).choose(&mut rand::rng())?;
         let key = *lock.ready.get(key_idx).unwrap();
@@ -486,6 +684,8 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
             lock.ready.swap_remove(key_idx);
         }
 
+        self.log.lock().unwrap().record(&next);
+
         Some(next)
     }
 
@@ -555,6 +755,15 @@ impl<Type: ProtocolEndpoint> TxHandle<Type> {
     pub fn another(&self) -> Self {
         self.shared_state.clone().new_queue()
     }
+
+    /// Sets compression of outgoing messages, for all queues of this connection.
+    ///
+    /// Applies only to messages sent after this call, messages that are already queued are sent
+    /// as they are. The peer must be using
+    /// [`COMPRESSION_VERSION`](mirrord_protocol::compression::COMPRESSION_VERSION).
+    pub fn set_compression(&self, compression: Option<Compression>) {
+        *self.shared_state.compression.lock().unwrap() = compression;
+    }
 }
 
 #[cfg(test)]
@@ -645,4 +854,56 @@ mod tests {
             assert_eq!(seq.next(), None);
         }
     }
+
+    #[tokio::test]
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn take_over_replays_and_redirects() {
+        let (previous, _previous_inbound_tx, previous_output) = Connection::<Test>::dummy();
+        previous.enable_replay();
+        let tx = previous.tx_handle();
+
+        let messages = (0..5).map(Message::new).collect::<Vec<_>>();
+        for message in messages.iter().take(3) {
+            tx.send(message.clone()).await;
+        }
+        for message in messages.iter().take(3) {
+            assert_eq!(previous_output.next().await.as_ref(), Some(message));
+        }
+        // Queued, but never sent on the previous connection.
+        tx.send(messages[3].clone()).await;
+
+        let (connection, _inbound_tx, output) = Connection::<Test>::dummy();
+        // The peer received only the first message.
+        assert!(connection.take_over(&previous, 1));
+        assert!(connection.take_over(&previous, 1).not());
+
+        tx.send(messages[4].clone()).await;
+
+        for message in messages.iter().skip(1) {
+            assert_eq!(output.next().await.as_ref(), Some(message));
+        }
+        // Replayed messages are not counted twice.
+        assert_eq!(connection.replayable_from(), 0);
+        assert!(
+            timeout(Duration::from_millis(100), previous_output.next())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn take_over_requires_replay() {
+        let (previous, _previous_inbound_tx, previous_output) = Connection::<Test>::dummy();
+        previous.tx_handle().send(Message::new(0)).await;
+        previous_output.next().await.unwrap();
+        assert_eq!(previous.replayable_from(), 1);
+
+        let (connection, _inbound_tx, _output) = Connection::<Test>::dummy();
+        assert!(connection.take_over(&previous, 0).not());
+        assert!(connection.take_over(&previous, 2).not());
+        assert!(connection.take_over(&previous, 1));
+    }
 }
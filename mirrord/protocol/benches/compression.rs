@@ -0,0 +1,126 @@
+//! Measures the throughput/latency tradeoff of [`Compression`] on the protocol connection.
+//!
+//! Encodes and decodes [`DaemonMessage::Tcp`] data messages of different sizes and
+//! compressibility, with and without compression, and prints the results as a table.
+//!
+//! Run with `cargo bench -p mirrord-protocol --bench compression`. The wire time assumes a link of
+//! [`LINK_BYTES_PER_SEC`], which roughly matches a port-forward to a remote cluster.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use actix_codec::{Decoder, Encoder};
+use bytes::BytesMut;
+use mirrord_protocol::{
+    ClientCodec, DaemonCodec, DaemonMessage, Payload,
+    compression::Compression,
+    tcp::{DaemonTcp, TcpData},
+};
+
+/// Assumed bandwidth of the connection, used to estimate the wire time.
+const LINK_BYTES_PER_SEC: f64 = 10.0 * 1024.0 * 1024.0;
+
+/// Minimal total amount of data encoded in a single measurement.
+const BYTES_PER_MEASUREMENT: usize = 256 * 1024 * 1024;
+
+const PAYLOAD_SIZES: [usize; 4] = [1024, 16 * 1024, 64 * 1024, 1024 * 1024];
+
+/// Payload that looks like a JSON response body.
+fn text_payload(size: usize) -> Vec<u8> {
+    br#"{"id":12345,"name":"mirrord","tags":["local","remote"],"active":true},"#
+        .iter()
+        .copied()
+        .cycle()
+        .take(size)
+        .collect()
+}
+
+/// Payload that does not compress, e.g. an already compressed image.
+fn random_payload(size: usize) -> Vec<u8> {
+    // xorshift, good enough to defeat the compression.
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    std::iter::repeat_with(|| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as u8
+    })
+    .take(size)
+    .collect()
+}
+
+struct Measurement {
+    /// Average encoded size of one message.
+    wire_size: usize,
+    /// Average time to encode and decode one message.
+    codec_time: Duration,
+}
+
+fn measure(payload: &[u8], compression: Option<Compression>) -> Measurement {
+    let mut daemon_codec = DaemonCodec::default();
+    daemon_codec.set_compression(compression);
+    let mut client_codec = ClientCodec::default();
+
+    let message = DaemonMessage::Tcp(DaemonTcp::Data(TcpData {
+        connection_id: 0,
+        bytes: Payload::from(payload.to_vec()),
+    }));
+    let iterations = (BYTES_PER_MEASUREMENT / payload.len()).max(1);
+
+    let mut buffer = BytesMut::new();
+    let mut wire_size = 0;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        daemon_codec
+            .encode(message.clone(), &mut buffer)
+            .expect("encoding failed");
+        wire_size += buffer.len();
+        let decoded = client_codec
+            .decode(&mut buffer)
+            .expect("decoding failed")
+            .expect("incomplete message");
+        black_box(decoded);
+    }
+    let elapsed = start.elapsed();
+
+    Measurement {
+        wire_size: wire_size / iterations,
+        codec_time: elapsed / iterations as u32,
+    }
+}
+
+fn main() {
+    println!(
+        "{:<8} {:>10} {:>12} {:>12} {:>12} {:>14} {:>12}",
+        "payload", "size", "mode", "wire size", "codec time", "codec MiB/s", "latency",
+    );
+
+    for (kind, generate) in [
+        ("text", text_payload as fn(usize) -> Vec<u8>),
+        ("random", random_payload),
+    ] {
+        for size in PAYLOAD_SIZES {
+            let payload = generate(size);
+
+            for (mode, compression) in [("plain", None), ("zstd", Some(Compression::default()))] {
+                let Measurement {
+                    wire_size,
+                    codec_time,
+                } = measure(&payload, compression);
+
+                let throughput = size as f64 / codec_time.as_secs_f64() / (1024.0 * 1024.0);
+                // Time to get the message across: encoding, sending and decoding.
+                let latency =
+                    codec_time + Duration::from_secs_f64(wire_size as f64 / LINK_BYTES_PER_SEC);
+
+                println!(
+                    "{kind:<8} {size:>10} {mode:>12} {wire_size:>12} {:>12} {throughput:>14.1} {:>12}",
+                    format!("{codec_time:.2?}"),
+                    format!("{latency:.2?}"),
+                );
+            }
+        }
+    }
+}
@@ -21,9 +21,10 @@ use semver::VersionReq;
 
 use crate::{
     ResponseError,
+    compression::{CompressedFrame, Compression},
     dns::{
-        GetAddrInfoRequest, GetAddrInfoRequestV2, GetAddrInfoResponse, ReverseDnsLookupRequest,
-        ReverseDnsLookupResponse,
+        DnsQueryRequest, DnsQueryResponse, GetAddrInfoRequest, GetAddrInfoRequestV2,
+        GetAddrInfoResponse, ReverseDnsLookupRequest, ReverseDnsLookupResponse,
     },
     file::*,
     outgoing::{
@@ -31,6 +32,7 @@ use crate::{
         tcp::{DaemonTcpOutgoing, LayerTcpOutgoing},
         udp::{DaemonUdpOutgoing, LayerUdpOutgoing},
     },
+    resume::{ClientSessionResume, DaemonSessionResume},
     tcp::{DaemonTcp, LayerTcp, LayerTcpSteal},
     vpn::{ClientVpn, ServerVpn},
 };
@@ -194,6 +196,19 @@ pub enum ClientMessage {
     /// These are the messages used by the `outgoing` feature (unix seqpacket), and handled by the
     /// `SeqpacketApi` in the agent.
     SeqpacketOutgoing(LayerSeqpacket),
+
+    /// Raw DNS query for records of any type.
+    ///
+    /// Sent by the layer for `res_query` and friends.
+    DnsQuery(DnsQueryRequest),
+
+    /// Enables compression of large [`DaemonMessage`]s sent after the response to this message.
+    ///
+    /// Supported from [`COMPRESSION_VERSION`](crate::compression::COMPRESSION_VERSION).
+    EnableCompression(Compression),
+
+    /// Supported from [`SESSION_RESUME_VERSION`](crate::resume::SESSION_RESUME_VERSION).
+    SessionResume(ClientSessionResume),
 }
 
 /// Type alias for `Result`s that should be returned from mirrord-agent to mirrord-layer.
@@ -259,6 +274,10 @@ pub enum DaemonMessage {
     /// Sent by the agent in response to [`ClientMessage::ReverseDnsLookup`].
     ReverseDnsLookup(RemoteResult<ReverseDnsLookupResponse>),
     SeqpacketOutgoing(DaemonSeqpacket),
+    /// Sent by the agent in response to [`ClientMessage::DnsQuery`].
+    DnsQuery(DnsQueryResponse),
+    /// Sent by the agent in response to [`ClientMessage::SessionResume`].
+    SessionResume(DaemonSessionResume),
 }
 
 #[derive(Encode, Decode, PartialEq, Eq, Clone, From, Into, Deref)]
@@ -274,6 +293,10 @@ impl core::fmt::Debug for RemoteEnvVars {
 
 pub struct ProtocolCodec<I, O> {
     config: bincode::config::Configuration,
+    /// If set, large outgoing messages are sent as [`CompressedFrame`]s.
+    ///
+    /// Incoming [`CompressedFrame`]s are always accepted.
+    compression: Option<Compression>,
     /// Phantom fields to make this struct generic over message types.
     _phantom_incoming_message: PhantomData<I>,
     _phantom_outgoing_message: PhantomData<O>,
@@ -297,18 +320,28 @@ impl<I, O> Default for ProtocolCodec<I, O> {
     fn default() -> Self {
         Self {
             config: bincode::config::standard(),
+            compression: None,
             _phantom_incoming_message: Default::default(),
             _phantom_outgoing_message: Default::default(),
         }
     }
 }
 
+impl<I, O> ProtocolCodec<I, O> {
+    /// Sets compression of outgoing messages.
+    ///
+    /// The peer must be using [`COMPRESSION_VERSION`](crate::compression::COMPRESSION_VERSION).
+    pub fn set_compression(&mut self, compression: Option<Compression>) {
+        self.compression = compression;
+    }
+}
+
 impl<I: bincode::Decode<()>, O> Decoder for ProtocolCodec<I, O> {
     type Item = I;
     type Error = io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Self::Item>> {
-        match bincode::decode_from_slice(&src[..], self.config) {
+        match crate::compression::decode_from_slice(&src[..]) {
             Ok((message, read)) => {
                 src.advance(read);
                 Ok(Some(message))
@@ -330,6 +363,18 @@ impl<I, O: bincode::Encode> Encoder<O> for ProtocolCodec<I, O> {
             msg.encode(&mut size_writer).map_err(io::Error::other)?;
             size_writer.into_writer().bytes_written
         };
+
+        // Compression needs the whole encoded message anyway.
+        if let Some(compression) = self.compression
+            && size >= compression.threshold as usize
+        {
+            let encoded = crate::compression::encode_to_vec(msg, Some(compression))
+                .map_err(io::Error::other)?;
+            dst.extend_from_slice(&encoded);
+
+            return Ok(());
+        }
+
         dst.reserve(size);
 
         /// Allows using [`BytesMut`] as bincode's [`Writer`].
@@ -388,6 +433,28 @@ mod tests {
         assert!(buf.is_empty());
     }
 
+    #[test]
+    fn compressed_daemon_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        daemon_codec.set_compression(Some(Compression::default()));
+        let mut buf = BytesMut::new();
+
+        let small = DaemonMessage::Pong;
+        let large = DaemonMessage::Tcp(DaemonTcp::Data(TcpData {
+            connection_id: 1,
+            bytes: Payload::from(vec![7; 64 * 1024]),
+        }));
+
+        daemon_codec.encode(small.clone(), &mut buf).unwrap();
+        daemon_codec.encode(large.clone(), &mut buf).unwrap();
+        assert!(buf.len() < 1024);
+
+        assert_eq!(client_codec.decode(&mut buf).unwrap().unwrap(), small);
+        assert_eq!(client_codec.decode(&mut buf).unwrap().unwrap(), large);
+        assert!(buf.is_empty());
+    }
+
     #[test]
     fn decode_client_invalid_data() {
         let mut codec = ClientCodec::default();
@@ -0,0 +1,267 @@
+//! Optional compression of large messages on the wire.
+//!
+//! Compression is enabled separately for each direction of the connection, with
+//! [`Compression`] settings:
+//! 1. The client enables compression of its own messages after [`COMPRESSION_VERSION`] is
+//!    negotiated with
+//!    [`ClientMessage::SwitchProtocolVersion`](crate::ClientMessage::SwitchProtocolVersion).
+//! 2. The client sends
+//!    [`ClientMessage::EnableCompression`](crate::ClientMessage::EnableCompression) to enable
+//!    compression of the server's messages.
+//!
+//! Compressed messages are sent as [`CompressedFrame`]s, preceded by [`COMPRESSED_FRAME_TAG`].
+//! Since the tag is not a valid enum discriminant for any of the protocol messages, receivers can
+//! tell compressed frames from regular messages without any additional state, and decoding does
+//! not depend on whether compression is enabled.
+
+use std::{io, sync::LazyLock};
+
+use bincode::{
+    Decode, Encode,
+    error::{DecodeError, EncodeError},
+};
+use semver::VersionReq;
+
+/// Minimal mirrord-protocol version that allows [`CompressedFrame`]s and
+/// [`ClientMessage::EnableCompression`](crate::ClientMessage::EnableCompression).
+pub static COMPRESSION_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.31.0".parse().expect("Bad Identifier"));
+
+/// Precedes every [`CompressedFrame`] on the wire.
+///
+/// Encoded in the place of the message enum discriminant, so it must never be a valid
+/// discriminant of [`ClientMessage`](crate::ClientMessage) or
+/// [`DaemonMessage`](crate::DaemonMessage).
+pub const COMPRESSED_FRAME_TAG: u32 = u32::MAX;
+
+/// Default value of [`Compression::threshold`].
+///
+/// Smaller messages (e.g. pings, requests for remote files) gain nothing from compression.
+pub const DEFAULT_COMPRESSION_THRESHOLD: u32 = 4 * 1024;
+
+/// Maximal [`CompressedFrame::uncompressed_len`] that we accept.
+///
+/// The length comes from the peer and determines how much memory we allocate for the
+/// decompressed message, so we don't trust it blindly. Larger messages are sent uncompressed.
+pub const MAX_UNCOMPRESSED_LEN: u32 = 16 * 1024 * 1024;
+
+/// zstd level used to compress the frames.
+///
+/// We're compressing messages on the fly, so we favor speed over the compression ratio.
+const ZSTD_LEVEL: i32 = 1;
+
+/// Algorithm used to compress a [`CompressedFrame`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CompressionAlgorithm {
+    Zstd,
+}
+
+/// Compression settings for one direction of the connection.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Compression {
+    pub algorithm: CompressionAlgorithm,
+    /// Messages smaller than this many bytes (when encoded) are sent uncompressed.
+    pub threshold: u32,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self {
+            algorithm: CompressionAlgorithm::Zstd,
+            threshold: DEFAULT_COMPRESSION_THRESHOLD,
+        }
+    }
+}
+
+impl Compression {
+    /// Compresses the given encoded message into a [`CompressedFrame`].
+    ///
+    /// Returns [`None`] if the message is smaller than [`Compression::threshold`], larger than
+    /// [`MAX_UNCOMPRESSED_LEN`], or if compression does not make it any smaller.
+    pub fn compress(&self, encoded: &[u8]) -> io::Result<Option<CompressedFrame>> {
+        if encoded.len() < self.threshold as usize || encoded.len() > MAX_UNCOMPRESSED_LEN as usize
+        {
+            return Ok(None);
+        }
+
+        let data = match self.algorithm {
+            CompressionAlgorithm::Zstd => zstd::bulk::compress(encoded, ZSTD_LEVEL)?,
+        };
+        if data.len() >= encoded.len() {
+            return Ok(None);
+        }
+
+        Ok(Some(CompressedFrame {
+            algorithm: self.algorithm,
+            uncompressed_len: encoded.len().try_into().map_err(io::Error::other)?,
+            data,
+        }))
+    }
+}
+
+/// A compressed message on the wire.
+#[derive(Encode, Decode, PartialEq, Eq, Clone)]
+pub struct CompressedFrame {
+    pub algorithm: CompressionAlgorithm,
+    /// Length of the encoded message, before compression.
+    pub uncompressed_len: u32,
+    /// Compressed encoded message.
+    pub data: Vec<u8>,
+}
+
+impl CompressedFrame {
+    /// Decompresses the encoded message from this frame.
+    ///
+    /// Fails if [`CompressedFrame::uncompressed_len`] exceeds [`MAX_UNCOMPRESSED_LEN`].
+    pub fn decompress(&self) -> io::Result<Vec<u8>> {
+        if self.uncompressed_len > MAX_UNCOMPRESSED_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "declared length of the compressed frame is too large",
+            ));
+        }
+
+        let decompressed = match self.algorithm {
+            CompressionAlgorithm::Zstd => {
+                zstd::bulk::decompress(&self.data, self.uncompressed_len as usize)?
+            }
+        };
+
+        if decompressed.len() != self.uncompressed_len as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "decompressed frame length does not match the declared length",
+            ));
+        }
+
+        Ok(decompressed)
+    }
+}
+
+impl std::fmt::Debug for CompressedFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompressedFrame")
+            .field("algorithm", &self.algorithm)
+            .field("uncompressed_len", &self.uncompressed_len)
+            .field("data (length)", &self.data.len())
+            .finish()
+    }
+}
+
+/// Encodes the given message with the standard [`bincode`] config, compressing it if
+/// `compression` is given and the message is large enough.
+///
+/// Produces the same bytes as [`ProtocolCodec`](crate::ProtocolCodec) with the same
+/// compression settings.
+pub fn encode_to_vec<M: Encode>(
+    message: M,
+    compression: Option<Compression>,
+) -> Result<Vec<u8>, EncodeError> {
+    let config = bincode::config::standard();
+    let encoded = bincode::encode_to_vec(message, config)?;
+
+    let Some(compression) = compression else {
+        return Ok(encoded);
+    };
+    match compression.compress(&encoded) {
+        Ok(Some(frame)) => bincode::encode_to_vec((COMPRESSED_FRAME_TAG, frame), config),
+        Ok(None) => Ok(encoded),
+        Err(error) => Err(EncodeError::OtherString(error.to_string())),
+    }
+}
+
+/// Decodes a message with the standard [`bincode`] config, decompressing it if it was sent in a
+/// [`CompressedFrame`].
+///
+/// Returns the message and the amount of bytes read from the slice, just like
+/// [`bincode::decode_from_slice`]. Used by [`ProtocolCodec`](crate::ProtocolCodec), and where
+/// messages are not framed by the codec (e.g. WebSocket connections).
+pub fn decode_from_slice<M: Decode<()>>(src: &[u8]) -> Result<(M, usize), DecodeError> {
+    let config = bincode::config::standard();
+
+    let tag_len = match bincode::decode_from_slice::<u32, _>(src, config) {
+        Ok((COMPRESSED_FRAME_TAG, tag_len)) => tag_len,
+        // Not a compressed frame, the regular decoding will report any errors.
+        _ => return bincode::decode_from_slice(src, config),
+    };
+
+    let (frame, frame_len) = bincode::decode_from_slice::<CompressedFrame, _>(
+        src.get(tag_len..).unwrap_or_default(),
+        config,
+    )?;
+    let decompressed = frame
+        .decompress()
+        .map_err(|error| DecodeError::OtherString(error.to_string()))?;
+    // The decompressed message is complete, `UnexpectedEnd` here means malformed data.
+    let (message, _) =
+        bincode::decode_from_slice(&decompressed, config).map_err(|error| match error {
+            DecodeError::UnexpectedEnd { .. } => {
+                DecodeError::OtherString("compressed frame contains an incomplete message".into())
+            }
+            error => error,
+        })?;
+
+    Ok((message, tag_len + frame_len))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ClientCodec, ClientMessage, DaemonMessage, FileResponse, file::ReadFileResponse};
+
+    fn large_read() -> DaemonMessage {
+        let bytes = b"mirrord ".repeat(4096);
+        DaemonMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+            read_amount: bytes.len() as u64,
+            bytes: bytes.into(),
+        })))
+    }
+
+    /// Verifies that [`COMPRESSED_FRAME_TAG`] can't be taken for a message discriminant.
+    #[test]
+    fn tag_is_not_a_discriminant() {
+        let config = bincode::config::standard();
+        for message in [ClientMessage::Ping, ClientMessage::ReadyForLogs] {
+            let encoded = bincode::encode_to_vec(message, config).unwrap();
+            let (discriminant, _): (u32, _) = bincode::decode_from_slice(&encoded, config).unwrap();
+            assert_ne!(discriminant, COMPRESSED_FRAME_TAG);
+        }
+    }
+
+    #[test]
+    fn small_messages_are_not_compressed() {
+        let compression = Compression::default();
+
+        assert_eq!(
+            encode_to_vec(DaemonMessage::Pong, Some(compression)).unwrap(),
+            encode_to_vec(DaemonMessage::Pong, None).unwrap(),
+        );
+    }
+
+    /// Frames declaring a huge message are rejected before anything is allocated.
+    #[test]
+    fn oversized_frame_is_rejected() {
+        let mut frame = Compression::default()
+            .compress(&encode_to_vec(large_read(), None).unwrap())
+            .unwrap()
+            .unwrap();
+        frame.uncompressed_len = u32::MAX;
+
+        let error = frame.decompress().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn round_trip_through_codec() {
+        use actix_codec::Decoder;
+
+        let plain = encode_to_vec(large_read(), None).unwrap();
+        let compressed = encode_to_vec(large_read(), Some(Compression::default())).unwrap();
+        assert!(compressed.len() < plain.len() / 10);
+
+        let mut buffer = bytes::BytesMut::from(compressed.as_slice());
+        let decoded = ClientCodec::default().decode(&mut buffer).unwrap();
+        assert_eq!(decoded, Some(large_read()));
+        assert!(buffer.is_empty());
+    }
+}
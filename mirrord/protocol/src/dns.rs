@@ -15,6 +15,10 @@ pub static ADDRINFO_V2_VERSION: LazyLock<VersionReq> =
 pub static REVERSE_DNS_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.25.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows [`DnsQueryRequest`].
+pub static DNS_QUERY_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.30.0".parse().expect("Bad Identifier"));
+
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct LookupRecord {
     pub name: String,
@@ -144,3 +148,55 @@ pub struct ReverseDnsLookupResponse {
     /// Contains an error if the lookup failed.
     pub hostname: RemoteResult<String>,
 }
+
+/// DNS record class `IN` (internet).
+pub const DNS_CLASS_IN: u16 = 1;
+
+/// Raw DNS query for records of any type (e.g. `SRV`, `TXT`, `MX`).
+///
+/// Triggered by the `mirrord-layer` hooks of `res_query`, `res_search` and `res_nquery`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct DnsQueryRequest {
+    /// Name to query, e.g. `_kafka._tcp.example.com`.
+    ///
+    /// When `search` is set, this name may be relative.
+    pub name: String,
+    /// Numeric record type, as defined in [RFC 1035](https://www.rfc-editor.org/rfc/rfc1035#section-3.2.2)
+    /// and later RFCs (e.g. `33` for `SRV`).
+    pub record_type: u16,
+    /// Numeric record class, almost always [`DNS_CLASS_IN`].
+    pub class: u16,
+    /// Whether the search domains from the remote `/etc/resolv.conf` should be applied to `name`
+    /// (`res_search` semantics).
+    pub search: bool,
+}
+
+/// Single resource record returned in a [`DnsQueryResponse`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct DnsRecord {
+    /// Owner name of the record, fully qualified.
+    pub name: String,
+    /// Numeric record type.
+    pub record_type: u16,
+    /// Numeric record class.
+    pub class: u16,
+    /// Time to live, in seconds.
+    pub ttl: u32,
+    /// Record data, in DNS wire format.
+    ///
+    /// Domain names inside the data are never compressed, so the data can be copied as is into
+    /// any DNS message.
+    pub data: Vec<u8>,
+}
+
+/// Response to a [`DnsQueryRequest`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct DnsQueryResponse(pub RemoteResult<Vec<DnsRecord>>);
+
+impl Deref for DnsQueryResponse {
+    type Target = RemoteResult<Vec<DnsRecord>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
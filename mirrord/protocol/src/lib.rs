@@ -59,6 +59,7 @@
 
 pub mod batched_body;
 pub mod codec;
+pub mod compression;
 pub mod dns;
 pub mod error;
 pub mod file;
@@ -66,9 +67,11 @@ pub mod outgoing;
 #[deprecated = "pause feature was removed"]
 pub mod pause;
 pub mod payload;
+pub mod resume;
 pub mod tcp;
 pub mod uid;
 pub mod vpn;
+pub mod websocket;
 
 use std::{collections::HashSet, ops::Deref, sync::LazyLock};
 
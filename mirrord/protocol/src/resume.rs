@@ -0,0 +1,83 @@
+//! Resumption of client sessions after the connection with the server was lost.
+//!
+//! 1. After [`SESSION_RESUME_VERSION`] is negotiated, the client sends
+//!    [`ClientSessionResume::Enable`] and receives a [`ResumeToken`] in
+//!    [`DaemonSessionResume::Token`].
+//! 2. When the connection is lost, the server keeps the session state (port subscriptions, open
+//!    files, outgoing connections) for a grace period.
+//! 3. The client makes a new connection and sends [`ClientSessionResume::Resume`] as the **first**
+//!    message on it. The server responds with [`DaemonSessionResume::Resumed`] and continues the
+//!    session on the new connection, or with [`DaemonSessionResume::Expired`] and closes the new
+//!    connection.
+//!
+//! # Replay
+//!
+//! Messages that were in flight when the connection was lost are replayed. To make this possible,
+//! both sides count the messages they send and receive during the whole session (starting with
+//! the first message on the first connection), and keep the last sent messages in a buffer. The
+//! resume handshake messages are not counted.
+//!
+//! When the session is resumed, both sides send again all messages that the peer did not
+//! receive, before any new messages. The session can be resumed only if both sides still have
+//! these messages in their buffers.
+
+use std::{fmt, sync::LazyLock};
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+
+/// Minimal mirrord-protocol version that allows [`ClientSessionResume`] and
+/// [`DaemonSessionResume`].
+pub static SESSION_RESUME_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.32.0".parse().expect("Bad Identifier"));
+
+/// Identifies a resumable session in the server.
+#[derive(Encode, Decode, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct ResumeToken(pub u128);
+
+impl fmt::Debug for ResumeToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ResumeToken").field(&"<REDACTED>").finish()
+    }
+}
+
+/// Sent by the client to resume a session on a new connection.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ResumeSessionRequest {
+    pub token: ResumeToken,
+    /// How many messages the client received in this session.
+    pub received: u64,
+    /// Sequence number of the oldest message that the client can still replay.
+    ///
+    /// Sequence numbers start from 0.
+    pub replayable_from: u64,
+}
+
+/// Session resumption messages sent by the client.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum ClientSessionResume {
+    /// Makes the current session resumable.
+    ///
+    /// The server responds with [`DaemonSessionResume::Token`]. Sending this again in the same
+    /// session returns the same token.
+    Enable,
+    /// Resumes the session with the given token on this connection.
+    ///
+    /// Must be the first message sent on the connection.
+    Resume(ResumeSessionRequest),
+}
+
+/// Session resumption messages sent by the server.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum DaemonSessionResume {
+    /// Token that allows for resuming this session.
+    Token(ResumeToken),
+    /// The session was resumed on this connection.
+    Resumed {
+        /// How many messages the server received in this session.
+        received: u64,
+    },
+    /// The session cannot be resumed, e.g. because the grace period has passed. The server closes
+    /// the connection after sending this.
+    Expired,
+}
@@ -92,6 +92,13 @@ pub enum LayerTcp {
     /// User is interested in mirroring traffic on this `Port`, so add it to the list of
     /// ports that the sniffer is filtering.
     PortSubscribeFilteredHttp(Port, HttpFilter),
+
+    /// User wants to compare responses of their app with the responses of the original
+    /// destination, so the agent should send a [`DaemonTcp::HttpResponseShadow`] for every
+    /// mirrored HTTP request.
+    ///
+    /// Gated by [`HTTP_SHADOW_VERSION`].
+    ShadowHttpResponses,
 }
 
 /// Messages related to Tcp handler from server.
@@ -110,6 +117,8 @@ pub enum DaemonTcp {
     HttpRequestFramed(HttpRequest<InternalHttpBody>),
     HttpRequestChunked(ChunkedRequest),
     NewConnectionV2(NewTcpConnectionV2),
+    /// Gated by [`HTTP_SHADOW_VERSION`].
+    HttpResponseShadow(HttpResponseShadow),
 }
 
 /// Contents of a chunked message from server.
@@ -384,6 +393,41 @@ pub enum HttpBodyFilter {
     },
 }
 
+/// Filter for WebSocket upgrade requests.
+///
+/// Matches only requests that carry `Upgrade: websocket`. When both inner filters are set, both
+/// must match.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+pub struct WebSocketFilter {
+    /// Matched against each subprotocol offered in the `Sec-WebSocket-Protocol` header.
+    pub subprotocol: Option<Filter>,
+    /// Matched against the first data message sent by the client after the upgrade.
+    ///
+    /// Text messages are matched as-is, binary messages are matched after a lossy UTF-8
+    /// conversion.
+    pub initial_message: Option<Filter>,
+}
+
+impl WebSocketFilter {
+    /// Whether this filter can only be evaluated after the first message is received.
+    pub fn needs_initial_message(&self) -> bool {
+        self.initial_message.is_some()
+    }
+}
+
+impl Display for WebSocketFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.subprotocol, &self.initial_message) {
+            (Some(subprotocol), Some(message)) => {
+                write!(f, "(subprotocol={subprotocol}, initial_message={message})")
+            }
+            (Some(subprotocol), None) => write!(f, "(subprotocol={subprotocol})"),
+            (None, Some(message)) => write!(f, "(initial_message={message})"),
+            (None, None) => write!(f, "(any)"),
+        }
+    }
+}
+
 /// Describes different types of HTTP filtering available
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
 pub enum HttpFilter {
@@ -406,6 +450,9 @@ pub enum HttpFilter {
 
     /// Filter by header using JQ
     HeaderJq(JqQuery),
+
+    /// Filter WebSocket upgrade requests
+    WebSocket(WebSocketFilter),
 }
 
 impl Display for HttpFilter {
@@ -444,6 +491,7 @@ impl Display for HttpFilter {
             },
             HttpFilter::Body(filter) => write!(f, "body={filter}"),
             HttpFilter::HeaderJq(filter) => write!(f, "header_jq={filter}"),
+            HttpFilter::WebSocket(filter) => write!(f, "websocket={filter}"),
         }
     }
 }
@@ -525,6 +573,8 @@ pub enum LayerTcpSteal {
     HttpResponse(HttpResponse<Payload>),
     HttpResponseFramed(HttpResponse<InternalHttpBody>),
     HttpResponseChunked(ChunkedResponse),
+    /// Gated by [`HTTP_FALLBACK_VERSION`].
+    HttpRequestFallback(HttpRequestFallback),
 }
 
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -660,6 +710,56 @@ pub static HTTP_BODY_JSON_FILTER_VERSION: LazyLock<VersionReq> =
 pub static HTTP_HEADER_JQ_FILTER_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.26.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows filtering WebSocket upgrades
+/// ([`HttpFilter::WebSocket`]).
+pub static HTTP_WEBSOCKET_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.29.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`LayerTcpSteal::HttpRequestFallback`].
+pub static HTTP_FALLBACK_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.33.0".parse().expect("Bad Identifier"));
+
+/// Lets the client hand stolen HTTP requests back to the agent, when the local application fails
+/// to handle them.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum HttpRequestFallback {
+    /// Sent once after the protocol version negotiation.
+    ///
+    /// From now on, the agent keeps a copy of every stolen HTTP request (up to some body size),
+    /// until the client starts sending the response.
+    Enable,
+    /// Instead of the response, the agent should pass the request through to its original
+    /// destination, as if it was never stolen.
+    ///
+    /// If the agent no longer has the request copy, it responds to the HTTP client with an error.
+    Request(HttpRequestFallbackRequest),
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct HttpRequestFallbackRequest {
+    pub connection_id: ConnectionId,
+    pub request_id: RequestId,
+}
+
+/// Minimal mirrord-protocol version that allows [`LayerTcp::ShadowHttpResponses`].
+pub static HTTP_SHADOW_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.34.0".parse().expect("Bad Identifier"));
+
+/// Response of the original destination to a mirrored HTTP request.
+///
+/// Sent by the agent after the client requested [`LayerTcp::ShadowHttpResponses`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct HttpResponseShadow {
+    pub connection_id: ConnectionId,
+    pub request_id: RequestId,
+    /// [`None`] if the agent failed to capture the response, e.g. when the HTTP client dropped
+    /// the request.
+    ///
+    /// The body is [`None`] if it was too big for the agent to keep a copy.
+    #[bincode(with_serde)]
+    pub response: Option<InternalHttpResponse<Option<Payload>>>,
+}
+
 /// Protocol break - on version 2, please add source port, dest/src IP to the message
 /// so we can avoid losing this information.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -0,0 +1,503 @@
+//! Utilities for inspecting WebSocket traffic ([RFC 6455](https://www.rfc-editor.org/rfc/rfc6455)).
+//!
+//! After an HTTP upgrade, WebSocket traffic flows in mirrord-protocol as opaque bytes
+//! ([`DaemonTcp::Data`](crate::tcp::DaemonTcp::Data) and friends). The types here allow the
+//! components to decode these bytes into frames and messages, e.g. to filter stolen connections on
+//! the first message sent by the client, or to display the traffic in a human friendly format.
+
+use std::{io, ops::Not};
+
+use actix_codec::Decoder;
+use bytes::{Buf, Bytes, BytesMut};
+use hyper::{
+    HeaderMap,
+    header::{SEC_WEBSOCKET_PROTOCOL, UPGRADE},
+};
+use thiserror::Error;
+
+/// Default limit for [`WebSocketFrameCodec::max_payload_len`].
+pub const DEFAULT_MAX_PAYLOAD_LEN: usize = 16 * 1024 * 1024;
+
+/// Checks whether the given request headers contain a WebSocket upgrade (`Upgrade: websocket`).
+pub fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    headers.get_all(UPGRADE).iter().any(|value| {
+        value.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .any(|protocol| protocol.trim().eq_ignore_ascii_case("websocket"))
+        })
+    })
+}
+
+/// Returns the subprotocols offered by the client in the `Sec-WebSocket-Protocol` request headers,
+/// in order of preference.
+pub fn offered_subprotocols(headers: &HeaderMap) -> impl Iterator<Item = &str> {
+    headers
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|protocol| protocol.is_empty().not())
+}
+
+/// Opcode of a [`WebSocketFrame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebSocketOpcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+    /// One of the opcodes reserved for further use by the RFC.
+    Reserved(u8),
+}
+
+impl WebSocketOpcode {
+    /// Whether this is a control frame opcode (close, ping, pong or reserved `0xB-0xF`).
+    pub fn is_control(self) -> bool {
+        match self {
+            Self::Close | Self::Ping | Self::Pong => true,
+            Self::Reserved(opcode) => opcode >= 0x8,
+            Self::Continuation | Self::Text | Self::Binary => false,
+        }
+    }
+}
+
+impl From<u8> for WebSocketOpcode {
+    fn from(value: u8) -> Self {
+        match value {
+            0x0 => Self::Continuation,
+            0x1 => Self::Text,
+            0x2 => Self::Binary,
+            0x8 => Self::Close,
+            0x9 => Self::Ping,
+            0xA => Self::Pong,
+            other => Self::Reserved(other),
+        }
+    }
+}
+
+/// A single decoded WebSocket frame, with the payload already unmasked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebSocketFrame {
+    pub fin: bool,
+    pub opcode: WebSocketOpcode,
+    pub payload: Bytes,
+}
+
+/// Errors that can occur when decoding WebSocket frames or messages.
+#[derive(Error, Debug)]
+pub enum WebSocketFrameError {
+    #[error("frame payload of {0} bytes exceeds the limit of {1} bytes")]
+    PayloadTooLarge(u64, usize),
+
+    #[error("received a fragmented control frame")]
+    FragmentedControlFrame,
+
+    #[error("received a continuation frame without a preceding data frame")]
+    UnexpectedContinuation,
+
+    #[error("received a new data frame before the previous message was finished")]
+    UnfinishedMessage,
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// [`Decoder`] of raw [`WebSocketFrame`]s.
+///
+/// Works for both directions of the connection, masked (client to server) and unmasked (server to
+/// client) frames are both accepted.
+#[derive(Debug, Clone, Copy)]
+pub struct WebSocketFrameCodec {
+    /// Frames with bigger payloads are rejected with [`WebSocketFrameError::PayloadTooLarge`].
+    pub max_payload_len: usize,
+}
+
+impl Default for WebSocketFrameCodec {
+    fn default() -> Self {
+        Self {
+            max_payload_len: DEFAULT_MAX_PAYLOAD_LEN,
+        }
+    }
+}
+
+impl Decoder for WebSocketFrameCodec {
+    type Item = WebSocketFrame;
+    type Error = WebSocketFrameError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let (Some(&first), Some(&second)) = (src.first(), src.get(1)) else {
+            return Ok(None);
+        };
+
+        let fin = first & 0x80 != 0;
+        let opcode = WebSocketOpcode::from(first & 0x0F);
+        let masked = second & 0x80 != 0;
+
+        let (payload_len, mut header_len) = match second & 0x7F {
+            126 => match src.get(2..4).and_then(|bytes| bytes.try_into().ok()) {
+                Some(bytes) => (u64::from(u16::from_be_bytes(bytes)), 4),
+                None => return Ok(None),
+            },
+            127 => match src.get(2..10).and_then(|bytes| bytes.try_into().ok()) {
+                Some(bytes) => (u64::from_be_bytes(bytes), 10),
+                None => return Ok(None),
+            },
+            len => (u64::from(len), 2),
+        };
+
+        if payload_len > self.max_payload_len as u64 {
+            return Err(WebSocketFrameError::PayloadTooLarge(
+                payload_len,
+                self.max_payload_len,
+            ));
+        }
+        // Checked against `max_payload_len` above.
+        let payload_len = payload_len as usize;
+
+        if opcode.is_control() && fin.not() {
+            return Err(WebSocketFrameError::FragmentedControlFrame);
+        }
+
+        let mask = if masked {
+            let Some(mask) = src
+                .get(header_len..header_len + 4)
+                .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+            else {
+                return Ok(None);
+            };
+            header_len += 4;
+            Some(mask)
+        } else {
+            None
+        };
+
+        if src.len() < header_len + payload_len {
+            src.reserve(header_len + payload_len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(header_len);
+        let mut payload = src.split_to(payload_len);
+        if let Some(mask) = mask {
+            payload
+                .iter_mut()
+                .zip(mask.iter().cycle())
+                .for_each(|(byte, mask)| *byte ^= mask);
+        }
+
+        Ok(Some(WebSocketFrame {
+            fin,
+            opcode,
+            payload: payload.freeze(),
+        }))
+    }
+}
+
+/// A complete WebSocket message, reassembled from one or more [`WebSocketFrame`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    /// Payload of a text message.
+    ///
+    /// Kept as raw bytes, as the peer is not trusted to send valid UTF-8.
+    Text(Bytes),
+    Binary(Bytes),
+    Close(Bytes),
+    Ping(Bytes),
+    Pong(Bytes),
+}
+
+impl WebSocketMessage {
+    /// Returns the payload of this message.
+    pub fn payload(&self) -> &Bytes {
+        match self {
+            Self::Text(payload)
+            | Self::Binary(payload)
+            | Self::Close(payload)
+            | Self::Ping(payload)
+            | Self::Pong(payload) => payload,
+        }
+    }
+
+    /// Whether this is a [`WebSocketMessage::Text`] or [`WebSocketMessage::Binary`] message.
+    pub fn is_data(&self) -> bool {
+        matches!(self, Self::Text(..) | Self::Binary(..))
+    }
+}
+
+/// [`Decoder`] of [`WebSocketMessage`]s.
+///
+/// Reassembles fragmented data messages. Control frames interleaved with the fragments are
+/// returned immediately, as required by the RFC.
+#[derive(Debug, Default)]
+pub struct WebSocketMessageCodec {
+    frames: WebSocketFrameCodec,
+    /// Opcode and collected payload of a fragmented message that is not finished yet.
+    fragmented: Option<(WebSocketOpcode, BytesMut)>,
+}
+
+impl WebSocketMessageCodec {
+    pub fn new(frames: WebSocketFrameCodec) -> Self {
+        Self {
+            frames,
+            fragmented: None,
+        }
+    }
+}
+
+impl Decoder for WebSocketMessageCodec {
+    type Item = WebSocketMessage;
+    type Error = WebSocketFrameError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        while let Some(frame) = self.frames.decode(src)? {
+            let (opcode, payload) = match (frame.opcode, self.fragmented.take()) {
+                (WebSocketOpcode::Close, fragmented) => {
+                    self.fragmented = fragmented;
+                    return Ok(Some(WebSocketMessage::Close(frame.payload)));
+                }
+                (WebSocketOpcode::Ping, fragmented) => {
+                    self.fragmented = fragmented;
+                    return Ok(Some(WebSocketMessage::Ping(frame.payload)));
+                }
+                (WebSocketOpcode::Pong, fragmented) => {
+                    self.fragmented = fragmented;
+                    return Ok(Some(WebSocketMessage::Pong(frame.payload)));
+                }
+                (WebSocketOpcode::Reserved(..), fragmented) => {
+                    // Extensions are not negotiated through mirrord, skip what we don't know.
+                    self.fragmented = fragmented;
+                    continue;
+                }
+
+                (WebSocketOpcode::Continuation, None) => {
+                    return Err(WebSocketFrameError::UnexpectedContinuation);
+                }
+                (WebSocketOpcode::Continuation, Some((opcode, mut payload))) => {
+                    if payload.len() + frame.payload.len() > self.frames.max_payload_len {
+                        return Err(WebSocketFrameError::PayloadTooLarge(
+                            (payload.len() + frame.payload.len()) as u64,
+                            self.frames.max_payload_len,
+                        ));
+                    }
+                    payload.extend_from_slice(&frame.payload);
+                    (opcode, payload.freeze())
+                }
+
+                (WebSocketOpcode::Text | WebSocketOpcode::Binary, Some(..)) => {
+                    return Err(WebSocketFrameError::UnfinishedMessage);
+                }
+                (opcode @ (WebSocketOpcode::Text | WebSocketOpcode::Binary), None) => {
+                    (opcode, frame.payload)
+                }
+            };
+
+            if frame.fin.not() {
+                self.fragmented = Some((opcode, BytesMut::from(payload)));
+                continue;
+            }
+
+            let message = match opcode {
+                WebSocketOpcode::Text => WebSocketMessage::Text(payload),
+                _ => WebSocketMessage::Binary(payload),
+            };
+
+            return Ok(Some(message));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Incremental decoder of [`WebSocketMessage`]s sent in one direction of a connection.
+///
+/// Wraps [`WebSocketMessageCodec`] together with its buffer, for when the data arrives in arbitrary
+/// chunks.
+#[derive(Debug, Default)]
+pub struct WebSocketMessageStream {
+    codec: WebSocketMessageCodec,
+    buffer: BytesMut,
+}
+
+impl WebSocketMessageStream {
+    pub fn new(codec: WebSocketMessageCodec) -> Self {
+        Self {
+            codec,
+            buffer: Default::default(),
+        }
+    }
+
+    /// Appends the given chunk of data and returns all messages that are now complete.
+    pub fn push(&mut self, data: &[u8]) -> Result<Vec<WebSocketMessage>, WebSocketFrameError> {
+        self.buffer.extend_from_slice(data);
+
+        let mut messages = vec![];
+        while let Some(message) = self.codec.decode(&mut self.buffer)? {
+            messages.push(message);
+        }
+
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_codec::Decoder;
+    use bytes::{BufMut, BytesMut};
+    use hyper::{HeaderMap, header::HeaderValue};
+
+    use super::*;
+
+    /// Encodes a single frame, optionally masked with the given key.
+    fn frame(fin: bool, opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_u8(if fin { 0x80 } else { 0 } | opcode);
+
+        let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+        match payload.len() {
+            len @ 0..=125 => buf.put_u8(mask_bit | len as u8),
+            len @ 126..=0xFFFF => {
+                buf.put_u8(mask_bit | 126);
+                buf.put_u16(len as u16);
+            }
+            len => {
+                buf.put_u8(mask_bit | 127);
+                buf.put_u64(len as u64);
+            }
+        }
+
+        match mask {
+            Some(mask) => {
+                buf.put_slice(&mask);
+                buf.extend(
+                    payload
+                        .iter()
+                        .zip(mask.iter().cycle())
+                        .map(|(byte, mask)| byte ^ mask),
+                );
+            }
+            None => buf.put_slice(payload),
+        }
+
+        buf
+    }
+
+    #[test]
+    fn decode_masked_and_unmasked_frames() {
+        let mut codec = WebSocketFrameCodec::default();
+
+        let mut buf = frame(true, 0x1, b"hello", Some([1, 2, 3, 4]));
+        buf.extend_from_slice(&frame(true, 0x2, b"world", None));
+
+        let first = codec.decode(&mut buf).unwrap().unwrap();
+        assert!(first.fin);
+        assert_eq!(first.opcode, WebSocketOpcode::Text);
+        assert_eq!(first.payload.as_ref(), b"hello");
+
+        let second = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(second.opcode, WebSocketOpcode::Binary);
+        assert_eq!(second.payload.as_ref(), b"world");
+
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn decode_partial_and_extended_length_frame() {
+        let mut codec = WebSocketFrameCodec::default();
+        let payload = vec![7; 300];
+        let encoded = frame(true, 0x2, &payload, Some([9, 8, 7, 6]));
+
+        let mut buf = BytesMut::new();
+        for chunk in encoded.chunks(50) {
+            assert!(codec.decode(&mut buf).unwrap().is_none());
+            buf.extend_from_slice(chunk);
+        }
+
+        let decoded = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(decoded.payload.as_ref(), payload.as_slice());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn reject_too_large_frame() {
+        let mut codec = WebSocketFrameCodec { max_payload_len: 4 };
+        let mut buf = frame(true, 0x1, b"hello", None);
+
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(WebSocketFrameError::PayloadTooLarge(5, 4))
+        ));
+    }
+
+    #[test]
+    fn reassemble_fragmented_message() {
+        let mut codec = WebSocketMessageCodec::default();
+
+        let mut buf = frame(false, 0x1, b"{\"type\":", Some([1, 1, 1, 1]));
+        buf.extend_from_slice(&frame(true, 0x9, b"ping", Some([2, 2, 2, 2])));
+        buf.extend_from_slice(&frame(true, 0x0, b"\"subscribe\"}", Some([3, 3, 3, 3])));
+
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(WebSocketMessage::Ping(Bytes::from_static(b"ping")))
+        );
+        assert_eq!(
+            codec.decode(&mut buf).unwrap(),
+            Some(WebSocketMessage::Text(Bytes::from_static(
+                b"{\"type\":\"subscribe\"}"
+            )))
+        );
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn stream_chunked_messages() {
+        let mut stream = WebSocketMessageStream::default();
+
+        let mut encoded = frame(true, 0x1, b"first", Some([5, 6, 7, 8]));
+        encoded.extend_from_slice(&frame(true, 0x2, b"second", Some([8, 7, 6, 5])));
+        let (head, tail) = encoded.split_at(9);
+
+        assert_eq!(
+            stream.push(head).unwrap(),
+            [WebSocketMessage::Text(Bytes::from_static(b"first"))]
+        );
+        assert_eq!(
+            stream.push(tail).unwrap(),
+            [WebSocketMessage::Binary(Bytes::from_static(b"second"))]
+        );
+    }
+
+    #[test]
+    fn reject_unexpected_continuation() {
+        let mut codec = WebSocketMessageCodec::default();
+        let mut buf = frame(true, 0x0, b"oops", None);
+
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(WebSocketFrameError::UnexpectedContinuation)
+        ));
+    }
+
+    #[test]
+    fn upgrade_headers() {
+        let mut headers = HeaderMap::new();
+        assert!(is_websocket_upgrade(&headers).not());
+
+        headers.insert(UPGRADE, HeaderValue::from_static("WebSocket"));
+        headers.append(
+            SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static("graphql-ws, graphql-transport-ws"),
+        );
+        headers.append(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static("mqtt"));
+
+        assert!(is_websocket_upgrade(&headers));
+        assert_eq!(
+            offered_subprotocols(&headers).collect::<Vec<_>>(),
+            ["graphql-ws", "graphql-transport-ws", "mqtt"],
+        );
+    }
+}
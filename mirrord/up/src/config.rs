@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     ops::Not,
     sync::Arc,
 };
@@ -16,6 +16,7 @@ use mirrord_config::{
     },
     target::Target,
 };
+use regex::Regex;
 use serde::{
     Deserialize, Serialize,
     de::{MapAccess, Unexpected, Visitor, value::MapAccessDeserializer},
@@ -23,6 +24,8 @@ use serde::{
 use strum::VariantArray;
 use strum_macros::{Display, IntoStaticStr, VariantArray};
 
+use crate::UpError;
+
 /// Incoming traffic mode for a service.
 #[derive(
     Clone, Debug, Default, Serialize, Deserialize, PartialEq, VariantArray, IntoStaticStr, Display,
@@ -36,6 +39,10 @@ pub enum ServiceMode {
     /// [`ServiceConfig::assemble`])
     #[default]
     Split,
+
+    /// All incoming traffic is stolen by the local service, and queue
+    /// messages are not split.
+    Steal,
 }
 
 // Manual impl because we want different user-facing docs
@@ -49,6 +56,7 @@ impl ValueEnum for ServiceMode {
         match self {
             ServiceMode::Split => Some(PossibleValue::new(name).help("Incoming traffic is split between the local and original services using HTTP filtering.
 If no filter is provided, a header filter matching the regex `baggage: .*mirrord-session={session key}.*` will be used.")),
+            ServiceMode::Steal => Some(PossibleValue::new(name).help("All incoming traffic is stolen by the local service, the HTTP filter is ignored.")),
         }
     }
 }
@@ -203,6 +211,7 @@ impl TargetConfig {
             Self::Targetless => Ok(mirrord_config::target::TargetConfig {
                 path: None,
                 namespace: None,
+                selector: None,
             }),
 
             Self::Specified(SpecifiedTarget {
@@ -219,6 +228,7 @@ impl TargetConfig {
             }) => Ok(mirrord_config::target::TargetConfig {
                 path: path.clone(),
                 namespace: namespace.as_deref().map(Into::into),
+                selector: None,
             }),
         }
     }
@@ -246,6 +256,177 @@ where
     }
 }
 
+/// How long [`ReadinessConfig::timeout`] waits by default, in seconds.
+const DEFAULT_READINESS_TIMEOUT_SECS: u64 = 300;
+
+/// Checks that tell when a service is ready, so that services that
+/// [`depend on`](ServiceConfig::depends_on) it can be started.
+///
+/// A service is always considered ready only after its mirrord session
+/// is, and after all the probes set here pass.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReadinessConfig {
+    /// Local TCP port that accepts connections once the service is ready.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) tcp: Option<u16>,
+
+    /// Local HTTP endpoint that responds with a `2xx` status once the
+    /// service is ready.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) http: Option<HttpProbe>,
+
+    /// Regex matched against the service's output lines, the service is
+    /// ready after the first match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) log: Option<LogPattern>,
+
+    /// How many seconds the service has to become ready, before it is
+    /// considered failed (and maybe restarted, see [`RestartPolicy`]).
+    #[serde(default = "ReadinessConfig::default_timeout")]
+    pub(crate) timeout: u64,
+}
+
+impl ReadinessConfig {
+    fn default_timeout() -> u64 {
+        DEFAULT_READINESS_TIMEOUT_SECS
+    }
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            tcp: None,
+            http: None,
+            log: None,
+            timeout: DEFAULT_READINESS_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// `GET` request sent to `localhost` to check if a service is ready.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpProbe {
+    pub(crate) port: u16,
+
+    #[serde(default = "HttpProbe::default_path")]
+    pub(crate) path: String,
+}
+
+impl HttpProbe {
+    fn default_path() -> String {
+        "/".to_owned()
+    }
+}
+
+/// A [`Regex`] that is validated when the config is parsed.
+#[derive(Clone, Debug)]
+pub struct LogPattern(pub(crate) Regex);
+
+impl PartialEq for LogPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl<'de> Deserialize<'de> for LogPattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Self)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for LogPattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+/// What to do when a service exits with a non-zero status.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// The whole `mirrord up` run fails.
+    #[default]
+    No,
+
+    /// The service is restarted, waiting longer after every consecutive
+    /// failure.
+    OnFailure,
+}
+
+impl RestartPolicy {
+    fn is_no(&self) -> bool {
+        *self == Self::No
+    }
+}
+
+/// How long [`WatchConfig::debounce_ms`] waits by default, in milliseconds.
+const DEFAULT_WATCH_DEBOUNCE_MS: u64 = 500;
+
+/// Local files that restart the service when they change.
+///
+/// For `exec` services, only the local process is restarted, and it
+/// reconnects to the same mirrord session. `container` services are
+/// restarted with a new session.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchConfig {
+    /// Glob patterns of the watched files, relative to the directory in
+    /// which `mirrord up` runs (e.g. `src/**/*.rs`).
+    pub(crate) paths: Vec<WatchPattern>,
+
+    /// Glob patterns of files that never restart the service, even if
+    /// they match [`Self::paths`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) ignore: Vec<WatchPattern>,
+
+    /// How many milliseconds to wait for more changes, before the service
+    /// is restarted.
+    #[serde(default = "WatchConfig::default_debounce_ms")]
+    pub(crate) debounce_ms: u64,
+}
+
+impl WatchConfig {
+    fn default_debounce_ms() -> u64 {
+        DEFAULT_WATCH_DEBOUNCE_MS
+    }
+}
+
+/// A [`glob::Pattern`] that is validated when the config is parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchPattern(pub(crate) glob::Pattern);
+
+impl<'de> Deserialize<'de> for WatchPattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        glob::Pattern::new(&pattern)
+            .map(Self)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for WatchPattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
 /// Per-service configuration.
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -264,6 +445,19 @@ pub struct ServiceConfig {
     #[serde(default)]
     pub(crate) ignore_ports: BTreeSet<u16>,
 
+    /// Services that have to be ready before this one is started.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) depends_on: Vec<Arc<str>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) readiness: Option<ReadinessConfig>,
+
+    #[serde(default, skip_serializing_if = "RestartPolicy::is_no")]
+    pub(crate) restart: RestartPolicy,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) watch: Option<WatchConfig>,
+
     pub(crate) run: RunConfig,
 }
 
@@ -307,6 +501,7 @@ impl ServiceConfig {
                     mirrord_config::target::TargetConfig {
                         path: target.resolved.path,
                         namespace: target.resolved.namespace.as_deref().map(Into::into),
+                        selector: None,
                     }
                 }
                 None => panic!(
@@ -317,31 +512,43 @@ impl ServiceConfig {
         };
 
         cfg.feature.env = self.env;
+        cfg.feature.network.incoming.ignore_ports = self.ignore_ports.into_iter().collect();
+        cfg.key = key;
+        self.default_mode.apply(&mut cfg, &self.http_filter);
 
-        match self.default_mode {
-            ServiceMode::Split => {
-                cfg.feature.network.incoming.mode = IncomingMode::Steal;
+        (cfg, self.run)
+    }
+}
+
+impl ServiceMode {
+    /// Sets up incoming traffic and queue splitting in `cfg` for this mode.
+    ///
+    /// `http_filter` is the filter from the service's config, and `cfg.key`
+    /// must already be set.
+    pub(crate) fn apply(&self, cfg: &mut LayerConfig, http_filter: &HttpFilterConfig) {
+        cfg.feature.network.incoming.mode = IncomingMode::Steal;
 
-                cfg.feature.network.incoming.http_filter = if self.http_filter.is_filter_set() {
-                    self.http_filter
+        match self {
+            ServiceMode::Split => {
+                cfg.feature.network.incoming.http_filter = if http_filter.is_filter_set() {
+                    http_filter.clone()
                 } else {
                     HttpFilterConfig {
                         header_filter: Some(format!(
                             "baggage: .*mirrord-session={}.*",
-                            key.as_str()
+                            cfg.key.as_str()
                         )),
                         ..Default::default()
                     }
                 };
 
-                cfg.feature.split_queues = SplitQueuesConfig::all_wildcard(&key);
+                cfg.feature.split_queues = SplitQueuesConfig::all_wildcard(&cfg.key);
+            }
+            ServiceMode::Steal => {
+                cfg.feature.network.incoming.http_filter = HttpFilterConfig::default();
+                cfg.feature.split_queues = SplitQueuesConfig::default();
             }
         }
-
-        cfg.feature.network.incoming.ignore_ports = self.ignore_ports.into_iter().collect();
-        cfg.key = key;
-
-        (cfg, self.run)
     }
 }
 
@@ -354,6 +561,22 @@ pub struct SubprocessCfg {
     pub service_name: Arc<str>,
     /// How to run this service (exec vs container, and the command).
     pub run: RunConfig,
+    /// Services that have to be ready before this one is started.
+    pub depends_on: Vec<Arc<str>>,
+    /// How to tell that this service is ready.
+    ///
+    /// [`None`] if the service has no `readiness` block, then it's ready as
+    /// soon as its mirrord session is, and it has no deadline.
+    pub readiness: Option<ReadinessConfig>,
+    /// What to do when this service fails.
+    pub restart: RestartPolicy,
+    /// Local files that restart this service when they change.
+    pub watch: Option<WatchConfig>,
+    /// Mode that was applied to the [`Self::config`].
+    pub mode: ServiceMode,
+    /// HTTP filter from the service's config, used when the mode is
+    /// switched back to [`ServiceMode::Split`].
+    pub http_filter: HttpFilterConfig,
 }
 
 impl UpConfig {
@@ -380,17 +603,82 @@ impl UpConfig {
             services,
         } = self;
 
-        services.into_iter().map(move |(service_name, svc)| {
+        services.into_iter().map(move |(service_name, mut svc)| {
+            let depends_on = std::mem::take(&mut svc.depends_on);
+            let readiness = svc.readiness.take();
+            let restart = svc.restart;
+            let watch = svc.watch.take();
+            let mode = svc.default_mode.clone();
+            let http_filter = svc.http_filter.clone();
+
             let (config, run) =
                 svc.assemble(&service_name, &defaults, key.clone(), resolved_targets);
             SubprocessCfg {
                 config,
                 service_name,
                 run,
+                depends_on,
+                readiness,
+                restart,
+                watch,
+                mode,
+                http_filter,
             }
         })
     }
 
+    /// Returns the names of all services, ordered so that every service
+    /// comes after the services it [`depends on`](ServiceConfig::depends_on).
+    ///
+    /// Fails if a service depends on a service that is not defined, or if
+    /// the dependencies form a cycle.
+    pub(crate) fn start_order(&self) -> Result<Vec<Arc<str>>, UpError> {
+        // Sorted, so that independent services always start in the same order.
+        let mut pending = BTreeMap::new();
+        for (name, svc) in &self.services {
+            let dependencies = svc
+                .depends_on
+                .iter()
+                .map(|dependency| {
+                    self.services
+                        .get_key_value(dependency)
+                        .map(|(dependency, _)| dependency)
+                        .ok_or_else(|| UpError::UnknownDependency {
+                            service: Arc::clone(name),
+                            dependency: Arc::clone(dependency),
+                        })
+                })
+                .collect::<Result<BTreeSet<_>, _>>()?;
+
+            pending.insert(name, dependencies);
+        }
+
+        let mut order = Vec::with_capacity(pending.len());
+        while pending.is_empty().not() {
+            let startable = pending
+                .iter()
+                .filter(|(_, dependencies)| dependencies.is_empty())
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>();
+
+            if startable.is_empty() {
+                return Err(UpError::DependencyCycle {
+                    services: pending.into_keys().cloned().collect(),
+                });
+            }
+
+            for name in startable {
+                pending.remove(name);
+                pending.values_mut().for_each(|dependencies| {
+                    dependencies.remove(name);
+                });
+                order.push(Arc::clone(name));
+            }
+        }
+
+        Ok(order)
+    }
+
     /// Produces an iterator of [`UnresolvedTarget`]s that yields an
     /// item for each service entry that requires querying the cluster
     /// to resolve the workload.
@@ -439,6 +727,10 @@ impl CollectAnalytics for &UpConfig {
         let mut count_default_mode: u32 = 0;
         let mut count_http_filter: u32 = 0;
         let mut count_ignore_ports: u32 = 0;
+        let mut count_depends_on: u32 = 0;
+        let mut count_readiness: u32 = 0;
+        let mut count_restart: u32 = 0;
+        let mut count_watch: u32 = 0;
 
         let mut count_exec: u32 = 0;
         let mut count_container: u32 = 0;
@@ -450,6 +742,10 @@ impl CollectAnalytics for &UpConfig {
                 default_mode,
                 http_filter,
                 ignore_ports,
+                depends_on,
+                readiness,
+                restart,
+                watch,
                 run,
             } = svc;
 
@@ -468,6 +764,18 @@ impl CollectAnalytics for &UpConfig {
             if ignore_ports.is_empty().not() {
                 count_ignore_ports += 1;
             }
+            if depends_on.is_empty().not() {
+                count_depends_on += 1;
+            }
+            if readiness.is_some() {
+                count_readiness += 1;
+            }
+            if restart.is_no().not() {
+                count_restart += 1;
+            }
+            if watch.is_some() {
+                count_watch += 1;
+            }
 
             match run.r#type {
                 RunType::Exec => count_exec += 1,
@@ -481,6 +789,10 @@ impl CollectAnalytics for &UpConfig {
         config_fields_used.add("default_mode", count_default_mode);
         config_fields_used.add("http_filter", count_http_filter);
         config_fields_used.add("ignore_ports", count_ignore_ports);
+        config_fields_used.add("depends_on", count_depends_on);
+        config_fields_used.add("readiness", count_readiness);
+        config_fields_used.add("restart", count_restart);
+        config_fields_used.add("watch", count_watch);
         analytics.add("config_fields_used", config_fields_used);
 
         let mut run_types = Analytics::default();
@@ -695,6 +1007,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn steal_mode_drops_filters_and_queue_splits() {
+        let config = parse(
+            r#"
+            services:
+              worker:
+                target:
+                  path: "deployment/sqs-printer"
+                http_filter:
+                  header_filter: "x-session: me"
+                run:
+                  command: ["echo"]
+            "#,
+        );
+
+        let mut service = config
+            .service_configs(&env_key(), &mut HashMap::new())
+            .next()
+            .unwrap();
+
+        ServiceMode::Steal.apply(&mut service.config, &service.http_filter);
+        let incoming = &service.config.feature.network.incoming;
+        assert_eq!(incoming.mode, IncomingMode::Steal);
+        assert!(incoming.http_filter.is_filter_set().not());
+        assert_eq!(
+            service.config.feature.split_queues,
+            SplitQueuesConfig::default()
+        );
+
+        ServiceMode::Split.apply(&mut service.config, &service.http_filter);
+        assert_eq!(
+            service
+                .config
+                .feature
+                .network
+                .incoming
+                .http_filter
+                .header_filter
+                .as_deref(),
+            Some("x-session: me")
+        );
+        assert!(
+            service
+                .config
+                .feature
+                .split_queues
+                .splits()
+                .is_empty()
+                .not()
+        );
+    }
+
     #[test]
     fn specified_target_parses_correctly() {
         let config = parse(
@@ -1066,6 +1430,10 @@ mod tests {
                     "default_mode": 0,
                     "http_filter": 0,
                     "ignore_ports": 0,
+                    "depends_on": 0,
+                    "readiness": 0,
+                    "restart": 0,
+                    "watch": 0,
                 },
                 "run_types": {
                     "exec": 1,
@@ -1139,4 +1507,186 @@ mod tests {
         assert_eq!(result["run_types"]["exec"], 2);
         assert_eq!(result["run_types"]["container"], 1);
     }
+
+    #[test]
+    fn readiness_and_restart_parse() {
+        let config = parse(
+            r#"
+            services:
+              api:
+                depends_on: [db]
+                readiness:
+                  http:
+                    port: 8080
+                    path: /healthz
+                  log: "listening on .*"
+                restart: on-failure
+                run:
+                  command: ["echo"]
+              db:
+                readiness:
+                  tcp: 5432
+                  timeout: 30
+                run:
+                  command: ["echo"]
+            "#,
+        );
+
+        let api = &config.services["api"];
+        assert_eq!(api.depends_on, vec![Arc::<str>::from("db")]);
+        assert_eq!(api.restart, RestartPolicy::OnFailure);
+        let readiness = api.readiness.as_ref().unwrap();
+        assert_eq!(
+            readiness.http,
+            Some(HttpProbe {
+                port: 8080,
+                path: "/healthz".to_owned(),
+            })
+        );
+        assert!(
+            readiness
+                .log
+                .as_ref()
+                .unwrap()
+                .0
+                .is_match("listening on :8080")
+        );
+        assert_eq!(readiness.timeout, DEFAULT_READINESS_TIMEOUT_SECS);
+
+        let db = &config.services["db"];
+        assert_eq!(db.restart, RestartPolicy::No);
+        assert_eq!(
+            db.readiness,
+            Some(ReadinessConfig {
+                tcp: Some(5432),
+                timeout: 30,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn error_invalid_log_regex() {
+        let result: Result<UpConfig, _> = serde_yaml::from_str(
+            r#"
+            services:
+              svc:
+                readiness:
+                  log: "listening (on"
+                run:
+                  command: ["echo"]
+            "#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn watch_parses_with_default_debounce() {
+        let config = parse(
+            r#"
+            services:
+              api:
+                watch:
+                  paths: ["src/**/*.rs", "Cargo.toml"]
+                  ignore: ["src/generated/**"]
+                run:
+                  command: ["cargo", "run"]
+            "#,
+        );
+
+        let watch = config.services["api"].watch.as_ref().unwrap();
+        assert_eq!(watch.debounce_ms, DEFAULT_WATCH_DEBOUNCE_MS);
+        assert!(watch.paths[0].0.matches("src/api/routes.rs"));
+        assert!(watch.paths[1].0.matches("Cargo.toml"));
+        assert!(watch.ignore[0].0.matches("src/generated/schema.rs"));
+    }
+
+    #[test]
+    fn error_invalid_watch_glob() {
+        let result: Result<UpConfig, _> = serde_yaml::from_str(
+            r#"
+            services:
+              svc:
+                watch:
+                  paths: ["src/[a-"]
+                run:
+                  command: ["echo"]
+            "#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn start_order_follows_dependencies() {
+        let config = parse(
+            r#"
+            services:
+              frontend:
+                depends_on: [api]
+                run:
+                  command: ["echo"]
+              api:
+                depends_on: [db, cache]
+                run:
+                  command: ["echo"]
+              db:
+                run:
+                  command: ["echo"]
+              cache:
+                run:
+                  command: ["echo"]
+            "#,
+        );
+
+        let order = config.start_order().unwrap();
+        assert_eq!(
+            order.iter().map(AsRef::as_ref).collect::<Vec<&str>>(),
+            ["cache", "db", "api", "frontend"]
+        );
+    }
+
+    #[test]
+    fn start_order_rejects_unknown_dependency() {
+        let config = parse(
+            r#"
+            services:
+              api:
+                depends_on: [db]
+                run:
+                  command: ["echo"]
+            "#,
+        );
+
+        assert!(matches!(
+            config.start_order(),
+            Err(UpError::UnknownDependency { service, dependency })
+                if service.as_ref() == "api" && dependency.as_ref() == "db"
+        ));
+    }
+
+    #[test]
+    fn start_order_rejects_cycles() {
+        let config = parse(
+            r#"
+            services:
+              a:
+                depends_on: [b]
+                run:
+                  command: ["echo"]
+              b:
+                depends_on: [a]
+                run:
+                  command: ["echo"]
+              c:
+                run:
+                  command: ["echo"]
+            "#,
+        );
+
+        assert!(matches!(
+            config.start_order(),
+            Err(UpError::DependencyCycle { services })
+                if services == [Arc::<str>::from("a"), Arc::from("b")]
+        ));
+    }
 }
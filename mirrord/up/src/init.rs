@@ -32,8 +32,8 @@ use strum::VariantArray;
 use thiserror::Error;
 
 use crate::config::{
-    CommonConfig, RunConfig, RunType, ServiceConfig, ServiceMode, SpecifiedTarget, TargetConfig,
-    UpConfig,
+    CommonConfig, RestartPolicy, RunConfig, RunType, ServiceConfig, ServiceMode, SpecifiedTarget,
+    TargetConfig, UpConfig,
 };
 
 /// Errors produced by the `mirrord up init` wizard.
@@ -172,6 +172,10 @@ fn prompt_service(
             default_mode,
             http_filter,
             ignore_ports,
+            depends_on: Vec::new(),
+            readiness: None,
+            restart: RestartPolicy::default(),
+            watch: None,
             run,
         },
     ))
@@ -237,6 +241,7 @@ fn prompt_http_filter(mode: &ServiceMode) -> Result<HttpFilterConfig, InitError>
 
             Ok(filter)
         }
+        ServiceMode::Steal => Ok(HttpFilterConfig::default()),
     }
 }
 
@@ -456,6 +461,10 @@ mod tests {
                 ..Default::default()
             },
             ignore_ports: [9090, 15090].into_iter().collect(),
+            depends_on: Vec::new(),
+            readiness: None,
+            restart: RestartPolicy::default(),
+            watch: None,
             run: RunConfig {
                 r#type: RunType::Exec,
                 command: vec!["go".to_owned(), "run".to_owned(), "./cmd/api".to_owned()],
@@ -515,6 +524,10 @@ mod tests {
                 ..Default::default()
             },
             ignore_ports: BTreeSet::new(),
+            depends_on: Vec::new(),
+            readiness: None,
+            restart: RestartPolicy::default(),
+            watch: None,
             run: RunConfig {
                 r#type: RunType::Exec,
                 command: vec!["echo".to_owned()],
@@ -553,6 +566,10 @@ mod tests {
             default_mode: ServiceMode::default(),
             http_filter: HttpFilterConfig::default(),
             ignore_ports: [9090, 9091, 15090].into_iter().collect(),
+            depends_on: Vec::new(),
+            readiness: None,
+            restart: RestartPolicy::default(),
+            watch: None,
             run: RunConfig {
                 r#type: RunType::Exec,
                 command: vec!["go".to_owned(), "run".to_owned(), "--opt=a,b".to_owned()],
@@ -596,6 +613,10 @@ mod tests {
             default_mode: ServiceMode::default(),
             http_filter: HttpFilterConfig::default(),
             ignore_ports: BTreeSet::new(),
+            depends_on: Vec::new(),
+            readiness: None,
+            restart: RestartPolicy::default(),
+            watch: None,
             run: RunConfig {
                 r#type: RunType::Exec,
                 command: vec!["echo".to_owned()],
@@ -9,13 +9,10 @@ use std::{
     collections::HashMap,
     ops::Not,
     path::{Path, PathBuf},
-    process::{ExitStatus, Stdio},
+    process::ExitStatus,
     str::FromStr,
-    sync::{
-        Arc, OnceLock,
-        atomic::{AtomicUsize, Ordering},
-    },
-    time::{Duration, Instant},
+    sync::{Arc, OnceLock},
+    time::Duration,
 };
 
 use config::{ResolvedTarget, SpecifiedTarget, UnresolvedTarget};
@@ -23,7 +20,6 @@ use futures::TryStreamExt;
 use inquire::{Confirm, Select};
 use k8s_openapi::api::core::v1::Namespace;
 use miette::Diagnostic;
-use mirrord_analytics::MIRRORD_UP_CORRELATION_ID_ENV;
 use mirrord_config::{
     config::{ConfigError, EnvKey},
     target::{Target, TargetType},
@@ -37,19 +33,23 @@ use yamlpatch::{Op, Patch, apply_yaml_patches};
 use yamlpath::{Document, route};
 mod config;
 mod init;
+mod supervisor;
+mod tui;
+mod watcher;
 
 pub use config::{ServiceMode, SubprocessCfg, UpConfig};
 pub use init::{InitError, run_wizard};
-use mirrord_progress::{MIRRORD_PROGRESS_ENV, messages::SESSION_READY_MESSAGE};
+use supervisor::{ServiceEvent, ServiceProcess, ServiceStatus, StackReadiness};
 use tokio::{
-    io::{AsyncBufReadExt, BufReader},
-    process::Command,
+    sync::{mpsc, watch},
     task::{JoinError, JoinSet},
 };
+use tui::TuiService;
 use uuid::Uuid;
 
 /// Shared slot that [`run`] fills with the time it took for **all** child
-/// sessions to become ready, measured from process spawn.
+/// sessions to become ready (including their readiness checks), measured
+/// from process spawn.
 ///
 /// The caller constructs one, passes a clone to [`run`], and reads
 /// [`ReadyTracker::time_to_ready`] afterwards (the value is captured even if
@@ -94,6 +94,49 @@ pub enum UpError {
         status: ExitStatus,
     },
 
+    /// A child mirrord service did not pass its readiness checks in time.
+    #[error("Service {name} did not become ready in {}s", timeout.as_secs())]
+    #[diagnostic(help(
+        "Check the service's output, or increase `readiness.timeout` in your mirrord-up.yaml."
+    ))]
+    NotReady {
+        /// Name of the service that did not become ready.
+        name: Arc<str>,
+        /// How long the service had to become ready.
+        timeout: Duration,
+    },
+
+    /// A service depends on a service that is not defined in the config.
+    #[error("Service {service} depends on {dependency}, which is not defined")]
+    #[diagnostic(help("Check the `depends_on` lists in your mirrord-up.yaml."))]
+    UnknownDependency {
+        /// Name of the service with the unknown dependency.
+        service: Arc<str>,
+        /// Name of the unknown dependency.
+        dependency: Arc<str>,
+    },
+
+    /// The `depends_on` lists of the services form a cycle.
+    #[error("Services {} can't be started, their dependencies form a cycle", services.join(", "))]
+    #[diagnostic(help("Check the `depends_on` lists in your mirrord-up.yaml."))]
+    DependencyCycle {
+        /// Services that are part of, or depend on, the cycle.
+        services: Vec<Arc<str>>,
+    },
+
+    /// Failed to start watching the files of a service.
+    #[error("failed to watch the files of service {name}: {error}")]
+    #[diagnostic(help(
+        "Check the `watch.paths` in your mirrord-up.yaml, the directories they start with have to exist."
+    ))]
+    Watch {
+        /// Name of the service with the watched files.
+        name: Arc<str>,
+        /// Error from the file watcher.
+        #[source]
+        error: notify::Error,
+    },
+
     /// A child process handler task panicked.
     #[error("Child process handler task panicked: {0:?}")]
     Panic(JoinError),
@@ -202,6 +245,7 @@ async fn list_workloads(client: &kube::Client, namespace: &str) -> Result<Vec<St
         client,
         namespace,
         copy_target: false,
+        label_selector: None,
     };
 
     // `operator_active: true` only lifts the seeker's restriction on listing
@@ -217,6 +261,7 @@ async fn list_namespaces(client: &kube::Client) -> Result<Vec<String>, UpError>
         client,
         namespace: client.default_namespace(),
         copy_target: false,
+        label_selector: None,
     };
 
     let namespaces = seeker
@@ -370,113 +415,115 @@ fn save_target(
 /// Genererate [`mirrord_config::LayerConfig`]s based on the provided [`UpConfig`] and
 /// [`EnvKey`] and spawn child mirrord processes. Stdout/stderr from
 /// children will be printed to the console, prefixed with the name of
-/// the session.
+/// the session, or shown in the interactive terminal UI when `tui` is set.
 ///
-/// `ready` is filled with the time-to-ready once every session has signalled
-/// readiness (see [`ReadyTracker`]).
+/// Services are started in dependency order: a service with `depends_on`
+/// is spawned only once all of its dependencies passed their readiness
+/// checks. Failed services may be restarted, according to their `restart`
+/// policy, and services with `watch` are restarted when their files change.
 ///
-/// Returns when one of the child mirrord sessions exits.
+/// `ready` is filled with the time-to-ready once every session has become
+/// ready (see [`ReadyTracker`]).
+///
+/// Returns when one of the child mirrord sessions exits, or fails and is
+/// not restarted. With `tui`, returns only when the user quits the UI.
 pub async fn run(
     up_config: UpConfig,
     config_path: &Path,
     key: EnvKey,
     correlation_id: Uuid,
     ready: ReadyTracker,
+    tui: bool,
 ) -> Result<(), UpError> {
+    let start_order = up_config.start_order()?;
     let mut resolved_targets = resolve_unresolved_workloads(&up_config, config_path).await?;
 
-    let commands: Vec<_> = up_config
+    let (events_tx, mut events) = mpsc::unbounded_channel();
+    let mut services = up_config
         .service_configs(&key, &mut resolved_targets)
         .map(|config| {
-            let SubprocessCfg {
-                config,
-                service_name,
-                run,
-            } = config;
-
-            let encoded_cfg = config.encode()?;
-
-            let mut cmd = Command::new(std::env::current_exe()?);
-            cmd.env(RESOLVED_CONFIG_ENV, encoded_cfg)
-                .env(MIRRORD_PROGRESS_ENV, "simple")
-                .env(MIRRORD_UP_CORRELATION_ID_ENV, correlation_id.to_string())
-                .arg(Into::<&'static str>::into(run.r#type))
-                .arg("--")
-                .args(run.command)
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .kill_on_drop(true);
-
-            Ok((service_name, cmd))
+            let service = ServiceProcess::new(config, correlation_id, events_tx.clone());
+            (Arc::clone(&service.name), service)
         })
-        .collect::<Result<_, UpError>>()?;
+        .collect::<HashMap<_, _>>();
+    drop(events_tx);
 
-    let start = Instant::now();
-    let total = commands.len();
-    let ready_count = Arc::new(AtomicUsize::new(0));
+    let stack = Arc::new(StackReadiness::new(services.len(), ready.clone()));
+    let mut readiness: HashMap<Arc<str>, watch::Receiver<bool>> = HashMap::new();
+    let mut tui_services = Vec::new();
 
     let mut handles = JoinSet::new();
-    for (name, mut command) in commands {
-        let mut child = command.spawn().unwrap();
-        let ready_count = Arc::clone(&ready_count);
-        let ready = ready.clone();
-        handles.spawn(async move {
-            let mut err = BufReader::new(child.stderr.take().unwrap()).lines();
-            let mut out = BufReader::new(child.stdout.take().unwrap()).lines();
-
-            // A session is only counted once: the user's binary inherits the
-            // same stdout after `execve`, so a later line matching the marker
-            // must not be double-counted.
-            let mut counted = false;
-
-            loop {
-                tokio::select! {
-                    line = out.next_line() => match line {
-                        Ok(Some(line)) => {
-                            if counted.not() && line.trim() == SESSION_READY_MESSAGE {
-                                counted = true;
-                                if ready_count.fetch_add(1, Ordering::Relaxed) + 1 == total {
-                                    // TODO(areg) downgrade to a
-                                    // `debug_assert` once the feature
-                                    // stabilizes.
-                                    ready.elapsed.set(start.elapsed()).expect("only the final task should set the ready marker");
-                                }
-                            }
-                            println!("{name}: {line}");
-                        }
-                        Ok(None) => {}
-                        Err(err) => println!("{name} error: {err:?}"),
-                    },
+    for name in start_order {
+        let service = services
+            .remove(&name)
+            .expect("every service should have a place in the start order");
+
+        // Dependencies come earlier in the start order, so their channels
+        // already exist.
+        let dependencies = service
+            .depends_on
+            .iter()
+            .filter_map(|dependency| {
+                readiness
+                    .get(dependency)
+                    .map(|ready| (Arc::clone(dependency), ready.clone()))
+            })
+            .collect();
+
+        let (ready_tx, ready_rx) = watch::channel(false);
+        readiness.insert(Arc::clone(&name), ready_rx);
+
+        // Without the UI, the sender is dropped right away, so the service
+        // task finishes as soon as the service is done.
+        let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+        if tui {
+            tui_services.push(TuiService {
+                name,
+                mode: service.mode.clone(),
+                commands: commands_tx,
+            });
+        }
 
-                    line = err.next_line() => match line {
-                        Ok(Some(line)) => println!("{name}: {line}"),
-                        Ok(None) => {}
-                        Err(err) => println!("{name} error: {err:?}"),
-                    },
+        handles.spawn(service.supervise(dependencies, ready_tx, Arc::clone(&stack), commands_rx));
+    }
 
-                    status = child.wait() => {
-                        let status = status?;
-                        if status.success() {
-                            break Ok(());
-                        } else {
-                            break Err(UpError::ServiceCrashed {
-                                name,
-                                status,
-                            })
-                        }
-                    }
-                }
-            }
-        });
+    if tui {
+        return tui::run(tui_services, events, ready).await;
     }
 
-    let Some(status) = handles.join_next().await else {
-        unreachable!("should have at least one service")
+    let status = loop {
+        tokio::select! {
+            biased;
+
+            Some((name, event)) = events.recv() => print_event(&name, event),
+
+            Some(status) = handles.join_next() => break status,
+
+            else => unreachable!("should have at least one service"),
+        }
     };
 
+    // Print whatever the services reported before the run ended.
+    while let Ok((name, event)) = events.try_recv() {
+        print_event(&name, event);
+    }
+
     // Handle JoinError and UpError from child handler tasks
     status.map_err(UpError::Panic)??;
 
     Ok(())
 }
+
+/// Prints a [`ServiceEvent`] when running without the [`tui`].
+fn print_event(name: &str, event: ServiceEvent) {
+    match event {
+        ServiceEvent::Output(line) => println!("{name}: {line}"),
+        ServiceEvent::Status(
+            status @ (ServiceStatus::Waiting(..)
+            | ServiceStatus::Restarting { .. }
+            | ServiceStatus::Reloading(..)
+            | ServiceStatus::Failed(..)),
+        ) => println!("{name}: {status}"),
+        ServiceEvent::Status(..) | ServiceEvent::SessionId(..) => {}
+    }
+}
@@ -0,0 +1,906 @@
+//! Runs a single `mirrord up` service: waits for the services it depends on,
+//! checks when it becomes ready, and restarts it according to its
+//! [`RestartPolicy`], [`ServiceCommand`]s or changes of its watched files.
+//!
+//! Instead of printing anything, the service reports [`ServiceEvent`]s, which
+//! are either printed by [`crate::run`] or shown in the [`crate::tui`].
+
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::Not,
+    path::PathBuf,
+    process::{ExitStatus, Stdio},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use mirrord_analytics::MIRRORD_UP_CORRELATION_ID_ENV;
+use mirrord_config::{LayerConfig, feature::network::incoming::http_filter::HttpFilterConfig};
+use mirrord_progress::{MIRRORD_PROGRESS_ENV, messages::SESSION_READY_MESSAGE};
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader, Lines},
+    net::TcpStream,
+    process::{Child, Command},
+    sync::{mpsc, watch},
+};
+use uuid::Uuid;
+
+use crate::{
+    RESOLVED_CONFIG_ENV, ReadyTracker, UpError,
+    config::{
+        ReadinessConfig, RestartPolicy, RunConfig, RunType, ServiceMode, SubprocessCfg, WatchConfig,
+    },
+    watcher::{self, FileWatcher},
+};
+
+/// How often the TCP and HTTP readiness probes are tried.
+const PROBE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long a single TCP or HTTP readiness probe may take.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Minimal `internal_proxy.idle_timeout` of services that keep their
+/// [`Session`] across restarts, so that the intproxy waits for the restarted
+/// process.
+const WATCH_IDLE_TIMEOUT_SECS: u64 = 30;
+
+/// Counts the services that became ready, to fill the [`ReadyTracker`] once
+/// all of them did.
+pub(crate) struct StackReadiness {
+    start: Instant,
+    total: usize,
+    ready_count: AtomicUsize,
+    tracker: ReadyTracker,
+}
+
+impl StackReadiness {
+    pub(crate) fn new(total: usize, tracker: ReadyTracker) -> Self {
+        Self {
+            start: Instant::now(),
+            total,
+            ready_count: AtomicUsize::new(0),
+            tracker,
+        }
+    }
+
+    /// Must be called only once per service, even if it's restarted.
+    fn service_ready(&self) {
+        if self.ready_count.fetch_add(1, Ordering::Relaxed) + 1 == self.total {
+            // TODO(areg) downgrade to a `debug_assert` once the feature
+            // stabilizes.
+            self.tracker
+                .elapsed
+                .set(self.start.elapsed())
+                .expect("only the final task should set the ready marker");
+        }
+    }
+}
+
+/// Why a single run of a service failed.
+#[derive(Debug)]
+enum Failure {
+    /// The process exited with a non-zero status.
+    Crashed(ExitStatus),
+    /// The service did not pass its readiness checks in time, and was killed.
+    NotReady(Duration),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Crashed(status) => write!(f, "crashed with exit status {status}"),
+            Self::NotReady(timeout) => {
+                write!(f, "did not become ready in {}s", timeout.as_secs())
+            }
+        }
+    }
+}
+
+impl Failure {
+    fn into_error(self, name: Arc<str>) -> UpError {
+        match self {
+            Self::Crashed(status) => UpError::ServiceCrashed { name, status },
+            Self::NotReady(timeout) => UpError::NotReady { name, timeout },
+        }
+    }
+}
+
+/// Delay before restarting a failed service, doubled after every
+/// consecutive failure.
+struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    const INITIAL: Duration = Duration::from_secs(1);
+    const MAX: Duration = Duration::from_secs(30);
+    /// A service that ran for this long before failing is restarted with
+    /// the [`Self::INITIAL`] delay again.
+    const RESET_AFTER: Duration = Duration::from_secs(60);
+
+    /// Returns the delay before the next restart of a service that failed
+    /// after running for `uptime`.
+    fn next(&mut self, uptime: Duration) -> Duration {
+        if uptime >= Self::RESET_AFTER {
+            self.delay = Self::INITIAL;
+        }
+
+        let delay = self.delay;
+        self.delay = (delay * 2).min(Self::MAX);
+        delay
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            delay: Self::INITIAL,
+        }
+    }
+}
+
+/// Tracks the readiness checks of a single run of a service.
+///
+/// Without a [`ReadinessConfig`], the service is ready as soon as its mirrord session is.
+struct ReadinessCheck<'a> {
+    config: Option<&'a ReadinessConfig>,
+    session_ready: bool,
+    log_matched: bool,
+    probes_passed: bool,
+}
+
+impl<'a> ReadinessCheck<'a> {
+    fn new(config: Option<&'a ReadinessConfig>) -> Self {
+        Self {
+            config,
+            session_ready: false,
+            log_matched: false,
+            probes_passed: config
+                .is_none_or(|config| config.tcp.is_none() && config.http.is_none()),
+        }
+    }
+
+    fn on_line(&mut self, line: &str) {
+        if line.trim() == SESSION_READY_MESSAGE {
+            self.session_ready = true;
+        } else if let Some(log) = self.config.and_then(|config| config.log.as_ref())
+            && log.0.is_match(line)
+        {
+            self.log_matched = true;
+        }
+    }
+
+    /// Probes are only tried once the mirrord session is ready, so that we
+    /// don't mistake some other local process for the service.
+    fn needs_probing(&self) -> bool {
+        self.session_ready && self.probes_passed.not()
+    }
+
+    async fn probe(&mut self) {
+        let Some(config) = self.config else {
+            return;
+        };
+
+        let tcp = match config.tcp {
+            Some(port) => {
+                tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(("localhost", port)))
+                    .await
+                    .is_ok_and(|connected| connected.is_ok())
+            }
+            None => true,
+        };
+
+        let http = match &config.http {
+            Some(http) if tcp => {
+                tokio::time::timeout(PROBE_TIMEOUT, http_status(http.port, &http.path))
+                    .await
+                    .is_ok_and(|status| {
+                        status.is_ok_and(|status| status.is_some_and(|code| code / 100 == 2))
+                    })
+            }
+            Some(_) => false,
+            None => true,
+        };
+
+        self.probes_passed = tcp && http;
+    }
+
+    /// Marks the mirrord session as ready, for processes that reuse a
+    /// [`Session`] and don't print the marker.
+    fn set_session_ready(&mut self) {
+        self.session_ready = true;
+    }
+
+    fn is_ready(&self) -> bool {
+        self.session_ready
+            && self.probes_passed
+            && (self.config.is_none_or(|config| config.log.is_none()) || self.log_matched)
+    }
+}
+
+/// Sends a `GET` request to `localhost:{port}{path}` and returns the status
+/// code of the response, if it has one.
+async fn http_status(port: u16, path: &str) -> std::io::Result<Option<u16>> {
+    let mut stream = TcpStream::connect(("localhost", port)).await?;
+    stream
+        .write_all(
+            format!("GET {path} HTTP/1.1\r\nHost: localhost:{port}\r\nConnection: close\r\n\r\n")
+                .as_bytes(),
+        )
+        .await?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).await?;
+
+    // `HTTP/1.1 200 OK`
+    Ok(status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok()))
+}
+
+/// Prefix of the line in which `mirrord exec` and `mirrord container` print
+/// the id of their session monitor API.
+const SESSION_ID_PREFIX: &str = "session ID: ";
+
+/// Status of a service, printed or shown in the [`crate::tui`].
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ServiceStatus {
+    /// Waiting for one of its dependencies to become ready.
+    Waiting(Arc<str>),
+    /// Started, but not ready yet.
+    Starting,
+    /// Passed its readiness checks.
+    Ready,
+    /// Failed, and is going to be restarted after `delay`.
+    Restarting { reason: String, delay: Duration },
+    /// Failed, and won't be restarted.
+    Failed(String),
+    /// Stopped by the user.
+    Stopped,
+    /// Exited successfully.
+    Exited,
+    /// One of the watched files changed, and the service is restarted.
+    Reloading(PathBuf),
+}
+
+impl fmt::Display for ServiceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Waiting(dependency) => write!(f, "waiting for {dependency} to be ready"),
+            Self::Starting => f.write_str("starting"),
+            Self::Ready => f.write_str("ready"),
+            Self::Restarting { reason, delay } => {
+                write!(f, "{reason}, restarting in {}s", delay.as_secs())
+            }
+            Self::Failed(reason) => f.write_str(reason),
+            Self::Stopped => f.write_str("stopped"),
+            Self::Exited => f.write_str("exited"),
+            Self::Reloading(path) => write!(f, "{} changed, restarting", path.display()),
+        }
+    }
+}
+
+/// Something that happened to a service.
+#[derive(Clone, Debug)]
+pub(crate) enum ServiceEvent {
+    /// A line of the service's stdout or stderr.
+    Output(String),
+    Status(ServiceStatus),
+    /// Id of the session monitor API of the service's current mirrord
+    /// session.
+    SessionId(String),
+}
+
+/// Where all services report their [`ServiceEvent`]s.
+pub(crate) type ServiceEventTx = mpsc::UnboundedSender<(Arc<str>, ServiceEvent)>;
+
+/// Request from the [`crate::tui`] user, to control a single service.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ServiceCommand {
+    /// Kills the service (if it's running), and starts it again.
+    Restart,
+    /// Kills the service, it won't be restarted until another command
+    /// comes.
+    Stop,
+    /// Restarts the service in a different mode, which requires a new
+    /// mirrord session.
+    SetMode(ServiceMode),
+}
+
+/// How a single run of a service ended.
+enum Outcome {
+    /// The process exited (or was killed because it did not become ready),
+    /// with [`None`] meaning success.
+    Exited(Option<Failure>),
+    /// The process was killed to handle a [`ServiceCommand`].
+    Command(ServiceCommand),
+    /// The process was killed because this watched file changed.
+    Changed(PathBuf),
+}
+
+/// A mirrord session started with `mirrord ext`, in which the service's
+/// process can be restarted without reconnecting to the cluster.
+///
+/// Deserialized from the output of `mirrord ext`.
+#[derive(Deserialize)]
+struct Session {
+    /// Includes the address of the session's intproxy.
+    environment: HashMap<String, String>,
+    env_to_unset: Vec<String>,
+    patched_path: Option<String>,
+    #[serde(skip)]
+    id: Option<String>,
+    /// When the last process of this session exited.
+    #[serde(skip)]
+    released: Option<Instant>,
+    /// The `mirrord ext` process, which runs until the intproxy exits, and
+    /// is killed when the session is dropped.
+    #[serde(skip)]
+    ext: Option<Child>,
+}
+
+impl Session {
+    /// Whether the intproxy might have exited, because `mirrord ext` is done
+    /// or no process was connected to it for too long.
+    ///
+    /// Uses half of the `idle_timeout`, to leave time for the new process
+    /// to connect.
+    fn expired(&mut self, idle_timeout: Duration) -> bool {
+        let ext_exited = self
+            .ext
+            .as_mut()
+            .is_some_and(|ext| matches!(ext.try_wait(), Ok(None)).not());
+
+        ext_exited
+            || self
+                .released
+                .is_some_and(|released| released.elapsed() >= idle_timeout / 2)
+    }
+
+    /// Runs `run.command` directly, with the environment of this session.
+    fn command(&self, run: &RunConfig) -> Result<Command, UpError> {
+        let (program, args) = run.command.split_first().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the service's command is empty",
+            )
+        })?;
+
+        let mut cmd = Command::new(self.patched_path.as_deref().unwrap_or(program));
+        cmd.args(args)
+            .envs(&self.environment)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        for name in &self.env_to_unset {
+            cmd.env_remove(name);
+        }
+
+        Ok(cmd)
+    }
+}
+
+/// Everything needed to (re)start one of the child mirrord sessions.
+pub(crate) struct ServiceProcess {
+    pub(crate) name: Arc<str>,
+    pub(crate) depends_on: Vec<Arc<str>>,
+    pub(crate) mode: ServiceMode,
+    config: LayerConfig,
+    http_filter: HttpFilterConfig,
+    correlation_id: Uuid,
+    run: RunConfig,
+    readiness: Option<ReadinessConfig>,
+    restart: RestartPolicy,
+    watch: Option<WatchConfig>,
+    /// Kept across restarts when [`Self::keeps_session`].
+    session: Option<Session>,
+    events: ServiceEventTx,
+}
+
+impl ServiceProcess {
+    pub(crate) fn new(config: SubprocessCfg, correlation_id: Uuid, events: ServiceEventTx) -> Self {
+        let SubprocessCfg {
+            mut config,
+            service_name,
+            run,
+            depends_on,
+            readiness,
+            restart,
+            watch,
+            mode,
+            http_filter,
+        } = config;
+
+        if watch.is_some() && run.r#type == RunType::Exec {
+            let idle_timeout = &mut config.internal_proxy.idle_timeout;
+            *idle_timeout = (*idle_timeout).max(WATCH_IDLE_TIMEOUT_SECS);
+            config.internal_proxy.keep_subscriptions = true;
+        }
+
+        Self {
+            name: service_name,
+            depends_on,
+            mode,
+            config,
+            http_filter,
+            correlation_id,
+            run,
+            readiness,
+            restart,
+            watch,
+            session: None,
+            events,
+        }
+    }
+
+    /// Whether the service's process is restarted in the same [`Session`].
+    ///
+    /// Only `exec` services with watched files do this, others get a new
+    /// mirrord session with every restart.
+    fn keeps_session(&self) -> bool {
+        self.watch.is_some() && self.run.r#type == RunType::Exec
+    }
+
+    /// Runs `mirrord {subcommand}` with the service's config.
+    fn mirrord_command(&self, subcommand: &str) -> Result<Command, UpError> {
+        let mut cmd = Command::new(std::env::current_exe()?);
+        cmd.env(RESOLVED_CONFIG_ENV, self.config.encode()?)
+            .env(MIRRORD_PROGRESS_ENV, "simple")
+            .env(
+                MIRRORD_UP_CORRELATION_ID_ENV,
+                self.correlation_id.to_string(),
+            )
+            .arg(subcommand)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        Ok(cmd)
+    }
+
+    fn command(&self) -> Result<Command, UpError> {
+        let mut cmd = self.mirrord_command(Into::<&'static str>::into(&self.run.r#type))?;
+        cmd.arg("--").args(&self.run.command);
+        Ok(cmd)
+    }
+
+    /// Starts the service's process, in the [`Session`] kept from the
+    /// previous run if there is one.
+    async fn spawn(&mut self) -> Result<Result<Child, Failure>, UpError> {
+        if self.keeps_session().not() {
+            return Ok(Ok(self.command()?.spawn()?));
+        }
+
+        let idle_timeout = Duration::from_secs(self.config.internal_proxy.idle_timeout);
+        if self
+            .session
+            .as_mut()
+            .is_some_and(|session| session.expired(idle_timeout))
+        {
+            self.session = None;
+        }
+
+        let session = match self.session.take() {
+            Some(session) => session,
+            None => match self.start_session().await? {
+                Ok(session) => session,
+                Err(failure) => return Ok(Err(failure)),
+            },
+        };
+        let session = self.session.insert(session);
+        session.released = None;
+
+        let child = session.command(&self.run)?.spawn()?;
+        if let Some(id) = session.id.clone() {
+            self.emit(ServiceEvent::SessionId(id));
+        }
+
+        Ok(Ok(child))
+    }
+
+    /// Starts a new [`Session`] with `mirrord ext`, forwarding its output.
+    ///
+    /// Returns as soon as `mirrord ext` prints the session, as it keeps
+    /// running until the intproxy exits. The rest of its output is forwarded
+    /// in the background.
+    async fn start_session(&self) -> Result<Result<Session, Failure>, UpError> {
+        let mut ext = self.mirrord_command("ext")?.spawn()?;
+        let mut out = BufReader::new(ext.stdout.take().expect("stdout is piped")).lines();
+        let mut err = BufReader::new(ext.stderr.take().expect("stderr is piped")).lines();
+        let mut out_open = true;
+        let mut err_open = true;
+
+        let mut session = None;
+        let mut session_id = None;
+        while session.is_none() {
+            tokio::select! {
+                line = out.next_line(), if out_open => match line {
+                    Ok(Some(line)) => self.on_ext_line(line, &mut session, &mut session_id),
+                    Ok(None) => out_open = false,
+                    Err(err) => self.emit(ServiceEvent::Output(format!("error: {err:?}"))),
+                },
+
+                line = err.next_line(), if err_open => match line {
+                    Ok(Some(line)) => self.on_ext_line(line, &mut session, &mut session_id),
+                    Ok(None) => err_open = false,
+                    Err(err) => self.emit(ServiceEvent::Output(format!("error: {err:?}"))),
+                },
+
+                else => break,
+            }
+        }
+
+        let Some(mut session) = session else {
+            let status = ext.wait().await?;
+            return Ok(Err(Failure::Crashed(status)));
+        };
+
+        self.forward_in_background(out);
+        self.forward_in_background(err);
+        session.id = session_id;
+        session.ext = Some(ext);
+
+        Ok(Ok(session))
+    }
+
+    /// Forwards the remaining `lines` of a session's `mirrord ext` output,
+    /// until it exits.
+    fn forward_in_background<R>(&self, mut lines: Lines<BufReader<R>>)
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let events = self.events.clone();
+        let name = Arc::clone(&self.name);
+
+        tokio::spawn(async move {
+            while let Ok(Some(line)) = lines.next_line().await {
+                forward_line(&events, &name, line);
+            }
+        });
+    }
+
+    fn emit(&self, event: ServiceEvent) {
+        let _ = self.events.send((Arc::clone(&self.name), event));
+    }
+
+    /// Applies a [`ServiceCommand`] to a service that is not running.
+    ///
+    /// Returns whether the service should be started.
+    fn handle_command(&mut self, command: ServiceCommand) -> bool {
+        match command {
+            ServiceCommand::Restart => true,
+            ServiceCommand::Stop => {
+                self.emit(ServiceEvent::Status(ServiceStatus::Stopped));
+                false
+            }
+            ServiceCommand::SetMode(mode) => {
+                mode.apply(&mut self.config, &self.http_filter);
+                self.mode = mode;
+                self.session = None;
+                true
+            }
+        }
+    }
+
+    /// Runs the service until it exits successfully, or fails in a way that
+    /// its [`RestartPolicy`] does not recover from.
+    ///
+    /// The service is started only once all `dependencies` are ready, and
+    /// `ready` is updated whenever this service becomes ready or fails.
+    ///
+    /// While someone holds the sender of `commands`, the service can be
+    /// controlled with [`ServiceCommand`]s, and this function returns only
+    /// when the sender is dropped. Services with watched files are
+    /// restarted whenever the files change, and never return.
+    pub(crate) async fn supervise(
+        mut self,
+        dependencies: Vec<(Arc<str>, watch::Receiver<bool>)>,
+        ready: watch::Sender<bool>,
+        stack: Arc<StackReadiness>,
+        mut commands: mpsc::UnboundedReceiver<ServiceCommand>,
+    ) -> Result<(), UpError> {
+        for (dependency, mut dependency_ready) in dependencies {
+            if *dependency_ready.borrow() {
+                continue;
+            }
+
+            self.emit(ServiceEvent::Status(ServiceStatus::Waiting(dependency)));
+            if dependency_ready.wait_for(|ready| *ready).await.is_err() {
+                // The dependency's task is done, which ends the whole run.
+                std::future::pending::<()>().await;
+            }
+        }
+
+        let mut watcher = match &self.watch {
+            Some(watch) => Some(FileWatcher::new(watch, &std::env::current_dir()?).map_err(
+                |error| UpError::Watch {
+                    name: Arc::clone(&self.name),
+                    error,
+                },
+            )?),
+            None => None,
+        };
+
+        let mut counted = false;
+        let mut backoff = Backoff::default();
+        let mut commands_open = true;
+        loop {
+            self.emit(ServiceEvent::Status(ServiceStatus::Starting));
+            let started = Instant::now();
+            let outcome = self
+                .run_once(&ready, &stack, &mut counted, &mut commands, &mut watcher)
+                .await?;
+            ready.send_replace(false);
+            if let Some(session) = &mut self.session {
+                session.released = Some(Instant::now());
+            }
+
+            let result = match outcome {
+                Outcome::Changed(path) => {
+                    self.emit(ServiceEvent::Status(ServiceStatus::Reloading(path)));
+                    continue;
+                }
+                Outcome::Command(command) => {
+                    if self.handle_command(command) {
+                        continue;
+                    }
+                    Ok(())
+                }
+                Outcome::Exited(None) => {
+                    self.emit(ServiceEvent::Status(ServiceStatus::Exited));
+                    Ok(())
+                }
+                Outcome::Exited(Some(failure)) if self.restart == RestartPolicy::OnFailure => {
+                    let delay = backoff.next(started.elapsed());
+                    self.emit(ServiceEvent::Status(ServiceStatus::Restarting {
+                        reason: failure.to_string(),
+                        delay,
+                    }));
+
+                    tokio::select! {
+                        () = tokio::time::sleep(delay) => continue,
+                        Some(command) = commands.recv() => {
+                            if self.handle_command(command) {
+                                continue;
+                            }
+                            Ok(())
+                        }
+                        path = watcher::changed(&mut watcher) => {
+                            self.emit(ServiceEvent::Status(ServiceStatus::Reloading(path)));
+                            continue;
+                        }
+                    }
+                }
+                Outcome::Exited(Some(failure)) => {
+                    let reason = if watcher.is_some() {
+                        format!("{failure}, waiting for changes")
+                    } else {
+                        failure.to_string()
+                    };
+                    self.emit(ServiceEvent::Status(ServiceStatus::Failed(reason)));
+                    Err(failure.into_error(Arc::clone(&self.name)))
+                }
+            };
+
+            // The service stays down until it's started again with a command,
+            // or a change of its watched files. Without either, the service
+            // is done.
+            loop {
+                tokio::select! {
+                    command = commands.recv(), if commands_open => match command {
+                        Some(command) if self.handle_command(command) => break,
+                        Some(_) => {}
+                        None if watcher.is_none() => return result,
+                        None => commands_open = false,
+                    },
+                    path = watcher::changed(&mut watcher) => {
+                        self.emit(ServiceEvent::Status(ServiceStatus::Reloading(path)));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Spawns the service once, and forwards its output until it exits, a
+    /// [`ServiceCommand`] comes, or one of its watched files changes.
+    async fn run_once(
+        &mut self,
+        ready: &watch::Sender<bool>,
+        stack: &StackReadiness,
+        counted: &mut bool,
+        commands: &mut mpsc::UnboundedReceiver<ServiceCommand>,
+        watcher: &mut Option<FileWatcher>,
+    ) -> Result<Outcome, UpError> {
+        let mut child = match self.spawn().await? {
+            Ok(child) => child,
+            Err(failure) => return Ok(Outcome::Exited(Some(failure))),
+        };
+        let mut out = BufReader::new(child.stdout.take().expect("stdout is piped")).lines();
+        let mut err = BufReader::new(child.stderr.take().expect("stderr is piped")).lines();
+        let mut out_open = true;
+        let mut err_open = true;
+
+        let mut check = ReadinessCheck::new(self.readiness.as_ref());
+        if self.keeps_session() {
+            check.set_session_ready();
+        }
+        // Services without a `readiness` block are never killed for not being ready.
+        let timeout = self
+            .readiness
+            .as_ref()
+            .map(|readiness| Duration::from_secs(readiness.timeout));
+        let deadline = tokio::time::sleep(timeout.unwrap_or(Duration::MAX));
+        tokio::pin!(deadline);
+        let mut probe_interval = tokio::time::interval(PROBE_INTERVAL);
+
+        loop {
+            let is_ready = *ready.borrow();
+
+            tokio::select! {
+                line = out.next_line(), if out_open => match line {
+                    Ok(Some(line)) => self.on_line(&mut check, line),
+                    Ok(None) => out_open = false,
+                    Err(err) => self.emit(ServiceEvent::Output(format!("error: {err:?}"))),
+                },
+
+                line = err.next_line(), if err_open => match line {
+                    Ok(Some(line)) => self.on_line(&mut check, line),
+                    Ok(None) => err_open = false,
+                    Err(err) => self.emit(ServiceEvent::Output(format!("error: {err:?}"))),
+                },
+
+                _ = probe_interval.tick(), if is_ready.not() && check.needs_probing() => {
+                    check.probe().await;
+                }
+
+                () = &mut deadline, if is_ready.not() && timeout.is_some() => {
+                    child.kill().await?;
+                    return Ok(Outcome::Exited(timeout.map(Failure::NotReady)));
+                }
+
+                Some(command) = commands.recv() => {
+                    child.kill().await?;
+                    return Ok(Outcome::Command(command));
+                }
+
+                path = watcher::changed(watcher) => {
+                    child.kill().await?;
+                    return Ok(Outcome::Changed(path));
+                }
+
+                status = child.wait() => {
+                    let status = status?;
+                    return Ok(Outcome::Exited(
+                        status.success().not().then_some(Failure::Crashed(status)),
+                    ));
+                }
+            }
+
+            // A session is only counted once, even if it's restarted, or if
+            // the user's binary (which inherits the same stdout after `execve`)
+            // prints a line matching the marker.
+            if is_ready.not() && check.is_ready() {
+                ready.send_replace(true);
+                self.emit(ServiceEvent::Status(ServiceStatus::Ready));
+                if counted.not() {
+                    *counted = true;
+                    stack.service_ready();
+                }
+            }
+        }
+    }
+
+    fn on_line(&self, check: &mut ReadinessCheck<'_>, line: String) {
+        check.on_line(&line);
+        self.forward(line);
+    }
+
+    /// Handles a line of `mirrord ext` output, which is either the
+    /// [`Session`], or forwarded like the service's output.
+    fn on_ext_line(
+        &self,
+        line: String,
+        session: &mut Option<Session>,
+        session_id: &mut Option<String>,
+    ) {
+        if let Ok(output) = serde_json::from_str(&line) {
+            *session = Some(output);
+            return;
+        }
+
+        if let Some(id) = line.trim().strip_prefix(SESSION_ID_PREFIX) {
+            *session_id = Some(id.to_owned());
+        }
+        self.forward(line);
+    }
+
+    fn forward(&self, line: String) {
+        forward_line(&self.events, &self.name, line);
+    }
+}
+
+/// Reports a line of output, and the session id if the line has it.
+fn forward_line(events: &ServiceEventTx, name: &Arc<str>, line: String) {
+    if let Some(session_id) = line.trim().strip_prefix(SESSION_ID_PREFIX) {
+        let _ = events.send((
+            Arc::clone(name),
+            ServiceEvent::SessionId(session_id.to_owned()),
+        ));
+    }
+    let _ = events.send((Arc::clone(name), ServiceEvent::Output(line)));
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    use super::*;
+    use crate::config::HttpProbe;
+
+    #[test]
+    fn backoff_doubles_and_resets() {
+        let mut backoff = Backoff::default();
+        let quick = Duration::from_secs(1);
+
+        let delays = (0..7).map(|_| backoff.next(quick)).collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [1, 2, 4, 8, 16, 30, 30].map(Duration::from_secs).to_vec()
+        );
+
+        assert_eq!(backoff.next(Backoff::RESET_AFTER), Backoff::INITIAL);
+    }
+
+    #[test]
+    fn ready_with_session_without_readiness_config() {
+        let mut check = ReadinessCheck::new(None);
+        assert!(check.is_ready().not());
+
+        check.on_line(SESSION_READY_MESSAGE);
+        assert!(check.needs_probing().not());
+        assert!(check.is_ready());
+    }
+
+    #[tokio::test]
+    async fn http_probe_requires_success_status() {
+        let listener = TcpListener::bind(("localhost", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            for status in ["503 Service Unavailable", "200 OK"] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await.unwrap();
+                stream
+                    .write_all(format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n").as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let config = ReadinessConfig {
+            http: Some(HttpProbe {
+                port,
+                path: "/healthz".to_owned(),
+            }),
+            ..Default::default()
+        };
+        let mut check = ReadinessCheck::new(Some(&config));
+        check.on_line(SESSION_READY_MESSAGE);
+
+        check.probe().await;
+        assert!(check.is_ready().not());
+
+        check.probe().await;
+        assert!(check.is_ready());
+    }
+}
@@ -0,0 +1,579 @@
+//! Interactive terminal UI of `mirrord up`, enabled with `--tui`.
+//!
+//! Every service gets its own tab with its output, [`ServiceStatus`] and [`ServiceMode`]. Services
+//! can be restarted, stopped, or switched to a different mode, which sends [`ServiceCommand`]s to
+//! their supervisors. Port subscriptions of each service's mirrord session are fetched from its
+//! session monitor API.
+
+use std::{collections::VecDeque, ops::Not, sync::Arc, time::Duration};
+
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use futures::StreamExt;
+use mirrord_session_monitor_client::{SessionClient, SessionEndpoint, sessions_dir};
+use mirrord_session_monitor_protocol::SessionInfo;
+use ratatui::{
+    DefaultTerminal, Frame,
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Tabs},
+};
+use tokio::sync::mpsc;
+
+use crate::{
+    ReadyTracker, UpError,
+    config::ServiceMode,
+    supervisor::{ServiceCommand, ServiceEvent, ServiceStatus},
+};
+
+/// How many output lines are kept for every service.
+const SCROLLBACK_LINES: usize = 10_000;
+
+/// How often the session info of every service is fetched from its session monitor API.
+const SESSION_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How many [`ServiceEvent`]s are handled at most between redraws.
+const MAX_EVENTS_PER_DRAW: usize = 1024;
+
+const HELP: &str = "q quit | ←/→ service | ↑/↓ PgUp/PgDn scroll | / search | n/N next/previous match \
+                    | r restart | s stop | m switch mode";
+
+/// A service shown in the UI, and the way to control it.
+pub(crate) struct TuiService {
+    pub(crate) name: Arc<str>,
+    pub(crate) mode: ServiceMode,
+    pub(crate) commands: mpsc::UnboundedSender<ServiceCommand>,
+}
+
+/// Runs the UI until the user quits.
+pub(crate) async fn run(
+    services: Vec<TuiService>,
+    mut events: mpsc::UnboundedReceiver<(Arc<str>, ServiceEvent)>,
+    ready: ReadyTracker,
+) -> Result<(), UpError> {
+    let mut app = App::new(services, ready);
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, &mut events).await;
+    ratatui::restore();
+
+    result
+}
+
+/// State of a single service tab.
+struct ServiceView {
+    service: TuiService,
+    status: ServiceStatus,
+    lines: VecDeque<String>,
+    /// How many lines from the bottom the view is scrolled up, `0` follows new output.
+    scroll: usize,
+    session_id: Option<String>,
+    session: Option<SessionInfo>,
+}
+
+impl ServiceView {
+    fn new(service: TuiService) -> Self {
+        Self {
+            service,
+            status: ServiceStatus::Starting,
+            lines: VecDeque::new(),
+            scroll: 0,
+            session_id: None,
+            session: None,
+        }
+    }
+
+    fn push_line(&mut self, line: String) {
+        if self.lines.len() == SCROLLBACK_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+
+        // Keep showing the same lines while scrolled up.
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.max_scroll());
+        }
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(1)
+    }
+
+    fn scroll_up(&mut self, lines: usize) {
+        self.scroll = (self.scroll + lines).min(self.max_scroll());
+    }
+
+    fn scroll_down(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    /// Scrolls to the closest line containing `query`, searching up (older output) or down from
+    /// the line at the bottom of the view.
+    ///
+    /// Returns whether a matching line was found.
+    fn find(&mut self, query: &str, up: bool) -> bool {
+        let bottom = self.max_scroll() - self.scroll;
+        let mut lines = self.lines.iter().enumerate();
+
+        let found = if up {
+            lines
+                .take(bottom)
+                .rev()
+                .find(|(_, line)| line.contains(query))
+        } else {
+            lines
+                .skip(bottom + 1)
+                .find(|(_, line)| line.contains(query))
+        };
+
+        match found {
+            Some((index, _)) => {
+                self.scroll = self.max_scroll() - index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The last `height` lines above the scroll position.
+    fn visible_lines(&self, height: usize) -> impl Iterator<Item = &String> {
+        let end = self.lines.len().saturating_sub(self.scroll);
+        self.lines.range(end.saturating_sub(height)..end)
+    }
+
+    fn on_event(&mut self, event: ServiceEvent) {
+        match event {
+            ServiceEvent::Output(line) => self.push_line(strip_ansi(&line)),
+            ServiceEvent::Status(status) => {
+                if status == ServiceStatus::Starting {
+                    self.session_id = None;
+                    self.session = None;
+                }
+
+                self.push_line(format!("[mirrord up] {status}"));
+                self.status = status;
+            }
+            ServiceEvent::SessionId(session_id) => self.session_id = Some(session_id),
+        }
+    }
+}
+
+struct App {
+    services: Vec<ServiceView>,
+    selected: usize,
+    ready: ReadyTracker,
+    /// Query being typed after `/`.
+    search_input: Option<String>,
+    /// Last submitted query, highlighted in the output.
+    search: Option<String>,
+    /// Result of the last action, shown instead of the help line.
+    message: Option<String>,
+    /// Height of the output view, used to scroll by pages.
+    page_size: usize,
+    quit: bool,
+}
+
+impl App {
+    fn new(services: Vec<TuiService>, ready: ReadyTracker) -> Self {
+        Self {
+            services: services.into_iter().map(ServiceView::new).collect(),
+            selected: 0,
+            ready,
+            search_input: None,
+            search: None,
+            message: None,
+            page_size: 1,
+            quit: false,
+        }
+    }
+
+    async fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        events: &mut mpsc::UnboundedReceiver<(Arc<str>, ServiceEvent)>,
+    ) -> Result<(), UpError> {
+        let mut keys = EventStream::new();
+        let mut refresh = tokio::time::interval(SESSION_REFRESH_INTERVAL);
+        let (sessions_tx, mut sessions) = mpsc::unbounded_channel();
+
+        while self.quit.not() {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            tokio::select! {
+                Some((name, event)) = events.recv() => {
+                    self.on_event(&name, event);
+
+                    // Output comes in bursts, no need to redraw after every line.
+                    for _ in 1..MAX_EVENTS_PER_DRAW {
+                        let Ok((name, event)) = events.try_recv() else {
+                            break;
+                        };
+                        self.on_event(&name, event);
+                    }
+                }
+
+                Some(event) = keys.next() => {
+                    if let Event::Key(key) = event?
+                        && key.kind == KeyEventKind::Press
+                    {
+                        self.on_key(key);
+                    }
+                }
+
+                Some((name, info)) = sessions.recv() => self.on_session_info(&name, info),
+
+                _ = refresh.tick() => self.refresh_sessions(&sessions_tx),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn view_mut(&mut self, name: &str) -> Option<&mut ServiceView> {
+        self.services
+            .iter_mut()
+            .find(|view| view.service.name.as_ref() == name)
+    }
+
+    fn on_event(&mut self, name: &str, event: ServiceEvent) {
+        if let Some(view) = self.view_mut(name) {
+            view.on_event(event);
+        }
+    }
+
+    fn on_session_info(&mut self, name: &str, info: SessionInfo) {
+        if let Some(view) = self.view_mut(name)
+            && view.session_id.as_ref() == Some(&info.session_id)
+        {
+            view.session = Some(info);
+        }
+    }
+
+    /// Fetches the session info of every service in the background, the results are sent to
+    /// `tx`.
+    fn refresh_sessions(&self, tx: &mpsc::UnboundedSender<(Arc<str>, SessionInfo)>) {
+        let Some(sessions_dir) = sessions_dir() else {
+            return;
+        };
+
+        for view in &self.services {
+            let Some(session_id) = &view.session_id else {
+                continue;
+            };
+
+            let client =
+                SessionClient::new(SessionEndpoint::for_session(session_id, &sessions_dir));
+            let name = Arc::clone(&view.service.name);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                // The session may be gone already, it's just not shown then.
+                if let Ok(info) = client.fetch_info().await {
+                    let _ = tx.send((name, info));
+                }
+            });
+        }
+    }
+
+    fn on_key(&mut self, key: KeyEvent) {
+        if let Some(input) = &mut self.search_input {
+            match key.code {
+                KeyCode::Enter => {
+                    let query = std::mem::take(input);
+                    self.search_input = None;
+                    if query.is_empty().not() {
+                        self.search = Some(query);
+                        self.find(true);
+                    }
+                }
+                KeyCode::Esc => self.search_input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+
+            return;
+        }
+
+        self.message = None;
+        let page_size = self.page_size;
+        let count = self.services.len();
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => {
+                self.selected = (self.selected + 1) % count.max(1);
+            }
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.selected = (self.selected + count.max(1) - 1) % count.max(1);
+            }
+            KeyCode::Char(c @ '1'..='9') => {
+                let index = usize::from(c as u8 - b'1');
+                if index < count {
+                    self.selected = index;
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => self.with_selected(|view| view.scroll_up(1)),
+            KeyCode::Down | KeyCode::Char('j') => self.with_selected(|view| view.scroll_down(1)),
+            KeyCode::PageUp => self.with_selected(|view| view.scroll_up(page_size)),
+            KeyCode::PageDown => self.with_selected(|view| view.scroll_down(page_size)),
+            KeyCode::Home | KeyCode::Char('g') => {
+                self.with_selected(|view| view.scroll = view.max_scroll())
+            }
+            KeyCode::End | KeyCode::Char('G') => self.with_selected(|view| view.scroll = 0),
+            KeyCode::Char('/') => self.search_input = Some(String::new()),
+            KeyCode::Char('n') => self.find(true),
+            KeyCode::Char('N') => self.find(false),
+            KeyCode::Char('r') => self.send(ServiceCommand::Restart),
+            KeyCode::Char('s') => self.send(ServiceCommand::Stop),
+            KeyCode::Char('m') => {
+                let Some(view) = self.services.get(self.selected) else {
+                    return;
+                };
+                let mode = match view.service.mode {
+                    ServiceMode::Split => ServiceMode::Steal,
+                    ServiceMode::Steal => ServiceMode::Split,
+                };
+                self.send(ServiceCommand::SetMode(mode));
+            }
+            _ => {}
+        }
+    }
+
+    fn with_selected<F: FnOnce(&mut ServiceView)>(&mut self, f: F) {
+        if let Some(view) = self.services.get_mut(self.selected) {
+            f(view);
+        }
+    }
+
+    fn find(&mut self, up: bool) {
+        let Some(query) = self.search.clone() else {
+            return;
+        };
+
+        let mut found = true;
+        self.with_selected(|view| found = view.find(&query, up));
+        if found.not() {
+            self.message = Some(format!("No more matches for `{query}`"));
+        }
+    }
+
+    fn send(&mut self, command: ServiceCommand) {
+        let Some(view) = self.services.get_mut(self.selected) else {
+            return;
+        };
+
+        if view.service.commands.send(command.clone()).is_err() {
+            self.message = Some(format!("{} is not running", view.service.name));
+            return;
+        }
+
+        if let ServiceCommand::SetMode(mode) = command {
+            self.message = Some(format!("Restarting {} in {mode} mode", view.service.name));
+            view.service.mode = mode;
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tabs_area, output_area, status_area, help_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let height = usize::from(output_area.height.saturating_sub(2));
+        self.page_size = height.max(1);
+
+        let titles = self.services.iter().map(|view| {
+            let (symbol, color) = status_symbol(&view.status);
+            Line::from(vec![
+                Span::styled(symbol, Style::new().fg(color)),
+                Span::raw(format!(" {}", view.service.name)),
+            ])
+        });
+        frame.render_widget(
+            Tabs::new(titles)
+                .select(self.selected)
+                .highlight_style(Style::new().add_modifier(Modifier::BOLD | Modifier::REVERSED)),
+            tabs_area,
+        );
+
+        let help = match (&self.search_input, &self.message) {
+            (Some(input), _) => format!("/{input}"),
+            (None, Some(message)) => message.clone(),
+            (None, None) => HELP.to_owned(),
+        };
+        frame.render_widget(Paragraph::new(help), help_area);
+
+        let Some(view) = self.services.get(self.selected) else {
+            return;
+        };
+
+        let query = self.search.as_deref();
+        let output = view
+            .visible_lines(height)
+            .map(|line| highlight(line, query))
+            .collect::<Vec<_>>();
+        let mut block = Block::bordered().title(format!(" {} ", view.service.name));
+        if view.scroll > 0 {
+            block = block.title_bottom(format!(" {} lines below ", view.scroll));
+        }
+        frame.render_widget(Paragraph::new(output).block(block), output_area);
+
+        let (_, color) = status_symbol(&view.status);
+        let mut status = vec![
+            Span::styled(view.status.to_string(), Style::new().fg(color)),
+            Span::raw(format!(" | {} mode", view.service.mode)),
+        ];
+        if let Some(session_id) = &view.session_id {
+            status.push(Span::raw(format!(" | session {session_id}")));
+        }
+        if let Some(session) = &view.session
+            && session.port_subscriptions.is_empty().not()
+        {
+            let ports = session
+                .port_subscriptions
+                .iter()
+                .map(|subscription| format!("{} ({})", subscription.port, subscription.mode))
+                .collect::<Vec<_>>()
+                .join(", ");
+            status.push(Span::raw(format!(" | ports {ports}")));
+        }
+        if let Some(elapsed) = self.ready.time_to_ready() {
+            status.push(Span::raw(format!(
+                " | all services ready in {}s",
+                elapsed.as_secs()
+            )));
+        }
+        frame.render_widget(Paragraph::new(Line::from(status)), status_area);
+    }
+}
+
+fn status_symbol(status: &ServiceStatus) -> (&'static str, Color) {
+    match status {
+        ServiceStatus::Waiting(..) => ("…", Color::Gray),
+        ServiceStatus::Starting => ("◌", Color::Yellow),
+        ServiceStatus::Ready => ("●", Color::Green),
+        ServiceStatus::Restarting { .. } => ("↻", Color::Yellow),
+        ServiceStatus::Failed(..) => ("✖", Color::Red),
+        ServiceStatus::Stopped => ("■", Color::Gray),
+        ServiceStatus::Exited => ("○", Color::Gray),
+        ServiceStatus::Reloading(..) => ("↻", Color::Cyan),
+    }
+}
+
+/// Highlights all occurrences of `query` in `line`.
+fn highlight<'a>(line: &'a str, query: Option<&str>) -> Line<'a> {
+    let Some(query) = query.filter(|query| query.is_empty().not()) else {
+        return Line::raw(line);
+    };
+
+    let mut spans = Vec::new();
+    let mut rest = line;
+    while let Some(index) = rest.find(query) {
+        let (before, after) = rest.split_at(index);
+        let (matched, after) = after.split_at(query.len());
+        spans.push(Span::raw(before));
+        spans.push(Span::styled(
+            matched,
+            Style::new().fg(Color::Black).bg(Color::Yellow),
+        ));
+        rest = after;
+    }
+    spans.push(Span::raw(rest));
+
+    Line::from(spans)
+}
+
+/// Removes ANSI escape sequences (e.g. colors) and other control characters from a line of
+/// output, so that they don't mess up the UI.
+fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                if chars.next() == Some('[') {
+                    chars.by_ref().find(|c| ('@'..='~').contains(c));
+                }
+            }
+            '\t' => stripped.push_str("    "),
+            c if c.is_control() => {}
+            c => stripped.push(c),
+        }
+    }
+
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(lines: &[&str]) -> ServiceView {
+        let mut view = ServiceView::new(TuiService {
+            name: "svc".into(),
+            mode: ServiceMode::Split,
+            commands: mpsc::unbounded_channel().0,
+        });
+        lines
+            .iter()
+            .for_each(|line| view.push_line((*line).to_owned()));
+        view
+    }
+
+    #[test]
+    fn find_scrolls_to_matches() {
+        let mut view = view(&["error: one", "ok", "error: two", "ok", "ok"]);
+
+        assert!(view.find("error", true));
+        assert_eq!(view.scroll, 2);
+        assert!(view.find("error", true));
+        assert_eq!(view.scroll, 4);
+        assert!(view.find("error", true).not());
+
+        assert!(view.find("error", false));
+        assert_eq!(view.scroll, 2);
+        assert_eq!(
+            view.visible_lines(2).collect::<Vec<_>>(),
+            ["ok", "error: two"]
+        );
+    }
+
+    #[test]
+    fn scrolled_view_stays_in_place() {
+        let mut view = view(&["a", "b", "c"]);
+        view.scroll_up(1);
+
+        view.push_line("d".to_owned());
+        assert_eq!(view.visible_lines(1).collect::<Vec<_>>(), ["b"]);
+
+        view.scroll_down(usize::MAX);
+        assert_eq!(view.visible_lines(1).collect::<Vec<_>>(), ["d"]);
+    }
+
+    #[test]
+    fn strip_ansi_removes_escapes() {
+        assert_eq!(
+            strip_ansi("\x1b[1;32mReady!\x1b[0m\tdone\r"),
+            "Ready!    done"
+        );
+    }
+
+    #[test]
+    fn highlight_splits_matches() {
+        let line = highlight("a-b-a", Some("a"));
+        assert_eq!(
+            line.spans
+                .iter()
+                .map(|span| span.content.as_ref())
+                .collect::<Vec<_>>(),
+            ["", "a", "-b-", "a", ""]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Watches the local files of a service, to restart it when they change
+//! (see [`WatchConfig`]).
+
+use std::{
+    collections::BTreeMap,
+    ops::Not,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use glob::MatchOptions;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::config::{WatchConfig, WatchPattern};
+
+/// `*` and `?` never match `/`, only `**` does.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Decides which changed paths restart the service.
+struct ChangeFilter {
+    /// Directory against which the patterns are matched.
+    base: PathBuf,
+    paths: Vec<WatchPattern>,
+    ignore: Vec<WatchPattern>,
+}
+
+impl ChangeFilter {
+    /// Returns `path` relative to the [`Self::base`], if it matches one of
+    /// the watched patterns, and none of the ignored ones.
+    fn matches(&self, path: &Path) -> Option<PathBuf> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        let matches = |patterns: &[WatchPattern]| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.0.matches_path_with(relative, MATCH_OPTIONS))
+        };
+
+        (matches(&self.paths) && matches(&self.ignore).not()).then(|| relative.to_owned())
+    }
+
+    /// Directories that have to be watched to notice changes of all the
+    /// files matching [`Self::paths`].
+    ///
+    /// Patterns are watched from their longest prefix without wildcards.
+    /// Files are watched through their parent directory, so that we notice
+    /// when editors replace the file instead of writing to it.
+    fn roots(&self) -> BTreeMap<PathBuf, RecursiveMode> {
+        let mut roots = BTreeMap::new();
+        for pattern in &self.paths {
+            let mut prefix = PathBuf::new();
+            let mut rest = Vec::new();
+            for component in Path::new(pattern.0.as_str()).components() {
+                let component = component.as_os_str().to_string_lossy();
+                if rest.is_empty() && component.contains(['*', '?', '[']).not() {
+                    prefix.push(&*component);
+                } else {
+                    rest.push(component);
+                }
+            }
+
+            let (root, mode) = match rest.as_slice() {
+                [] => (
+                    prefix.parent().map(Path::to_owned).unwrap_or_default(),
+                    RecursiveMode::NonRecursive,
+                ),
+                [file] if file.contains("**").not() => (prefix, RecursiveMode::NonRecursive),
+                _ => (prefix, RecursiveMode::Recursive),
+            };
+
+            let watched = roots.entry(self.base.join(root)).or_insert(mode);
+            if mode == RecursiveMode::Recursive {
+                *watched = mode;
+            }
+        }
+
+        roots
+    }
+}
+
+/// Watches the files of a single service.
+pub(crate) struct FileWatcher {
+    /// Stops watching when dropped.
+    _watcher: RecommendedWatcher,
+    changes: mpsc::UnboundedReceiver<PathBuf>,
+    debounce: Duration,
+}
+
+impl FileWatcher {
+    /// Starts watching the files from `config`, with patterns relative to
+    /// `base`.
+    pub(crate) fn new(config: &WatchConfig, base: &Path) -> Result<Self, notify::Error> {
+        let filter = ChangeFilter {
+            // Paths in the events are canonical, e.g. `/private/var` instead
+            // of `/var` on macOS.
+            base: base.canonicalize()?,
+            paths: config.paths.clone(),
+            ignore: config.ignore.clone(),
+        };
+        let roots = filter.roots();
+
+        let (changes_tx, changes) = mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: Result<notify::Event, notify::Error>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                if matches!(event.kind, EventKind::Access(..)) {
+                    return;
+                }
+
+                event
+                    .paths
+                    .iter()
+                    .filter_map(|path| filter.matches(path))
+                    .for_each(|path| {
+                        let _ = changes_tx.send(path);
+                    });
+            })?;
+
+        for (root, mode) in roots {
+            watcher.watch(&root, mode)?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+            debounce: Duration::from_millis(config.debounce_ms),
+        })
+    }
+
+    /// Waits until one of the watched files changes, and then until there
+    /// are no more changes for the debounce duration.
+    ///
+    /// Returns the first changed file, relative to the base directory.
+    pub(crate) async fn changed(&mut self) -> PathBuf {
+        let Some(path) = self.changes.recv().await else {
+            // The sender lives in the watcher's callback, which we own.
+            return std::future::pending().await;
+        };
+
+        while let Ok(Some(..)) = tokio::time::timeout(self.debounce, self.changes.recv()).await {}
+
+        path
+    }
+}
+
+/// Waits for a change of the watched files, or forever when there's no
+/// [`FileWatcher`].
+pub(crate) async fn changed(watcher: &mut Option<FileWatcher>) -> PathBuf {
+    match watcher {
+        Some(watcher) => watcher.changed().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(paths: &[&str], ignore: &[&str]) -> ChangeFilter {
+        let patterns = |patterns: &[&str]| {
+            patterns
+                .iter()
+                .map(|pattern| WatchPattern(glob::Pattern::new(pattern).unwrap()))
+                .collect()
+        };
+
+        ChangeFilter {
+            base: PathBuf::from("/project"),
+            paths: patterns(paths),
+            ignore: patterns(ignore),
+        }
+    }
+
+    #[test]
+    fn matches_relative_to_base() {
+        let filter = filter(&["src/*.rs", "Cargo.toml"], &["src/generated.rs"]);
+
+        assert_eq!(
+            filter.matches(Path::new("/project/src/main.rs")),
+            Some(PathBuf::from("src/main.rs"))
+        );
+        assert_eq!(
+            filter.matches(Path::new("/project/Cargo.toml")),
+            Some(PathBuf::from("Cargo.toml"))
+        );
+        assert_eq!(
+            filter.matches(Path::new("/project/src/api/routes.rs")),
+            None
+        );
+        assert_eq!(filter.matches(Path::new("/project/src/generated.rs")), None);
+        assert_eq!(filter.matches(Path::new("/other/src/main.rs")), None);
+    }
+
+    #[test]
+    fn roots_stop_at_first_wildcard() {
+        let filter = filter(
+            &["src/**/*.rs", "src/bin/*.rs", "Cargo.toml", "config/*.json"],
+            &[],
+        );
+
+        assert_eq!(
+            filter.roots(),
+            BTreeMap::from([
+                (PathBuf::from("/project"), RecursiveMode::NonRecursive),
+                (
+                    PathBuf::from("/project/config"),
+                    RecursiveMode::NonRecursive
+                ),
+                (PathBuf::from("/project/src"), RecursiveMode::Recursive),
+                (
+                    PathBuf::from("/project/src/bin"),
+                    RecursiveMode::NonRecursive
+                ),
+            ])
+        );
+    }
+}